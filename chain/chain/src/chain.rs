@@ -1,4 +1,5 @@
 use std::collections::{HashMap, HashSet};
+use std::io;
 use std::sync::Arc;
 use std::time::{Duration as TimeDuration, Instant};
 
@@ -606,6 +607,30 @@ impl Chain {
         Ok(())
     }
 
+    /// Cold storage of archival nodes.
+    ///
+    /// Archival nodes don't clear data, but if their store is split into the hot and the cold
+    /// databases, the data of blocks below the GC stop height is moved to the cold database,
+    /// up to `blocks_limit` heights at a time. The cold head is the last height moved.
+    pub fn migrate_to_cold(&mut self, blocks_limit: NumBlocks) -> Result<(), Error> {
+        let store = self.store.owned_store();
+        let split_db = match store.get_split_db() {
+            Some(split_db) => split_db,
+            None => return Ok(()),
+        };
+        let head = self.store.head()?;
+        let gc_stop_height = self.runtime_adapter.get_gc_stop_height(&head.last_block_hash);
+        let cold_head = split_db
+            .cold_head()
+            .map_err(|err| -> io::Error { err.into() })?
+            .unwrap_or_else(|| self.genesis.header().height());
+        for height in (cold_head + 1..gc_stop_height).take(blocks_limit as usize) {
+            let keys = self.store.cold_keys_at_height(height)?;
+            split_db.move_to_cold(keys, height).map_err(|err| -> io::Error { err.into() })?;
+        }
+        Ok(())
+    }
+
     pub fn clear_forks_data(
         &mut self,
         tries: ShardTries,
@@ -7,6 +7,7 @@ use std::sync::Arc;
 use borsh::{BorshDeserialize, BorshSerialize};
 use cached::{Cached, SizedCache};
 use chrono::Utc;
+use log::warn;
 use tracing::debug;
 
 use near_chain_primitives::error::{Error, ErrorKind};
@@ -37,16 +38,21 @@ use near_store::{
     read_with_cache, ColBlock, ColBlockExtra, ColBlockHeader, ColBlockHeight, ColBlockInfo,
     ColBlockMerkleTree, ColBlockMisc, ColBlockOrdinal, ColBlockPerHeight, ColBlockRefCount,
     ColBlocksToCatchup, ColChallengedBlocks, ColChunkExtra, ColChunkHashesByHeight,
-    ColChunkPerHeightShard, ColChunks, ColEpochLightClientBlocks, ColGCCount, ColIncomingReceipts,
-    ColInvalidChunks, ColLastBlockWithNewChunk, ColNextBlockHashes, ColNextBlockWithNewChunk,
-    ColOutcomeIds, ColOutgoingReceipts, ColPartialChunks, ColProcessedBlockHeights,
-    ColReceiptIdToShardId, ColReceipts, ColState, ColStateChanges, ColStateDlInfos,
-    ColStateHeaders, ColStateParts, ColTransactionResult, ColTransactions, ColTrieChanges, DBCol,
-    KeyForStateChanges, ShardTries, Store, StoreUpdate, TrieChanges, WrappedTrieChanges,
-    CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
+    ColChunkPerHeightShard, ColChunks, ColEpochLightClientBlocks, ColFlatStateDeltas, ColGCCount,
+    ColIncomingReceipts, ColInvalidChunks, ColLastBlockWithNewChunk, ColNextBlockHashes,
+    ColNextBlockWithNewChunk, ColOutcomeIds, ColOutgoingReceipts, ColPartialChunks,
+    ColProcessedBlockHeights, ColReceiptIdToShardId, ColReceipts, ColState, ColStateChanges,
+    ColStateDlInfos, ColStateHeaders, ColStateParts, ColTransactionResult, ColTransactions,
+    ColTrieChanges, DBCol, KeyForStateChanges, ShardTries, Store, StoreUpdate, TrieChanges,
+    WrappedTrieChanges, CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
     LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, SHOULD_COL_GC, TAIL_KEY,
 };
 
+use near_store::flat_state::{
+    get_flat_state_head, remove_flat_state_head, set_flat_state_head, FlatStateChanges,
+    FlatStateDelta, FlatStateHead,
+};
+
 use crate::types::{Block, BlockHeader, LatestKnown};
 use crate::{byzantine_assert, ReceiptResult};
 
@@ -61,6 +67,10 @@ const CACHE_SIZE: usize = 1;
 #[cfg(feature = "no_cache")]
 const CHUNK_CACHE_SIZE: usize = 1;
 
+/// Max number of blocks between the flat state head and the new chain head that the flat state
+/// is moved over. Further heads, e.g. after state sync, disable the flat state of the shard.
+const MAX_FLAT_STATE_MOVE_BLOCKS: usize = 128;
+
 #[derive(Clone)]
 pub enum GCMode {
     Fork(ShardTries),
@@ -541,6 +551,71 @@ impl ChainStore {
             Ok(self.get_block_header(hash)?.height())
         }
     }
+
+    /// Returns keys of the data of blocks at `height` that an archival node moves to the cold
+    /// database once the height is below the GC horizon: blocks, chunks included in them with
+    /// their transactions and receipts, outcomes, receipts, state changes and the state nodes
+    /// replaced by the blocks. Headers and indices stay in the hot database.
+    pub fn cold_keys_at_height(
+        &mut self,
+        height: BlockHeight,
+    ) -> Result<Vec<(DBCol, Vec<u8>)>, Error> {
+        let mut keys = vec![];
+        let block_hashes = match self.get_all_block_hashes_by_height(height) {
+            Ok(block_hashes) => block_hashes.values().flatten().cloned().collect::<Vec<_>>(),
+            Err(_) => return Ok(keys),
+        };
+        for block_hash in block_hashes {
+            let block = self.get_block(&block_hash)?.clone();
+            for chunk_header in block.chunks().iter() {
+                let shard_id = chunk_header.shard_id();
+                let block_shard_id = get_block_shard_id(&block_hash, shard_id);
+                if chunk_header.height_included() == height {
+                    let chunk_hash = chunk_header.chunk_hash();
+                    if let Ok(chunk) = self.get_chunk(&chunk_hash) {
+                        for transaction in chunk.transactions() {
+                            keys.push((ColTransactions, transaction.get_hash().into()));
+                        }
+                        for receipt in chunk.receipts() {
+                            keys.push((ColReceipts, receipt.get_hash().into()));
+                        }
+                    }
+                    keys.push((ColChunks, chunk_hash.0.into()));
+                    keys.push((ColPartialChunks, chunk_hash.0.into()));
+                    for outcome_id in
+                        self.get_outcomes_by_block_hash_and_shard_id(&block_hash, shard_id)?
+                    {
+                        keys.push((ColTransactionResult, outcome_id.into()));
+                    }
+                    keys.push((ColOutcomeIds, block_shard_id.clone()));
+                }
+                if let Some(trie_changes) =
+                    self.store.get_ser::<TrieChanges>(ColTrieChanges, &block_shard_id)?
+                {
+                    keys.extend(
+                        trie_changes
+                            .deleted_state_keys(shard_id)
+                            .into_iter()
+                            .map(|key| (ColState, key)),
+                    );
+                }
+                keys.push((ColTrieChanges, block_shard_id.clone()));
+                keys.push((ColFlatStateDeltas, block_shard_id.clone()));
+                keys.push((ColIncomingReceipts, block_shard_id.clone()));
+                keys.push((ColOutgoingReceipts, block_shard_id.clone()));
+                keys.push((ColChunkExtra, block_shard_id));
+            }
+            let storage_key = KeyForStateChanges::get_prefix(&block_hash);
+            keys.extend(
+                self.store
+                    .iter_prefix(ColStateChanges, storage_key.as_ref())
+                    .map(|(key, _value)| (ColStateChanges, key.into())),
+            );
+            keys.push((ColBlock, block_hash.into()));
+            keys.push((ColBlockExtra, block_hash.into()));
+        }
+        Ok(keys)
+    }
 }
 
 impl ChainStoreAccess for ChainStore {
@@ -2144,6 +2219,7 @@ impl<'a> ChainStoreUpdate<'a> {
             self.gc_col(ColChunkPerHeightShard, &block_shard_id);
             self.gc_col(ColNextBlockWithNewChunk, &block_shard_id);
             self.gc_col(ColChunkExtra, &block_shard_id);
+            self.gc_col(ColFlatStateDeltas, &block_shard_id);
 
             // For incoming State Parts it's done in chain.clear_downloaded_parts()
             // The following code is mostly for outgoing State Parts.
@@ -2417,6 +2493,9 @@ impl<'a> ChainStoreUpdate<'a> {
             DBCol::ColTrieChanges => {
                 store_update.delete(col, key);
             }
+            DBCol::ColFlatStateDeltas => {
+                store_update.delete(col, key);
+            }
             DBCol::ColBlockPerHeight => {
                 panic!("Must use gc_col_glock_per_height method to gc ColBlockPerHeight");
             }
@@ -2456,7 +2535,9 @@ impl<'a> ChainStoreUpdate<'a> {
             | DBCol::ColEpochStart
             | DBCol::ColBlockOrdinal
             | DBCol::_ColTransactionRefCount
-            | DBCol::ColCachedContractCode => {
+            | DBCol::ColCachedContractCode
+            | DBCol::ColFlatState
+            | DBCol::ColFlatStateHead => {
                 unreachable!();
             }
         }
@@ -2464,6 +2545,115 @@ impl<'a> ChainStoreUpdate<'a> {
         self.merge(store_update);
     }
 
+    /// Moves the flat state of every shard that has it to the new chain head, reverting the
+    /// deltas of the blocks that left the chain and applying the deltas of the blocks that joined
+    /// it. If some delta is unavailable, e.g. because the state was synced, the flat state of the
+    /// shard is disabled until it's built again.
+    fn move_flat_state(
+        &mut self,
+        store_update: &mut StoreUpdate,
+        new_head: &CryptoHash,
+        new_deltas: &HashMap<(CryptoHash, ShardId), FlatStateDelta>,
+    ) -> Result<(), Error> {
+        let num_shards = self.get_block_header(new_head)?.chunk_mask().len() as ShardId;
+        for shard_id in 0..num_shards {
+            let flat_head = match get_flat_state_head(self.store(), shard_id)? {
+                Some(flat_head) => flat_head,
+                None => continue,
+            };
+            if &flat_head.block_hash == new_head {
+                continue;
+            }
+            match self.flat_state_changes(shard_id, &flat_head.block_hash, new_head, new_deltas)? {
+                Some(changes) => {
+                    let state_root = self.get_chunk_extra(new_head, shard_id)?.state_root;
+                    changes.write(shard_id, store_update);
+                    set_flat_state_head(
+                        store_update,
+                        shard_id,
+                        &FlatStateHead {
+                            block_hash: *new_head,
+                            state_root,
+                            version: flat_head.version + 1,
+                        },
+                    )?;
+                }
+                None => {
+                    warn!(
+                        target: "chain",
+                        "Can't move flat state of shard {} from {} to {}, disabling it",
+                        shard_id,
+                        flat_head.block_hash,
+                        new_head
+                    );
+                    remove_flat_state_head(store_update, shard_id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the changes of the flat state between the two blocks, `None` if the blocks are too
+    /// far apart or the delta of some block in between is unavailable.
+    fn flat_state_changes(
+        &mut self,
+        shard_id: ShardId,
+        from: &CryptoHash,
+        to: &CryptoHash,
+        new_deltas: &HashMap<(CryptoHash, ShardId), FlatStateDelta>,
+    ) -> Result<Option<FlatStateChanges>, Error> {
+        let mut reverted = vec![];
+        let mut applied = vec![];
+        let (mut from, mut to) = (*from, *to);
+        while from != to {
+            if reverted.len() + applied.len() >= MAX_FLAT_STATE_MOVE_BLOCKS {
+                return Ok(None);
+            }
+            let from_header = match self.get_block_header(&from) {
+                Ok(header) => header.clone(),
+                Err(_) => return Ok(None),
+            };
+            let to_header = match self.get_block_header(&to) {
+                Ok(header) => header.clone(),
+                Err(_) => return Ok(None),
+            };
+            if from_header.height() >= to_header.height() {
+                reverted.push(from);
+                from = *from_header.prev_hash();
+            } else {
+                applied.push(to);
+                to = *to_header.prev_hash();
+            }
+        }
+
+        let mut changes = FlatStateChanges::default();
+        for block_hash in reverted.iter() {
+            match self.get_flat_state_delta(block_hash, shard_id, new_deltas)? {
+                Some(delta) => changes.revert(&delta),
+                None => return Ok(None),
+            }
+        }
+        for block_hash in applied.iter().rev() {
+            match self.get_flat_state_delta(block_hash, shard_id, new_deltas)? {
+                Some(delta) => changes.apply(&delta),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(changes))
+    }
+
+    fn get_flat_state_delta(
+        &self,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+        new_deltas: &HashMap<(CryptoHash, ShardId), FlatStateDelta>,
+    ) -> Result<Option<FlatStateDelta>, Error> {
+        if let Some(delta) = new_deltas.get(&(*block_hash, shard_id)) {
+            return Ok(Some(delta.clone()));
+        }
+        Ok(self.store().get_ser(ColFlatStateDeltas, &get_block_shard_id(block_hash, shard_id))?)
+    }
+
     /// Merge another StoreUpdate into this one
     pub fn merge(&mut self, store_update: StoreUpdate) {
         self.store_updates.push(store_update);
@@ -2482,6 +2672,7 @@ impl<'a> ChainStoreUpdate<'a> {
 
     fn finalize(&mut self) -> Result<StoreUpdate, Error> {
         let mut store_update = self.store().store_update();
+        let new_head = self.head.as_ref().map(|tip| tip.last_block_hash);
         Self::write_col_misc(&mut store_update, HEAD_KEY, &mut self.head)?;
         Self::write_col_misc(&mut store_update, TAIL_KEY, &mut self.tail)?;
         Self::write_col_misc(&mut store_update, CHUNK_TAIL_KEY, &mut self.chunk_tail)?;
@@ -2659,11 +2850,28 @@ impl<'a> ChainStoreUpdate<'a> {
         {
             store_update.set_ser(ColBlockOrdinal, &index_to_bytes(*block_ordinal), block_hash)?;
         }
+        let mut flat_state_deltas = HashMap::new();
         for mut wrapped_trie_changes in self.trie_changes.drain(..) {
+            let shard_id = wrapped_trie_changes.shard_id();
+            if get_flat_state_head(&self.chain_store.store, shard_id)?.is_some() {
+                let block_hash = *wrapped_trie_changes.block_hash();
+                let delta = wrapped_trie_changes
+                    .flat_state_delta()
+                    .map_err(|err| ErrorKind::Other(err.to_string()))?;
+                store_update.set_ser(
+                    ColFlatStateDeltas,
+                    &get_block_shard_id(&block_hash, shard_id),
+                    &delta,
+                )?;
+                flat_state_deltas.insert((block_hash, shard_id), delta);
+            }
             wrapped_trie_changes
                 .wrapped_into(&mut store_update)
                 .map_err(|err| ErrorKind::Other(err.to_string()))?;
         }
+        if let Some(new_head) = new_head {
+            self.move_flat_state(&mut store_update, &new_head, &flat_state_deltas)?;
+        }
 
         let mut affected_catchup_blocks = HashSet::new();
         for (prev_hash, hash) in self.remove_blocks_to_catchup.drain(..) {
@@ -519,6 +519,17 @@ impl RuntimeAdapter for KeyValueRuntime {
         Ok(None)
     }
 
+    fn transaction_fee(
+        &self,
+        gas_price: Balance,
+        transaction: &SignedTransaction,
+        _current_protocol_version: ProtocolVersion,
+    ) -> Balance {
+        let gas: Gas =
+            transaction.transaction.actions.iter().map(|action| action.get_prepaid_gas()).sum();
+        gas_price.saturating_mul(Balance::from(gas))
+    }
+
     fn prepare_transactions(
         &self,
         _gas_price: Balance,
@@ -808,6 +819,17 @@ impl RuntimeAdapter for KeyValueRuntime {
         }
     }
 
+    fn query_with_proof(
+        &self,
+        _shard_id: ShardId,
+        _state_root: &StateRoot,
+        _block_height: BlockHeight,
+        _block_hash: &CryptoHash,
+        _request: &QueryRequest,
+    ) -> Result<(QueryResponse, PartialStorage), Box<dyn std::error::Error>> {
+        Err("KeyValueRuntime doesn't have a trie to prove queries against".into())
+    }
+
     fn obtain_state_part(
         &self,
         _shard_id: ShardId,
@@ -265,6 +265,17 @@ pub trait RuntimeAdapter: Send + Sync {
         current_protocol_version: ProtocolVersion,
     ) -> Result<Option<InvalidTxError>, Error>;
 
+    /// Returns the non-refundable fee of the transaction at the given gas price: the gas burnt
+    /// for converting it into a receipt plus the execution fees of its actions. The prepaid gas of
+    /// function calls is left out, since its unused part is refunded. Used as the priority of the
+    /// transaction in the transaction pool.
+    fn transaction_fee(
+        &self,
+        gas_price: Balance,
+        transaction: &SignedTransaction,
+        current_protocol_version: ProtocolVersion,
+    ) -> Balance;
+
     /// Returns an ordered list of valid transactions from the pool up the given limits.
     /// Pulls transactions from the given pool iterators one by one. Validates each transaction
     /// against the given `chain_validate` closure and runtime's transaction verifier.
@@ -544,6 +555,19 @@ pub trait RuntimeAdapter: Send + Sync {
         request: &QueryRequest,
     ) -> Result<QueryResponse, Box<dyn std::error::Error>>;
 
+    /// Query runtime the same way as `query`, but also record the trie nodes touched while
+    /// answering the request, so that the result can be verified against `state_root`.
+    /// Requests which fail (e.g. a missing account) are answered with
+    /// `QueryResponseKind::Error`, and the recorded nodes prove the absence.
+    fn query_with_proof(
+        &self,
+        shard_id: ShardId,
+        state_root: &StateRoot,
+        block_height: BlockHeight,
+        block_hash: &CryptoHash,
+        request: &QueryRequest,
+    ) -> Result<(QueryResponse, PartialStorage), Box<dyn std::error::Error>>;
+
     fn get_validator_info(&self, block_hash: &CryptoHash) -> Result<EpochValidatorInfo, Error>;
 
     /// Get the part of the state from given state root.
@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use near_chain::test_utils::KeyValueRuntime;
+use near_chain::{
+    Block, Chain, ChainGenesis, ChainStore, ChainStoreAccess, DoomslugThresholdMode, Provenance,
+};
+use near_crypto::KeyType;
+use near_logger_utils::init_test_logger;
+use near_primitives::validator_signer::InMemoryValidatorSigner;
+use near_store::db::Database;
+use near_store::{create_split_store_with_config, ColBlock, ColBlockHeader, StoreConfig};
+
+/// Blocks below the GC horizon of an archival node are moved to the cold database and are
+/// still read through the chain store.
+#[test]
+fn test_migrate_to_cold() {
+    init_test_logger();
+    let hot_dir = tempfile::Builder::new().prefix("cold_storage_hot").tempdir().unwrap();
+    let cold_dir = tempfile::Builder::new().prefix("cold_storage_cold").tempdir().unwrap();
+    let store = create_split_store_with_config(
+        hot_dir.path().to_str().unwrap(),
+        cold_dir.path().to_str().unwrap(),
+        &StoreConfig::default(),
+    );
+    let runtime = Arc::new(KeyValueRuntime::new(store.clone()));
+    let chain_genesis = ChainGenesis::test();
+    let mut chain =
+        Chain::new(runtime, &chain_genesis, DoomslugThresholdMode::NoApprovals).unwrap();
+    let signer = InMemoryValidatorSigner::from_seed("test", KeyType::ED25519, "test");
+
+    // The runtime keeps 5 epochs of 5 blocks, so the GC stop height ends up at 5.
+    let mut blocks = vec![];
+    for _ in 0..30 {
+        let prev_hash = *chain.head_header().unwrap().hash();
+        let prev = chain.get_block(&prev_hash).unwrap();
+        let block = Block::empty(&prev, &signer);
+        blocks.push(block.clone());
+        chain.process_block(&None, block, Provenance::PRODUCED, |_| {}, |_| {}, |_| {}).unwrap();
+    }
+
+    chain.migrate_to_cold(3).unwrap();
+    let split_db = store.get_split_db().unwrap();
+    assert_eq!(split_db.cold_head().unwrap(), Some(3));
+    // Only the limit of heights is moved at a time, and never above the GC stop height.
+    chain.migrate_to_cold(100).unwrap();
+    assert_eq!(split_db.cold_head().unwrap(), Some(4));
+
+    let mut chain_store = ChainStore::new(store.clone(), chain_genesis.height);
+    for block in blocks.iter() {
+        let hash = block.hash().as_ref();
+        let height = block.header().height();
+        let moved = height <= 4;
+        assert_eq!(split_db.cold().get(ColBlock, hash).unwrap().is_some(), moved);
+        assert_eq!(split_db.hot().get(ColBlock, hash).unwrap().is_some(), !moved);
+        // Headers stay in the hot database.
+        assert!(split_db.hot().get(ColBlockHeader, hash).unwrap().is_some());
+        assert_eq!(chain_store.get_block(block.hash()).unwrap(), block);
+        assert_eq!(chain_store.get_block_hash_by_height(height).unwrap(), *block.hash());
+    }
+}
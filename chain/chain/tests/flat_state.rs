@@ -0,0 +1,98 @@
+use near_chain::test_utils::setup;
+use near_chain::{Block, Chain, ChainStoreAccess, Provenance};
+use near_logger_utils::init_test_logger;
+use near_primitives::hash::CryptoHash;
+use near_primitives::utils::get_block_shard_id;
+use near_store::flat_state::{
+    get_flat_state_head, set_flat_state_head, FlatState, FlatStateChanges, FlatStateDelta,
+    FlatStateHead, FlatStateValueChange,
+};
+use near_store::ColFlatStateDeltas;
+
+fn process_block(chain: &mut Chain, block: Block) {
+    chain.process_block(&None, block, Provenance::PRODUCED, |_| {}, |_| {}, |_| {}).unwrap();
+}
+
+fn set_value(key: &[u8], old_value: Option<u8>, new_value: Option<u8>) -> FlatStateDelta {
+    FlatStateDelta {
+        changes: vec![FlatStateValueChange {
+            key: key.to_vec(),
+            old_value: old_value.map(|value| vec![value]),
+            new_value: new_value.map(|value| vec![value]),
+        }],
+    }
+}
+
+fn save_delta(chain: &mut Chain, block_hash: &CryptoHash, delta: &FlatStateDelta) {
+    let mut store_update = chain.store().store().store_update();
+    store_update.set_ser(ColFlatStateDeltas, &get_block_shard_id(block_hash, 0), delta).unwrap();
+    store_update.commit().unwrap();
+}
+
+fn get_flat_value(chain: &mut Chain, key: &[u8]) -> Option<Vec<u8>> {
+    let head = chain.head().unwrap();
+    let state_root = chain.get_chunk_extra(&head.last_block_hash, 0).unwrap().state_root;
+    let flat_state = FlatState::new(chain.store().owned_store(), 0, &state_root).unwrap();
+    flat_state.get(key).unwrap().unwrap()
+}
+
+/// The flat state follows the head through a reorg, reverting the deltas of the blocks that
+/// left the chain and applying the ones of the new fork, and is disabled when the head moves
+/// too far at once.
+#[test]
+fn test_flat_state_reorg() {
+    init_test_logger();
+    let (mut chain, _, signer) = setup();
+    let mut blocks = vec![chain.get_block(&chain.genesis().hash().clone()).unwrap().clone()];
+    for _ in 0..130 {
+        let block = Block::empty(blocks.last().unwrap(), &*signer);
+        blocks.push(block.clone());
+        process_block(&mut chain, block);
+    }
+
+    // The last two blocks set `x` and `y`, the flat state is at the head.
+    let deltas = vec![set_value(b"x", Some(1), Some(2)), set_value(b"y", None, Some(3))];
+    let store = chain.store().owned_store();
+    let mut store_update = store.store_update();
+    let mut changes = FlatStateChanges::default();
+    changes.apply(&set_value(b"x", None, Some(1)));
+    for (block, delta) in blocks[129..].iter().zip(deltas.iter()) {
+        changes.apply(delta);
+        store_update
+            .set_ser(ColFlatStateDeltas, &get_block_shard_id(block.hash(), 0), delta)
+            .unwrap();
+    }
+    changes.write(0, &mut store_update);
+    let state_root = chain.get_chunk_extra(blocks[130].hash(), 0).unwrap().state_root;
+    let flat_head = FlatStateHead { block_hash: *blocks[130].hash(), state_root, version: 0 };
+    set_flat_state_head(&mut store_update, 0, &flat_head).unwrap();
+    store_update.commit().unwrap();
+    assert_eq!(get_flat_value(&mut chain, b"x"), Some(vec![2]));
+    assert_eq!(get_flat_value(&mut chain, b"y"), Some(vec![3]));
+
+    // A fork from block 128 that doesn't take over the head yet, so its delta is only saved.
+    let fork_block = Block::empty_with_height(&blocks[128], 130, &*signer);
+    process_block(&mut chain, fork_block.clone());
+    assert_eq!(chain.head().unwrap().last_block_hash, *blocks[130].hash());
+    assert_eq!(get_flat_state_head(&store, 0).unwrap(), Some(flat_head.clone()));
+    save_delta(&mut chain, fork_block.hash(), &set_value(b"x", Some(1), Some(4)));
+
+    // The fork becomes the head: blocks 130 and 129 are reverted, then the fork is applied.
+    let fork_head = Block::empty(&fork_block, &*signer);
+    process_block(&mut chain, fork_head.clone());
+    assert_eq!(chain.head().unwrap().last_block_hash, *fork_head.hash());
+    let new_flat_head = get_flat_state_head(&store, 0).unwrap().unwrap();
+    assert_eq!(new_flat_head.block_hash, *fork_head.hash());
+    assert_eq!(new_flat_head.version, 1);
+    assert_eq!(get_flat_value(&mut chain, b"x"), Some(vec![4]));
+    assert_eq!(get_flat_value(&mut chain, b"y"), None);
+
+    // Moving the flat state from block 1 to the head takes more blocks than allowed, so it is
+    // disabled instead.
+    let mut store_update = store.store_update();
+    let far_flat_head = FlatStateHead { block_hash: *blocks[1].hash(), ..new_flat_head };
+    set_flat_state_head(&mut store_update, 0, &far_flat_head).unwrap();
+    store_update.commit().unwrap();
+    process_block(&mut chain, Block::empty(&fork_head, &*signer));
+    assert_eq!(get_flat_state_head(&store, 0).unwrap(), None);
+}
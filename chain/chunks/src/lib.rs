@@ -13,6 +13,7 @@ use near_chain::validate::validate_chunk_proofs;
 use near_chain::{
     byzantine_assert, ChainStore, ChainStoreAccess, ChainStoreUpdate, ErrorKind, RuntimeAdapter,
 };
+use near_chain_configs::TransactionPoolConfig;
 #[cfg(feature = "protocol_feature_forward_chunk_parts")]
 use near_network::types::PartialEncodedChunkForwardMsg;
 use near_network::types::{
@@ -20,6 +21,7 @@ use near_network::types::{
     PartialEncodedChunkResponseMsg,
 };
 use near_network::NetworkRequests;
+use near_pool::types::InsertTransactionResult;
 use near_pool::{PoolIteratorWrapper, TransactionPool};
 use near_primitives::block::{BlockHeader, Tip};
 use near_primitives::hash::{hash, CryptoHash};
@@ -378,6 +380,7 @@ pub struct ShardsManager {
     me: Option<AccountId>,
 
     tx_pools: HashMap<ShardId, TransactionPool>,
+    tx_pool_config: TransactionPoolConfig,
 
     runtime_adapter: Arc<dyn RuntimeAdapter>,
     network_adapter: Arc<dyn NetworkAdapter>,
@@ -395,10 +398,12 @@ impl ShardsManager {
         me: Option<AccountId>,
         runtime_adapter: Arc<dyn RuntimeAdapter>,
         network_adapter: Arc<dyn NetworkAdapter>,
+        tx_pool_config: TransactionPoolConfig,
     ) -> Self {
         Self {
             me: me.clone(),
             tx_pools: HashMap::new(),
+            tx_pool_config,
             runtime_adapter: runtime_adapter.clone(),
             network_adapter,
             encoded_chunks: EncodedChunksCache::new(),
@@ -770,9 +775,37 @@ impl ShardsManager {
         self.encoded_chunks.get_chunk_headers_for_block(&prev_block_hash)
     }
 
-    /// Returns true if transaction is not in the pool before call
-    pub fn insert_transaction(&mut self, shard_id: ShardId, tx: SignedTransaction) -> bool {
-        self.tx_pools.entry(shard_id).or_insert_with(TransactionPool::new).insert_transaction(tx)
+    /// Inserts the transaction into the pool of the shard, prioritized by the fee its signer
+    /// pays at the given gas price.
+    pub fn insert_transaction(
+        &mut self,
+        shard_id: ShardId,
+        tx: SignedTransaction,
+        gas_price: Balance,
+        protocol_version: ProtocolVersion,
+    ) -> InsertTransactionResult {
+        let config = self.tx_pool_config;
+        let fee = self.runtime_adapter.transaction_fee(gas_price, &tx, protocol_version);
+        self.tx_pools
+            .entry(shard_id)
+            .or_insert_with(|| TransactionPool::new(config))
+            .insert_transaction(tx, fee)
+    }
+
+    /// Removes the transaction with the given hash from the pool of whichever shard has it.
+    /// Returns the shard the transaction was removed from.
+    pub fn remove_transaction_by_hash(&mut self, tx_hash: &CryptoHash) -> Option<ShardId> {
+        for (shard_id, pool) in self.tx_pools.iter_mut() {
+            if pool.remove_transaction(tx_hash) {
+                return Some(*shard_id);
+            }
+        }
+        None
+    }
+
+    /// Transaction pools of the shards that received transactions so far.
+    pub fn tx_pools(&self) -> &HashMap<ShardId, TransactionPool> {
+        &self.tx_pools
     }
 
     pub fn remove_transactions(
@@ -789,11 +822,20 @@ impl ShardsManager {
         &mut self,
         shard_id: ShardId,
         transactions: &Vec<SignedTransaction>,
+        gas_price: Balance,
+        protocol_version: ProtocolVersion,
     ) {
+        let config = self.tx_pool_config;
+        let transactions = transactions
+            .iter()
+            .map(|tx| {
+                (tx.clone(), self.runtime_adapter.transaction_fee(gas_price, tx, protocol_version))
+            })
+            .collect();
         self.tx_pools
             .entry(shard_id)
-            .or_insert_with(TransactionPool::new)
-            .reintroduce_transactions(transactions.clone());
+            .or_insert_with(|| TransactionPool::new(config))
+            .reintroduce_transactions(transactions);
     }
 
     pub fn group_receipts_by_shard(
@@ -1740,8 +1782,12 @@ mod test {
     fn test_request_partial_encoded_chunk_from_self() {
         let runtime_adapter = Arc::new(KeyValueRuntime::new(create_test_store()));
         let network_adapter = Arc::new(MockNetworkAdapter::default());
-        let mut shards_manager =
-            ShardsManager::new(Some("test".to_string()), runtime_adapter, network_adapter.clone());
+        let mut shards_manager = ShardsManager::new(
+            Some("test".to_string()),
+            runtime_adapter,
+            network_adapter.clone(),
+            TransactionPoolConfig::default(),
+        );
         shards_manager.requested_partial_encoded_chunks.insert(
             ChunkHash(hash(&[1])),
             ChunkRequestInfo {
@@ -1795,6 +1841,7 @@ mod test {
             Some("test".to_string()),
             runtime_adapter.clone(),
             network_adapter.clone(),
+            TransactionPoolConfig::default(),
         );
         let signer = InMemoryValidatorSigner::from_seed("test", KeyType::ED25519, "test");
         let mut rs = ReedSolomonWrapper::new(4, 10);
@@ -1960,6 +2007,7 @@ mod test {
             Some(fixture.mock_chunk_part_owner.clone()),
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
+            TransactionPoolConfig::default(),
         );
         let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&fixture.mock_part_ords);
         let result = shards_manager
@@ -2016,6 +2064,7 @@ mod test {
             Some(fixture.mock_shard_tracker.clone()),
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
+            TransactionPoolConfig::default(),
         );
         let (most_parts, other_parts) = {
             let mut most_parts = fixture.mock_chunk_parts.clone();
@@ -5,6 +5,7 @@ use chrono::Utc;
 use near_chain::test_utils::KeyValueRuntime;
 use near_chain::types::RuntimeAdapter;
 use near_chain::ChainStore;
+use near_chain_configs::TransactionPoolConfig;
 use near_crypto::KeyType;
 use near_network::test_utils::MockNetworkAdapter;
 use near_primitives::block::BlockHeader;
@@ -204,6 +205,7 @@ impl Default for ChunkForwardingTestFixture {
             Some(mock_chunk_producer.clone()),
             mock_runtime.clone(),
             mock_network.clone(),
+            TransactionPoolConfig::default(),
         );
         let receipts = Vec::new();
         let receipts_hashes = mock_runtime.build_receipts_hashes(&receipts);
@@ -1,6 +1,7 @@
 #[cfg(feature = "metric_recorder")]
 use near_network::recorder::MetricRecorder;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
@@ -9,23 +10,27 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 use near_chain_configs::ProtocolConfigView;
+use near_network::compression::Compression;
 use near_network::types::{AccountOrPeerIdOrHash, KnownProducer};
 use near_network::PeerInfo;
 use near_primitives::errors::InvalidTxError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{MerklePath, PartialMerkleTree};
+use near_primitives::network::PeerId;
 use near_primitives::sharding::ChunkHash;
 use near_primitives::types::{
-    AccountId, BlockHeight, BlockReference, MaybeBlockId, ShardId, TransactionOrReceiptId,
+    AccountId, BlockHeight, BlockId, BlockReference, MaybeBlockId, ShardId, StateRoot,
+    TransactionOrReceiptId,
 };
 use near_primitives::utils::generate_random_string;
 use near_primitives::views::{
     BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
     FinalExecutionOutcomeViewEnum, GasPriceView, LightClientBlockLiteView, LightClientBlockView,
-    QueryRequest, QueryResponse, ReceiptView, StateChangesKindsView, StateChangesRequestView,
-    StateChangesView, ValidatorStakeView,
+    QueryRequest, QueryResponse, ReceiptView, SignedTransactionView, StateChangesKindsView,
+    StateChangesRequestView, StateChangesView, StateDiffView, TrieProofPath, ValidatorStakeView,
 };
 pub use near_primitives::views::{StatusResponse, StatusSyncInfo};
+use near_store::checkpoint::CheckpointManifest;
 
 /// Combines errors coming from chain, tx pool and block producer.
 #[derive(Debug)]
@@ -267,6 +272,27 @@ impl Message for Query {
     type Result = Result<Option<QueryResponse>, String>;
 }
 
+/// Queries the state of the shard as of the beginning of the given block (i.e. against
+/// `prev_state_root` of the block's chunk) and returns the result with the proof of it.
+pub struct GetQueryProof {
+    pub block_reference: BlockReference,
+    pub request: QueryRequest,
+}
+
+pub struct GetQueryProofResponse {
+    pub query_response: QueryResponse,
+    /// Serialized trie nodes touched while answering the query, encoded in base64.
+    pub state_proof: TrieProofPath,
+    /// `prev_state_root` of the chunk of the shard that answered the query.
+    pub chunk_prev_state_root: StateRoot,
+    /// Proof of `chunk_prev_state_root` against `prev_state_root` of the block header.
+    pub chunk_prev_state_root_proof: MerklePath,
+}
+
+impl Message for GetQueryProof {
+    type Result = Result<GetQueryProofResponse, String>;
+}
+
 pub struct Status {
     pub is_health_check: bool,
 }
@@ -306,10 +332,71 @@ pub struct NetworkInfoResponse {
     pub received_bytes_per_sec: u64,
     /// Accounts of known block and chunk producers from routing table.
     pub known_producers: Vec<KnownProducer>,
+    /// Compression of the messages of the active peers that use it.
+    #[serde(default)]
+    pub peer_compression: HashMap<PeerId, Compression>,
     #[cfg(feature = "metric_recorder")]
     pub metric_recorder: MetricRecorder,
 }
 
+/// Number and total size of the transactions in the transaction pool of each shard.
+pub struct GetPoolStatus {}
+
+impl Message for GetPoolStatus {
+    type Result = Result<PoolStatusResponse, String>;
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ShardPoolStatus {
+    pub shard_id: ShardId,
+    pub num_transactions: usize,
+    pub size_bytes: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PoolStatusResponse {
+    pub shards: Vec<ShardPoolStatus>,
+}
+
+/// Transactions in the transaction pools, optionally only the ones of the given signer.
+pub struct GetPoolTransactions {
+    pub signer_id: Option<AccountId>,
+}
+
+impl Message for GetPoolTransactions {
+    type Result = Result<PoolTransactionsResponse, String>;
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PoolTransactionView {
+    pub shard_id: ShardId,
+    pub transaction: SignedTransactionView,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PoolTransactionsResponse {
+    pub transactions: Vec<PoolTransactionView>,
+}
+
+/// Removes the transaction from the transaction pool it is in.
+/// Returns the shard of the pool or `None` if no pool has the transaction.
+pub struct RemovePoolTransaction {
+    pub transaction_hash: CryptoHash,
+}
+
+impl Message for RemovePoolTransaction {
+    type Result = Result<Option<ShardId>, String>;
+}
+
+/// Creates a checkpoint of the node database in the given directory, which must not exist.
+pub struct CreateCheckpoint {
+    pub path: PathBuf,
+}
+
+impl Message for CreateCheckpoint {
+    type Result = Result<CheckpointManifest, String>;
+}
+
 /// Status of given transaction including all the subsequent receipts.
 pub struct TxStatus {
     pub tx_hash: CryptoHash,
@@ -385,6 +472,21 @@ impl Message for GetStateChangesWithCauseInBlock {
     type Result = Result<StateChangesView, String>;
 }
 
+/// Differences between the states of a shard at two blocks, both of which must have the state of
+/// the shard available. Up to `limit` keys starting from `start_key` are returned, the view
+/// client caps the limit.
+pub struct GetStateDiff {
+    pub shard_id: ShardId,
+    pub from_block_id: BlockId,
+    pub to_block_id: BlockId,
+    pub start_key: Vec<u8>,
+    pub limit: Option<usize>,
+}
+
+impl Message for GetStateDiff {
+    type Result = Result<StateDiffView, String>;
+}
+
 pub struct GetExecutionOutcome {
     pub id: TransactionOrReceiptId,
 }
@@ -21,6 +21,7 @@ use near_chain_configs::ClientConfig;
 use near_chunks::{ProcessPartialEncodedChunkResult, ShardsManager};
 use near_network::types::PartialEncodedChunkResponseMsg;
 use near_network::{FullPeerInfo, NetworkAdapter, NetworkClientResponses, NetworkRequests};
+use near_pool::types::InsertTransactionResult;
 use near_primitives::block::{Approval, ApprovalInner, ApprovalMessage, Block, BlockHeader, Tip};
 use near_primitives::challenge::{Challenge, ChallengeBody};
 use near_primitives::hash::CryptoHash;
@@ -42,7 +43,10 @@ use near_primitives::validator_signer::ValidatorSigner;
 use crate::metrics;
 use crate::sync::{BlockSync, HeaderSync, StateSync, StateSyncResult};
 use crate::SyncStatus;
-use near_client_primitives::types::{Error, ShardSyncDownload};
+use near_client_primitives::types::{
+    Error, PoolStatusResponse, PoolTransactionView, PoolTransactionsResponse, ShardPoolStatus,
+    ShardSyncDownload,
+};
 use near_primitives::block_header::ApprovalType;
 use near_primitives::version::{ProtocolVersion, PROTOCOL_VERSION};
 
@@ -109,6 +113,7 @@ impl Client {
             validator_signer.as_ref().map(|x| x.validator_id().clone()),
             runtime_adapter.clone(),
             network_adapter.clone(),
+            config.transaction_pool,
         );
         let sync_status = SyncStatus::AwaitingPeers;
         let header_sync = HeaderSync::new(
@@ -197,6 +202,12 @@ impl Client {
     }
 
     pub fn reintroduce_transactions_for_block(&mut self, me: AccountId, block: &Block) {
+        // The fees only prioritize the transactions in the pool, so the latest protocol version
+        // is good enough if the epoch of the block is already unknown.
+        let protocol_version = self
+            .runtime_adapter
+            .get_epoch_protocol_version(block.header().epoch_id())
+            .unwrap_or(PROTOCOL_VERSION);
         for (shard_id, chunk_header) in block.chunks().iter().enumerate() {
             let shard_id = shard_id as ShardId;
             if block.header().height() == chunk_header.height_included() {
@@ -210,6 +221,8 @@ impl Client {
                         shard_id,
                         // By now the chunk must be in store, otherwise the block would have been orphaned
                         self.chain.get_chunk(&chunk_header.chunk_hash()).unwrap().transactions(),
+                        block.header().gas_price(),
+                        protocol_version,
                     );
                 }
             }
@@ -613,7 +626,12 @@ impl Client {
         };
         // Reintroduce valid transactions back to the pool. They will be removed when the chunk is
         // included into the block.
-        shards_mgr.reintroduce_transactions(shard_id, &transactions);
+        shards_mgr.reintroduce_transactions(
+            shard_id,
+            &transactions,
+            prev_block_header.gas_price(),
+            protocol_version,
+        );
         Ok(transactions)
     }
 
@@ -1343,6 +1361,56 @@ impl Client {
         Ok(())
     }
 
+    /// Number and size of the transactions in the pool of every shard, ordered by shard.
+    pub fn pool_status(&self) -> PoolStatusResponse {
+        let mut shards = self
+            .shards_mgr
+            .tx_pools()
+            .iter()
+            .map(|(shard_id, pool)| ShardPoolStatus {
+                shard_id: *shard_id,
+                num_transactions: pool.len(),
+                size_bytes: pool.size_bytes(),
+            })
+            .collect::<Vec<_>>();
+        shards.sort_by_key(|status| status.shard_id);
+        PoolStatusResponse { shards }
+    }
+
+    /// Transactions in the pools, optionally only the ones of the given signer, ordered by
+    /// shard, signer and nonce.
+    pub fn pool_transactions(&self, signer_id: Option<&AccountId>) -> PoolTransactionsResponse {
+        let mut transactions = vec![];
+        for (shard_id, pool) in self.shards_mgr.tx_pools() {
+            for tx in pool.transactions() {
+                if signer_id.map_or(true, |signer_id| signer_id == &tx.transaction.signer_id) {
+                    transactions.push(PoolTransactionView {
+                        shard_id: *shard_id,
+                        transaction: tx.clone().into(),
+                    });
+                }
+            }
+        }
+        transactions.sort_by(|a, b| {
+            (a.shard_id, &a.transaction.signer_id, a.transaction.nonce).cmp(&(
+                b.shard_id,
+                &b.transaction.signer_id,
+                b.transaction.nonce,
+            ))
+        });
+        PoolTransactionsResponse { transactions }
+    }
+
+    /// Removes the transaction from the pool it is in, so that it isn't included into chunks
+    /// produced by this node. Returns the shard of the pool, `None` if no pool has it.
+    pub fn remove_pool_transaction(&mut self, tx_hash: &CryptoHash) -> Option<ShardId> {
+        let shard_id = self.shards_mgr.remove_transaction_by_hash(tx_hash);
+        if let Some(shard_id) = shard_id {
+            info!(target: "client", "Removed transaction {} from the pool of shard {}", tx_hash, shard_id);
+        }
+        shard_id
+    }
+
     pub fn process_tx(
         &mut self,
         tx: SignedTransaction,
@@ -1461,7 +1529,15 @@ impl Client {
                     shard_id,
                     is_forwarded
                 );
-                self.shards_mgr.insert_transaction(shard_id, tx.clone());
+                if let InsertTransactionResult::Rejected(err) = self.shards_mgr.insert_transaction(
+                    shard_id,
+                    tx.clone(),
+                    gas_price,
+                    protocol_version,
+                ) {
+                    debug!(target: "client", "Transaction pool rejected tx: {:?}", err);
+                    return Ok(NetworkClientResponses::InvalidTx(err.into()));
+                }
 
                 // Active validator:
                 //   possibly forward to next epoch validators
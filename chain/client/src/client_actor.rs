@@ -36,7 +36,7 @@ use near_performance_metrics;
 use near_performance_metrics_macros::{perf, perf_with_debug};
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
-use near_primitives::types::{BlockHeight, EpochId};
+use near_primitives::types::{BlockHeight, EpochId, ShardId};
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::{from_timestamp, MaybeValidated};
 use near_primitives::validator_signer::ValidatorSigner;
@@ -53,8 +53,9 @@ use crate::sync::{highest_height_peer, StateSync, StateSyncResult};
 use crate::AdversarialControls;
 use crate::StatusResponse;
 use near_client_primitives::types::{
-    Error, GetNetworkInfo, NetworkInfoResponse, ShardSyncDownload, ShardSyncStatus, Status,
-    StatusSyncInfo, SyncStatus,
+    Error, GetNetworkInfo, GetPoolStatus, GetPoolTransactions, NetworkInfoResponse,
+    PoolStatusResponse, PoolTransactionsResponse, RemovePoolTransaction, ShardSyncDownload,
+    ShardSyncStatus, Status, StatusSyncInfo, SyncStatus,
 };
 use near_primitives::block_header::ApprovalType;
 
@@ -151,6 +152,7 @@ impl ClientActor {
                 received_bytes_per_sec: 0,
                 sent_bytes_per_sec: 0,
                 known_producers: vec![],
+                peer_compression: HashMap::new(),
                 #[cfg(feature = "metric_recorder")]
                 metric_recorder: MetricRecorder::default(),
                 peer_counter: 0,
@@ -617,12 +619,40 @@ impl Handler<GetNetworkInfo> for ClientActor {
             sent_bytes_per_sec: self.network_info.sent_bytes_per_sec,
             received_bytes_per_sec: self.network_info.received_bytes_per_sec,
             known_producers: self.network_info.known_producers.clone(),
+            peer_compression: self.network_info.peer_compression.clone(),
             #[cfg(feature = "metric_recorder")]
             metric_recorder: self.network_info.metric_recorder.clone(),
         })
     }
 }
 
+impl Handler<GetPoolStatus> for ClientActor {
+    type Result = Result<PoolStatusResponse, String>;
+
+    #[perf]
+    fn handle(&mut self, _msg: GetPoolStatus, _ctx: &mut Context<Self>) -> Self::Result {
+        Ok(self.client.pool_status())
+    }
+}
+
+impl Handler<GetPoolTransactions> for ClientActor {
+    type Result = Result<PoolTransactionsResponse, String>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetPoolTransactions, _ctx: &mut Context<Self>) -> Self::Result {
+        Ok(self.client.pool_transactions(msg.signer_id.as_ref()))
+    }
+}
+
+impl Handler<RemovePoolTransaction> for ClientActor {
+    type Result = Result<Option<ShardId>, String>;
+
+    #[perf]
+    fn handle(&mut self, msg: RemovePoolTransaction, _ctx: &mut Context<Self>) -> Self::Result {
+        Ok(self.client.remove_pool_transaction(&msg.transaction_hash))
+    }
+}
+
 impl ClientActor {
     fn sign_announce_account(&self, epoch_id: &EpochId) -> Result<Signature, ()> {
         if let Some(validator_signer) = self.client.validator_signer.as_ref() {
@@ -0,0 +1,69 @@
+//! Cold storage of archival nodes.
+//!
+//! Moving the data below the GC horizon to the cold database reads and rewrites whole blocks,
+//! so it runs in its own arbiter instead of the client, which would otherwise delay block
+//! processing.
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix::{Actor, Addr, Arbiter, AsyncContext, Context};
+use log::{error, info};
+
+use near_chain::{Chain, ChainGenesis, DoomslugThresholdMode, RuntimeAdapter};
+use near_chain_configs::ClientConfig;
+use near_primitives::types::NumBlocks;
+
+/// How often the data is moved to the cold database.
+const COLD_STORE_MIGRATION_PERIOD: Duration = Duration::from_secs(1);
+
+/// Periodically moves up to `blocks_limit` heights below the GC stop height to the cold
+/// database, the same number of heights the GC of non-archival nodes clears per block.
+pub struct ColdStoreActor {
+    chain: Chain,
+    blocks_limit: NumBlocks,
+}
+
+impl ColdStoreActor {
+    fn migrate_to_cold(&mut self, ctx: &mut Context<Self>) {
+        if let Err(err) = self.chain.migrate_to_cold(self.blocks_limit) {
+            error!(target: "client", "Can't move old data to cold storage, {:?}", err);
+        }
+        ctx.run_later(COLD_STORE_MIGRATION_PERIOD, |act, ctx| act.migrate_to_cold(ctx));
+    }
+}
+
+impl Actor for ColdStoreActor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.migrate_to_cold(ctx);
+    }
+}
+
+/// Starts moving old data to the cold database in a new arbiter (thread) if the node is an
+/// archival one with its store split into the hot and the cold databases.
+pub fn start_cold_store(
+    chain_genesis: ChainGenesis,
+    runtime_adapter: Arc<dyn RuntimeAdapter>,
+    config: &ClientConfig,
+) -> Option<(Addr<ColdStoreActor>, Arbiter)> {
+    if !config.archive {
+        return None;
+    }
+    if runtime_adapter.get_tries().get_store().get_split_db().is_none() {
+        return None;
+    }
+    info!(target: "client", "Starting to move old data to cold storage");
+    let blocks_limit = config.gc_blocks_limit;
+    let arbiter = Arbiter::new();
+    let addr = ColdStoreActor::start_in_arbiter(&arbiter, move |_ctx| {
+        let chain = Chain::new_for_view_client(
+            runtime_adapter,
+            &chain_genesis,
+            DoomslugThresholdMode::TwoThirds,
+        )
+        .unwrap();
+        ColdStoreActor { chain, blocks_limit }
+    });
+    Some((addr, arbiter))
+}
@@ -2,21 +2,25 @@
 extern crate lazy_static;
 
 pub use near_client_primitives::types::{
-    Error, GetBlock, GetBlockProof, GetBlockProofResponse, GetBlockWithMerkleTree, GetChunk,
-    GetExecutionOutcome, GetExecutionOutcomeResponse, GetExecutionOutcomesForBlock, GetGasPrice,
-    GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetStateChanges,
-    GetStateChangesInBlock, GetStateChangesWithCauseInBlock, GetValidatorInfo, GetValidatorOrdered,
-    Query, Status, StatusResponse, SyncStatus, TxStatus, TxStatusError,
+    CreateCheckpoint, Error, GetBlock, GetBlockProof, GetBlockProofResponse,
+    GetBlockWithMerkleTree, GetChunk, GetExecutionOutcome, GetExecutionOutcomeResponse,
+    GetExecutionOutcomesForBlock, GetGasPrice, GetNetworkInfo, GetNextLightClientBlock,
+    GetPoolStatus, GetPoolTransactions, GetProtocolConfig, GetQueryProof, GetQueryProofResponse,
+    GetReceipt, GetStateChanges, GetStateChangesInBlock, GetStateChangesWithCauseInBlock,
+    GetStateDiff, GetValidatorInfo, GetValidatorOrdered, Query, RemovePoolTransaction, Status,
+    StatusResponse, SyncStatus, TxStatus, TxStatusError,
 };
 
 pub use crate::client::Client;
 pub use crate::client_actor::{start_client, ClientActor};
+pub use crate::cold_store_actor::{start_cold_store, ColdStoreActor};
 #[cfg(feature = "adversarial")]
 pub use crate::view_client::AdversarialControls;
 pub use crate::view_client::{start_view_client, ViewClientActor};
 
 mod client;
 mod client_actor;
+mod cold_store_actor;
 mod info;
 mod metrics;
 pub mod sync;
@@ -474,6 +474,7 @@ pub fn setup_mock_all_validators(
                             sent_bytes_per_sec: 0,
                             received_bytes_per_sec: 0,
                             known_producers: vec![],
+                            peer_compression: HashMap::new(),
                             #[cfg(feature = "metric_recorder")]
                             metric_recorder: MetricRecorder::default(),
                             peer_counter: 0,
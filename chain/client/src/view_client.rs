@@ -27,6 +27,7 @@ use near_primitives::block::{Block, BlockHeader, GenesisId, Tip};
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{merklize, PartialMerkleTree};
 use near_primitives::network::AnnounceAccount;
+use near_primitives::serialize::to_base64;
 use near_primitives::sharding::ShardChunk;
 use near_primitives::syncing::{
     ShardStateSyncResponse, ShardStateSyncResponseHeader, ShardStateSyncResponseV1,
@@ -40,24 +41,28 @@ use near_primitives::views::{
     BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
     FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum, FinalExecutionStatus, GasPriceView,
     LightClientBlockView, QueryRequest, QueryResponse, ReceiptView, StateChangesKindsView,
-    StateChangesView, ValidatorStakeView,
+    StateChangesView, StateDiffView, ValidatorStakeView,
 };
+use near_store::checkpoint::{create_checkpoint, CheckpointManifest};
 
 use crate::{
-    sync, GetChunk, GetExecutionOutcomeResponse, GetNextLightClientBlock, GetStateChanges,
-    GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered,
+    sync, GetChunk, GetExecutionOutcomeResponse, GetNextLightClientBlock, GetQueryProof,
+    GetQueryProofResponse, GetStateChanges, GetStateChangesInBlock, GetValidatorInfo,
+    GetValidatorOrdered,
 };
 use near_client_primitives::types::{
-    Error, GetBlock, GetBlockError, GetBlockProof, GetBlockProofResponse, GetBlockWithMerkleTree,
-    GetChunkError, GetExecutionOutcome, GetExecutionOutcomesForBlock, GetGasPrice,
-    GetProtocolConfig, GetProtocolConfigError, GetReceipt, GetReceiptError,
-    GetStateChangesWithCauseInBlock, Query, TxStatus, TxStatusError,
+    CreateCheckpoint, Error, GetBlock, GetBlockError, GetBlockProof, GetBlockProofResponse,
+    GetBlockWithMerkleTree, GetChunkError, GetExecutionOutcome, GetExecutionOutcomesForBlock,
+    GetGasPrice, GetProtocolConfig, GetProtocolConfigError, GetReceipt, GetReceiptError,
+    GetStateChangesWithCauseInBlock, GetStateDiff, Query, TxStatus, TxStatusError,
 };
 use near_performance_metrics_macros::perf;
 use near_performance_metrics_macros::perf_with_debug;
 
 /// Max number of queries that we keep.
 const QUERY_REQUEST_LIMIT: usize = 500;
+/// Default and max number of keys returned by a state diff request.
+const STATE_DIFF_LIMIT: usize = 1000;
 /// Waiting time between requests, in ms
 const REQUEST_WAIT_TIME: u64 = 1000;
 
@@ -186,30 +191,25 @@ impl ViewClientActor {
         }
     }
 
-    fn handle_query(&mut self, msg: Query) -> Result<Option<QueryResponse>, String> {
-        {
-            let mut request_manager = self.request_manager.write().expect(POISONED_LOCK_ERR);
-            if let Some(response) = request_manager.query_responses.cache_remove(&msg.query_id) {
-                request_manager.query_requests.cache_remove(&msg.query_id);
-                return response.map(Some);
-            }
-        }
-
-        let header = match msg.block_reference {
+    fn get_block_header_by_reference(
+        &mut self,
+        block_reference: &BlockReference,
+    ) -> Result<BlockHeader, String> {
+        let header = match block_reference {
             BlockReference::BlockId(BlockId::Height(block_height)) => {
-                self.chain.get_header_by_height(block_height)
+                self.chain.get_header_by_height(*block_height)
             }
             BlockReference::BlockId(BlockId::Hash(block_hash)) => {
-                self.chain.get_block_header(&block_hash)
+                self.chain.get_block_header(block_hash)
             }
-            BlockReference::Finality(ref finality) => {
+            BlockReference::Finality(finality) => {
                 let block_hash =
-                    self.get_block_hash_by_finality(&finality).map_err(|e| e.to_string())?;
+                    self.get_block_hash_by_finality(finality).map_err(|e| e.to_string())?;
                 self.chain.get_block_header(&block_hash)
             }
-            BlockReference::SyncCheckpoint(ref synchronization_checkpoint) => {
+            BlockReference::SyncCheckpoint(synchronization_checkpoint) => {
                 if let Some(block_hash) = self
-                    .get_block_hash_by_sync_checkpoint(&synchronization_checkpoint)
+                    .get_block_hash_by_sync_checkpoint(synchronization_checkpoint)
                     .map_err(|e| e.to_string())?
                 {
                     self.chain.get_block_header(&block_hash)
@@ -218,17 +218,21 @@ impl ViewClientActor {
                 }
             }
         };
-        let header = header.map_err(|e| e.to_string())?.clone();
-
-        let account_id = match &msg.request {
-            QueryRequest::ViewAccount { account_id, .. } => account_id,
-            QueryRequest::ViewState { account_id, .. } => account_id,
-            QueryRequest::ViewAccessKey { account_id, .. } => account_id,
-            QueryRequest::ViewAccessKeyList { account_id, .. } => account_id,
-            QueryRequest::CallFunction { account_id, .. } => account_id,
-            QueryRequest::ViewCode { account_id, .. } => account_id,
-        };
-        let shard_id = self.runtime_adapter.account_id_to_shard_id(account_id);
+        header.map_err(|e| e.to_string()).map(Clone::clone)
+    }
+
+    fn handle_query(&mut self, msg: Query) -> Result<Option<QueryResponse>, String> {
+        {
+            let mut request_manager = self.request_manager.write().expect(POISONED_LOCK_ERR);
+            if let Some(response) = request_manager.query_responses.cache_remove(&msg.query_id) {
+                request_manager.query_requests.cache_remove(&msg.query_id);
+                return response.map(Some);
+            }
+        }
+
+        let header = self.get_block_header_by_reference(&msg.block_reference)?;
+
+        let shard_id = self.runtime_adapter.account_id_to_shard_id(query_account_id(&msg.request));
 
         // If we have state for the shard that we query return query result directly.
         // Otherwise route query to peers.
@@ -441,6 +445,17 @@ impl ViewClientActor {
     }
 }
 
+fn query_account_id(request: &QueryRequest) -> &AccountId {
+    match request {
+        QueryRequest::ViewAccount { account_id, .. } => account_id,
+        QueryRequest::ViewState { account_id, .. } => account_id,
+        QueryRequest::ViewAccessKey { account_id, .. } => account_id,
+        QueryRequest::ViewAccessKeyList { account_id, .. } => account_id,
+        QueryRequest::CallFunction { account_id, .. } => account_id,
+        QueryRequest::ViewCode { account_id, .. } => account_id,
+    }
+}
+
 impl Actor for ViewClientActor {
     type Context = SyncContext<Self>;
 }
@@ -454,6 +469,51 @@ impl Handler<Query> for ViewClientActor {
     }
 }
 
+impl Handler<GetQueryProof> for ViewClientActor {
+    type Result = Result<GetQueryProofResponse, String>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetQueryProof, _: &mut Self::Context) -> Self::Result {
+        let header = self.get_block_header_by_reference(&msg.block_reference)?;
+        let shard_id = self.runtime_adapter.account_id_to_shard_id(query_account_id(&msg.request));
+        if !self.runtime_adapter.cares_about_shard(
+            self.validator_account_id.as_ref(),
+            header.prev_hash(),
+            shard_id,
+            true,
+        ) {
+            return Err(format!("Node doesn't track shard {}", shard_id));
+        }
+        let block = self.chain.get_block(header.hash()).map_err(|e| e.to_string())?;
+        let prev_state_roots = block
+            .chunks()
+            .iter()
+            .map(|chunk_header| chunk_header.prev_state_root())
+            .collect::<Vec<_>>();
+        let chunk_prev_state_root = *prev_state_roots.get(shard_id as usize).ok_or_else(|| {
+            format!("Block {} has no chunk for shard {}", header.hash(), shard_id)
+        })?;
+        let chunk_prev_state_root_proof = merklize(&prev_state_roots).1[shard_id as usize].clone();
+
+        let (query_response, partial_storage) = self
+            .runtime_adapter
+            .query_with_proof(
+                shard_id,
+                &chunk_prev_state_root,
+                header.height(),
+                header.hash(),
+                &msg.request,
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(GetQueryProofResponse {
+            query_response,
+            state_proof: partial_storage.nodes.0.iter().map(to_base64).collect(),
+            chunk_prev_state_root,
+            chunk_prev_state_root_proof,
+        })
+    }
+}
+
 /// Handles retrieving block from the chain.
 impl Handler<GetBlock> for ViewClientActor {
     type Result = Result<BlockView, GetBlockError>;
@@ -637,6 +697,60 @@ impl Handler<GetStateChangesWithCauseInBlock> for ViewClientActor {
     }
 }
 
+/// Returns the differences between the states of a shard at two blocks.
+impl Handler<GetStateDiff> for ViewClientActor {
+    type Result = Result<StateDiffView, String>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetStateDiff, _: &mut Self::Context) -> Self::Result {
+        let from_block_hash = self
+            .maybe_block_id_to_block_hash(Some(msg.from_block_id))
+            .map_err(|err| err.to_string())?;
+        let to_block_hash = self
+            .maybe_block_id_to_block_hash(Some(msg.to_block_id))
+            .map_err(|err| err.to_string())?;
+        let from_state_root = self
+            .chain
+            .get_chunk_extra(&from_block_hash, msg.shard_id)
+            .map_err(|err| err.to_string())?
+            .state_root;
+        let to_state_root = self
+            .chain
+            .get_chunk_extra(&to_block_hash, msg.shard_id)
+            .map_err(|err| err.to_string())?
+            .state_root;
+        let limit = msg.limit.unwrap_or(STATE_DIFF_LIMIT).min(STATE_DIFF_LIMIT);
+        let diff = self
+            .runtime_adapter
+            .get_view_trie_for_shard(msg.shard_id)
+            .diff(&from_state_root, &to_state_root, &msg.start_key, limit)
+            .map_err(|err| err.to_string())?;
+        Ok(StateDiffView {
+            shard_id: msg.shard_id,
+            from_block_hash,
+            from_state_root,
+            to_block_hash,
+            to_state_root,
+            changes: diff.items.into_iter().map(Into::into).collect(),
+            next_key: diff.next_key,
+        })
+    }
+}
+
+/// Creates a checkpoint of the database. It's done here rather than in the client actor
+/// so that the block processing isn't blocked while the files are linked or copied.
+impl Handler<CreateCheckpoint> for ViewClientActor {
+    type Result = Result<CheckpointManifest, String>;
+
+    #[perf]
+    fn handle(&mut self, msg: CreateCheckpoint, _: &mut Self::Context) -> Self::Result {
+        let manifest = create_checkpoint(&self.chain.store().owned_store(), &msg.path)
+            .map_err(|err| err.to_string())?;
+        info!(target: "client", "Created checkpoint at height {} in {}", manifest.height, msg.path.display());
+        Ok(manifest)
+    }
+}
+
 /// Returns the next light client block, given the hash of the last block known to the light client.
 /// There are three cases:
 ///  1. The last block known to the light client is in the same epoch as the tip:
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::iter::FromIterator;
 use std::path::Path;
@@ -782,6 +782,7 @@ fn client_sync_headers() {
                 sent_bytes_per_sec: 0,
                 received_bytes_per_sec: 0,
                 known_producers: vec![],
+                peer_compression: HashMap::new(),
                 #[cfg(feature = "metric_recorder")]
                 metric_recorder: MetricRecorder::default(),
                 peer_counter: 0,
@@ -1343,6 +1344,61 @@ fn test_gc_execution_outcome() {
     assert!(env.clients[0].chain.get_final_transaction_result(&tx_hash).is_err());
 }
 
+/// Pending transactions show up in the pool status and transactions, and a removed transaction
+/// is not included into the chain.
+#[test]
+fn test_pool_transactions_and_removal() {
+    let genesis = Genesis::test(vec!["test0", "test1"], 1);
+    let mut env = TestEnv::new_with_runtime(
+        ChainGenesis::from(&genesis),
+        1,
+        1,
+        create_nightshade_runtimes(&genesis, 1),
+    );
+    let genesis_hash = *env.clients[0].chain.genesis().hash();
+    let signer = InMemorySigner::from_seed("test0", KeyType::ED25519, "test0");
+    let transactions: Vec<_> = (1..=2)
+        .map(|nonce| {
+            SignedTransaction::send_money(
+                nonce,
+                "test0".to_string(),
+                "test1".to_string(),
+                &signer,
+                100,
+                genesis_hash,
+            )
+        })
+        .collect();
+    for tx in transactions.iter() {
+        assert_eq!(
+            env.clients[0].process_tx(tx.clone(), false, false),
+            NetworkClientResponses::ValidTx
+        );
+    }
+
+    let status = env.clients[0].pool_status();
+    assert_eq!(status.shards.len(), 1);
+    assert_eq!(status.shards[0].num_transactions, 2);
+    let pending = env.clients[0].pool_transactions(Some(&"test0".to_string())).transactions;
+    assert_eq!(
+        pending.iter().map(|view| view.transaction.hash).collect::<Vec<_>>(),
+        transactions.iter().map(|tx| tx.get_hash()).collect::<Vec<_>>()
+    );
+    assert!(env.clients[0].pool_transactions(Some(&"test1".to_string())).transactions.is_empty());
+
+    let removed_hash = transactions[0].get_hash();
+    assert_eq!(env.clients[0].remove_pool_transaction(&removed_hash), Some(0));
+    assert_eq!(env.clients[0].remove_pool_transaction(&removed_hash), None);
+    assert_eq!(env.clients[0].pool_status().shards[0].num_transactions, 1);
+
+    for height in 1..5 {
+        env.produce_block(0, height);
+    }
+    assert!(env.clients[0].chain.get_final_transaction_result(&removed_hash).is_err());
+    assert!(env.clients[0].chain.get_final_transaction_result(&transactions[1].get_hash()).is_ok());
+    assert_eq!(env.clients[0].pool_status().shards[0].num_transactions, 0);
+}
+
 #[cfg(feature = "expensive_tests")]
 #[test]
 fn test_gc_after_state_sync() {
@@ -7,13 +7,16 @@
 //! See the [example] for further details.
 //!
 //! [example]: https://github.com/nearprotocol/nearcore/tree/master/tools/indexer/example
-use tokio::sync::mpsc;
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, watch};
 
 pub use neard::{get_default_home, init_configs, NearConfig};
 mod streamer;
 
 pub use self::streamer::{
-    IndexerChunkView, IndexerExecutionOutcomeWithReceipt, IndexerTransactionWithOutcome,
+    ActionKind, CheckpointError, IndexerAcknowledger, IndexerChunkView,
+    IndexerExecutionOutcomeWithReceipt, IndexerFilter, IndexerTransactionWithOutcome,
     StreamerMessage,
 };
 pub use near_primitives;
@@ -38,6 +41,21 @@ pub enum AwaitForNodeSyncedEnum {
     StreamWhileSyncing,
 }
 
+/// Enum to define delivery guarantees of `StreamerMessage`s across restarts of NEAR Indexer
+#[derive(Debug, Clone)]
+pub enum DeliveryModeEnum {
+    /// Block is considered delivered as soon as it is sent to the channel (`FromInterruption`
+    /// may skip the blocks the consumer hasn't processed before the crash)
+    AtMostOnce,
+    /// Consumer acknowledges processed blocks with `IndexerAcknowledger`, and `FromInterruption`
+    /// resumes right after the last acknowledged block, so unacknowledged blocks are streamed again
+    AtLeastOnce,
+    /// Same as `AtLeastOnce`, but the next block is not streamed until the previous one is
+    /// acknowledged, so only the block the consumer hasn't committed can be streamed again.
+    /// Consumer should acknowledge the block atomically with committing its results
+    ExactlyOnce,
+}
+
 /// NEAR Indexer configuration to be provided to `Indexer::new(IndexerConfig)`
 #[derive(Debug, Clone)]
 pub struct IndexerConfig {
@@ -47,6 +65,23 @@ pub struct IndexerConfig {
     pub sync_mode: SyncModeEnum,
     /// Whether await for node to be synced or not
     pub await_for_node_synced: AwaitForNodeSyncedEnum,
+    /// Filter to stream only the data related to the accounts, actions and methods of interest
+    pub filter: IndexerFilter,
+    /// Delivery guarantees of `StreamerMessage`s
+    pub delivery_mode: DeliveryModeEnum,
+}
+
+/// Configuration of `HistoricalIndexer` to be provided to `HistoricalIndexer::new(HistoricalIndexerConfig)`
+#[derive(Debug, Clone)]
+pub struct HistoricalIndexerConfig {
+    /// Path to `home_dir` where configs and the archival database can be found
+    pub home_dir: std::path::PathBuf,
+    /// Height of the first block to stream
+    pub start_block_height: near_primitives::types::BlockHeight,
+    /// Height of the last block to stream (inclusive)
+    pub end_block_height: near_primitives::types::BlockHeight,
+    /// Filter to stream only the data related to the accounts, actions and methods of interest
+    pub filter: IndexerFilter,
 }
 
 /// This is the core component, which handles `nearcore` and internal `streamer`.
@@ -55,6 +90,9 @@ pub struct Indexer {
     near_config: neard::NearConfig,
     view_client: actix::Addr<near_client::ViewClientActor>,
     client: actix::Addr<near_client::ClientActor>,
+    db: Arc<rocksdb::DB>,
+    acknowledger: IndexerAcknowledger,
+    committed_block_height: watch::Receiver<Option<near_primitives::types::BlockHeight>>,
 }
 
 impl Indexer {
@@ -69,9 +107,30 @@ impl Indexer {
             ",
             indexer_config.home_dir.join("config.json").display()
         );
+        let db = Arc::new(
+            streamer::open_indexer_db(&indexer_config.home_dir)
+                .expect("Failed to open the indexer database"),
+        );
+        let committed_block_height = streamer::get_committed_block_height(&db)
+            .expect("Failed to read the committed block height from the indexer database");
+        let (committed_block_height_sender, committed_block_height_receiver) =
+            watch::channel(committed_block_height);
+        let acknowledger = IndexerAcknowledger::new(
+            db.clone(),
+            committed_block_height,
+            committed_block_height_sender,
+        );
         let (client, view_client, _) =
             neard::start_with_config(&indexer_config.home_dir, near_config.clone());
-        Self { view_client, client, near_config, indexer_config }
+        Self {
+            view_client,
+            client,
+            near_config,
+            indexer_config,
+            db,
+            acknowledger,
+            committed_block_height: committed_block_height_receiver,
+        }
     }
 
     /// Boots up `near_indexer::streamer`, so it monitors the new blocks with chunks, transactions, receipts, and execution outcomes inside. The returned stream handler should be drained and handled on the user side.
@@ -82,11 +141,19 @@ impl Indexer {
             self.client.clone(),
             self.near_config.clone(),
             self.indexer_config.clone(),
+            self.db.clone(),
+            self.committed_block_height.clone(),
             sender,
         ));
         receiver
     }
 
+    /// Returns a handle to acknowledge the `StreamerMessage`s the consumer has committed.
+    /// Required for `DeliveryModeEnum::AtLeastOnce` and `DeliveryModeEnum::ExactlyOnce`
+    pub fn acknowledger(&self) -> IndexerAcknowledger {
+        self.acknowledger.clone()
+    }
+
     /// Expose neard config
     pub fn near_config(&self) -> &neard::NearConfig {
         &self.near_config
@@ -99,3 +166,56 @@ impl Indexer {
         (self.view_client.clone(), self.client.clone())
     }
 }
+
+/// Indexer which replays the blocks of the given height range from an existing archival database
+/// without joining the network. Only `ViewClientActor` is started on top of the database, which
+/// builds the views from `ChainStore` directly, so no node must be running on the same `home_dir`.
+pub struct HistoricalIndexer {
+    indexer_config: HistoricalIndexerConfig,
+    near_config: neard::NearConfig,
+    view_client: actix::Addr<near_client::ViewClientActor>,
+}
+
+impl HistoricalIndexer {
+    /// Initialize HistoricalIndexer by opening the existing database
+    pub fn new(indexer_config: HistoricalIndexerConfig) -> Self {
+        let near_config = neard::load_config(&indexer_config.home_dir);
+        neard::genesis_validate::validate_genesis(&near_config.genesis);
+        assert!(
+            near_config.client_config.archive,
+            "Historical indexing requires an archival node database. \n\
+            Tip: You may want to update {} with `\"archive\": true`
+            ",
+            indexer_config.home_dir.join("config.json").display()
+        );
+        assert!(
+            indexer_config.start_block_height <= indexer_config.end_block_height,
+            "Start block height #{} is greater than end block height #{}",
+            indexer_config.start_block_height,
+            indexer_config.end_block_height
+        );
+        let view_client = neard::start_view_client_with_existing_store(
+            &indexer_config.home_dir,
+            near_config.clone(),
+        );
+        Self { indexer_config, near_config, view_client }
+    }
+
+    /// Boots up `near_indexer::streamer` over the configured block range. The returned stream
+    /// handler should be drained and handled on the user side, it is closed after the last block.
+    pub fn streamer(&self) -> mpsc::Receiver<streamer::StreamerMessage> {
+        let (sender, receiver) = mpsc::channel(16);
+        actix::spawn(streamer::start_historical(
+            self.view_client.clone(),
+            self.near_config.clone(),
+            self.indexer_config.clone(),
+            sender,
+        ));
+        receiver
+    }
+
+    /// Expose neard config
+    pub fn near_config(&self) -> &neard::NearConfig {
+        &self.near_config
+    }
+}
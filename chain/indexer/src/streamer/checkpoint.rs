@@ -0,0 +1,248 @@
+//! Persistent checkpoints of the Indexer.
+//!
+//! Besides the height of the last block the streamer has sent (`last_synced_block_height`),
+//! the height of the last block the consumer has acknowledged is stored in a dedicated
+//! `checkpoints` column family of the indexer database.
+use std::sync::{Arc, Mutex};
+
+use rocksdb::DB;
+use tokio::sync::watch;
+
+use near_primitives::types::BlockHeight;
+
+use super::errors::CheckpointError;
+use crate::{DeliveryModeEnum, SyncModeEnum};
+
+const CHECKPOINTS_COLUMN: &str = "checkpoints";
+const LAST_SYNCED_BLOCK_HEIGHT_KEY: &[u8] = b"last_synced_block_height";
+const COMMITTED_BLOCK_HEIGHT_KEY: &[u8] = b"committed_block_height";
+
+/// Opens the indexer database (`<store>/indexer`) with all the required column families
+pub(crate) fn open_indexer_db(home_dir: &std::path::Path) -> Result<DB, CheckpointError> {
+    let mut indexer_db_path = neard::get_store_path(home_dir);
+    indexer_db_path.push_str("/indexer");
+
+    let mut options = rocksdb::Options::default();
+    options.create_if_missing(true);
+    options.create_missing_column_families(true);
+    Ok(DB::open_cf(&options, indexer_db_path, &[CHECKPOINTS_COLUMN])?)
+}
+
+fn checkpoints_column(db: &DB) -> Result<&rocksdb::ColumnFamily, CheckpointError> {
+    db.cf_handle(CHECKPOINTS_COLUMN).ok_or(CheckpointError::MissingColumnFamily)
+}
+
+fn parse_block_height(value: Vec<u8>) -> Result<BlockHeight, CheckpointError> {
+    std::str::from_utf8(&value)
+        .ok()
+        .and_then(|value| value.parse::<BlockHeight>().ok())
+        .ok_or(CheckpointError::InvalidBlockHeight(value))
+}
+
+/// Returns the height of the last block sent by the streamer
+pub(crate) fn get_last_synced_block_height(
+    db: &DB,
+) -> Result<Option<BlockHeight>, CheckpointError> {
+    db.get(LAST_SYNCED_BLOCK_HEIGHT_KEY)?.map(parse_block_height).transpose()
+}
+
+pub(crate) fn set_last_synced_block_height(
+    db: &DB,
+    block_height: BlockHeight,
+) -> Result<(), CheckpointError> {
+    Ok(db.put(LAST_SYNCED_BLOCK_HEIGHT_KEY, &block_height.to_string())?)
+}
+
+/// Returns the height of the last block acknowledged by the consumer
+pub(crate) fn get_committed_block_height(db: &DB) -> Result<Option<BlockHeight>, CheckpointError> {
+    db.get_cf(checkpoints_column(db)?, COMMITTED_BLOCK_HEIGHT_KEY)?
+        .map(parse_block_height)
+        .transpose()
+}
+
+/// Returns the height of the block the streamer should start from, given the height of the
+/// latest final block when it (re)starts.
+pub(crate) fn get_start_block_height(
+    db: &DB,
+    sync_mode: &SyncModeEnum,
+    delivery_mode: &DeliveryModeEnum,
+    committed_block_height: Option<BlockHeight>,
+    latest_block_height: BlockHeight,
+) -> Result<BlockHeight, CheckpointError> {
+    Ok(match sync_mode {
+        SyncModeEnum::FromInterruption => match delivery_mode {
+            DeliveryModeEnum::AtMostOnce => {
+                get_last_synced_block_height(db)?.unwrap_or(latest_block_height)
+            }
+            DeliveryModeEnum::AtLeastOnce | DeliveryModeEnum::ExactlyOnce => {
+                match committed_block_height {
+                    Some(committed_block_height) => committed_block_height + 1,
+                    None => latest_block_height,
+                }
+            }
+        },
+        SyncModeEnum::LatestSynced => latest_block_height,
+        SyncModeEnum::BlockHeight(height) => *height,
+    })
+}
+
+/// Handle to acknowledge `StreamerMessage`s the consumer has committed.
+///
+/// Acknowledged height is persisted, so the streamer started with
+/// `SyncModeEnum::FromInterruption` and a `DeliveryModeEnum` other than `AtMostOnce` resumes
+/// right after the last acknowledged block.
+#[derive(Clone)]
+pub struct IndexerAcknowledger {
+    db: Arc<DB>,
+    committed_block_height: Arc<Mutex<Option<BlockHeight>>>,
+    committed_block_height_sender: Arc<watch::Sender<Option<BlockHeight>>>,
+}
+
+impl IndexerAcknowledger {
+    pub(crate) fn new(
+        db: Arc<DB>,
+        committed_block_height: Option<BlockHeight>,
+        committed_block_height_sender: watch::Sender<Option<BlockHeight>>,
+    ) -> Self {
+        Self {
+            db,
+            committed_block_height: Arc::new(Mutex::new(committed_block_height)),
+            committed_block_height_sender: Arc::new(committed_block_height_sender),
+        }
+    }
+
+    /// Records that the consumer has committed all the blocks up to `block_height` (inclusive).
+    /// Acknowledging a height lower than the already committed one is a no-op.
+    pub fn acknowledge(&self, block_height: BlockHeight) -> Result<(), CheckpointError> {
+        let mut committed_block_height =
+            self.committed_block_height.lock().expect("Lock should not be poisoned");
+        if matches!(*committed_block_height, Some(committed) if committed >= block_height) {
+            return Ok(());
+        }
+        self.db.put_cf(
+            checkpoints_column(&self.db)?,
+            COMMITTED_BLOCK_HEIGHT_KEY,
+            &block_height.to_string(),
+        )?;
+        *committed_block_height = Some(block_height);
+        // The streamer might have been stopped already, there is nobody to notify then
+        let _ = self.committed_block_height_sender.send(Some(block_height));
+        Ok(())
+    }
+
+    /// Returns the height of the last block acknowledged by the consumer
+    pub fn committed_block_height(&self) -> Option<BlockHeight> {
+        *self.committed_block_height.lock().expect("Lock should not be poisoned")
+    }
+}
+
+/// Waits until the consumer acknowledges the block of the given height.
+/// Returns `false` if acknowledgements can't be received anymore.
+pub(crate) async fn wait_for_acknowledgement(
+    committed_block_height: &mut watch::Receiver<Option<BlockHeight>>,
+    block_height: BlockHeight,
+) -> bool {
+    loop {
+        if matches!(*committed_block_height.borrow(), Some(committed) if committed >= block_height)
+        {
+            return true;
+        }
+        if committed_block_height.changed().await.is_err() {
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::sync::watch;
+
+    use super::{
+        get_committed_block_height, get_last_synced_block_height, get_start_block_height,
+        open_indexer_db, set_last_synced_block_height, wait_for_acknowledgement,
+        IndexerAcknowledger,
+    };
+    use crate::{DeliveryModeEnum, SyncModeEnum};
+
+    #[test]
+    fn test_acknowledge_and_resume() {
+        let home_dir = tempfile::Builder::new().prefix("indexer").tempdir().unwrap();
+        std::fs::create_dir_all(neard::get_store_path(home_dir.path())).unwrap();
+
+        {
+            let db = Arc::new(open_indexer_db(home_dir.path()).unwrap());
+            assert_eq!(get_committed_block_height(&db).unwrap(), None);
+            assert_eq!(get_last_synced_block_height(&db).unwrap(), None);
+            // Nothing is acknowledged yet, so the streamer starts from the latest block.
+            assert_eq!(
+                get_start_block_height(
+                    &db,
+                    &SyncModeEnum::FromInterruption,
+                    &DeliveryModeEnum::AtLeastOnce,
+                    None,
+                    100
+                )
+                .unwrap(),
+                100
+            );
+
+            let (sender, mut receiver) = watch::channel(None);
+            let acknowledger = IndexerAcknowledger::new(db.clone(), None, sender);
+            acknowledger.acknowledge(10).unwrap();
+            // Acknowledging an older block doesn't move the checkpoint back.
+            acknowledger.acknowledge(7).unwrap();
+            assert_eq!(acknowledger.committed_block_height(), Some(10));
+            assert!(futures::executor::block_on(wait_for_acknowledgement(&mut receiver, 10)));
+            set_last_synced_block_height(&db, 12).unwrap();
+        }
+
+        let db = open_indexer_db(home_dir.path()).unwrap();
+        let committed_block_height = get_committed_block_height(&db).unwrap();
+        assert_eq!(committed_block_height, Some(10));
+        assert_eq!(get_last_synced_block_height(&db).unwrap(), Some(12));
+        let start_block_height = |sync_mode, delivery_mode| {
+            get_start_block_height(&db, &sync_mode, &delivery_mode, committed_block_height, 100)
+                .unwrap()
+        };
+        // Unacknowledged blocks are streamed again.
+        assert_eq!(
+            start_block_height(SyncModeEnum::FromInterruption, DeliveryModeEnum::AtLeastOnce),
+            11
+        );
+        assert_eq!(
+            start_block_height(SyncModeEnum::FromInterruption, DeliveryModeEnum::ExactlyOnce),
+            11
+        );
+        assert_eq!(
+            start_block_height(SyncModeEnum::FromInterruption, DeliveryModeEnum::AtMostOnce),
+            12
+        );
+        assert_eq!(
+            start_block_height(SyncModeEnum::LatestSynced, DeliveryModeEnum::AtLeastOnce),
+            100
+        );
+        assert_eq!(
+            start_block_height(SyncModeEnum::BlockHeight(5), DeliveryModeEnum::AtLeastOnce),
+            5
+        );
+    }
+
+    #[test]
+    fn test_invalid_checkpoint() {
+        let home_dir = tempfile::Builder::new().prefix("indexer").tempdir().unwrap();
+        std::fs::create_dir_all(neard::get_store_path(home_dir.path())).unwrap();
+        let db = open_indexer_db(home_dir.path()).unwrap();
+        db.put(super::LAST_SYNCED_BLOCK_HEIGHT_KEY, b"not a height").unwrap();
+        assert!(get_last_synced_block_height(&db).is_err());
+        assert!(get_start_block_height(
+            &db,
+            &SyncModeEnum::FromInterruption,
+            &DeliveryModeEnum::AtMostOnce,
+            None,
+            100
+        )
+        .is_err());
+    }
+}
@@ -12,3 +12,35 @@ impl From<MailboxError> for FailedToFetchData {
         FailedToFetchData::MailboxError(actix_error)
     }
 }
+
+/// Error occurs in case of failed read or write of the checkpoints in the indexer database
+#[derive(Debug)]
+pub enum CheckpointError {
+    RocksDBError(rocksdb::Error),
+    /// The database was opened without the `checkpoints` column family
+    MissingColumnFamily,
+    /// The stored value is not a block height
+    InvalidBlockHeight(Vec<u8>),
+}
+
+impl From<rocksdb::Error> for CheckpointError {
+    fn from(rocksdb_error: rocksdb::Error) -> Self {
+        CheckpointError::RocksDBError(rocksdb_error)
+    }
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::RocksDBError(err) => write!(f, "RocksDB error: {}", err),
+            CheckpointError::MissingColumnFamily => {
+                write!(f, "Indexer database has no checkpoints column family")
+            }
+            CheckpointError::InvalidBlockHeight(value) => {
+                write!(f, "Invalid block height {:?} in the indexer database", value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
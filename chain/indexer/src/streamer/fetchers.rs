@@ -10,6 +10,7 @@ pub use near_primitives::hash::CryptoHash;
 pub use near_primitives::{types, views};
 
 use super::errors::FailedToFetchData;
+use super::filter::IndexerFilter;
 use super::types::IndexerExecutionOutcomeWithReceipt;
 use super::INDEXER;
 
@@ -57,14 +58,38 @@ pub(crate) async fn fetch_block_by_hash(
         .map_err(|err| FailedToFetchData::String(err.to_string()))
 }
 
+/// Fetches the state changes of the block. If the `filter` is interested in particular accounts,
+/// only the changes of their accounts, access keys, contract code and data are requested.
 pub(crate) async fn fetch_state_changes(
     client: &Addr<near_client::ViewClientActor>,
     block_hash: CryptoHash,
+    filter: &IndexerFilter,
 ) -> Result<views::StateChangesView, FailedToFetchData> {
-    client
-        .send(near_client::GetStateChangesWithCauseInBlock { block_hash })
-        .await?
-        .map_err(FailedToFetchData::String)
+    let account_ids = match filter.state_change_account_ids() {
+        Some(account_ids) => account_ids,
+        None => {
+            return client
+                .send(near_client::GetStateChangesWithCauseInBlock { block_hash })
+                .await?
+                .map_err(FailedToFetchData::String);
+        }
+    };
+    let requests = vec![
+        views::StateChangesRequestView::AccountChanges { account_ids: account_ids.clone() },
+        views::StateChangesRequestView::AllAccessKeyChanges { account_ids: account_ids.clone() },
+        views::StateChangesRequestView::ContractCodeChanges { account_ids: account_ids.clone() },
+        views::StateChangesRequestView::DataChanges { account_ids, key_prefix: vec![].into() },
+    ];
+    let mut state_changes = views::StateChangesView::new();
+    for state_changes_request in requests {
+        state_changes.extend(
+            client
+                .send(near_client::GetStateChanges { block_hash, state_changes_request })
+                .await?
+                .map_err(FailedToFetchData::String)?,
+        );
+    }
+    Ok(state_changes)
 }
 
 /// Fetches single chunk (as `near_primitives::views::ChunkView`) by provided `near_client::GetChunk` enum
@@ -77,9 +102,11 @@ async fn fetch_single_chunk(
 
 /// Fetch all ExecutionOutcomeWithId for current block
 /// Returns a HashMap where the key is Receipt id or Transaction hash and the value is ExecutionOutcome wth id and proof
+/// Receipts are fetched only for the outcomes whose executor passes the `filter`
 pub(crate) async fn fetch_outcomes(
     client: &Addr<near_client::ViewClientActor>,
     block_hash: CryptoHash,
+    filter: &IndexerFilter,
 ) -> Result<
     HashMap<near_primitives::types::ShardId, Vec<IndexerExecutionOutcomeWithReceipt>>,
     FailedToFetchData,
@@ -94,8 +121,18 @@ pub(crate) async fn fetch_outcomes(
         Vec<IndexerExecutionOutcomeWithReceipt>,
     > = HashMap::new();
     for (shard_id, shard_outcomes) in outcomes {
+        if !filter.matches_shard(shard_id) {
+            continue;
+        }
         let mut outcomes_with_receipts: Vec<IndexerExecutionOutcomeWithReceipt> = vec![];
         for outcome in shard_outcomes {
+            if !filter.matches_receiver(&outcome.outcome.executor_id) {
+                outcomes_with_receipts.push(IndexerExecutionOutcomeWithReceipt {
+                    execution_outcome: outcome,
+                    receipt: None,
+                });
+                continue;
+            }
             let receipt = match fetch_receipt_by_id(&client, outcome.id).await {
                 Ok(res) => res,
                 Err(e) => {
@@ -0,0 +1,285 @@
+use std::collections::HashSet;
+
+use near_primitives::types::{AccountId, ShardId};
+use near_primitives::views;
+
+/// Kind of an action, used to filter transactions and receipts by the actions they contain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActionKind {
+    CreateAccount,
+    DeployContract,
+    FunctionCall,
+    Transfer,
+    Stake,
+    AddKey,
+    DeleteKey,
+    DeleteAccount,
+}
+
+impl From<&views::ActionView> for ActionKind {
+    fn from(action: &views::ActionView) -> Self {
+        match action {
+            views::ActionView::CreateAccount => Self::CreateAccount,
+            views::ActionView::DeployContract { .. } => Self::DeployContract,
+            views::ActionView::FunctionCall { .. } => Self::FunctionCall,
+            views::ActionView::Transfer { .. } => Self::Transfer,
+            views::ActionView::Stake { .. } => Self::Stake,
+            views::ActionView::AddKey { .. } => Self::AddKey,
+            views::ActionView::DeleteKey { .. } => Self::DeleteKey,
+            views::ActionView::DeleteAccount { .. } => Self::DeleteAccount,
+        }
+    }
+}
+
+/// Filter to limit the data streamed by NEAR Indexer to the transactions, receipts
+/// and state changes the consumer is interested in.
+///
+/// Every empty set matches anything. Non-empty sets are combined with logical AND,
+/// e.g. `receiver_ids: ["app.near"]` and `method_names: ["ft_transfer"]` streams only
+/// `ft_transfer` calls to `app.near`.
+///
+/// Data which doesn't match the filter is dropped inside the streamer, so the chunks of
+/// unmatched shards, the receipts of unmatched execution outcomes and the state changes of
+/// unmatched accounts are never fetched from the node.
+#[derive(Debug, Clone, Default)]
+pub struct IndexerFilter {
+    /// Shards whose chunks and execution outcomes to stream
+    pub shard_ids: HashSet<ShardId>,
+    /// Receivers of transactions and receipts to stream
+    pub receiver_ids: HashSet<AccountId>,
+    /// Signers of transactions and action receipts to stream
+    pub signer_ids: HashSet<AccountId>,
+    /// Transactions and action receipts should contain at least one action of these kinds
+    pub action_kinds: HashSet<ActionKind>,
+    /// Transactions and action receipts should contain at least one `FunctionCall` to one of these methods
+    pub method_names: HashSet<String>,
+}
+
+impl IndexerFilter {
+    /// Returns `true` if the filter matches everything
+    pub fn is_empty(&self) -> bool {
+        self.shard_ids.is_empty() && self.matches_any_receipt()
+    }
+
+    /// Returns `true` if the filter doesn't look into transactions and receipts
+    pub(crate) fn matches_any_receipt(&self) -> bool {
+        self.receiver_ids.is_empty()
+            && self.signer_ids.is_empty()
+            && self.action_kinds.is_empty()
+            && self.method_names.is_empty()
+    }
+
+    pub(crate) fn matches_shard(&self, shard_id: ShardId) -> bool {
+        self.shard_ids.is_empty() || self.shard_ids.contains(&shard_id)
+    }
+
+    pub(crate) fn matches_receiver(&self, receiver_id: &AccountId) -> bool {
+        self.receiver_ids.is_empty() || self.receiver_ids.contains(receiver_id)
+    }
+
+    fn matches_signer(&self, signer_id: &AccountId) -> bool {
+        self.signer_ids.is_empty() || self.signer_ids.contains(signer_id)
+    }
+
+    fn matches_actions(&self, actions: &[views::ActionView]) -> bool {
+        let action_kind_matches = self.action_kinds.is_empty()
+            || actions.iter().any(|action| self.action_kinds.contains(&ActionKind::from(action)));
+        let method_name_matches = self.method_names.is_empty()
+            || actions.iter().any(|action| match action {
+                views::ActionView::FunctionCall { method_name, .. } => {
+                    self.method_names.contains(method_name)
+                }
+                _ => false,
+            });
+        action_kind_matches && method_name_matches
+    }
+
+    pub(crate) fn matches_transaction(&self, transaction: &views::SignedTransactionView) -> bool {
+        self.matches_receiver(&transaction.receiver_id)
+            && self.matches_signer(&transaction.signer_id)
+            && self.matches_actions(&transaction.actions)
+    }
+
+    pub(crate) fn matches_receipt(&self, receipt: &views::ReceiptView) -> bool {
+        if !self.matches_receiver(&receipt.receiver_id) {
+            return false;
+        }
+        match &receipt.receipt {
+            views::ReceiptEnumView::Action { signer_id, actions, .. } => {
+                self.matches_signer(signer_id) && self.matches_actions(actions)
+            }
+            // Data receipts have neither a signer nor actions
+            views::ReceiptEnumView::Data { .. } => {
+                self.signer_ids.is_empty()
+                    && self.action_kinds.is_empty()
+                    && self.method_names.is_empty()
+            }
+        }
+    }
+
+    /// Accounts whose state changes to stream, either the receivers or the signers the filter
+    /// is interested in. `None` if the state changes of all accounts should be streamed.
+    pub(crate) fn state_change_account_ids(&self) -> Option<Vec<AccountId>> {
+        if self.receiver_ids.is_empty() && self.signer_ids.is_empty() {
+            return None;
+        }
+        let mut account_ids: Vec<AccountId> =
+            self.receiver_ids.union(&self.signer_ids).cloned().collect();
+        account_ids.sort();
+        Some(account_ids)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{KeyType, PublicKey, Signature};
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::views;
+
+    use super::{ActionKind, IndexerFilter};
+
+    fn transfer() -> views::ActionView {
+        views::ActionView::Transfer { deposit: 1 }
+    }
+
+    fn function_call(method_name: &str) -> views::ActionView {
+        views::ActionView::FunctionCall {
+            method_name: method_name.to_string(),
+            args: String::new(),
+            gas: 0,
+            deposit: 0,
+        }
+    }
+
+    fn transaction(
+        signer_id: &str,
+        receiver_id: &str,
+        actions: Vec<views::ActionView>,
+    ) -> views::SignedTransactionView {
+        views::SignedTransactionView {
+            signer_id: signer_id.to_string(),
+            public_key: PublicKey::empty(KeyType::ED25519),
+            nonce: 0,
+            receiver_id: receiver_id.to_string(),
+            actions,
+            signature: Signature::default(),
+            hash: CryptoHash::default(),
+        }
+    }
+
+    fn action_receipt(
+        signer_id: &str,
+        receiver_id: &str,
+        actions: Vec<views::ActionView>,
+    ) -> views::ReceiptView {
+        views::ReceiptView {
+            predecessor_id: signer_id.to_string(),
+            receiver_id: receiver_id.to_string(),
+            receipt_id: CryptoHash::default(),
+            receipt: views::ReceiptEnumView::Action {
+                signer_id: signer_id.to_string(),
+                signer_public_key: PublicKey::empty(KeyType::ED25519),
+                gas_price: 0,
+                output_data_receivers: vec![],
+                input_data_ids: vec![],
+                actions,
+            },
+        }
+    }
+
+    fn data_receipt(receiver_id: &str) -> views::ReceiptView {
+        views::ReceiptView {
+            predecessor_id: "alice".to_string(),
+            receiver_id: receiver_id.to_string(),
+            receipt_id: CryptoHash::default(),
+            receipt: views::ReceiptEnumView::Data { data_id: CryptoHash::default(), data: None },
+        }
+    }
+
+    #[test]
+    fn test_empty_filter_matches_everything() {
+        let filter = IndexerFilter::default();
+        assert!(filter.is_empty());
+        assert!(filter.matches_shard(3));
+        assert!(filter.matches_transaction(&transaction("alice", "bob", vec![])));
+        assert!(filter.matches_receipt(&action_receipt("alice", "bob", vec![transfer()])));
+        assert!(filter.matches_receipt(&data_receipt("bob")));
+        assert_eq!(filter.state_change_account_ids(), None);
+    }
+
+    #[test]
+    fn test_shard_filter() {
+        let filter =
+            IndexerFilter { shard_ids: vec![1].into_iter().collect(), ..Default::default() };
+        assert!(!filter.is_empty());
+        assert!(filter.matches_shard(1));
+        assert!(!filter.matches_shard(0));
+        // Transactions and receipts of the streamed shards are not filtered any further.
+        assert!(filter.matches_any_receipt());
+        assert!(filter.matches_transaction(&transaction("alice", "bob", vec![])));
+        assert!(filter.matches_receipt(&data_receipt("bob")));
+    }
+
+    #[test]
+    fn test_account_filter() {
+        let filter = IndexerFilter {
+            receiver_ids: vec!["app".to_string()].into_iter().collect(),
+            signer_ids: vec!["alice".to_string()].into_iter().collect(),
+            ..Default::default()
+        };
+        assert!(!filter.matches_any_receipt());
+        assert!(filter.matches_transaction(&transaction("alice", "app", vec![transfer()])));
+        assert!(!filter.matches_transaction(&transaction("bob", "app", vec![transfer()])));
+        assert!(!filter.matches_transaction(&transaction("alice", "bob", vec![transfer()])));
+        assert!(filter.matches_receipt(&action_receipt("alice", "app", vec![])));
+        assert!(!filter.matches_receipt(&action_receipt("alice", "bob", vec![])));
+        // Data receipts have no signer to match.
+        assert!(!filter.matches_receipt(&data_receipt("app")));
+
+        // State changes of either the receivers or the signers are streamed.
+        assert_eq!(
+            filter.state_change_account_ids(),
+            Some(vec!["alice".to_string(), "app".to_string()])
+        );
+
+        let filter = IndexerFilter {
+            receiver_ids: vec!["app".to_string()].into_iter().collect(),
+            ..Default::default()
+        };
+        assert!(filter.matches_receipt(&data_receipt("app")));
+        assert!(!filter.matches_receipt(&data_receipt("bob")));
+    }
+
+    #[test]
+    fn test_action_filter() {
+        let filter = IndexerFilter {
+            action_kinds: vec![ActionKind::FunctionCall].into_iter().collect(),
+            method_names: vec!["ft_transfer".to_string()].into_iter().collect(),
+            ..Default::default()
+        };
+        let ft_transfer = vec![transfer(), function_call("ft_transfer")];
+        assert!(filter.matches_transaction(&transaction("alice", "app", ft_transfer.clone())));
+        assert!(filter.matches_receipt(&action_receipt("alice", "app", ft_transfer)));
+        assert!(!filter.matches_transaction(&transaction("alice", "app", vec![transfer()])));
+        assert!(!filter.matches_receipt(&action_receipt(
+            "alice",
+            "app",
+            vec![function_call("ft_balance_of")]
+        )));
+        assert!(!filter.matches_receipt(&data_receipt("app")));
+        // Action filters don't apply to state changes.
+        assert_eq!(filter.state_change_account_ids(), None);
+
+        let filter = IndexerFilter {
+            action_kinds: vec![ActionKind::Transfer, ActionKind::Stake].into_iter().collect(),
+            ..Default::default()
+        };
+        assert!(filter.matches_transaction(&transaction("alice", "bob", vec![transfer()])));
+        assert!(!filter.matches_transaction(&transaction(
+            "alice",
+            "bob",
+            vec![function_call("f")]
+        )));
+        assert!(!filter.matches_transaction(&transaction("alice", "bob", vec![])));
+    }
+}
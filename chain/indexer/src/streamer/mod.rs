@@ -1,28 +1,38 @@
+use std::sync::Arc;
 use std::time::Duration;
 
 use actix::Addr;
 use rocksdb::DB;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
 use tokio::time;
-use tracing::{debug, info};
+use tracing::{debug, error, info};
 
 pub use near_primitives::views;
 
-use crate::{AwaitForNodeSyncedEnum, IndexerConfig};
+use crate::{AwaitForNodeSyncedEnum, DeliveryModeEnum, HistoricalIndexerConfig, IndexerConfig};
 
+pub use self::checkpoint::IndexerAcknowledger;
+pub(crate) use self::checkpoint::{get_committed_block_height, open_indexer_db};
+use self::checkpoint::{
+    get_start_block_height, set_last_synced_block_height, wait_for_acknowledgement,
+};
+pub use self::errors::CheckpointError;
 use self::errors::FailedToFetchData;
 use self::fetchers::{
     fetch_block_by_height, fetch_chunks, fetch_latest_block, fetch_outcomes, fetch_state_changes,
     fetch_status,
 };
+pub use self::filter::{ActionKind, IndexerFilter};
 pub use self::types::{
     IndexerChunkView, IndexerExecutionOutcomeWithReceipt, IndexerTransactionWithOutcome,
     StreamerMessage,
 };
 use self::utils::convert_transactions_sir_into_local_receipts;
 
+mod checkpoint;
 mod errors;
 mod fetchers;
+mod filter;
 mod types;
 mod utils;
 
@@ -32,16 +42,18 @@ const INTERVAL: Duration = Duration::from_millis(500);
 /// This function supposed to return the entire `StreamerMessage`.
 /// It fetches the block and all related parts (chunks, outcomes, state changes etc.)
 /// and returns everything together in one struct
+/// Transactions, receipts and state changes which don't match the `filter` are dropped
 async fn build_streamer_message(
     client: &Addr<near_client::ViewClientActor>,
     block: views::BlockView,
     near_config: &neard::NearConfig,
+    filter: &IndexerFilter,
 ) -> Result<StreamerMessage, FailedToFetchData> {
     let chunks_to_fetch = block
         .chunks
         .iter()
         .filter_map(|c| {
-            if c.height_included == block.header.height {
+            if c.height_included == block.header.height && filter.matches_shard(c.shard_id) {
                 Some(c.chunk_hash)
             } else {
                 None
@@ -51,7 +63,7 @@ async fn build_streamer_message(
     let chunks = fetch_chunks(&client, chunks_to_fetch).await?;
 
     let mut local_receipts: Vec<views::ReceiptView> = vec![];
-    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash).await?;
+    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash, filter).await?;
     let mut indexer_chunks: Vec<IndexerChunkView> = vec![];
 
     for chunk in chunks {
@@ -72,6 +84,7 @@ async fn build_streamer_message(
                 );
                 IndexerTransactionWithOutcome { outcome, transaction }
             })
+            .filter(|tx| filter.matches_transaction(&tx.transaction))
             .collect::<Vec<IndexerTransactionWithOutcome>>();
 
         let chunk_local_receipts = convert_transactions_sir_into_local_receipts(
@@ -88,7 +101,9 @@ async fn build_streamer_message(
         local_receipts.extend_from_slice(&chunk_local_receipts);
 
         let mut chunk_receipts = chunk_local_receipts;
-        chunk_receipts.extend(chunk_non_local_receipts);
+        chunk_receipts.extend(
+            chunk_non_local_receipts.into_iter().filter(|receipt| filter.matches_receipt(receipt)),
+        );
 
         // Add local receipts to corresponding outcomes
         for receipt in &local_receipts {
@@ -100,6 +115,11 @@ async fn build_streamer_message(
                 outcome.receipt = Some(receipt.clone());
             }
         }
+        // Receipts of the outcomes which didn't pass the filter are not fetched at all
+        receipt_outcomes.retain(|outcome| match &outcome.receipt {
+            Some(receipt) => filter.matches_receipt(receipt),
+            None => filter.matches_any_receipt(),
+        });
 
         indexer_chunks.push(IndexerChunkView {
             author,
@@ -110,7 +130,7 @@ async fn build_streamer_message(
         });
     }
 
-    let state_changes = fetch_state_changes(&client, block.header.hash).await?;
+    let state_changes = fetch_state_changes(&client, block.header.hash, filter).await?;
 
     Ok(StreamerMessage { block, chunks: indexer_chunks, state_changes })
 }
@@ -119,20 +139,19 @@ async fn build_streamer_message(
 /// compares to already fetched block height and in case it differs fetches new block of given height.
 ///
 /// We have to pass `client: Addr<near_client::ClientActor>` and `view_client: Addr<near_client::ViewClientActor>`.
+/// `committed_block_height` receives the heights acknowledged by the consumer via `IndexerAcknowledger`.
 pub(crate) async fn start(
     view_client: Addr<near_client::ViewClientActor>,
     client: Addr<near_client::ClientActor>,
     near_config: neard::NearConfig,
     indexer_config: IndexerConfig,
+    db: Arc<DB>,
+    mut committed_block_height: watch::Receiver<Option<near_primitives::types::BlockHeight>>,
     blocks_sink: mpsc::Sender<StreamerMessage>,
 ) {
     info!(target: INDEXER, "Starting Streamer...");
-    let mut indexer_db_path = neard::get_store_path(&indexer_config.home_dir);
-    indexer_db_path.push_str("/indexer");
-
-    // TODO: implement proper error handling
-    let db = DB::open_default(indexer_db_path).unwrap();
     let mut last_synced_block_height: Option<near_primitives::types::BlockHeight> = None;
+    let mut last_sent_block_height: Option<near_primitives::types::BlockHeight> = None;
 
     'main: loop {
         time::sleep(INTERVAL).await;
@@ -155,22 +174,28 @@ pub(crate) async fn start(
         };
 
         let latest_block_height = block.header.height;
-        let start_syncing_block_height = if let Some(last_synced_block_height) =
-            last_synced_block_height
-        {
-            last_synced_block_height + 1
-        } else {
-            match indexer_config.sync_mode {
-                crate::SyncModeEnum::FromInterruption => {
-                    match db.get(b"last_synced_block_height").unwrap() {
-                        Some(value) => String::from_utf8(value).unwrap().parse::<u64>().unwrap(),
-                        None => latest_block_height,
+        let start_syncing_block_height =
+            if let Some(last_synced_block_height) = last_synced_block_height {
+                last_synced_block_height + 1
+            } else {
+                let committed_block_height = *committed_block_height.borrow();
+                match get_start_block_height(
+                    &db,
+                    &indexer_config.sync_mode,
+                    &indexer_config.delivery_mode,
+                    committed_block_height,
+                    latest_block_height,
+                ) {
+                    Ok(block_height) => block_height,
+                    Err(err) => {
+                        error!(
+                            target: INDEXER,
+                            "Unable to read the checkpoint: {}. terminating...", err
+                        );
+                        break 'main;
                     }
                 }
-                crate::SyncModeEnum::LatestSynced => latest_block_height,
-                crate::SyncModeEnum::BlockHeight(height) => height,
-            }
-        };
+            };
 
         debug!(
             target: INDEXER,
@@ -180,11 +205,33 @@ pub(crate) async fn start(
         );
         for block_height in start_syncing_block_height..=latest_block_height {
             if let Ok(block) = fetch_block_by_height(&view_client, block_height).await {
-                let response = build_streamer_message(&view_client, block, &near_config).await;
+                let response = build_streamer_message(
+                    &view_client,
+                    block,
+                    &near_config,
+                    &indexer_config.filter,
+                )
+                .await;
 
                 match response {
                     Ok(streamer_message) => {
                         debug!(target: INDEXER, "{:#?}", &streamer_message);
+                        if let (DeliveryModeEnum::ExactlyOnce, Some(last_sent_block_height)) =
+                            (&indexer_config.delivery_mode, last_sent_block_height)
+                        {
+                            if !wait_for_acknowledgement(
+                                &mut committed_block_height,
+                                last_sent_block_height,
+                            )
+                            .await
+                            {
+                                info!(
+                                    target: INDEXER,
+                                    "Unable to receive acknowledgements, listener has gone. terminating..."
+                                );
+                                break 'main;
+                            }
+                        }
                         if blocks_sink.send(streamer_message).await.is_err() {
                             info!(
                                 target: INDEXER,
@@ -192,6 +239,7 @@ pub(crate) async fn start(
                             );
                             break 'main;
                         }
+                        last_sent_block_height = Some(block_height);
                     }
                     Err(err) => {
                         debug!(
@@ -202,8 +250,65 @@ pub(crate) async fn start(
                     }
                 }
             }
-            db.put(b"last_synced_block_height", &block_height.to_string()).unwrap();
+            if let Err(err) = set_last_synced_block_height(&db, block_height) {
+                error!(
+                    target: INDEXER,
+                    "Unable to store the checkpoint of block #{}: {}. terminating...",
+                    block_height,
+                    err
+                );
+                break 'main;
+            }
             last_synced_block_height = Some(block_height);
         }
     }
 }
+
+/// Function that streams the blocks of the given height range `[start, end]` (inclusive)
+/// from an existing database. Unlike `start`, it doesn't wait for anything and doesn't store
+/// checkpoints, the channel is closed once the last block is sent.
+pub(crate) async fn start_historical(
+    view_client: Addr<near_client::ViewClientActor>,
+    near_config: neard::NearConfig,
+    indexer_config: HistoricalIndexerConfig,
+    blocks_sink: mpsc::Sender<StreamerMessage>,
+) {
+    info!(
+        target: INDEXER,
+        "Starting historical Streamer for blocks #{}..=#{}...",
+        indexer_config.start_block_height,
+        indexer_config.end_block_height
+    );
+    for block_height in indexer_config.start_block_height..=indexer_config.end_block_height {
+        // There might be no block at some heights
+        let block = match fetch_block_by_height(&view_client, block_height).await {
+            Ok(block) => block,
+            Err(err) => {
+                debug!(target: INDEXER, "No block at height #{}: {:?}", block_height, err);
+                continue;
+            }
+        };
+        match build_streamer_message(&view_client, block, &near_config, &indexer_config.filter)
+            .await
+        {
+            Ok(streamer_message) => {
+                debug!(target: INDEXER, "{:#?}", &streamer_message);
+                if blocks_sink.send(streamer_message).await.is_err() {
+                    info!(
+                        target: INDEXER,
+                        "Unable to send StreamerMessage to listener, listener doesn't listen. terminating..."
+                    );
+                    return;
+                }
+            }
+            Err(err) => {
+                debug!(target: INDEXER, "Missing data, skipping block #{}...", block_height);
+                debug!(target: INDEXER, "{:#?}", err);
+            }
+        }
+    }
+    info!(
+        target: INDEXER,
+        "Historical Streamer has reached block #{}", indexer_config.end_block_height
+    );
+}
@@ -2,15 +2,21 @@
 //!
 //! NOTE: This module should be only used in RPC server and RPC client implementations, and
 //! should not leak these types anywhere else.
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::MerklePath;
+use near_primitives::network::PeerId;
+use near_primitives::serialize::option_base64_format;
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, BlockReference, MaybeBlockId, TransactionOrReceiptId};
+use near_primitives::types::{
+    AccountId, BlockId, BlockReference, MaybeBlockId, ShardId, StateRoot, TransactionOrReceiptId,
+};
 use near_primitives::views::{
-    ExecutionOutcomeWithIdView, LightClientBlockLiteView, QueryRequest, StateChangeWithCauseView,
-    StateChangesKindsView, StateChangesRequestView,
+    ExecutionOutcomeWithIdView, LightClientBlockLiteView, QueryRequest, QueryResponse,
+    StateChangeWithCauseView, StateChangesKindsView, StateChangesRequestView, TrieProofPath,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -47,6 +53,18 @@ pub struct RpcStateChangesInBlockResponse {
     pub changes: StateChangesKindsView,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RpcStateDiffRequest {
+    pub shard_id: ShardId,
+    pub from_block_id: BlockId,
+    pub to_block_id: BlockId,
+    /// Raw key to start from, the `next_key_base64` of the previous response.
+    #[serde(default, rename = "start_key_base64", with = "option_base64_format")]
+    pub start_key: Option<Vec<u8>>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RpcBroadcastTxSyncResponse {
     pub transaction_hash: String,
@@ -68,12 +86,73 @@ pub struct RpcLightClientExecutionProofResponse {
     pub block_proof: MerklePath,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RpcLightClientQueryProofRequest {
+    #[serde(flatten)]
+    pub block_reference: BlockReference,
+    #[serde(flatten)]
+    pub request: QueryRequest,
+    pub light_client_head: CryptoHash,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcLightClientQueryProofResponse {
+    pub query_response: QueryResponse,
+    pub state_proof: TrieProofPath,
+    pub chunk_prev_state_root: StateRoot,
+    pub chunk_prev_state_root_proof: MerklePath,
+    pub block_header_lite: LightClientBlockLiteView,
+    pub block_proof: MerklePath,
+}
+
 #[derive(Clone, Debug)]
 pub enum TransactionInfo {
     Transaction(SignedTransaction),
     TransactionId { hash: CryptoHash, account_id: AccountId },
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct RpcCreateCheckpointRequest {
+    /// Directory on the node's machine to create the checkpoint in, it must not exist.
+    pub path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcPoolTransactionsRequest {
+    #[serde(default)]
+    pub signer_id: Option<AccountId>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcPoolRemoveRequest {
+    pub transaction_hash: CryptoHash,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcPoolRemoveResponse {
+    /// Shard of the pool the transaction was removed from, `None` if it wasn't in any pool.
+    pub shard_id: Option<ShardId>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcPeerRequest {
+    pub peer_id: PeerId,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcPeerBanRequest {
+    pub peer_id: PeerId,
+    /// Name of the reason for the ban, e.g. `Abusive`, `None` if not given.
+    #[serde(default)]
+    pub ban_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcPeerConnectRequest {
+    /// Peer to connect to in the `boot_nodes` format, `<peer_id>@<ip>:<port>`.
+    pub peer_info: String,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct RpcValidatorsOrderedRequest {
     pub block_id: MaybeBlockId,
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Identifier of a subscription, unique within a single WebSocket connection.
+pub type SubscriptionId = u64;
+
+/// Events that a WebSocket client can subscribe to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "subscription_type", rename_all = "snake_case")]
+pub enum Subscription {
+    /// Every new final block (`BlockView`).
+    NewFinalBlocks,
+    /// Every new chunk included into a final block for the given shard (`ChunkView`).
+    NewChunks { shard_id: near_primitives::types::ShardId },
+    /// State changes of the given kind in every new final block (`RpcStateChangesResponse`).
+    /// Blocks without matching changes are not reported.
+    StateChanges {
+        #[serde(flatten)]
+        state_changes_request: near_primitives::views::StateChangesRequestView,
+    },
+    /// Final execution outcome of the given transaction (`FinalExecutionOutcomeView`).
+    /// The subscription is closed by the server once the outcome has been delivered.
+    TransactionOutcome {
+        tx_hash: near_primitives::hash::CryptoHash,
+        sender_account_id: near_primitives::types::AccountId,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcSubscribeRequest {
+    #[serde(flatten)]
+    pub subscription: Subscription,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcSubscribeResponse {
+    pub subscription_id: SubscriptionId,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RpcUnsubscribeRequest {
+    pub subscription_id: SubscriptionId,
+}
+
+/// Payload of the `EXPERIMENTAL_subscription` notifications pushed to the client.
+/// Exactly one of `result` and `error` is set. A notification with an `error` is the last one
+/// of the subscription.
+#[derive(Serialize, Deserialize)]
+pub struct RpcSubscriptionNotification {
+    pub subscription_id: SubscriptionId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<crate::errors::RpcError>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum RpcSubscriptionError {
+    #[error("Subscription {0} does not exist")]
+    UnknownSubscription(SubscriptionId),
+    #[error("Too many subscriptions on this connection, the limit is {limit}")]
+    TooManySubscriptions { limit: usize },
+}
+
+impl RpcSubscribeRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        let subscription = crate::utils::parse_params::<Subscription>(value)?;
+        Ok(Self { subscription })
+    }
+}
+
+impl RpcUnsubscribeRequest {
+    pub fn parse(value: Option<Value>) -> Result<Self, crate::errors::RpcParseError> {
+        // Accept both positional `[subscription_id]` and named `{"subscription_id": ..}` args
+        if let Ok((subscription_id,)) =
+            crate::utils::parse_params::<(SubscriptionId,)>(value.clone())
+        {
+            Ok(Self { subscription_id })
+        } else {
+            crate::utils::parse_params::<Self>(value)
+        }
+    }
+}
+
+impl From<RpcSubscriptionError> for crate::errors::RpcError {
+    fn from(error: RpcSubscriptionError) -> Self {
+        Self::new(-32_000, "Server error".to_string(), Some(Value::String(error.to_string())))
+    }
+}
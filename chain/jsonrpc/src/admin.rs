@@ -0,0 +1,172 @@
+//! Admin JSON RPC to manage the node at runtime, e.g. its peers, its transaction pool and its
+//! database, and to run queries that are too expensive to be public. It is served on its own
+//! address, `admin_addr` in `RpcConfig`, so that it can be kept private while the public RPC is
+//! exposed.
+use std::str::FromStr;
+
+use actix::Addr;
+use actix_web::{middleware, web, App, Error as HttpError, HttpResponse, HttpServer};
+use futures::{Future, FutureExt};
+use serde_json::Value;
+
+use near_client::{
+    ClientActor, CreateCheckpoint, GetStateDiff, RemovePoolTransaction, ViewClientActor,
+};
+use near_jsonrpc_primitives::errors::RpcError;
+use near_jsonrpc_primitives::message::{Message, Request};
+use near_jsonrpc_primitives::rpc::{
+    RpcCreateCheckpointRequest, RpcPeerBanRequest, RpcPeerConnectRequest, RpcPeerRequest,
+    RpcPoolRemoveRequest, RpcPoolRemoveResponse, RpcStateDiffRequest,
+};
+use near_network::types::{PeerAdminRequest, ReasonForBan};
+use near_network::{PeerInfo, PeerManagerActor};
+
+use crate::{jsonify, metrics, parse_params};
+
+struct AdminRpcHandler {
+    client_addr: Addr<ClientActor>,
+    view_client_addr: Addr<ViewClientActor>,
+    peer_manager_addr: Addr<PeerManagerActor>,
+}
+
+impl AdminRpcHandler {
+    async fn process(&self, message: Message) -> Result<Message, HttpError> {
+        let id = message.id();
+        match message {
+            Message::Request(request) => {
+                Ok(Message::response(id, self.process_request(request).await))
+            }
+            _ => Ok(Message::error(RpcError::invalid_request())),
+        }
+    }
+
+    async fn process_request(&self, request: Request) -> Result<Value, RpcError> {
+        near_metrics::inc_counter_vec(&metrics::HTTP_RPC_REQUEST_COUNT, &[request.method.as_ref()]);
+
+        let response = match request.method.as_ref() {
+            "EXPERIMENTAL_create_checkpoint" => self.create_checkpoint(request.params).await,
+            "EXPERIMENTAL_pool_remove" => self.pool_remove(request.params).await,
+            "EXPERIMENTAL_state_diff" => self.state_diff(request.params).await,
+            _ => match self.parse_peer_request(&request) {
+                Ok(msg) => jsonify(self.peer_manager_addr.send(msg).await),
+                Err(err) => Err(err),
+            },
+        };
+
+        if let Err(err) = &response {
+            near_metrics::inc_counter_vec(
+                &metrics::RPC_ERROR_COUNT,
+                &[request.method.as_ref(), &err.code.to_string()],
+            );
+        }
+
+        response
+    }
+
+    /// Creates a checkpoint of the node database in a directory on the node's machine, which can
+    /// be restored with `neard restore_backup`.
+    async fn create_checkpoint(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let RpcCreateCheckpointRequest { path } =
+            parse_params::<RpcCreateCheckpointRequest>(params)?;
+        jsonify(self.view_client_addr.send(CreateCheckpoint { path }).await)
+    }
+
+    /// Removes a transaction from the transaction pool, so that this node doesn't include it
+    /// into its chunks.
+    async fn pool_remove(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let RpcPoolRemoveRequest { transaction_hash } =
+            parse_params::<RpcPoolRemoveRequest>(params)?;
+        jsonify(
+            self.client_addr
+                .send(RemovePoolTransaction { transaction_hash })
+                .await
+                .map(|response| response.map(|shard_id| RpcPoolRemoveResponse { shard_id })),
+        )
+    }
+
+    /// Returns the keys of the state of a shard that differ between two blocks, a limited number
+    /// at a time. The whole state may differ, so this is only served here and not by the public
+    /// RPC.
+    async fn state_diff(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let RpcStateDiffRequest { shard_id, from_block_id, to_block_id, start_key, limit } =
+            parse_params::<RpcStateDiffRequest>(params)?;
+        let start_key = start_key.unwrap_or_default();
+        jsonify(
+            self.view_client_addr
+                .send(GetStateDiff { shard_id, from_block_id, to_block_id, start_key, limit })
+                .await,
+        )
+    }
+
+    fn parse_peer_request(&self, request: &Request) -> Result<PeerAdminRequest, RpcError> {
+        let params = request.params.clone();
+        match request.method.as_ref() {
+            // Handlers ordered alphabetically
+            "EXPERIMENTAL_peer_ban" => {
+                let RpcPeerBanRequest { peer_id, ban_reason } =
+                    parse_params::<RpcPeerBanRequest>(params)?;
+                let ban_reason = match ban_reason {
+                    Some(ban_reason) => {
+                        serde_json::from_value::<ReasonForBan>(Value::String(ban_reason)).map_err(
+                            |err| RpcError::invalid_params(format!("Invalid ban_reason: {}", err)),
+                        )?
+                    }
+                    None => ReasonForBan::None,
+                };
+                Ok(PeerAdminRequest::BanPeer { peer_id, ban_reason })
+            }
+            "EXPERIMENTAL_peer_connect" => {
+                let RpcPeerConnectRequest { peer_info } =
+                    parse_params::<RpcPeerConnectRequest>(params)?;
+                let peer_info = PeerInfo::from_str(&peer_info).map_err(|err| {
+                    RpcError::invalid_params(format!("Invalid peer_info: {}", err))
+                })?;
+                Ok(PeerAdminRequest::ConnectPeer { peer_info })
+            }
+            "EXPERIMENTAL_peer_disconnect" => {
+                let RpcPeerRequest { peer_id } = parse_params::<RpcPeerRequest>(params)?;
+                Ok(PeerAdminRequest::DisconnectPeer { peer_id })
+            }
+            "EXPERIMENTAL_peer_list" => Ok(PeerAdminRequest::ListPeers),
+            "EXPERIMENTAL_peer_unban" => {
+                let RpcPeerRequest { peer_id } = parse_params::<RpcPeerRequest>(params)?;
+                Ok(PeerAdminRequest::UnbanPeer { peer_id })
+            }
+            _ => Err(RpcError::method_not_found(request.method.clone())),
+        }
+    }
+}
+
+fn admin_rpc_handler(
+    message: web::Json<Message>,
+    handler: web::Data<AdminRpcHandler>,
+) -> impl Future<Output = Result<HttpResponse, HttpError>> {
+    let response = async move {
+        let message = handler.process(message.0).await?;
+        Ok(HttpResponse::Ok().json(message))
+    };
+    response.boxed()
+}
+
+pub fn start_admin_http(
+    addr: String,
+    client_addr: Addr<ClientActor>,
+    view_client_addr: Addr<ViewClientActor>,
+    peer_manager_addr: Addr<PeerManagerActor>,
+) {
+    HttpServer::new(move || {
+        App::new()
+            .data(AdminRpcHandler {
+                client_addr: client_addr.clone(),
+                view_client_addr: view_client_addr.clone(),
+                peer_manager_addr: peer_manager_addr.clone(),
+            })
+            .wrap(middleware::Logger::default())
+            .service(web::resource("/").route(web::post().to(admin_rpc_handler)))
+    })
+    .bind(addr)
+    .unwrap()
+    .workers(1)
+    .shutdown_timeout(5)
+    .run();
+}
@@ -17,18 +17,19 @@ use tokio::time::{sleep, timeout};
 use near_chain_configs::GenesisConfig;
 use near_client::{
     ClientActor, GetBlock, GetBlockProof, GetChunk, GetExecutionOutcome, GetGasPrice,
-    GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetStateChanges,
-    GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered, Query, Status, TxStatus,
-    TxStatusError, ViewClientActor,
+    GetNetworkInfo, GetNextLightClientBlock, GetPoolStatus, GetPoolTransactions, GetProtocolConfig,
+    GetQueryProof, GetReceipt, GetStateChanges, GetStateChangesInBlock, GetValidatorInfo,
+    GetValidatorOrdered, Query, Status, TxStatus, TxStatusError, ViewClientActor,
 };
 pub use near_jsonrpc_client as client;
 use near_jsonrpc_primitives::errors::RpcError;
 use near_jsonrpc_primitives::message::{Message, Request};
 use near_jsonrpc_primitives::rpc::{
     RpcBroadcastTxSyncResponse, RpcLightClientExecutionProofRequest,
-    RpcLightClientExecutionProofResponse, RpcQueryRequest, RpcStateChangesInBlockRequest,
-    RpcStateChangesInBlockResponse, RpcStateChangesRequest, RpcStateChangesResponse,
-    RpcValidatorsOrderedRequest, TransactionInfo,
+    RpcLightClientExecutionProofResponse, RpcLightClientQueryProofRequest,
+    RpcLightClientQueryProofResponse, RpcPoolTransactionsRequest, RpcQueryRequest,
+    RpcStateChangesInBlockRequest, RpcStateChangesInBlockResponse, RpcStateChangesRequest,
+    RpcStateChangesResponse, RpcValidatorsOrderedRequest, TransactionInfo,
 };
 use near_jsonrpc_primitives::types::config::RpcProtocolConfigResponse;
 use near_metrics::{Encoder, TextEncoder};
@@ -45,7 +46,11 @@ use near_primitives::views::{
 };
 use near_runtime_utils::is_valid_account_id;
 
+mod admin;
 mod metrics;
+mod subscriptions;
+
+pub use admin::start_admin_http;
 
 /// Max size of the query path (soft-deprecated)
 const QUERY_DATA_MAX_SIZE: usize = 10 * 1024;
@@ -84,6 +89,11 @@ pub struct RpcConfig {
     pub polling_config: RpcPollingConfig,
     #[serde(default)]
     pub limits_config: RpcLimitsConfig,
+    /// Address of the admin RPC to manage the peers, the transaction pool and the database of
+    /// the node. It should not be reachable from the outside. The admin RPC is disabled if not
+    /// set.
+    #[serde(default)]
+    pub admin_addr: Option<String>,
 }
 
 impl Default for RpcConfig {
@@ -93,6 +103,7 @@ impl Default for RpcConfig {
             cors_allowed_origins: vec!["*".to_owned()],
             polling_config: Default::default(),
             limits_config: Default::default(),
+            admin_addr: None,
         }
     }
 }
@@ -250,6 +261,8 @@ impl JsonRpcHandler {
             "EXPERIMENTAL_changes_in_block" => self.changes_in_block(request.params).await,
             "EXPERIMENTAL_check_tx" => self.check_tx(request.params).await,
             "EXPERIMENTAL_genesis_config" => self.genesis_config().await,
+            "EXPERIMENTAL_pool_status" => self.pool_status().await,
+            "EXPERIMENTAL_pool_transactions" => self.pool_transactions(request.params).await,
             "EXPERIMENTAL_protocol_config" => {
                 let rpc_protocol_config_request =
                     near_jsonrpc_primitives::types::config::RpcProtocolConfigRequest::parse(
@@ -261,6 +274,9 @@ impl JsonRpcHandler {
             "EXPERIMENTAL_light_client_proof" => {
                 self.light_client_execution_outcome_proof(request.params).await
             }
+            "EXPERIMENTAL_light_client_query_proof" => {
+                self.light_client_query_proof(request.params).await
+            }
             "EXPERIMENTAL_receipt" => {
                 let rpc_receipt_request =
                     near_jsonrpc_primitives::types::receipts::RpcReceiptRequest::parse(
@@ -810,10 +826,50 @@ impl JsonRpcHandler {
         jsonify(Ok(res))
     }
 
+    async fn light_client_query_proof(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let RpcLightClientQueryProofRequest { block_reference, request, light_client_head } =
+            parse_params(params)?;
+        let query_proof = self
+            .view_client_addr
+            .send(GetQueryProof { block_reference, request })
+            .await
+            .map_err(|e| RpcError::from(ServerError::from(e)))?
+            .map_err(|e| RpcError::server_error(Some(e)))?;
+        let block_proof = self
+            .view_client_addr
+            .send(GetBlockProof {
+                block_hash: query_proof.query_response.block_hash,
+                head_block_hash: light_client_head,
+            })
+            .await
+            .map_err(|e| RpcError::from(ServerError::from(e)))?;
+        let res = block_proof.map(|block_proof| RpcLightClientQueryProofResponse {
+            query_response: query_proof.query_response,
+            state_proof: query_proof.state_proof,
+            chunk_prev_state_root: query_proof.chunk_prev_state_root,
+            chunk_prev_state_root_proof: query_proof.chunk_prev_state_root_proof,
+            block_header_lite: block_proof.block_header_lite,
+            block_proof: block_proof.proof,
+        });
+        jsonify(Ok(res))
+    }
+
     async fn network_info(&self) -> Result<Value, RpcError> {
         jsonify(self.client_addr.send(GetNetworkInfo {}).await)
     }
 
+    async fn pool_status(&self) -> Result<Value, RpcError> {
+        jsonify(self.client_addr.send(GetPoolStatus {}).await)
+    }
+
+    async fn pool_transactions(&self, params: Option<Value>) -> Result<Value, RpcError> {
+        let RpcPoolTransactionsRequest { signer_id } = match params {
+            Some(params) => parse_params::<RpcPoolTransactionsRequest>(Some(params))?,
+            None => RpcPoolTransactionsRequest { signer_id: None },
+        };
+        jsonify(self.client_addr.send(GetPoolTransactions { signer_id }).await)
+    }
+
     async fn gas_price(&self, params: Option<Value>) -> Result<Value, RpcError> {
         let (block_id,) = parse_params::<(MaybeBlockId,)>(params)?;
         jsonify(self.view_client_addr.send(GetGasPrice { block_id }).await)
@@ -1037,7 +1093,8 @@ pub fn start_http(
     client_addr: Addr<ClientActor>,
     view_client_addr: Addr<ViewClientActor>,
 ) {
-    let RpcConfig { addr, cors_allowed_origins, polling_config, limits_config } = config;
+    let RpcConfig { addr, cors_allowed_origins, polling_config, limits_config, admin_addr: _ } =
+        config;
     HttpServer::new(move || {
         App::new()
             .wrap(get_cors(&cors_allowed_origins))
@@ -1050,6 +1107,7 @@ pub fn start_http(
             .app_data(web::JsonConfig::default().limit(limits_config.json_payload_max_size))
             .wrap(middleware::Logger::default())
             .service(web::resource("/").route(web::post().to(rpc_handler)))
+            .service(web::resource("/ws").route(web::get().to(subscriptions::ws_handler)))
             .service(
                 web::resource("/status")
                     .route(web::get().to(status_handler))
@@ -1,5 +1,5 @@
 use lazy_static::lazy_static;
-use near_metrics::{HistogramVec, IntCounter, IntCounterVec};
+use near_metrics::{HistogramVec, IntCounter, IntCounterVec, IntGauge};
 
 lazy_static! {
     pub static ref RPC_PROCESSING_TIME: near_metrics::Result<HistogramVec> =
@@ -36,4 +36,20 @@ lazy_static! {
             "Total count of errors by method and message",
             &["method", "err_code"]
         );
+    pub static ref WS_CONNECTION_COUNT: near_metrics::Result<IntCounter> =
+        near_metrics::try_create_int_counter(
+            "near_ws_connections_total",
+            "Total count of WebSocket connections opened"
+        );
+    pub static ref WS_RPC_REQUEST_COUNT: near_metrics::Result<IntCounterVec> =
+        near_metrics::try_create_int_counter_vec(
+            "near_ws_rpc_total_count",
+            "Total count of WebSocket RPC requests received, by method",
+            &["method"]
+        );
+    pub static ref WS_ACTIVE_SUBSCRIPTIONS: near_metrics::Result<IntGauge> =
+        near_metrics::try_create_int_gauge(
+            "near_ws_active_subscriptions",
+            "Number of active WebSocket subscriptions"
+        );
 }
@@ -0,0 +1,332 @@
+//! WebSocket endpoint which lets clients subscribe to new final blocks, chunks, state changes
+//! and transaction outcomes instead of polling the request/response methods.
+//!
+//! Every subscription is served by a separate future which polls `ViewClientActor` for new
+//! final blocks (the same way `near-indexer` streamer does) and pushes the results to the
+//! `WsSession` actor, which forwards them to the client as JSON-RPC notifications.
+use std::collections::HashMap;
+use std::time::Duration;
+
+use actix::{Actor, ActorContext, Addr, AsyncContext, Handler, StreamHandler};
+use actix_web::{web, Error as HttpError, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use futures::future::{abortable, AbortHandle};
+use futures::FutureExt;
+use serde_json::Value;
+use tokio::time::sleep;
+
+use near_client::{GetBlock, GetChunk, GetStateChanges, TxStatus, TxStatusError, ViewClientActor};
+use near_jsonrpc_primitives::errors::RpcError;
+use near_jsonrpc_primitives::message::{from_str, Message, Request};
+use near_jsonrpc_primitives::rpc::RpcStateChangesResponse;
+use near_jsonrpc_primitives::types::subscriptions::{
+    RpcSubscribeRequest, RpcSubscribeResponse, RpcSubscriptionError, RpcSubscriptionNotification,
+    RpcUnsubscribeRequest, Subscription, SubscriptionId,
+};
+use near_primitives::types::{BlockHeight, BlockId, BlockReference, Finality};
+use near_primitives::views::BlockView;
+
+use crate::{metrics, RpcPollingConfig};
+
+/// Maximum number of active subscriptions on a single WebSocket connection.
+const MAX_SUBSCRIPTIONS_PER_CONNECTION: usize = 32;
+
+/// Method name of the notifications pushed to the subscribers.
+const SUBSCRIPTION_NOTIFICATION_METHOD: &str = "EXPERIMENTAL_subscription";
+
+/// Event produced by a subscription future, to be delivered to the client.
+#[derive(actix::Message)]
+#[rtype(result = "()")]
+struct SubscriptionEvent {
+    subscription_id: SubscriptionId,
+    result: Result<Value, RpcError>,
+    /// Whether this is the last event of the subscription, so it can be forgotten.
+    is_last: bool,
+}
+
+/// Actor serving a single WebSocket connection.
+pub(crate) struct WsSession {
+    view_client_addr: Addr<ViewClientActor>,
+    polling_config: RpcPollingConfig,
+    next_subscription_id: SubscriptionId,
+    subscriptions: HashMap<SubscriptionId, AbortHandle>,
+}
+
+impl WsSession {
+    fn new(view_client_addr: Addr<ViewClientActor>, polling_config: RpcPollingConfig) -> Self {
+        Self {
+            view_client_addr,
+            polling_config,
+            next_subscription_id: 0,
+            subscriptions: HashMap::new(),
+        }
+    }
+
+    fn process_text(&mut self, text: &str, ctx: &mut ws::WebsocketContext<Self>) -> Message {
+        match from_str(text) {
+            Ok(Message::Request(request)) => {
+                let id = request.id.clone();
+                Message::response(id, self.process_request(request, ctx))
+            }
+            Ok(_) => Message::error(RpcError::invalid_request()),
+            Err(broken) => broken.reply(),
+        }
+    }
+
+    fn process_request(
+        &mut self,
+        request: Request,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) -> Result<Value, RpcError> {
+        near_metrics::inc_counter_vec(&metrics::WS_RPC_REQUEST_COUNT, &[request.method.as_ref()]);
+        match request.method.as_ref() {
+            "EXPERIMENTAL_subscribe" => {
+                let RpcSubscribeRequest { subscription } =
+                    RpcSubscribeRequest::parse(request.params)?;
+                let subscription_id = self.subscribe(subscription, ctx)?;
+                serde_json::to_value(RpcSubscribeResponse { subscription_id })
+                    .map_err(|err| RpcError::parse_error(err.to_string()))
+            }
+            "EXPERIMENTAL_unsubscribe" => {
+                let RpcUnsubscribeRequest { subscription_id } =
+                    RpcUnsubscribeRequest::parse(request.params)?;
+                self.unsubscribe(subscription_id)?;
+                Ok(Value::Null)
+            }
+            _ => Err(RpcError::method_not_found(request.method.clone())),
+        }
+    }
+
+    fn subscribe(
+        &mut self,
+        subscription: Subscription,
+        ctx: &mut ws::WebsocketContext<Self>,
+    ) -> Result<SubscriptionId, RpcSubscriptionError> {
+        if self.subscriptions.len() >= MAX_SUBSCRIPTIONS_PER_CONNECTION {
+            return Err(RpcSubscriptionError::TooManySubscriptions {
+                limit: MAX_SUBSCRIPTIONS_PER_CONNECTION,
+            });
+        }
+        let subscription_id = self.next_subscription_id;
+        self.next_subscription_id += 1;
+
+        let (task, abort_handle) = abortable(run_subscription(
+            self.view_client_addr.clone(),
+            self.polling_config.polling_interval,
+            subscription,
+            subscription_id,
+            ctx.address(),
+        ));
+        actix::spawn(task.map(|_| ()));
+        self.subscriptions.insert(subscription_id, abort_handle);
+        near_metrics::inc_gauge(&metrics::WS_ACTIVE_SUBSCRIPTIONS);
+        Ok(subscription_id)
+    }
+
+    fn unsubscribe(&mut self, subscription_id: SubscriptionId) -> Result<(), RpcSubscriptionError> {
+        match self.subscriptions.remove(&subscription_id) {
+            Some(abort_handle) => {
+                abort_handle.abort();
+                near_metrics::dec_gauge(&metrics::WS_ACTIVE_SUBSCRIPTIONS);
+                Ok(())
+            }
+            None => Err(RpcSubscriptionError::UnknownSubscription(subscription_id)),
+        }
+    }
+}
+
+impl Actor for WsSession {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        for (_, abort_handle) in self.subscriptions.drain() {
+            abort_handle.abort();
+            near_metrics::dec_gauge(&metrics::WS_ACTIVE_SUBSCRIPTIONS);
+        }
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WsSession {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(bytes)) => ctx.pong(&bytes),
+            Ok(ws::Message::Text(text)) => {
+                let response: String = self.process_text(&text, ctx).into();
+                ctx.text(response);
+            }
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(err) => {
+                tracing::debug!(target: "jsonrpc", "WebSocket protocol error: {:?}", err);
+                ctx.stop();
+            }
+        }
+    }
+}
+
+impl Handler<SubscriptionEvent> for WsSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscriptionEvent, ctx: &mut Self::Context) {
+        let SubscriptionEvent { subscription_id, result, is_last } = msg;
+        if is_last && self.subscriptions.remove(&subscription_id).is_some() {
+            near_metrics::dec_gauge(&metrics::WS_ACTIVE_SUBSCRIPTIONS);
+        }
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        let params = match serde_json::to_value(RpcSubscriptionNotification {
+            subscription_id,
+            result,
+            error,
+        }) {
+            Ok(params) => params,
+            Err(err) => {
+                tracing::warn!(target: "jsonrpc", "Failed to serialize notification: {}", err);
+                return;
+            }
+        };
+        let notification: String =
+            Message::notification(SUBSCRIPTION_NOTIFICATION_METHOD.to_string(), Some(params))
+                .into();
+        ctx.text(notification);
+    }
+}
+
+/// Drives a single subscription until it is aborted (or, for one-shot subscriptions, until
+/// the result is delivered).
+async fn run_subscription(
+    view_client_addr: Addr<ViewClientActor>,
+    polling_interval: Duration,
+    subscription: Subscription,
+    subscription_id: SubscriptionId,
+    session: Addr<WsSession>,
+) {
+    let notify = |result: Result<Value, RpcError>, is_last: bool| {
+        session.do_send(SubscriptionEvent { subscription_id, result, is_last })
+    };
+
+    if let Subscription::TransactionOutcome { tx_hash, sender_account_id } = &subscription {
+        loop {
+            sleep(polling_interval).await;
+            match view_client_addr
+                .send(TxStatus {
+                    tx_hash: *tx_hash,
+                    signer_account_id: sender_account_id.clone(),
+                    fetch_receipt: false,
+                })
+                .await
+            {
+                Ok(Ok(Some(outcome))) => {
+                    notify(
+                        serde_json::to_value(outcome)
+                            .map_err(|err| RpcError::parse_error(err.to_string())),
+                        true,
+                    );
+                    return;
+                }
+                // The transaction might not be included yet, keep polling.
+                Ok(Ok(None)) | Ok(Err(TxStatusError::MissingTransaction(_))) => {}
+                // Any other error won't go away by polling again, so report it and close the
+                // subscription.
+                Ok(Err(err)) => {
+                    tracing::debug!(target: "jsonrpc", "Failed to fetch tx status: {:?}", err);
+                    notify(Err(RpcError::server_error(Some(String::from(err)))), true);
+                    return;
+                }
+                Err(_) => return,
+            }
+        }
+    }
+
+    let mut last_notified_height: Option<BlockHeight> = None;
+    loop {
+        sleep(polling_interval).await;
+        let final_block = match view_client_addr
+            .send(GetBlock(BlockReference::Finality(Finality::Final)))
+            .await
+        {
+            Ok(Ok(block)) => block,
+            Ok(Err(_)) => continue,
+            Err(_) => return,
+        };
+        let final_height = final_block.header.height;
+        let start_height = match last_notified_height {
+            Some(height) => height + 1,
+            None => final_height,
+        };
+
+        for height in start_height..=final_height {
+            let block = if height == final_height {
+                final_block.clone()
+            } else {
+                match view_client_addr.send(GetBlock(BlockId::Height(height).into())).await {
+                    Ok(Ok(block)) => block,
+                    // There might be no block at this height.
+                    Ok(Err(_)) => continue,
+                    Err(_) => return,
+                }
+            };
+            if let Some(result) = fetch_block_event(&view_client_addr, &subscription, block).await {
+                notify(Ok(result), false);
+            }
+        }
+        last_notified_height = Some(final_height);
+    }
+}
+
+/// Collects the data the subscription is interested in from the given block.
+/// Returns `None` if there is nothing to report for this block.
+async fn fetch_block_event(
+    view_client_addr: &Addr<ViewClientActor>,
+    subscription: &Subscription,
+    block: BlockView,
+) -> Option<Value> {
+    match subscription {
+        Subscription::NewFinalBlocks => serde_json::to_value(block).ok(),
+        Subscription::NewChunks { shard_id } => {
+            let chunk_header = block.chunks.iter().find(|chunk| {
+                chunk.shard_id == *shard_id && chunk.height_included == block.header.height
+            })?;
+            let chunk = view_client_addr
+                .send(GetChunk::ChunkHash(chunk_header.chunk_hash.into()))
+                .await
+                .ok()?
+                .ok()?;
+            serde_json::to_value(chunk).ok()
+        }
+        Subscription::StateChanges { state_changes_request } => {
+            let changes = view_client_addr
+                .send(GetStateChanges {
+                    block_hash: block.header.hash,
+                    state_changes_request: state_changes_request.clone(),
+                })
+                .await
+                .ok()?
+                .ok()?;
+            if changes.is_empty() {
+                return None;
+            }
+            serde_json::to_value(RpcStateChangesResponse { block_hash: block.header.hash, changes })
+                .ok()
+        }
+        Subscription::TransactionOutcome { .. } => None,
+    }
+}
+
+pub(crate) async fn ws_handler(
+    request: HttpRequest,
+    stream: web::Payload,
+    handler: web::Data<crate::JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    near_metrics::inc_counter(&metrics::WS_CONNECTION_COUNT);
+    ws::start(
+        WsSession::new(handler.view_client_addr.clone(), handler.polling_config),
+        &request,
+        stream,
+    )
+}
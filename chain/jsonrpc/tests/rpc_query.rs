@@ -727,3 +727,58 @@ fn test_get_chunk_with_object_in_params() {
         assert_eq!(chunk.header.chunk_hash, same_chunk.header.chunk_hash);
     });
 }
+
+#[test]
+fn test_pool_status_and_transactions() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let status: serde_json::Value = test_utils::call_method(
+            &client.client,
+            &client.server_addr,
+            "EXPERIMENTAL_pool_status",
+            json!([]),
+        )
+        .await
+        .unwrap();
+        assert!(status["shards"].as_array().unwrap().is_empty());
+
+        let transactions: serde_json::Value = test_utils::call_method(
+            &client.client,
+            &client.server_addr,
+            "EXPERIMENTAL_pool_transactions",
+            json!({ "signer_id": "test1" }),
+        )
+        .await
+        .unwrap();
+        assert!(transactions["transactions"].as_array().unwrap().is_empty());
+    });
+}
+
+/// `EXPERIMENTAL_pool_remove` is only served by the admin RPC.
+#[test]
+fn test_pool_remove_requires_admin() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let response: Result<serde_json::Value, _> = test_utils::call_method(
+            &client.client,
+            &client.server_addr,
+            "EXPERIMENTAL_pool_remove",
+            json!({ "transaction_hash": CryptoHash::default() }),
+        )
+        .await;
+        assert!(response.is_err());
+    });
+}
+
+/// `EXPERIMENTAL_state_diff` is only served by the admin RPC.
+#[test]
+fn test_state_diff_requires_admin() {
+    test_with_client!(test_utils::NodeType::NonValidator, client, async move {
+        let response: Result<serde_json::Value, _> = test_utils::call_method(
+            &client.client,
+            &client.server_addr,
+            "EXPERIMENTAL_state_diff",
+            json!({ "shard_id": 0, "from_block_id": 0, "to_block_id": 0 }),
+        )
+        .await;
+        assert!(response.is_err());
+    });
+}
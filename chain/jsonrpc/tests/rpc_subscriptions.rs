@@ -0,0 +1,158 @@
+use actix::System;
+use awc::ws;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+
+use near_jsonrpc_primitives::errors::RpcError;
+use near_jsonrpc_primitives::message::{from_slice, Message};
+use near_jsonrpc_primitives::types::subscriptions::{
+    RpcSubscribeResponse, RpcSubscriptionNotification,
+};
+use near_logger_utils::init_test_logger;
+use near_primitives::views::BlockView;
+
+#[macro_use]
+pub mod test_utils;
+
+type WsConnection = actix_codec::Framed<awc::BoxedSocket, ws::Codec>;
+
+async fn connect(addr: &str) -> WsConnection {
+    let (_, connection) =
+        awc::Client::new().ws(format!("ws://{}/ws", addr)).connect().await.unwrap();
+    connection
+}
+
+/// Reads the next JSON-RPC message, skipping the control frames.
+async fn next_message(connection: &mut WsConnection) -> Message {
+    loop {
+        match connection.next().await.unwrap().unwrap() {
+            ws::Frame::Text(bytes) => return from_slice(&bytes).unwrap(),
+            ws::Frame::Ping(_) | ws::Frame::Pong(_) => {}
+            frame => panic!("unexpected frame {:?}", frame),
+        }
+    }
+}
+
+/// Sends a request and returns its response. Notifications received in between are dropped.
+async fn call(
+    connection: &mut WsConnection,
+    method: &str,
+    params: Value,
+) -> Result<Value, RpcError> {
+    let request = json!({"jsonrpc": "2.0", "method": method, "id": "dontcare", "params": params});
+    connection.send(ws::Message::Text(request.to_string().into())).await.unwrap();
+    loop {
+        if let Message::Response(response) = next_message(connection).await {
+            return response.result;
+        }
+    }
+}
+
+async fn subscribe(connection: &mut WsConnection, params: Value) -> Result<u64, RpcError> {
+    call(connection, "EXPERIMENTAL_subscribe", params).await.map(|result| {
+        serde_json::from_value::<RpcSubscribeResponse>(result).unwrap().subscription_id
+    })
+}
+
+async fn next_notification(connection: &mut WsConnection) -> RpcSubscriptionNotification {
+    loop {
+        if let Message::Notification(notification) = next_message(connection).await {
+            assert_eq!(notification.method, "EXPERIMENTAL_subscription");
+            return serde_json::from_value(notification.params.unwrap()).unwrap();
+        }
+    }
+}
+
+fn run_ws_test<F>(test: impl FnOnce(String) -> F + 'static)
+where
+    F: std::future::Future<Output = ()> + 'static,
+{
+    init_test_logger();
+
+    System::builder()
+        .stop_on_panic(true)
+        .run(|| {
+            let (_, addr) = test_utils::start_all(test_utils::NodeType::Validator);
+            actix::spawn(async move {
+                test(addr).await;
+                System::current().stop();
+            });
+        })
+        .unwrap();
+}
+
+/// Subscribes to new final blocks, gets notified about them and unsubscribes.
+#[test]
+fn test_subscribe_new_final_blocks() {
+    run_ws_test(|addr| async move {
+        let mut connection = connect(&addr).await;
+        let subscription_id =
+            subscribe(&mut connection, json!({"subscription_type": "new_final_blocks"}))
+                .await
+                .unwrap();
+
+        let mut last_height = None;
+        for _ in 0..2 {
+            let notification = next_notification(&mut connection).await;
+            assert_eq!(notification.subscription_id, subscription_id);
+            assert!(notification.error.is_none());
+            let block: BlockView = serde_json::from_value(notification.result.unwrap()).unwrap();
+            assert!(last_height.map_or(true, |height| block.header.height > height));
+            last_height = Some(block.header.height);
+        }
+
+        assert_eq!(
+            call(&mut connection, "EXPERIMENTAL_unsubscribe", json!([subscription_id])).await,
+            Ok(Value::Null)
+        );
+        // The subscription is gone, so unsubscribing again fails.
+        let error = call(
+            &mut connection,
+            "EXPERIMENTAL_unsubscribe",
+            json!({ "subscription_id": subscription_id }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.data,
+            Some(Value::String(format!("Subscription {} does not exist", subscription_id)))
+        );
+    });
+}
+
+/// Subscription ids are per connection and the number of subscriptions of a connection is limited.
+#[test]
+fn test_too_many_subscriptions() {
+    run_ws_test(|addr| async move {
+        let mut connection = connect(&addr).await;
+        let params = json!({"subscription_type": "new_chunks", "shard_id": 0});
+        for expected_id in 0..32 {
+            assert_eq!(subscribe(&mut connection, params.clone()).await, Ok(expected_id));
+        }
+        let error = subscribe(&mut connection, params.clone()).await.unwrap_err();
+        assert_eq!(
+            error.data,
+            Some(Value::String(
+                "Too many subscriptions on this connection, the limit is 32".to_string()
+            ))
+        );
+
+        // Unsubscribing frees a slot.
+        call(&mut connection, "EXPERIMENTAL_unsubscribe", json!([0])).await.unwrap();
+        assert_eq!(subscribe(&mut connection, params.clone()).await, Ok(32));
+
+        // Other connections have their own subscriptions.
+        let mut other_connection = connect(&addr).await;
+        assert_eq!(subscribe(&mut other_connection, params).await, Ok(0));
+    });
+}
+
+/// Unknown methods and invalid subscriptions are rejected.
+#[test]
+fn test_invalid_subscribe() {
+    run_ws_test(|addr| async move {
+        let mut connection = connect(&addr).await;
+        assert!(subscribe(&mut connection, json!({"subscription_type": "unknown"})).await.is_err());
+        assert!(call(&mut connection, "status", json!([])).await.is_err());
+    });
+}
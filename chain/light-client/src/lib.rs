@@ -0,0 +1,539 @@
+//! Light client which follows the chain by `LightClientBlockView`s (as returned by the
+//! `next_light_client_block` RPC method) and verifies execution outcome proofs (as returned by
+//! the `light_client_proof` RPC method) without trusting the node it talks to.
+//!
+//! The client is bootstrapped from a trusted block and the block producers of its epoch.
+//! Every next block is accepted only if it is approved by more than 2/3 of the stake of the
+//! block producers of its epoch. The block producers of the next epoch are taken from the
+//! `next_bps` of the accepted blocks, which are committed to by `next_bp_hash`.
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use near_primitives::block::{Approval, ApprovalInner, BlockHeaderInnerLite};
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::merkle::{
+    combine_hash, compute_root_from_path_and_item, verify_hash, verify_path, MerklePath,
+};
+use near_primitives::serialize::from_base64;
+use near_primitives::transaction::PartialExecutionStatus;
+use near_primitives::types::{AccountId, Balance, BlockHeight};
+use near_primitives::views::{
+    ExecutionOutcomeWithIdView, ExecutionStatusView, LightClientBlockLiteView,
+    LightClientBlockView, ValidatorStakeView,
+};
+
+pub use crate::store::{FileLightClientStore, InMemoryLightClientStore, LightClientStore};
+
+mod store;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Block height {height} is not above the head height {head_height}")]
+    NotAboveHead { height: BlockHeight, head_height: BlockHeight },
+    #[error("Block epoch {0} is neither the epoch of the head nor the next one")]
+    UnknownEpoch(CryptoHash),
+    #[error("Block producers of epoch {0} are unknown")]
+    UnknownEpochBlockProducers(CryptoHash),
+    #[error("The first block of the next epoch doesn't contain the next block producers")]
+    MissingNextBlockProducers,
+    #[error("Next block producers don't match next_bp_hash of the block")]
+    InvalidNextBlockProducers,
+    #[error("Invalid approval signature of {0}")]
+    InvalidApprovalSignature(AccountId),
+    #[error(
+        "Block has {num_approvals} approvals, but there are {num_block_producers} block producers"
+    )]
+    InvalidNumberOfApprovals { num_approvals: usize, num_block_producers: usize },
+    #[error("Block is approved by {approved_stake} out of {total_stake} stake, more than 2/3 is required")]
+    NotEnoughApprovals { approved_stake: Balance, total_stake: Balance },
+    #[error("Execution outcome proof doesn't match the block")]
+    InvalidOutcomeProof,
+    #[error("Block {0} is not an ancestor of the light client head")]
+    InvalidBlockProof(CryptoHash),
+    #[error("Light client store error: {0}")]
+    Store(#[from] std::io::Error),
+}
+
+/// Everything the light client needs to know to validate the next blocks.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct LightClientState {
+    pub head: LightClientBlockLiteView,
+    /// Ordered block producers of the epoch of the head.
+    pub epoch_block_producers: Vec<ValidatorStakeView>,
+    /// Ordered block producers of the next epoch, if they are known already.
+    pub next_epoch_block_producers: Option<Vec<ValidatorStakeView>>,
+}
+
+/// Computes the hash of the block from the parts of its header known to the light client.
+pub fn light_client_block_hash(block: &LightClientBlockLiteView) -> CryptoHash {
+    let inner_lite = BlockHeaderInnerLite::from(block.inner_lite.clone());
+    let inner_lite_hash = hash(&inner_lite.try_to_vec().expect("Failed to serialize"));
+    combine_hash(combine_hash(inner_lite_hash, block.inner_rest_hash), block.prev_block_hash)
+}
+
+fn to_lite_view(block: &LightClientBlockView) -> LightClientBlockLiteView {
+    LightClientBlockLiteView {
+        prev_block_hash: block.prev_block_hash,
+        inner_rest_hash: block.inner_rest_hash,
+        inner_lite: block.inner_lite.clone(),
+    }
+}
+
+/// Hashes the execution outcome the same way as `ExecutionOutcomeWithId::to_hashes`.
+fn outcome_hashes(outcome_with_id: &ExecutionOutcomeWithIdView) -> Result<Vec<CryptoHash>, Error> {
+    let outcome = &outcome_with_id.outcome;
+    let status = match &outcome.status {
+        ExecutionStatusView::Unknown => PartialExecutionStatus::Unknown,
+        ExecutionStatusView::SuccessValue(value) => PartialExecutionStatus::SuccessValue(
+            from_base64(value).map_err(|_| Error::InvalidOutcomeProof)?,
+        ),
+        ExecutionStatusView::Failure(_) => PartialExecutionStatus::Failure,
+        ExecutionStatusView::SuccessReceiptId(receipt_id) => {
+            PartialExecutionStatus::SuccessReceiptId(*receipt_id)
+        }
+    };
+    // Same Borsh layout as `PartialExecutionOutcome`
+    let partial_outcome = (
+        outcome.receipt_ids.clone(),
+        outcome.gas_burnt,
+        outcome.tokens_burnt,
+        outcome.executor_id.clone(),
+        status,
+    );
+    let mut result =
+        vec![outcome_with_id.id, hash(&partial_outcome.try_to_vec().expect("Failed to serialize"))];
+    result.extend(outcome.logs.iter().map(|log| hash(log.as_bytes())));
+    Ok(result)
+}
+
+pub struct LightClient<S: LightClientStore> {
+    state: LightClientState,
+    store: S,
+}
+
+impl<S: LightClientStore> LightClient<S> {
+    /// Restores the light client from the store.
+    /// Returns `None` if the store is empty and the client has to be bootstrapped.
+    pub fn open(store: S) -> Result<Option<Self>, Error> {
+        Ok(store.load()?.map(|state| Self { state, store }))
+    }
+
+    /// Bootstraps the light client from a trusted block and the ordered block producers of
+    /// its epoch, and persists its state.
+    pub fn bootstrap(
+        mut store: S,
+        trusted_block: &LightClientBlockView,
+        epoch_block_producers: Vec<ValidatorStakeView>,
+    ) -> Result<Self, Error> {
+        if let Some(next_bps) = &trusted_block.next_bps {
+            Self::validate_next_block_producers(trusted_block, next_bps)?;
+        }
+        let state = LightClientState {
+            head: to_lite_view(trusted_block),
+            epoch_block_producers,
+            next_epoch_block_producers: trusted_block.next_bps.clone(),
+        };
+        store.save(&state)?;
+        Ok(Self { state, store })
+    }
+
+    pub fn head(&self) -> &LightClientBlockLiteView {
+        &self.state.head
+    }
+
+    pub fn head_hash(&self) -> CryptoHash {
+        light_client_block_hash(&self.state.head)
+    }
+
+    pub fn state(&self) -> &LightClientState {
+        &self.state
+    }
+
+    fn validate_next_block_producers(
+        block: &LightClientBlockView,
+        next_bps: &[ValidatorStakeView],
+    ) -> Result<(), Error> {
+        let next_bps_hash = hash(&next_bps.try_to_vec().expect("Failed to serialize"));
+        if next_bps_hash != block.inner_lite.next_bp_hash {
+            return Err(Error::InvalidNextBlockProducers);
+        }
+        Ok(())
+    }
+
+    /// Checks that the block can become the new head of the light client.
+    pub fn validate_block(&self, block: &LightClientBlockView) -> Result<(), Error> {
+        let head = &self.state.head;
+        if block.inner_lite.height <= head.inner_lite.height {
+            return Err(Error::NotAboveHead {
+                height: block.inner_lite.height,
+                head_height: head.inner_lite.height,
+            });
+        }
+
+        let block_producers = if block.inner_lite.epoch_id == head.inner_lite.epoch_id {
+            &self.state.epoch_block_producers
+        } else if block.inner_lite.epoch_id == head.inner_lite.next_epoch_id {
+            if block.next_bps.is_none() {
+                return Err(Error::MissingNextBlockProducers);
+            }
+            self.state
+                .next_epoch_block_producers
+                .as_ref()
+                .ok_or(Error::UnknownEpochBlockProducers(block.inner_lite.epoch_id))?
+        } else {
+            return Err(Error::UnknownEpoch(block.inner_lite.epoch_id));
+        };
+
+        // Approvals are signatures of the block two blocks ahead, which endorse the next block
+        let current_block_hash = light_client_block_hash(&to_lite_view(block));
+        let next_block_hash = combine_hash(block.next_block_inner_hash, current_block_hash);
+        let approval_message = Approval::get_data_for_sig(
+            &ApprovalInner::Endorsement(next_block_hash),
+            block.inner_lite.height + 2,
+        );
+
+        // Every block producer has a slot in the approvals, otherwise a truncated list of
+        // approvals would leave the stake of the missing block producers out of the total.
+        // Approvals of the block producers of the next epoch may follow at the end of the list
+        // when the block is close to the epoch boundary, they don't count towards the stake.
+        if block.approvals_after_next.len() < block_producers.len() {
+            return Err(Error::InvalidNumberOfApprovals {
+                num_approvals: block.approvals_after_next.len(),
+                num_block_producers: block_producers.len(),
+            });
+        }
+        let total_stake: Balance =
+            block_producers.iter().map(|block_producer| block_producer.stake).sum();
+        let mut approved_stake = 0;
+        for (maybe_signature, block_producer) in
+            block.approvals_after_next[..block_producers.len()].iter().zip(block_producers.iter())
+        {
+            let signature = match maybe_signature {
+                Some(signature) => signature,
+                None => continue,
+            };
+            approved_stake += block_producer.stake;
+            if !signature.verify(&approval_message, &block_producer.public_key) {
+                return Err(Error::InvalidApprovalSignature(block_producer.account_id.clone()));
+            }
+        }
+        if approved_stake * 3 <= total_stake * 2 {
+            return Err(Error::NotEnoughApprovals { approved_stake, total_stake });
+        }
+
+        if let Some(next_bps) = &block.next_bps {
+            Self::validate_next_block_producers(block, next_bps)?;
+        }
+        Ok(())
+    }
+
+    /// Validates the block and makes it the new head of the light client.
+    /// The new state is persisted before it is applied.
+    pub fn update_head(&mut self, block: &LightClientBlockView) -> Result<(), Error> {
+        self.validate_block(block)?;
+
+        let mut state = self.state.clone();
+        if block.inner_lite.epoch_id != state.head.inner_lite.epoch_id {
+            state.epoch_block_producers = state
+                .next_epoch_block_producers
+                .take()
+                .expect("Block producers of the next epoch are checked by validate_block");
+        }
+        if let Some(next_bps) = &block.next_bps {
+            state.next_epoch_block_producers = Some(next_bps.clone());
+        }
+        state.head = to_lite_view(block);
+
+        self.store.save(&state)?;
+        self.state = state;
+        Ok(())
+    }
+
+    /// Verifies the execution outcome proof returned by `light_client_proof` RPC method,
+    /// which has to be requested with the hash of the current head as `light_client_head`.
+    pub fn verify_outcome_proof(
+        &self,
+        outcome_proof: &ExecutionOutcomeWithIdView,
+        outcome_root_proof: &MerklePath,
+        block_header_lite: &LightClientBlockLiteView,
+        block_proof: &MerklePath,
+    ) -> Result<(), Error> {
+        let chunk_outcome_root =
+            compute_root_from_path_and_item(&outcome_proof.proof, &outcome_hashes(outcome_proof)?);
+        if !verify_path(
+            block_header_lite.inner_lite.outcome_root,
+            outcome_root_proof,
+            &chunk_outcome_root,
+        ) {
+            return Err(Error::InvalidOutcomeProof);
+        }
+        let block_hash = light_client_block_hash(block_header_lite);
+        if block_hash != outcome_proof.block_hash {
+            return Err(Error::InvalidOutcomeProof);
+        }
+        self.verify_block_proof(block_hash, block_proof)
+    }
+
+    /// Verifies that the block is the head or one of its ancestors. `block_merkle_root` of
+    /// the head is the root of the merkle tree of all the blocks before it.
+    pub fn verify_block_proof(
+        &self,
+        block_hash: CryptoHash,
+        block_proof: &MerklePath,
+    ) -> Result<(), Error> {
+        if block_hash == self.head_hash()
+            || verify_hash(self.state.head.inner_lite.block_merkle_root, block_proof, block_hash)
+        {
+            Ok(())
+        } else {
+            Err(Error::InvalidBlockProof(block_hash))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::KeyType;
+    use near_primitives::merkle::merklize;
+    use near_primitives::transaction::{ExecutionOutcome, ExecutionOutcomeWithId, ExecutionStatus};
+    use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
+    use near_primitives::views::BlockHeaderInnerLiteView;
+
+    use super::*;
+
+    fn create_signers(epoch: &str) -> Vec<InMemoryValidatorSigner> {
+        (0..4)
+            .map(|i| {
+                let account_id = format!("test{}", i);
+                InMemoryValidatorSigner::from_seed(
+                    &account_id,
+                    KeyType::ED25519,
+                    &format!("{}{}", epoch, account_id),
+                )
+            })
+            .collect()
+    }
+
+    fn to_block_producers(signers: &[InMemoryValidatorSigner]) -> Vec<ValidatorStakeView> {
+        signers
+            .iter()
+            .map(|signer| ValidatorStakeView {
+                account_id: signer.validator_id().clone(),
+                public_key: signer.public_key(),
+                stake: 100,
+            })
+            .collect()
+    }
+
+    fn create_block(
+        height: BlockHeight,
+        epoch_id: CryptoHash,
+        next_epoch_id: CryptoHash,
+        next_bps: &[ValidatorStakeView],
+        signers: &[InMemoryValidatorSigner],
+        num_approvals: usize,
+    ) -> LightClientBlockView {
+        let mut block = LightClientBlockView {
+            prev_block_hash: hash(&height.to_le_bytes()),
+            next_block_inner_hash: hash(&(height + 1).to_le_bytes()),
+            inner_lite: BlockHeaderInnerLiteView {
+                height,
+                epoch_id,
+                next_epoch_id,
+                prev_state_root: CryptoHash::default(),
+                outcome_root: CryptoHash::default(),
+                timestamp: height,
+                timestamp_nanosec: height,
+                next_bp_hash: hash(&next_bps.try_to_vec().unwrap()),
+                block_merkle_root: CryptoHash::default(),
+            },
+            inner_rest_hash: CryptoHash::default(),
+            next_bps: Some(next_bps.to_vec()),
+            approvals_after_next: vec![],
+        };
+        let next_block_hash = combine_hash(
+            block.next_block_inner_hash,
+            light_client_block_hash(&to_lite_view(&block)),
+        );
+        block.approvals_after_next =
+            signers
+                .iter()
+                .enumerate()
+                .map(|(i, signer)| {
+                    if i < num_approvals {
+                        Some(signer.sign_approval(
+                            &ApprovalInner::Endorsement(next_block_hash),
+                            height + 2,
+                        ))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+        block
+    }
+
+    #[test]
+    fn test_update_head() {
+        let (epoch0, epoch1, epoch2) = (hash(b"epoch0"), hash(b"epoch1"), hash(b"epoch2"));
+        let (signers0, signers1, signers2) =
+            (create_signers("epoch0"), create_signers("epoch1"), create_signers("epoch2"));
+        let (bps0, bps1, bps2) = (
+            to_block_producers(&signers0),
+            to_block_producers(&signers1),
+            to_block_producers(&signers2),
+        );
+        let trusted_block = create_block(10, epoch0, epoch1, &bps1, &signers0, 4);
+        let mut light_client = LightClient::bootstrap(
+            InMemoryLightClientStore::default(),
+            &trusted_block,
+            bps0.clone(),
+        )
+        .unwrap();
+
+        // 2 out of 4 equal stakes is not enough
+        let block = create_block(15, epoch0, epoch1, &bps1, &signers0, 2);
+        assert!(matches!(light_client.update_head(&block), Err(Error::NotEnoughApprovals { .. })));
+        // Truncating the approvals doesn't drop the stake of the missing block producers
+        let mut block = create_block(15, epoch0, epoch1, &bps1, &signers0, 3);
+        block.approvals_after_next.truncate(3);
+        assert!(matches!(
+            light_client.update_head(&block),
+            Err(Error::InvalidNumberOfApprovals { num_approvals: 3, num_block_producers: 4 })
+        ));
+        let block = create_block(15, epoch0, epoch1, &bps1, &signers0, 3);
+        light_client.update_head(&block).unwrap();
+        assert_eq!(light_client.head().inner_lite.height, 15);
+        assert!(matches!(light_client.update_head(&block), Err(Error::NotAboveHead { .. })));
+
+        // Blocks of the next epoch must be signed by its block producers
+        let block = create_block(20, epoch1, epoch2, &bps2, &signers0, 4);
+        assert!(matches!(
+            light_client.update_head(&block),
+            Err(Error::InvalidApprovalSignature(_))
+        ));
+        let mut block = create_block(20, epoch1, epoch2, &bps2, &signers1, 4);
+        block.next_bps = Some(bps0.clone());
+        assert!(matches!(light_client.update_head(&block), Err(Error::InvalidNextBlockProducers)));
+        let block = create_block(20, epoch1, epoch2, &bps2, &signers1, 4);
+        light_client.update_head(&block).unwrap();
+        assert_eq!(light_client.state().epoch_block_producers, bps1);
+        assert_eq!(light_client.state().next_epoch_block_producers, Some(bps2));
+
+        // Epochs can't be skipped
+        let block = create_block(30, hash(b"epoch3"), hash(b"epoch4"), &[], &signers2, 4);
+        assert!(matches!(light_client.update_head(&block), Err(Error::UnknownEpoch(_))));
+    }
+
+    #[test]
+    fn test_approvals_of_next_epoch() {
+        let (epoch0, epoch1) = (hash(b"epoch0"), hash(b"epoch1"));
+        let (signers0, signers1) = (create_signers("epoch0"), create_signers("epoch1"));
+        let (bps0, bps1) = (to_block_producers(&signers0), to_block_producers(&signers1));
+        let trusted_block = create_block(10, epoch0, epoch1, &bps1, &signers0, 4);
+        let light_client =
+            LightClient::bootstrap(InMemoryLightClientStore::default(), &trusted_block, bps0)
+                .unwrap();
+
+        // Approvals of the next epoch's block producers follow the ones of the current epoch
+        let mut block = create_block(15, epoch0, epoch1, &bps1, &signers0, 3);
+        let next_epoch_block = create_block(15, epoch0, epoch1, &bps1, &signers1, 4);
+        block.approvals_after_next.extend(next_epoch_block.approvals_after_next);
+        light_client.validate_block(&block).unwrap();
+
+        // They don't make up for missing approvals of the current epoch
+        let mut block = create_block(15, epoch0, epoch1, &bps1, &signers0, 2);
+        let next_epoch_block = create_block(15, epoch0, epoch1, &bps1, &signers1, 4);
+        block.approvals_after_next.extend(next_epoch_block.approvals_after_next);
+        assert!(matches!(
+            light_client.validate_block(&block),
+            Err(Error::NotEnoughApprovals { .. })
+        ));
+    }
+
+    #[test]
+    fn test_persist_head() {
+        let dir = tempfile::Builder::new().prefix("light_client").tempdir().unwrap();
+        let path = dir.path().join("state");
+        let (epoch0, epoch1) = (hash(b"epoch0"), hash(b"epoch1"));
+        let signers = create_signers("epoch0");
+        let bps = to_block_producers(&signers);
+
+        assert!(LightClient::open(FileLightClientStore::new(&path)).unwrap().is_none());
+        let trusted_block = create_block(10, epoch0, epoch1, &bps, &signers, 4);
+        let mut light_client =
+            LightClient::bootstrap(FileLightClientStore::new(&path), &trusted_block, bps.clone())
+                .unwrap();
+        let block = create_block(11, epoch0, epoch1, &bps, &signers, 4);
+        light_client.update_head(&block).unwrap();
+
+        let light_client = LightClient::open(FileLightClientStore::new(&path)).unwrap().unwrap();
+        assert_eq!(light_client.head_hash(), light_client_block_hash(&to_lite_view(&block)));
+        assert_eq!(light_client.state().epoch_block_producers, bps);
+    }
+
+    #[test]
+    fn test_verify_outcome_proof() {
+        let outcomes = vec![
+            ExecutionOutcomeWithId {
+                id: hash(b"tx"),
+                outcome: ExecutionOutcome {
+                    logs: vec!["log".to_string()],
+                    receipt_ids: vec![hash(b"receipt")],
+                    gas_burnt: 100,
+                    tokens_burnt: 1000,
+                    executor_id: "test0".to_string(),
+                    status: ExecutionStatus::SuccessValue(vec![1, 2, 3]),
+                },
+            },
+            ExecutionOutcomeWithId {
+                id: hash(b"receipt"),
+                outcome: ExecutionOutcome {
+                    executor_id: "test1".to_string(),
+                    status: ExecutionStatus::SuccessReceiptId(hash(b"receipt2")),
+                    ..Default::default()
+                },
+            },
+        ];
+        let (chunk_outcome_root, outcome_proofs) =
+            merklize(&outcomes.iter().map(ExecutionOutcomeWithId::to_hashes).collect::<Vec<_>>());
+        let (outcome_root, outcome_root_proofs) =
+            merklize(&[chunk_outcome_root, hash(b"other_shard")]);
+
+        let signers = create_signers("epoch0");
+        let bps = to_block_producers(&signers);
+        let mut trusted_block =
+            create_block(10, hash(b"epoch0"), hash(b"epoch1"), &bps, &signers, 4);
+        trusted_block.inner_lite.outcome_root = outcome_root;
+        let light_client =
+            LightClient::bootstrap(InMemoryLightClientStore::default(), &trusted_block, bps)
+                .unwrap();
+        let block_header_lite = to_lite_view(&trusted_block);
+
+        for (outcome, proof) in outcomes.into_iter().zip(outcome_proofs) {
+            let mut outcome_proof = ExecutionOutcomeWithIdView {
+                proof,
+                block_hash: light_client.head_hash(),
+                id: outcome.id,
+                outcome: outcome.outcome.into(),
+            };
+            light_client
+                .verify_outcome_proof(
+                    &outcome_proof,
+                    &outcome_root_proofs[0],
+                    &block_header_lite,
+                    &vec![],
+                )
+                .unwrap();
+            outcome_proof.outcome.gas_burnt += 1;
+            assert!(matches!(
+                light_client.verify_outcome_proof(
+                    &outcome_proof,
+                    &outcome_root_proofs[0],
+                    &block_header_lite,
+                    &vec![],
+                ),
+                Err(Error::InvalidOutcomeProof)
+            ));
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::LightClientState;
+
+/// Storage of the light client state, so that the client can be restarted without
+/// bootstrapping it from a trusted block again.
+pub trait LightClientStore {
+    /// Returns the persisted state, or `None` if nothing has been persisted yet.
+    fn load(&self) -> io::Result<Option<LightClientState>>;
+
+    fn save(&mut self, state: &LightClientState) -> io::Result<()>;
+}
+
+/// Keeps the state in a single Borsh-encoded file, which is replaced atomically on every save.
+pub struct FileLightClientStore {
+    path: PathBuf,
+}
+
+impl FileLightClientStore {
+    pub fn new(path: &Path) -> Self {
+        Self { path: path.to_path_buf() }
+    }
+}
+
+impl LightClientStore for FileLightClientStore {
+    fn load(&self) -> io::Result<Option<LightClientState>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => LightClientState::try_from_slice(&bytes).map(Some),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn save(&mut self, state: &LightClientState) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, state.try_to_vec()?)?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
+
+/// Keeps the state in memory only.
+#[derive(Default)]
+pub struct InMemoryLightClientStore {
+    state: Option<LightClientState>,
+}
+
+impl LightClientStore for InMemoryLightClientStore {
+    fn load(&self) -> io::Result<Option<LightClientState>> {
+        Ok(self.state.clone())
+    }
+
+    fn save(&mut self, state: &LightClientState) -> io::Result<()> {
+        self.state = Some(state.clone());
+        Ok(())
+    }
+}
@@ -6,7 +6,7 @@ use tokio_util::codec::{Decoder, Encoder};
 
 use crate::types::{PeerMessage, ReasonForBan};
 
-const NETWORK_MESSAGE_MAX_SIZE: u32 = 512 << 20; // 512MB
+pub(crate) const NETWORK_MESSAGE_MAX_SIZE: u32 = 512 << 20; // 512MB
 
 pub struct Codec {
     max_length: u32,
@@ -136,6 +136,8 @@ mod test {
         version::{OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION, PROTOCOL_VERSION},
     };
 
+    use crate::compression::{is_compression_supported, Compression};
+    use crate::encryption::{is_encryption_supported, EphemeralKey};
     use crate::routing::EdgeInfo;
     use crate::types::{
         Handshake, HandshakeFailureReason, HandshakeV2, PeerChainInfo, PeerChainInfoV2,
@@ -245,11 +247,51 @@ mod test {
                 archival: false,
             },
             edge_info: EdgeInfo::default(),
+            encryption_offer: None,
+            compression: vec![],
         };
         let msg = PeerMessage::Handshake(fake_handshake);
         test_codec(msg);
     }
 
+    #[test]
+    fn test_peer_message_handshake_versioned_fields() {
+        let secret_key = SecretKey::from_random(KeyType::ED25519);
+        let peer_id = PeerId::new(secret_key.public_key());
+        let offer = EphemeralKey::new(&secret_key).offer().clone();
+        for version in [OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION, PROTOCOL_VERSION].iter() {
+            let handshake = Handshake::new(
+                *version,
+                peer_id.clone(),
+                peer_id.clone(),
+                None,
+                PeerChainInfoV2::default(),
+                EdgeInfo::default(),
+                Some(offer.clone()),
+                vec![Compression::Zstd],
+            );
+            let bytes = peer_message_to_bytes(&PeerMessage::Handshake(handshake)).unwrap();
+            // Versions without encryption keep the layout that older peers can read.
+            match bytes_to_peer_message(&bytes).unwrap() {
+                PeerMessage::Handshake(handshake) => {
+                    assert_eq!(
+                        handshake.encryption_offer,
+                        Some(offer.clone()).filter(|_| is_encryption_supported(*version))
+                    );
+                    assert_eq!(
+                        handshake.compression,
+                        if is_compression_supported(*version) {
+                            vec![Compression::Zstd]
+                        } else {
+                            vec![]
+                        }
+                    );
+                }
+                _ => panic!("Expected handshake"),
+            }
+        }
+    }
+
     #[test]
     fn test_peer_message_handshake_v2() {
         let peer_info = PeerInfo::random();
@@ -0,0 +1,321 @@
+//! Optional compression of the large messages sent to peers, such as blocks, batches of headers,
+//! state parts and partial encoded chunks.
+//!
+//! The outbound side lists the algorithms it supports in its `Handshake` and the inbound side
+//! answers with the one it picked, or none. From then on every message of the connection starts
+//! with a byte telling whether it is compressed. Only messages above the threshold of the sender
+//! are compressed. The algorithms are only part of the handshake from the protocol version with
+//! `ProtocolFeature::PeerCompression`, so connections with older peers stay uncompressed.
+use std::fmt;
+use std::io::{self, Read};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use near_primitives::checked_feature;
+use near_primitives::version::ProtocolVersion;
+
+use crate::codec::NETWORK_MESSAGE_MAX_SIZE;
+use crate::types::PeerType;
+
+const UNCOMPRESSED: u8 = 0;
+const COMPRESSED: u8 = 1;
+
+#[derive(
+    BorshSerialize,
+    BorshDeserialize,
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    Zstd,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// Algorithms offered to the peers, in order of preference. Empty to disable compression.
+    pub algorithms: Vec<Compression>,
+    /// Messages smaller than this number of bytes are sent uncompressed.
+    pub threshold: usize,
+    /// zstd compression level.
+    pub zstd_level: i32,
+    /// Compressed messages larger than this number of bytes once decompressed are rejected, and
+    /// larger messages are sent uncompressed. Peers must use the same limit, so it is the maximum
+    /// size of a message by default.
+    pub max_decompressed_size: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            algorithms: vec![Compression::Zstd],
+            threshold: 4096,
+            zstd_level: 3,
+            max_decompressed_size: NETWORK_MESSAGE_MAX_SIZE as usize,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CompressionError {
+    /// The peer answered our handshake with algorithms we didn't offer.
+    UnexpectedAlgorithm(Vec<Compression>),
+}
+
+impl fmt::Display for CompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressionError::UnexpectedAlgorithm(algorithms) => {
+                write!(f, "peer answered with compression {:?} we didn't offer", algorithms)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CompressionError {}
+
+/// Whether handshakes with this protocol version list the compression algorithms.
+pub fn is_compression_supported(protocol_version: ProtocolVersion) -> bool {
+    checked_feature!("protocol_feature_peer_compression", PeerCompression, protocol_version)
+}
+
+/// Algorithms to list in the handshake of the outbound side.
+pub fn compression_offer(
+    config: &CompressionConfig,
+    protocol_version: ProtocolVersion,
+) -> Vec<Compression> {
+    if is_compression_supported(protocol_version) {
+        config.algorithms.clone()
+    } else {
+        vec![]
+    }
+}
+
+/// Decides the compression of the connection once the handshake of the peer is received. The
+/// inbound side picks the first algorithm of the peer that it supports, the outbound side uses
+/// the one picked by the peer.
+pub fn negotiate_compression(
+    config: &CompressionConfig,
+    peer_type: PeerType,
+    protocol_version: ProtocolVersion,
+    peer_algorithms: &[Compression],
+) -> Result<Option<Compression>, CompressionError> {
+    let algorithms = compression_offer(config, protocol_version);
+    match peer_type {
+        PeerType::Inbound => {
+            Ok(peer_algorithms.iter().find(|algorithm| algorithms.contains(*algorithm)).cloned())
+        }
+        PeerType::Outbound => match peer_algorithms {
+            [] => Ok(None),
+            [algorithm] if algorithms.contains(algorithm) => Ok(Some(*algorithm)),
+            _ => Err(CompressionError::UnexpectedAlgorithm(peer_algorithms.to_vec())),
+        },
+    }
+}
+
+/// Compresses and decompresses the messages of one connection.
+pub struct MessageCompressor {
+    compressor: zstd::block::Compressor,
+    threshold: usize,
+    zstd_level: i32,
+    max_decompressed_size: usize,
+}
+
+impl MessageCompressor {
+    pub fn new(compression: Compression, config: &CompressionConfig) -> Self {
+        match compression {
+            Compression::Zstd => MessageCompressor {
+                compressor: zstd::block::Compressor::new(),
+                threshold: config.threshold,
+                zstd_level: config.zstd_level,
+                max_decompressed_size: config.max_decompressed_size,
+            },
+        }
+    }
+
+    /// Compresses the message if it is above the threshold and gets smaller. Messages over the
+    /// limit of decompressed messages are sent uncompressed, so that the peer doesn't reject them.
+    pub fn compress(&mut self, message: &[u8]) -> Vec<u8> {
+        if message.len() >= self.threshold && message.len() <= self.max_decompressed_size {
+            if let Ok(compressed) = self.compressor.compress(message, self.zstd_level) {
+                if compressed.len() + 4 < message.len() {
+                    let mut result = Vec::with_capacity(compressed.len() + 5);
+                    result.push(COMPRESSED);
+                    result.extend_from_slice(&(message.len() as u32).to_le_bytes());
+                    result.extend_from_slice(&compressed);
+                    return result;
+                }
+            }
+        }
+        let mut result = Vec::with_capacity(message.len() + 1);
+        result.push(UNCOMPRESSED);
+        result.extend_from_slice(message);
+        result
+    }
+
+    pub fn decompress(&mut self, message: &[u8]) -> io::Result<Vec<u8>> {
+        let invalid_data = |error: &str| io::Error::new(io::ErrorKind::InvalidData, error);
+        match message.split_first() {
+            Some((&UNCOMPRESSED, message)) => Ok(message.to_vec()),
+            Some((&COMPRESSED, message)) if message.len() >= 4 => {
+                let (length, compressed) = message.split_at(4);
+                let mut length_bytes = [0u8; 4];
+                length_bytes.copy_from_slice(length);
+                let length = u32::from_le_bytes(length_bytes) as usize;
+                if length > self.max_decompressed_size {
+                    return Err(invalid_data("Decompressed message is too long"));
+                }
+                // The length is only claimed by the peer, so the buffer grows with the data that
+                // is actually decompressed instead of being allocated up front.
+                let mut decompressed = Vec::new();
+                zstd::stream::read::Decoder::with_buffer(compressed)?
+                    .single_frame()
+                    .take(length as u64 + 1)
+                    .read_to_end(&mut decompressed)?;
+                if decompressed.len() != length {
+                    return Err(invalid_data("Unexpected length of decompressed message"));
+                }
+                Ok(decompressed)
+            }
+            _ => Err(invalid_data("Invalid compressed message header")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::version::{OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION, PROTOCOL_VERSION};
+
+    use super::{
+        is_compression_supported, negotiate_compression, Compression, CompressionConfig,
+        CompressionError, MessageCompressor,
+    };
+    use crate::codec::NETWORK_MESSAGE_MAX_SIZE;
+    use crate::types::PeerType;
+
+    #[test]
+    fn test_message_compressor() {
+        let config = CompressionConfig {
+            threshold: 100,
+            max_decompressed_size: 10_000,
+            ..Default::default()
+        };
+        let mut compressor = MessageCompressor::new(Compression::Zstd, &config);
+
+        // Small and incompressible messages are sent as they are.
+        let small = b"ping".to_vec();
+        let compressed = compressor.compress(&small);
+        assert_eq!(compressed.len(), small.len() + 1);
+        assert_eq!(compressor.decompress(&compressed).unwrap(), small);
+        let random: Vec<u8> = (0..1000).map(|_| rand::random()).collect();
+        let compressed = compressor.compress(&random);
+        assert_eq!(compressed.len(), random.len() + 1);
+        assert_eq!(compressor.decompress(&compressed).unwrap(), random);
+
+        let large = vec![7u8; 5000];
+        let compressed = compressor.compress(&large);
+        assert!(compressed.len() < large.len() / 10);
+        assert_eq!(compressor.decompress(&compressed).unwrap(), large);
+
+        // Messages over the limit are sent uncompressed.
+        let over_limit = vec![7u8; 20_000];
+        let compressed = compressor.compress(&over_limit);
+        assert_eq!(compressed.len(), over_limit.len() + 1);
+        assert_eq!(compressor.decompress(&compressed).unwrap(), over_limit);
+
+        // Compressed messages over the limit once decompressed and invalid headers are rejected.
+        let mut unlimited =
+            MessageCompressor::new(Compression::Zstd, &CompressionConfig::default());
+        let compressed = unlimited.compress(&over_limit);
+        assert!(compressed.len() < over_limit.len() / 10);
+        assert!(compressor.decompress(&compressed).is_err());
+        assert!(compressor.decompress(&[]).is_err());
+        assert!(compressor.decompress(&[2, 0]).is_err());
+    }
+
+    /// By default compressed messages up to the maximum size of a message are accepted, so large
+    /// messages such as state parts aren't rejected once decompressed.
+    #[test]
+    fn test_default_limit_allows_large_messages() {
+        let config = CompressionConfig::default();
+        assert_eq!(config.max_decompressed_size, NETWORK_MESSAGE_MAX_SIZE as usize);
+        let mut compressor = MessageCompressor::new(Compression::Zstd, &config);
+        let message = vec![7u8; 100 << 20];
+        let compressed = compressor.compress(&message);
+        assert!(compressed.len() < message.len() / 10);
+        assert_eq!(compressor.decompress(&compressed).unwrap(), message);
+    }
+
+    #[test]
+    fn test_forged_length_header() {
+        let config = CompressionConfig {
+            threshold: 100,
+            max_decompressed_size: 10_000,
+            ..Default::default()
+        };
+        let mut compressor = MessageCompressor::new(Compression::Zstd, &config);
+        let message = vec![7u8; 5000];
+        let compressed = compressor.compress(&message);
+        let forged = |length: u32| {
+            let mut forged = compressed.clone();
+            forged[1..5].copy_from_slice(&length.to_le_bytes());
+            forged
+        };
+
+        // A length over the limit is rejected before decompressing, a length within the limit
+        // that doesn't match the decompressed message is rejected too.
+        for length in [u32::MAX, 10_001, 10_000, 5001, 4999, 0].iter().cloned() {
+            assert!(compressor.decompress(&forged(length)).is_err(), "length {}", length);
+        }
+        assert_eq!(compressor.decompress(&forged(5000)).unwrap(), message);
+    }
+
+    #[test]
+    fn test_negotiate_compression() {
+        let config = CompressionConfig::default();
+        let disabled = CompressionConfig { algorithms: vec![], ..Default::default() };
+        let zstd = [Compression::Zstd];
+        let supported = is_compression_supported(PROTOCOL_VERSION);
+
+        // Older peers don't list any algorithm and the connection stays uncompressed.
+        let old_version = OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION;
+        assert!(!is_compression_supported(old_version));
+        for peer_type in [PeerType::Inbound, PeerType::Outbound].iter().cloned() {
+            assert_eq!(negotiate_compression(&config, peer_type, old_version, &[]), Ok(None));
+            assert_eq!(negotiate_compression(&config, peer_type, PROTOCOL_VERSION, &[]), Ok(None));
+        }
+
+        assert_eq!(
+            negotiate_compression(&config, PeerType::Inbound, PROTOCOL_VERSION, &zstd),
+            Ok(Some(Compression::Zstd).filter(|_| supported))
+        );
+        assert_eq!(
+            negotiate_compression(&disabled, PeerType::Inbound, PROTOCOL_VERSION, &zstd),
+            Ok(None)
+        );
+        assert_eq!(
+            negotiate_compression(&config, PeerType::Outbound, PROTOCOL_VERSION, &zstd),
+            if supported {
+                Ok(Some(Compression::Zstd))
+            } else {
+                Err(CompressionError::UnexpectedAlgorithm(zstd.to_vec()))
+            }
+        );
+        assert_eq!(
+            negotiate_compression(&disabled, PeerType::Outbound, PROTOCOL_VERSION, &zstd),
+            Err(CompressionError::UnexpectedAlgorithm(zstd.to_vec()))
+        );
+        assert_eq!(
+            negotiate_compression(&config, PeerType::Outbound, PROTOCOL_VERSION, &[zstd[0]; 2]),
+            Err(CompressionError::UnexpectedAlgorithm(vec![Compression::Zstd; 2]))
+        );
+    }
+}
@@ -0,0 +1,465 @@
+//! Optional encryption of the connections between peers, in the spirit of the Noise protocol.
+//!
+//! Each side of a connection generates an ephemeral X25519 key, signs it with its node key and
+//! sends it in its `Handshake`. After the handshakes are exchanged both sides derive a pair of
+//! keys, one per direction, from the Diffie-Hellman secret and the transcript of the key exchange.
+//! Every following message is encrypted with ChaCha20-Poly1305, with the index of the message as
+//! the nonce so that dropped, reordered or replayed messages are detected.
+//!
+//! The offer is only part of the handshake from the protocol version with
+//! `ProtocolFeature::PeerEncryption`. Peers with an older version reject the handshake with
+//! `HandshakeFailureReason::ProtocolVersionMismatch`, the handshake is sent again with their
+//! version and the connection falls back to plaintext unless `EncryptionMode::Required` is set.
+//! A handshake without an offer at a version that supports encryption is treated as a downgrade
+//! by a node that encrypts, so an offer stripped from the handshake fails the connection instead
+//! of falling back to plaintext.
+use std::fmt;
+
+use blake2::{Blake2b, Digest};
+use borsh::{BorshDeserialize, BorshSerialize};
+use chacha20poly1305::aead::{Aead, NewAead};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use curve25519_dalek::constants::X25519_BASEPOINT;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use curve25519_dalek::scalar::Scalar;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use near_crypto::{SecretKey, Signature};
+use near_primitives::checked_feature;
+use near_primitives::network::PeerId;
+use near_primitives::version::ProtocolVersion;
+
+use crate::types::PeerType;
+
+/// Personalization of all the BLAKE2b hashes, to separate them from other uses of the keys.
+const PERSONA: &[u8] = b"near-peer-enc-v1";
+/// Length of the authentication tag appended to every encrypted message.
+pub const TAG_LENGTH: usize = 16;
+
+/// Whether this node encrypts the connections with its peers.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EncryptionMode {
+    /// Never offer encryption. Peers that encrypt reject the connection unless its protocol
+    /// version doesn't support encryption.
+    Disabled,
+    /// Encrypt the connections with peers that support it, use plaintext with the peers with an
+    /// older protocol version.
+    Enabled,
+    /// Only accept connections with peers that encrypt them.
+    Required,
+}
+
+impl Default for EncryptionMode {
+    fn default() -> Self {
+        EncryptionMode::Enabled
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EncryptionError {
+    /// This node requires encryption but the protocol version of the peer doesn't support it.
+    Required,
+    /// The protocol version of the peer supports encryption but its handshake has no offer.
+    Downgrade,
+    /// The peer answered our handshake with an offer although we didn't make one.
+    UnexpectedOffer,
+    /// The offer is not signed by the peer.
+    InvalidSignature,
+    /// The ephemeral key of the peer is a low order point.
+    InvalidKey,
+    /// The message was not encrypted with the key of the session, or was modified.
+    InvalidTag,
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            EncryptionError::Required => "peer doesn't support encryption",
+            EncryptionError::Downgrade => "peer didn't offer encryption",
+            EncryptionError::UnexpectedOffer => "peer offered encryption without our offer",
+            EncryptionError::InvalidSignature => "invalid signature of the encryption offer",
+            EncryptionError::InvalidKey => "invalid ephemeral key",
+            EncryptionError::InvalidTag => "invalid authentication tag",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+/// Whether handshakes with this protocol version carry an `EncryptionOffer`.
+pub fn is_encryption_supported(protocol_version: ProtocolVersion) -> bool {
+    checked_feature!("protocol_feature_peer_encryption", PeerEncryption, protocol_version)
+}
+
+/// Decides whether to encrypt the connection once the handshake of the peer is received, given
+/// the protocol version used with the peer and whether its handshake carries an offer.
+///
+/// The inbound side answers with an offer only if the outbound side made one, so an offer in the
+/// answer to a handshake without one means that the handshakes were tampered with. A node that
+/// encrypts never falls back to plaintext at a protocol version that supports encryption, since
+/// the offer could have been stripped from the handshake.
+pub fn negotiate_encryption(
+    mode: EncryptionMode,
+    peer_type: PeerType,
+    protocol_version: ProtocolVersion,
+    peer_offered: bool,
+) -> Result<bool, EncryptionError> {
+    if mode == EncryptionMode::Disabled {
+        return match (peer_type, peer_offered) {
+            (PeerType::Outbound, true) => Err(EncryptionError::UnexpectedOffer),
+            _ => Ok(false),
+        };
+    }
+    match (is_encryption_supported(protocol_version), peer_offered) {
+        (true, true) => Ok(true),
+        (true, false) => Err(EncryptionError::Downgrade),
+        (false, true) if peer_type == PeerType::Outbound => Err(EncryptionError::UnexpectedOffer),
+        _ if mode == EncryptionMode::Required => Err(EncryptionError::Required),
+        _ => Ok(false),
+    }
+}
+
+/// Ephemeral public key of the sender of a handshake, signed with its node key.
+#[derive(BorshSerialize, BorshDeserialize, Serialize, PartialEq, Eq, Clone, Debug)]
+pub struct EncryptionOffer {
+    pub public_key: [u8; 32],
+    pub signature: Signature,
+}
+
+impl EncryptionOffer {
+    fn signed_data(public_key: &[u8; 32]) -> Vec<u8> {
+        [PERSONA, &public_key[..]].concat()
+    }
+
+    pub fn verify(&self, peer_id: &PeerId) -> bool {
+        self.signature.verify(&Self::signed_data(&self.public_key), &peer_id.public_key())
+    }
+}
+
+/// Ephemeral key of this node for one connection.
+pub struct EphemeralKey {
+    secret: Scalar,
+    offer: EncryptionOffer,
+}
+
+impl EphemeralKey {
+    pub fn new(secret_key: &SecretKey) -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        // Clamping as in X25519.
+        bytes[0] &= 248;
+        bytes[31] &= 127;
+        bytes[31] |= 64;
+        let secret = Scalar::from_bits(bytes);
+        let public_key = (&X25519_BASEPOINT * &secret).to_bytes();
+        let signature = secret_key.sign(&EncryptionOffer::signed_data(&public_key));
+        EphemeralKey { secret, offer: EncryptionOffer { public_key, signature } }
+    }
+
+    pub fn offer(&self) -> &EncryptionOffer {
+        &self.offer
+    }
+
+    /// Derives the session of the connection of this node `node_id` with the peer `peer_id`, which
+    /// made `peer_offer`. The keys are bound to the protocol version, the peer ids and both offers,
+    /// so both sides derive the same keys only if they saw the same key exchange.
+    pub fn session(
+        &self,
+        node_id: &PeerId,
+        peer_id: &PeerId,
+        peer_type: PeerType,
+        protocol_version: ProtocolVersion,
+        peer_offer: &EncryptionOffer,
+    ) -> Result<EncryptedSession, EncryptionError> {
+        if !peer_offer.verify(peer_id) {
+            return Err(EncryptionError::InvalidSignature);
+        }
+        let shared_secret = (&MontgomeryPoint(peer_offer.public_key) * &self.secret).to_bytes();
+        if bool::from(shared_secret.ct_eq(&[0u8; 32])) {
+            return Err(EncryptionError::InvalidKey);
+        }
+
+        let ((outbound_id, outbound_offer), (inbound_id, inbound_offer)) = match peer_type {
+            PeerType::Outbound => ((node_id, &self.offer), (peer_id, peer_offer)),
+            PeerType::Inbound => ((peer_id, peer_offer), (node_id, &self.offer)),
+        };
+        let transcript = (
+            protocol_version,
+            outbound_id,
+            inbound_id,
+            outbound_offer.public_key,
+            inbound_offer.public_key,
+        )
+            .try_to_vec()
+            .expect("Failed to serialize");
+        let outbound_to_inbound = CipherState::derive(&shared_secret, &transcript, 0);
+        let inbound_to_outbound = CipherState::derive(&shared_secret, &transcript, 1);
+        Ok(match peer_type {
+            PeerType::Outbound => {
+                EncryptedSession { send: outbound_to_inbound, receive: inbound_to_outbound }
+            }
+            PeerType::Inbound => {
+                EncryptedSession { send: inbound_to_outbound, receive: outbound_to_inbound }
+            }
+        })
+    }
+}
+
+fn keyed_hash(key: &[u8], data: &[&[u8]]) -> [u8; 64] {
+    let mut hasher = Blake2b::with_params(key, &[], PERSONA);
+    for part in data {
+        hasher.update(part);
+    }
+    let mut result = [0u8; 64];
+    result.copy_from_slice(&hasher.finalize());
+    result
+}
+
+/// Cipher and message index of one direction of the connection.
+struct CipherState {
+    cipher: ChaCha20Poly1305,
+    nonce: u64,
+}
+
+impl CipherState {
+    fn derive(shared_secret: &[u8; 32], transcript: &[u8], direction: u8) -> Self {
+        let keys = keyed_hash(shared_secret, &[transcript, &[direction]]);
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&keys[..32]));
+        CipherState { cipher, nonce: 0 }
+    }
+
+    /// Nonce of the next message, its index padded with zeros as in the Noise protocol.
+    fn nonce(&self) -> Nonce {
+        let mut nonce = [0u8; 12];
+        nonce[4..].copy_from_slice(&self.nonce.to_le_bytes());
+        Nonce::clone_from_slice(&nonce)
+    }
+
+    fn encrypt(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let message = self.cipher.encrypt(&self.nonce(), plaintext).expect("Failed to encrypt");
+        self.nonce = self.nonce.checked_add(1).expect("Nonce of the session overflowed");
+        message
+    }
+
+    fn decrypt(&mut self, message: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let plaintext =
+            self.cipher.decrypt(&self.nonce(), message).map_err(|_| EncryptionError::InvalidTag)?;
+        self.nonce = self.nonce.checked_add(1).expect("Nonce of the session overflowed");
+        Ok(plaintext)
+    }
+}
+
+/// Keys of an encrypted connection.
+pub struct EncryptedSession {
+    send: CipherState,
+    receive: CipherState,
+}
+
+impl EncryptedSession {
+    /// Encrypts the next message sent to the peer.
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        self.send.encrypt(plaintext)
+    }
+
+    /// Decrypts the next message received from the peer.
+    pub fn decrypt(&mut self, message: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        self.receive.decrypt(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{KeyType, SecretKey};
+    use near_primitives::network::PeerId;
+    use near_primitives::version::{OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION, PROTOCOL_VERSION};
+
+    use super::{
+        is_encryption_supported, negotiate_encryption, EncryptionError, EncryptionMode,
+        EphemeralKey, TAG_LENGTH,
+    };
+    use crate::types::PeerType;
+
+    struct Node {
+        secret_key: SecretKey,
+        peer_id: PeerId,
+    }
+
+    fn node(seed: &str) -> Node {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, seed);
+        let peer_id = PeerId::new(secret_key.public_key());
+        Node { secret_key, peer_id }
+    }
+
+    #[test]
+    fn test_encrypted_session() {
+        let (outbound, inbound) = (node("outbound"), node("inbound"));
+        let outbound_key = EphemeralKey::new(&outbound.secret_key);
+        let inbound_key = EphemeralKey::new(&inbound.secret_key);
+        let mut outbound_session = outbound_key
+            .session(
+                &outbound.peer_id,
+                &inbound.peer_id,
+                PeerType::Outbound,
+                PROTOCOL_VERSION,
+                inbound_key.offer(),
+            )
+            .unwrap();
+        let mut inbound_session = inbound_key
+            .session(
+                &inbound.peer_id,
+                &outbound.peer_id,
+                PeerType::Inbound,
+                PROTOCOL_VERSION,
+                outbound_key.offer(),
+            )
+            .unwrap();
+
+        let messages: [&[u8]; 3] = [b"", b"block", &[7u8; 1000]];
+        for message in messages.iter() {
+            let encrypted = outbound_session.encrypt(message);
+            assert_eq!(encrypted.len(), message.len() + TAG_LENGTH);
+            assert!(message.is_empty() || &encrypted[..message.len()] != *message);
+            assert_eq!(inbound_session.decrypt(&encrypted).unwrap(), message.to_vec());
+            let encrypted = inbound_session.encrypt(message);
+            assert_eq!(outbound_session.decrypt(&encrypted).unwrap(), message.to_vec());
+        }
+
+        // Modified, replayed and reordered messages are rejected.
+        let mut encrypted = outbound_session.encrypt(b"transaction");
+        encrypted[0] ^= 1;
+        assert_eq!(inbound_session.decrypt(&encrypted), Err(EncryptionError::InvalidTag));
+        let first = inbound_session.encrypt(b"first");
+        let second = inbound_session.encrypt(b"second");
+        assert_eq!(outbound_session.decrypt(&second), Err(EncryptionError::InvalidTag));
+        assert_eq!(outbound_session.decrypt(&first).unwrap(), b"first");
+        assert_eq!(outbound_session.decrypt(&first), Err(EncryptionError::InvalidTag));
+    }
+
+    #[test]
+    fn test_tampered_key_exchange() {
+        let (outbound, inbound, attacker) = (node("outbound"), node("inbound"), node("attacker"));
+        let outbound_key = EphemeralKey::new(&outbound.secret_key);
+        let inbound_key = EphemeralKey::new(&inbound.secret_key);
+
+        // An offer replaced by the attacker is not signed by the peer.
+        let attacker_key = EphemeralKey::new(&attacker.secret_key);
+        assert_eq!(
+            inbound_key
+                .session(
+                    &inbound.peer_id,
+                    &outbound.peer_id,
+                    PeerType::Inbound,
+                    PROTOCOL_VERSION,
+                    attacker_key.offer(),
+                )
+                .err(),
+            Some(EncryptionError::InvalidSignature)
+        );
+        let mut offer = outbound_key.offer().clone();
+        offer.public_key[0] ^= 1;
+        assert!(!offer.verify(&outbound.peer_id));
+
+        // Sides that saw different protocol versions derive different keys.
+        let mut outbound_session = outbound_key
+            .session(
+                &outbound.peer_id,
+                &inbound.peer_id,
+                PeerType::Outbound,
+                PROTOCOL_VERSION,
+                inbound_key.offer(),
+            )
+            .unwrap();
+        let mut inbound_session = inbound_key
+            .session(
+                &inbound.peer_id,
+                &outbound.peer_id,
+                PeerType::Inbound,
+                PROTOCOL_VERSION - 1,
+                outbound_key.offer(),
+            )
+            .unwrap();
+        let encrypted = outbound_session.encrypt(b"block");
+        assert_eq!(inbound_session.decrypt(&encrypted), Err(EncryptionError::InvalidTag));
+    }
+
+    #[test]
+    fn test_negotiate_encryption_downgrade() {
+        let old_version = OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION;
+        assert!(!is_encryption_supported(old_version));
+        let supported = is_encryption_supported(PROTOCOL_VERSION);
+
+        for peer_type in [PeerType::Inbound, PeerType::Outbound].iter().cloned() {
+            // A peer with an old protocol version falls back to plaintext unless encryption is
+            // required.
+            for mode in [EncryptionMode::Disabled, EncryptionMode::Enabled].iter().cloned() {
+                assert_eq!(negotiate_encryption(mode, peer_type, old_version, false), Ok(false));
+            }
+            assert_eq!(
+                negotiate_encryption(EncryptionMode::Required, peer_type, old_version, false),
+                Err(EncryptionError::Required)
+            );
+
+            // A node with encryption disabled never encrypts.
+            assert_eq!(
+                negotiate_encryption(EncryptionMode::Disabled, peer_type, PROTOCOL_VERSION, false),
+                Ok(false)
+            );
+
+            // A peer that supports encryption but doesn't offer it is rejected, since the offer
+            // could have been stripped from its handshake.
+            for mode in [EncryptionMode::Enabled, EncryptionMode::Required].iter().cloned() {
+                let expected = if supported {
+                    Err(EncryptionError::Downgrade)
+                } else if mode == EncryptionMode::Required {
+                    Err(EncryptionError::Required)
+                } else {
+                    Ok(false)
+                };
+                assert_eq!(
+                    negotiate_encryption(mode, peer_type, PROTOCOL_VERSION, false),
+                    expected
+                );
+            }
+        }
+
+        // An inbound node with encryption disabled ignores the offer, the outbound node rejects
+        // an offer it didn't ask for.
+        assert_eq!(
+            negotiate_encryption(
+                EncryptionMode::Disabled,
+                PeerType::Inbound,
+                PROTOCOL_VERSION,
+                true
+            ),
+            Ok(false)
+        );
+        assert_eq!(
+            negotiate_encryption(
+                EncryptionMode::Disabled,
+                PeerType::Outbound,
+                PROTOCOL_VERSION,
+                true
+            ),
+            Err(EncryptionError::UnexpectedOffer)
+        );
+
+        for peer_type in [PeerType::Inbound, PeerType::Outbound].iter().cloned() {
+            for mode in [EncryptionMode::Enabled, EncryptionMode::Required].iter().cloned() {
+                let expected = if supported {
+                    Ok(true)
+                } else if peer_type == PeerType::Outbound {
+                    Err(EncryptionError::UnexpectedOffer)
+                } else if mode == EncryptionMode::Required {
+                    Err(EncryptionError::Required)
+                } else {
+                    Ok(false)
+                };
+                assert_eq!(negotiate_encryption(mode, peer_type, PROTOCOL_VERSION, true), expected);
+            }
+        }
+    }
+}
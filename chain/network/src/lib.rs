@@ -9,12 +9,14 @@ pub use types::{
 
 mod cache;
 mod codec;
+pub mod compression;
+pub mod encryption;
 pub mod metrics;
 mod peer;
 mod peer_manager;
 pub mod peer_store;
 mod rate_counter;
-#[cfg(feature = "metric_recorder")]
+pub mod rate_limiter;
 pub mod recorder;
 pub mod routing;
 pub mod types;
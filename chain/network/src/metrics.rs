@@ -16,6 +16,11 @@ lazy_static! {
             "near_peer_message_received_total",
             "Number of messages received from peers"
         );
+    pub static ref PEER_MESSAGE_RATE_LIMITED_TOTAL: near_metrics::Result<IntCounter> =
+        try_create_int_counter(
+            "near_peer_message_rate_limited_total",
+            "Number of messages from peers dropped for exceeding the rate limits"
+        );
     pub static ref PEER_CLIENT_MESSAGE_RECEIVED_TOTAL: near_metrics::Result<IntCounter> =
         try_create_int_counter(
             "near_peer_client_message_received_total",
@@ -12,6 +12,8 @@ use actix::{
     Actor, ActorContext, ActorFuture, Addr, Arbiter, AsyncContext, Context, ContextFutureSpawner,
     Handler, Recipient, Running, StreamHandler, WrapFuture,
 };
+#[cfg(feature = "metric_recorder")]
+use chrono::Utc;
 use tracing::{debug, error, info, trace, warn};
 
 use near_metrics;
@@ -20,23 +22,33 @@ use near_primitives::block::GenesisId;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::PeerId;
 use near_primitives::unwrap_option_or_return;
+#[cfg(feature = "metric_recorder")]
+use near_primitives::utils::to_timestamp;
 use near_primitives::utils::DisplayOption;
 use near_primitives::version::{
     ProtocolVersion, OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION, PROTOCOL_VERSION,
 };
 
 use crate::codec::{self, bytes_to_peer_message, peer_message_to_bytes, Codec};
+use crate::compression::{
+    compression_offer, negotiate_compression, Compression, CompressionConfig, MessageCompressor,
+};
+use crate::encryption::{
+    is_encryption_supported, negotiate_encryption, EncryptedSession, EncryptionError,
+    EncryptionMode, EphemeralKey,
+};
 use crate::rate_counter::RateCounter;
+use crate::rate_limiter::{PeerRateLimiter, RateLimitResult};
 #[cfg(feature = "metric_recorder")]
-use crate::recorder::{PeerMessageMetadata, Status};
+use crate::recorder::{CapturedMessage, PeerMessageMetadata, Status};
 use crate::routing::{Edge, EdgeInfo};
 use crate::types::{
     Ban, Consolidate, ConsolidateResponse, Handshake, HandshakeFailureReason, HandshakeV2,
     NetworkClientMessages, NetworkClientResponses, NetworkRequests, NetworkViewClientMessages,
-    NetworkViewClientResponses, PeerChainInfo, PeerChainInfoV2, PeerInfo, PeerManagerRequest,
-    PeerMessage, PeerRequest, PeerResponse, PeerStatsResult, PeerStatus, PeerType, PeersRequest,
-    PeersResponse, QueryPeerStats, ReasonForBan, RoutedMessage, RoutedMessageBody,
-    RoutedMessageFrom, SendMessage, StateResponseInfo, Unregister,
+    NetworkViewClientResponses, PeerChainInfo, PeerChainInfoV2, PeerIdOrHash, PeerInfo,
+    PeerManagerRequest, PeerMessage, PeerRequest, PeerResponse, PeerStatsResult, PeerStatus,
+    PeerType, PeersRequest, PeersResponse, QueryPeerStats, ReasonForBan, RoutedMessage,
+    RoutedMessageBody, RoutedMessageFrom, SendMessage, StateResponseInfo, Unregister,
     UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE,
 };
 use crate::PeerManagerActor;
@@ -182,6 +194,25 @@ pub struct Peer {
     txns_since_last_block: Arc<AtomicUsize>,
     /// How many peer actors are created
     peer_counter: Arc<AtomicUsize>,
+    /// Limits on the rate of messages received from this peer.
+    rate_limiter: PeerRateLimiter,
+    /// Whether to encrypt the connection.
+    encryption_mode: EncryptionMode,
+    /// Ephemeral key offered to the peer for the encryption, unless it is disabled.
+    ephemeral_key: Option<EphemeralKey>,
+    /// Session negotiated with an inbound peer, used once our handshake is sent.
+    pending_session: Option<EncryptedSession>,
+    /// Session of the encrypted connection, used for all messages sent and received.
+    session: Option<EncryptedSession>,
+    /// Compression of the large messages sent to the peer.
+    compression_config: CompressionConfig,
+    /// Compression negotiated with an inbound peer, used once our handshake is sent.
+    pending_compression: Option<Compression>,
+    /// Compression of the connection, used for all messages sent and received.
+    compressor: Option<MessageCompressor>,
+    /// Whether to send all messages sent and received to the peer manager to be captured.
+    #[cfg(feature = "metric_recorder")]
+    capture_messages: bool,
 }
 
 impl Peer {
@@ -199,6 +230,11 @@ impl Peer {
         network_metrics: NetworkMetrics,
         txns_since_last_block: Arc<AtomicUsize>,
         peer_counter: Arc<AtomicUsize>,
+        rate_limiter: PeerRateLimiter,
+        encryption_mode: EncryptionMode,
+        ephemeral_key: Option<EphemeralKey>,
+        compression_config: CompressionConfig,
+        #[cfg(feature = "metric_recorder")] capture_messages: bool,
     ) -> Self {
         Peer {
             node_info,
@@ -220,6 +256,16 @@ impl Peer {
             network_metrics,
             txns_since_last_block,
             peer_counter,
+            rate_limiter,
+            encryption_mode,
+            ephemeral_key,
+            pending_session: None,
+            session: None,
+            compression_config,
+            pending_compression: None,
+            compressor: None,
+            #[cfg(feature = "metric_recorder")]
+            capture_messages,
         }
     }
 
@@ -232,6 +278,20 @@ impl Peer {
             || self.tracker.sent_bytes.count_per_min() > MAX_PEER_MSG_PER_MIN
     }
 
+    #[cfg(feature = "metric_recorder")]
+    fn capture_message(&self, status: Status, bytes: &[u8]) {
+        if self.capture_messages {
+            self.peer_manager_addr.do_send(CapturedMessage {
+                timestamp: to_timestamp(Utc::now()),
+                status,
+                local_peer_id: self.node_id(),
+                peer_id: self.peer_id(),
+                peer_addr: self.peer_addr.to_string(),
+                message: bytes.to_vec(),
+            });
+        }
+    }
+
     fn send_message(&mut self, msg: &PeerMessage) {
         // Skip sending block and headers if we received it or header from this peer.
         // Record block requests in tracker.
@@ -254,6 +314,16 @@ impl Peer {
             Ok(bytes) => {
                 #[cfg(feature = "metric_recorder")]
                 self.peer_manager_addr.do_send(metadata.set_size(bytes.len()));
+                #[cfg(feature = "metric_recorder")]
+                self.capture_message(Status::Sent, &bytes);
+                let bytes = match &mut self.compressor {
+                    Some(compressor) => compressor.compress(&bytes),
+                    None => bytes,
+                };
+                let bytes = match &mut self.session {
+                    Some(session) => session.encrypt(&bytes),
+                    None => bytes,
+                };
                 self.tracker.increment_sent(bytes.len() as u64);
                 self.framed.write(bytes);
             }
@@ -296,6 +366,23 @@ impl Peer {
                     tracked_shards,
                     archival,
                 }) => {
+                    // The inbound side only answers with an offer if encryption was negotiated.
+                    let encryption_offer = match act.peer_type {
+                        PeerType::Outbound if is_encryption_supported(act.protocol_version) => {
+                            act.ephemeral_key.as_ref()
+                        }
+                        PeerType::Outbound => None,
+                        PeerType::Inbound => {
+                            act.pending_session.as_ref().and(act.ephemeral_key.as_ref())
+                        }
+                    }
+                    .map(|ephemeral_key| ephemeral_key.offer().clone());
+                    let compression = match act.peer_type {
+                        PeerType::Outbound => {
+                            compression_offer(&act.compression_config, act.protocol_version)
+                        }
+                        PeerType::Inbound => act.pending_compression.into_iter().collect(),
+                    };
                     let handshake = match act.protocol_version {
                         39..=PROTOCOL_VERSION => PeerMessage::Handshake(Handshake::new(
                             act.protocol_version,
@@ -304,6 +391,8 @@ impl Peer {
                             act.node_info.addr_port(),
                             PeerChainInfoV2 { genesis_id, height, tracked_shards, archival },
                             act.edge_info.as_ref().unwrap().clone(),
+                            encryption_offer,
+                            compression,
                         )),
                         34..=38 => PeerMessage::HandshakeV2(HandshakeV2::new(
                             act.protocol_version,
@@ -320,6 +409,13 @@ impl Peer {
                     };
 
                     act.send_message(&handshake);
+                    // All the messages after our handshake are encrypted and compressed.
+                    if act.pending_session.is_some() {
+                        act.session = act.pending_session.take();
+                    }
+                    if let Some(compression) = act.pending_compression.take() {
+                        act.compressor = Some(act.message_compressor(compression));
+                    }
                     actix::fut::ready(())
                 }
                 Err(err) => {
@@ -331,6 +427,37 @@ impl Peer {
             .spawn(ctx);
     }
 
+    /// Derives the encrypted session from the handshake of the peer, if both sides offered it.
+    fn encryption_session(
+        &self,
+        handshake: &Handshake,
+    ) -> Result<Option<EncryptedSession>, EncryptionError> {
+        if !negotiate_encryption(
+            self.encryption_mode,
+            self.peer_type,
+            self.protocol_version,
+            handshake.encryption_offer.is_some(),
+        )? {
+            return Ok(None);
+        }
+        match (&self.ephemeral_key, &handshake.encryption_offer) {
+            (Some(ephemeral_key), Some(offer)) => ephemeral_key
+                .session(
+                    &self.node_id(),
+                    &handshake.peer_id,
+                    self.peer_type,
+                    self.protocol_version,
+                    offer,
+                )
+                .map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    fn message_compressor(&self, compression: Compression) -> MessageCompressor {
+        MessageCompressor::new(compression, &self.compression_config)
+    }
+
     fn ban_peer(&mut self, ctx: &mut Context<Peer>, ban_reason: ReasonForBan) {
         warn!(target: "network", "Banning peer {} for {:?}", self.peer_info, ban_reason);
         self.peer_status = PeerStatus::Banned(ban_reason);
@@ -342,6 +469,18 @@ impl Peer {
         self.node_info.id.clone()
     }
 
+    /// Whether the message counts against the rate limits of the peer. Routed messages only
+    /// count when they are targeted at this node, the peer merely relays the others and banning
+    /// it for them would punish the honest peers on the route of an abusive author.
+    fn is_rate_limited(&self, peer_msg: &PeerMessage) -> bool {
+        match peer_msg {
+            PeerMessage::Routed(routed_message) => {
+                routed_message.target == PeerIdOrHash::PeerId(self.node_id())
+            }
+            _ => true,
+        }
+    }
+
     fn peer_id(&self) -> Option<PeerId> {
         self.peer_info.as_ref().as_ref().map(|peer_info| peer_info.id.clone())
     }
@@ -678,8 +817,33 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
         near_metrics::inc_counter_by(&metrics::PEER_DATA_RECEIVED_BYTES, msg.len() as u64);
         near_metrics::inc_counter(&metrics::PEER_MESSAGE_RECEIVED_TOTAL);
 
+        let msg = match &mut self.session {
+            Some(session) => match session.decrypt(&msg) {
+                Ok(msg) => msg,
+                Err(err) => {
+                    warn!(target: "network", "Failed to decrypt message from {}: {}", self.peer_info, err);
+                    ctx.stop();
+                    return;
+                }
+            },
+            None => msg,
+        };
+        let msg = match &mut self.compressor {
+            Some(compressor) => match compressor.decompress(&msg) {
+                Ok(msg) => msg,
+                Err(err) => {
+                    warn!(target: "network", "Failed to decompress message from {}: {}", self.peer_info, err);
+                    ctx.stop();
+                    return;
+                }
+            },
+            None => msg,
+        };
+
         #[cfg(feature = "metric_recorder")]
         let msg_size = msg.len();
+        #[cfg(feature = "metric_recorder")]
+        self.capture_message(Status::Received, &msg);
 
         self.tracker.increment_received(msg.len() as u64);
         if codec::is_forward_tx(&msg).unwrap_or(false) {
@@ -752,6 +916,21 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
             msg.len() as u64,
         );
 
+        if self.peer_status == PeerStatus::Ready && self.is_rate_limited(&peer_msg) {
+            match self.rate_limiter.check(peer_msg.msg_variant()) {
+                RateLimitResult::Allowed => {}
+                RateLimitResult::Dropped => {
+                    near_metrics::inc_counter(&metrics::PEER_MESSAGE_RATE_LIMITED_TOTAL);
+                    trace!(target: "network", "Dropping {} from {}: rate limit exceeded", peer_msg.msg_variant(), self.peer_info);
+                    return;
+                }
+                RateLimitResult::Abusive => {
+                    self.ban_peer(ctx, ReasonForBan::Abusive);
+                    return;
+                }
+            }
+        }
+
         if let PeerMessage::HandshakeV2(handshake) = peer_msg {
             peer_msg = PeerMessage::Handshake(handshake.into());
         }
@@ -851,6 +1030,33 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
                     }
                 }
 
+                let session = match self.encryption_session(&handshake) {
+                    Ok(session) => session,
+                    Err(EncryptionError::InvalidSignature) => {
+                        warn!(target: "network", "Received invalid signature on encryption offer. Disconnecting peer {}", handshake.peer_id);
+                        self.ban_peer(ctx, ReasonForBan::InvalidSignature);
+                        return;
+                    }
+                    Err(err) => {
+                        info!(target: "network", "Failed to negotiate encryption with peer {}: {}. Disconnecting.", handshake.peer_id, err);
+                        ctx.stop();
+                        return;
+                    }
+                };
+                let compression = match negotiate_compression(
+                    &self.compression_config,
+                    self.peer_type,
+                    self.protocol_version,
+                    &handshake.compression,
+                ) {
+                    Ok(compression) => compression,
+                    Err(err) => {
+                        info!(target: "network", "Failed to negotiate compression with peer {}: {}. Disconnecting.", handshake.peer_id, err);
+                        ctx.stop();
+                        return;
+                    }
+                };
+
                 let peer_info = PeerInfo {
                     id: handshake.peer_id.clone(),
                     addr: handshake
@@ -867,6 +1073,7 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
                         chain_info: handshake.chain_info.clone(),
                         this_edge_info: self.edge_info.clone(),
                         other_edge_info: handshake.edge_info.clone(),
+                        compression,
                     })
                     .into_actor(self)
                     .then(move |res, act, ctx| {
@@ -877,7 +1084,15 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for Peer {
                                 // Respond to handshake if it's inbound and connection was consolidated.
                                 if act.peer_type == PeerType::Inbound {
                                     act.edge_info = edge_info;
+                                    act.pending_session = session;
+                                    act.pending_compression = compression;
                                     act.send_handshake(ctx);
+                                } else {
+                                    // The peer encrypts and compresses all the messages after its
+                                    // handshake.
+                                    act.session = session;
+                                    act.compressor = compression
+                                        .map(|compression| act.message_compressor(compression));
                                 }
                                 actix::fut::ready(())
                             },
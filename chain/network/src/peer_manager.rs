@@ -26,11 +26,14 @@ use near_primitives::utils::from_timestamp;
 use near_store::Store;
 
 use crate::codec::Codec;
+use crate::compression::Compression;
+use crate::encryption::{EncryptionMode, EphemeralKey};
 use crate::metrics;
 use crate::peer::Peer;
 use crate::peer_store::{PeerStore, TrustLevel};
+use crate::rate_limiter::PeerRateLimiter;
 #[cfg(feature = "metric_recorder")]
-use crate::recorder::{MetricRecorder, PeerMessageMetadata};
+use crate::recorder::{CaptureWriter, CapturedMessage, MetricRecorder, PeerMessageMetadata};
 use crate::routing::{Edge, EdgeInfo, EdgeType, ProcessEdgeResult, RoutingTable, MAX_NUM_PEERS};
 use crate::types::{
     AccountOrPeerIdOrHash, Ban, BlockedPorts, Consolidate, ConsolidateResponse, FullPeerInfo,
@@ -42,7 +45,7 @@ use crate::types::{
 };
 use crate::types::{
     EdgeList, KnownPeerState, NetworkClientMessages, NetworkConfig, NetworkRequests,
-    NetworkResponses, PeerInfo,
+    NetworkResponses, PeerAdminRequest, PeerAdminResponse, PeerInfo,
 };
 #[cfg(feature = "delay_detector")]
 use delay_detector::DelayDetector;
@@ -101,6 +104,8 @@ struct ActivePeer {
     connection_established_time: Instant,
     /// Who started connection. Inbound (other) or Outbound (us).
     peer_type: PeerType,
+    /// Compression of the messages with this peer.
+    compression: Option<Compression>,
 }
 
 struct EdgeVerifier {}
@@ -147,6 +152,9 @@ pub struct PeerManagerActor {
     /// Store all collected metrics from a node.
     #[cfg(feature = "metric_recorder")]
     metric_recorder: MetricRecorder,
+    /// Capture of all messages sent and received by the node, if `capture_path` is set.
+    #[cfg(feature = "metric_recorder")]
+    capture: Option<CaptureWriter>,
     edge_verifier_pool: Addr<EdgeVerifier>,
     txns_since_last_block: Arc<AtomicUsize>,
     pending_incoming_connections_counter: Arc<AtomicUsize>,
@@ -176,6 +184,14 @@ impl PeerManagerActor {
 
         #[cfg(feature = "metric_recorder")]
         let metric_recorder = MetricRecorder::default().set_me(me.clone());
+        #[cfg(feature = "metric_recorder")]
+        let capture = match &config.capture_path {
+            Some(path) => {
+                info!(target: "network", "Capturing network messages to {}", path.display());
+                Some(CaptureWriter::create(path)?)
+            }
+            None => None,
+        };
 
         let txns_since_last_block = Arc::new(AtomicUsize::new(0));
 
@@ -195,6 +211,8 @@ impl PeerManagerActor {
             edge_verifier_pool,
             #[cfg(feature = "metric_recorder")]
             metric_recorder,
+            #[cfg(feature = "metric_recorder")]
+            capture,
             txns_since_last_block,
             pending_incoming_connections_counter: Arc::new(AtomicUsize::new(0)),
             peer_counter: Arc::new(AtomicUsize::new(0)),
@@ -229,6 +247,7 @@ impl PeerManagerActor {
         edge_info: EdgeInfo,
         peer_type: PeerType,
         addr: Addr<Peer>,
+        compression: Option<Compression>,
         ctx: &mut Context<Self>,
     ) {
         debug!(target: "network", "Consolidated connection with {:?}", full_peer_info);
@@ -262,6 +281,7 @@ impl PeerManagerActor {
                 last_time_received_message: Instant::now(),
                 connection_established_time: Instant::now(),
                 peer_type,
+                compression,
             },
         );
 
@@ -435,6 +455,15 @@ impl PeerManagerActor {
 
         let network_metrics = self.network_metrics.clone();
         let txns_since_last_block = Arc::clone(&self.txns_since_last_block);
+        let rate_limiter = PeerRateLimiter::new(&self.config.rate_limits);
+        let encryption_mode = self.config.encryption;
+        let compression_config = self.config.compression.clone();
+        let ephemeral_key = match encryption_mode {
+            EncryptionMode::Disabled => None,
+            _ => Some(EphemeralKey::new(&self.config.secret_key)),
+        };
+        #[cfg(feature = "metric_recorder")]
+        let capture_messages = self.capture.is_some();
 
         // Start every peer actor on separate thread.
         let arbiter = Arbiter::new();
@@ -472,6 +501,12 @@ impl PeerManagerActor {
                 network_metrics,
                 txns_since_last_block,
                 peer_counter,
+                rate_limiter,
+                encryption_mode,
+                ephemeral_key,
+                compression_config,
+                #[cfg(feature = "metric_recorder")]
+                capture_messages,
             )
         });
     }
@@ -694,6 +729,8 @@ impl PeerManagerActor {
                             trace!(target: "network", "Banning peer {} for abuse ({} sent, {} recv)", peer_id1, res.message_counts.0, res.message_counts.1);
                             // TODO(MarX, #1586): Ban peer if we found them abusive. Fix issue with heavy
                             //  network traffic that flags honest peers.
+                            //  Peers exceeding the per message type `rate_limits` are already banned
+                            //  as abusive by the peer instance itself.
                             // Send ban signal to peer instance. It should send ban signal back and stop the instance.
                             // if let Some(active_peer) = act.active_peers.get(&peer_id1) {
                             //     active_peer.addr.do_send(PeerManagerRequest::BanPeer(ReasonForBan::Abusive));
@@ -1131,6 +1168,13 @@ impl PeerManagerActor {
                     addr: None,
                 })
                 .collect(),
+            peer_compression: self
+                .active_peers
+                .iter()
+                .filter_map(|(peer_id, active_peer)| {
+                    active_peer.compression.map(|compression| (peer_id.clone(), compression))
+                })
+                .collect(),
             #[cfg(feature = "metric_recorder")]
             metric_recorder: self.metric_recorder.clone(),
             peer_counter: self.peer_counter.load(Ordering::SeqCst),
@@ -1769,6 +1813,7 @@ impl Handler<Consolidate> for PeerManagerActor {
             edge_info,
             msg.peer_type,
             msg.actor,
+            msg.compression,
             ctx,
         );
 
@@ -1798,6 +1843,68 @@ impl Handler<Ban> for PeerManagerActor {
     }
 }
 
+impl Handler<PeerAdminRequest> for PeerManagerActor {
+    type Result = Result<PeerAdminResponse, String>;
+
+    #[perf]
+    fn handle(&mut self, msg: PeerAdminRequest, ctx: &mut Self::Context) -> Self::Result {
+        #[cfg(feature = "delay_detector")]
+        let _d = DelayDetector::new(format!("peer admin request {}", msg.as_ref()).into());
+        match msg {
+            PeerAdminRequest::ListPeers => Ok(PeerAdminResponse::Peers(
+                self.peer_store.iter().map(|(_, peer_state)| peer_state.clone()).collect(),
+            )),
+            PeerAdminRequest::BanPeer { peer_id, ban_reason } => {
+                if !self.active_peers.contains_key(&peer_id)
+                    && self.peer_store.get_peer_state(&peer_id).is_none()
+                {
+                    return Err(format!("Peer {} is not known", peer_id));
+                }
+                self.try_ban_peer(ctx, &peer_id, ban_reason);
+                Ok(PeerAdminResponse::Done)
+            }
+            PeerAdminRequest::UnbanPeer { peer_id } => {
+                if !self.peer_store.is_banned(&peer_id) {
+                    return Err(format!("Peer {} is not banned", peer_id));
+                }
+                info!(target: "network", "Unbanning peer {} by admin request", peer_id);
+                self.peer_store.peer_unban(&peer_id).map_err(|err| err.to_string())?;
+                Ok(PeerAdminResponse::Done)
+            }
+            PeerAdminRequest::ConnectPeer { peer_info } => {
+                if peer_info.addr.is_none() {
+                    return Err(format!("Peer {} has no address", peer_info.id));
+                }
+                if peer_info.id == self.peer_id {
+                    return Err("Can't connect to ourself".to_string());
+                }
+                if self.active_peers.contains_key(&peer_info.id)
+                    || self.outgoing_peers.contains(&peer_info.id)
+                {
+                    return Err(format!("Peer {} is already connected", peer_info.id));
+                }
+                if self.peer_store.is_banned(&peer_info.id) {
+                    return Err(format!("Peer {} is banned", peer_info.id));
+                }
+                self.peer_store
+                    .add_trusted_peer(peer_info.clone(), TrustLevel::Direct)
+                    .map_err(|err| err.to_string())?;
+                self.outgoing_peers.insert(peer_info.id.clone());
+                ctx.notify(OutboundTcpConnect { peer_info });
+                Ok(PeerAdminResponse::Done)
+            }
+            PeerAdminRequest::DisconnectPeer { peer_id } => match self.active_peers.get(&peer_id) {
+                Some(active_peer) => {
+                    info!(target: "network", "Disconnecting peer {} by admin request", peer_id);
+                    active_peer.addr.do_send(PeerManagerRequest::UnregisterPeer);
+                    Ok(PeerAdminResponse::Done)
+                }
+                None => Err(format!("Peer {} is not connected", peer_id)),
+            },
+        }
+    }
+}
+
 impl Handler<PeersRequest> for PeerManagerActor {
     type Result = PeerList;
 
@@ -1926,3 +2033,19 @@ impl Handler<PeerMessageMetadata> for PeerManagerActor {
         self.metric_recorder.handle_peer_message(msg);
     }
 }
+
+#[cfg(feature = "metric_recorder")]
+impl Handler<CapturedMessage> for PeerManagerActor {
+    type Result = ();
+    #[perf]
+    fn handle(&mut self, msg: CapturedMessage, _ctx: &mut Self::Context) -> Self::Result {
+        #[cfg(feature = "delay_detector")]
+        let _d = DelayDetector::new("captured message".into());
+        if let Some(capture) = self.capture.as_mut() {
+            if let Err(err) = capture.write(msg) {
+                error!(target: "network", "Failed to write captured message, stopping the capture: {}", err);
+                self.capture = None;
+            }
+        }
+    }
+}
@@ -120,6 +120,10 @@ impl PeerStore {
         self.peer_states.is_empty()
     }
 
+    pub fn get_peer_state(&self, peer_id: &PeerId) -> Option<&KnownPeerState> {
+        self.peer_states.get(peer_id)
+    }
+
     pub fn is_banned(&self, peer_id: &PeerId) -> bool {
         self.peer_states
             .get(&peer_id)
@@ -419,6 +423,30 @@ mod test {
         }
     }
 
+    #[test]
+    fn unban_and_add_peer_store() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_store_unban").tempdir().unwrap();
+        let peer_info_banned = gen_peer_info(0);
+        let peer_info_added = gen_peer_info(1);
+        {
+            let store = create_store(tmp_dir.path().to_str().unwrap());
+            let mut peer_store = PeerStore::new(store, &[peer_info_banned.clone()]).unwrap();
+            peer_store.peer_ban(&peer_info_banned.id, ReasonForBan::None).unwrap();
+            assert!(peer_store.is_banned(&peer_info_banned.id));
+            peer_store.peer_unban(&peer_info_banned.id).unwrap();
+            peer_store.add_trusted_peer(peer_info_added.clone(), TrustLevel::Direct).unwrap();
+        }
+        {
+            let store_new = create_store(tmp_dir.path().to_str().unwrap());
+            let peer_store_new = PeerStore::new(store_new, &[]).unwrap();
+            assert!(!peer_store_new.is_banned(&peer_info_banned.id));
+            assert_eq!(
+                peer_store_new.get_peer_state(&peer_info_added.id).unwrap().peer_info,
+                peer_info_added
+            );
+        }
+    }
+
     fn check_exist(
         peer_store: &PeerStore,
         peer_id: &PeerId,
@@ -0,0 +1,167 @@
+//! Limits on the rate of messages of each type received from a peer. Messages over the limit
+//! are dropped, and peers that keep sending them are banned as abusive.
+use std::collections::HashMap;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::rate_counter::RateCounter;
+
+/// Token bucket limit: up to `burst` messages at once and `rate_per_sec` messages per second on
+/// average.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    pub rate_per_sec: f64,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn new(rate_per_sec: f64, burst: u32) -> Self {
+        RateLimit { rate_per_sec, burst }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct RateLimitsConfig {
+    /// Limits per peer keyed by the name of the `PeerMessage` variant, or of the
+    /// `RoutedMessageBody` variant for routed messages, e.g. `BlockRequest` or `QueryRequest`.
+    /// Messages of other types and routed messages that are only relayed by this node are not
+    /// limited.
+    pub limits: HashMap<String, RateLimit>,
+    /// Peers with more messages dropped by the limits within the last minute are banned.
+    pub max_dropped_per_min: u64,
+}
+
+impl Default for RateLimitsConfig {
+    fn default() -> Self {
+        let limits = vec![
+            ("BlockRequest", RateLimit::new(100.0, 500)),
+            ("BlockHeadersRequest", RateLimit::new(10.0, 50)),
+            ("StateRequestHeader", RateLimit::new(10.0, 50)),
+            ("StateRequestPart", RateLimit::new(20.0, 100)),
+            ("QueryRequest", RateLimit::new(50.0, 200)),
+            ("TxStatusRequest", RateLimit::new(50.0, 200)),
+            ("ReceiptOutcomeRequest", RateLimit::new(50.0, 200)),
+            ("ForwardTx", RateLimit::new(500.0, 2000)),
+        ];
+        RateLimitsConfig {
+            limits: limits
+                .into_iter()
+                .map(|(msg_variant, limit)| (msg_variant.to_string(), limit))
+                .collect(),
+            max_dropped_per_min: 600,
+        }
+    }
+}
+
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last_update: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        TokenBucket { limit, tokens: limit.burst as f64, last_update: now }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last_update).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.limit.rate_per_sec).min(self.limit.burst as f64);
+        self.last_update = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum RateLimitResult {
+    Allowed,
+    /// The message is over the limit and should be dropped.
+    Dropped,
+    /// The message is over the limit and the peer exceeded the limits too often.
+    Abusive,
+}
+
+/// Rate limits of the messages received from one peer.
+pub(crate) struct PeerRateLimiter {
+    buckets: HashMap<String, TokenBucket>,
+    dropped: RateCounter,
+    max_dropped_per_min: u64,
+}
+
+impl PeerRateLimiter {
+    pub fn new(config: &RateLimitsConfig) -> Self {
+        let now = Instant::now();
+        PeerRateLimiter {
+            buckets: config
+                .limits
+                .iter()
+                .map(|(msg_variant, limit)| (msg_variant.clone(), TokenBucket::new(*limit, now)))
+                .collect(),
+            dropped: RateCounter::new(),
+            max_dropped_per_min: config.max_dropped_per_min,
+        }
+    }
+
+    /// Checks the next message of the given type, see `PeerMessage::msg_variant`.
+    pub fn check(&mut self, msg_variant: &str) -> RateLimitResult {
+        self.check_at(msg_variant, Instant::now())
+    }
+
+    fn check_at(&mut self, msg_variant: &str, now: Instant) -> RateLimitResult {
+        let bucket = match self.buckets.get_mut(msg_variant) {
+            Some(bucket) => bucket,
+            None => return RateLimitResult::Allowed,
+        };
+        if bucket.try_take(now) {
+            return RateLimitResult::Allowed;
+        }
+        self.dropped.increment(0);
+        if self.dropped.count_per_min() > self.max_dropped_per_min {
+            RateLimitResult::Abusive
+        } else {
+            RateLimitResult::Dropped
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    use super::{PeerRateLimiter, RateLimit, RateLimitResult, RateLimitsConfig};
+
+    #[test]
+    fn test_peer_rate_limiter() {
+        let mut limits = HashMap::new();
+        limits.insert("BlockRequest".to_string(), RateLimit::new(10.0, 2));
+        let config = RateLimitsConfig { limits, max_dropped_per_min: 2 };
+        let mut rate_limiter = PeerRateLimiter::new(&config);
+        let now = Instant::now();
+
+        // Burst is allowed, other message types are not limited.
+        assert_eq!(rate_limiter.check_at("BlockRequest", now), RateLimitResult::Allowed);
+        assert_eq!(rate_limiter.check_at("BlockRequest", now), RateLimitResult::Allowed);
+        assert_eq!(rate_limiter.check_at("BlockRequest", now), RateLimitResult::Dropped);
+        assert_eq!(rate_limiter.check_at("Block", now), RateLimitResult::Allowed);
+
+        // One token is refilled every 100ms, up to the burst.
+        let later = now + Duration::from_millis(100);
+        assert_eq!(rate_limiter.check_at("BlockRequest", later), RateLimitResult::Allowed);
+        assert_eq!(rate_limiter.check_at("BlockRequest", later), RateLimitResult::Dropped);
+        let much_later = now + Duration::from_secs(10);
+        assert_eq!(rate_limiter.check_at("BlockRequest", much_later), RateLimitResult::Allowed);
+        assert_eq!(rate_limiter.check_at("BlockRequest", much_later), RateLimitResult::Allowed);
+
+        // The third dropped message within a minute makes the peer abusive.
+        assert_eq!(rate_limiter.check_at("BlockRequest", much_later), RateLimitResult::Abusive);
+    }
+}
@@ -1,14 +1,21 @@
 use crate::types::PeerMessage;
 use actix::Message;
+use borsh::{BorshDeserialize, BorshSerialize};
 use near_primitives::{hash::CryptoHash, network::PeerId};
 use serde::ser::SerializeMap;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
-use tracing::info;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
 
 const WEIGHTED_LATENCY_DECAY: f64 = 0.8;
 
-#[derive(Clone, Copy)]
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Status {
     Sent,
     Received,
@@ -219,3 +226,264 @@ impl From<&PeerMessage> for PeerMessageMetadata {
         }
     }
 }
+
+/// Message sent or received by a node, as written to the capture file.
+#[derive(BorshSerialize, BorshDeserialize, Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct CapturedMessage {
+    /// Time the message was sent or received, in nanoseconds since the unix epoch.
+    pub timestamp: u64,
+    pub status: Status,
+    /// Node which captured the message.
+    pub local_peer_id: PeerId,
+    /// The other side of the connection, `None` until the handshake is done.
+    pub peer_id: Option<PeerId>,
+    /// Address of the other side of the connection, identifies the connection.
+    pub peer_addr: String,
+    /// Borsh serialized `PeerMessage` as sent over the connection.
+    pub message: Vec<u8>,
+}
+
+impl CapturedMessage {
+    pub fn peer_message(&self) -> io::Result<PeerMessage> {
+        PeerMessage::try_from_slice(&self.message)
+    }
+}
+
+/// Number of captured messages waiting to be written before new ones are dropped.
+const CAPTURE_QUEUE_SIZE: usize = 10_000;
+/// How often the captured messages are flushed to the file.
+const CAPTURE_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Writes captured messages to a file, each one Borsh serialized and prefixed by its length as
+/// four little endian bytes, the same framing used on the wire.
+///
+/// The messages are written by a separate thread, so that a slow disk doesn't stall the caller.
+/// They are dropped if the thread falls behind by more than `CAPTURE_QUEUE_SIZE` messages, and
+/// flushed every `CAPTURE_FLUSH_INTERVAL` and when the writer is dropped.
+pub struct CaptureWriter {
+    sender: Option<SyncSender<CapturedMessage>>,
+    thread: Option<JoinHandle<()>>,
+    /// Number of messages dropped because the queue was full.
+    dropped: u64,
+}
+
+impl CaptureWriter {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Self::create_with(path, CAPTURE_QUEUE_SIZE, CAPTURE_FLUSH_INTERVAL)
+    }
+
+    fn create_with(path: &Path, queue_size: usize, flush_interval: Duration) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let (sender, receiver) = sync_channel::<CapturedMessage>(queue_size);
+        let thread =
+            thread::Builder::new().name("capture writer".to_string()).spawn(move || {
+                if let Err(err) = write_captured_messages(&mut writer, receiver, flush_interval) {
+                    error!(target: "network", "Failed to write captured messages: {}", err);
+                }
+            })?;
+        Ok(Self { sender: Some(sender), thread: Some(thread), dropped: 0 })
+    }
+
+    /// Queues the message to be written. Fails if the messages can't be written anymore.
+    pub fn write(&mut self, captured_message: CapturedMessage) -> io::Result<()> {
+        match self.sender.as_ref().unwrap().try_send(captured_message) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.dropped += 1;
+                if self.dropped.is_power_of_two() {
+                    warn!(target: "network", "Capture can't keep up, dropped {} messages", self.dropped);
+                }
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                Err(io::Error::new(io::ErrorKind::Other, "Capture writer thread stopped"))
+            }
+        }
+    }
+
+    /// Queues the message to be written, waiting for the thread to catch up instead of dropping
+    /// the message when the queue is full. Fails if the messages can't be written anymore.
+    pub fn write_blocking(&mut self, captured_message: CapturedMessage) -> io::Result<()> {
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send(captured_message)
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Capture writer thread stopped"))
+    }
+}
+
+impl Drop for CaptureWriter {
+    fn drop(&mut self) {
+        // The thread writes out the queued messages and exits once the sender is gone.
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn write_captured_messages(
+    writer: &mut BufWriter<File>,
+    receiver: Receiver<CapturedMessage>,
+    flush_interval: Duration,
+) -> io::Result<()> {
+    let mut last_flush = Instant::now();
+    loop {
+        match receiver.recv_timeout(flush_interval) {
+            Ok(captured_message) => {
+                let bytes = captured_message.try_to_vec()?;
+                writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+                writer.write_all(&bytes)?;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return writer.flush(),
+        }
+        if last_flush.elapsed() >= flush_interval {
+            writer.flush()?;
+            last_flush = Instant::now();
+        }
+    }
+}
+
+/// Reads the messages written by `CaptureWriter`.
+pub struct CaptureReader {
+    reader: BufReader<File>,
+}
+
+impl CaptureReader {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self { reader: BufReader::new(File::open(path)?) })
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = io::Result<CapturedMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut len_bytes = [0u8; 4];
+        match self.reader.read_exact(&mut len_bytes) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return None,
+            Err(err) => return Some(Err(err)),
+        }
+        let mut bytes = vec![0u8; u32::from_le_bytes(len_bytes) as usize];
+        Some(
+            self.reader
+                .read_exact(&mut bytes)
+                .and_then(|()| CapturedMessage::try_from_slice(&bytes)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use near_crypto::{KeyType, SecretKey};
+    use near_primitives::network::PeerId;
+
+    use crate::types::PeerMessage;
+
+    use super::{CaptureReader, CaptureWriter, CapturedMessage, Status};
+
+    fn peer_id(seed: &str) -> PeerId {
+        SecretKey::from_seed(KeyType::ED25519, seed).public_key().into()
+    }
+
+    #[test]
+    fn test_capture_write_read() {
+        let tmp_dir = tempfile::Builder::new().prefix("capture").tempdir().unwrap();
+        let path = tmp_dir.path().join("capture");
+        let messages = vec![
+            CapturedMessage {
+                timestamp: 1,
+                status: Status::Received,
+                local_peer_id: peer_id("local"),
+                peer_id: None,
+                peer_addr: "127.0.0.1:24567".to_string(),
+                message: vec![1, 2, 3],
+            },
+            CapturedMessage {
+                timestamp: 2,
+                status: Status::Sent,
+                local_peer_id: peer_id("local"),
+                peer_id: Some(peer_id("other")),
+                peer_addr: "127.0.0.1:24567".to_string(),
+                message: borsh::BorshSerialize::try_to_vec(&PeerMessage::PeersRequest).unwrap(),
+            },
+        ];
+        let mut writer = CaptureWriter::create(&path).unwrap();
+        for message in messages.iter() {
+            writer.write(message.clone()).unwrap();
+        }
+        // Dropping the writer writes out the queued messages.
+        drop(writer);
+
+        let read = CaptureReader::open(&path).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].timestamp, 1);
+        assert_eq!(read[0].peer_id, None);
+        assert_eq!(read[1].status, Status::Sent);
+        assert_eq!(read[1].peer_id, Some(peer_id("other")));
+        assert!(read[0].peer_message().is_err());
+        assert_eq!(read[1].peer_message().unwrap().msg_variant(), "PeersRequest");
+    }
+
+    #[test]
+    fn test_capture_flushed_periodically() {
+        let tmp_dir = tempfile::Builder::new().prefix("capture").tempdir().unwrap();
+        let path = tmp_dir.path().join("capture");
+        let mut writer = CaptureWriter::create_with(&path, 10, Duration::from_millis(10)).unwrap();
+        writer
+            .write(CapturedMessage {
+                timestamp: 1,
+                status: Status::Received,
+                local_peer_id: peer_id("local"),
+                peer_id: None,
+                peer_addr: "127.0.0.1:24567".to_string(),
+                message: vec![1, 2, 3],
+            })
+            .unwrap();
+
+        // The message is written while the writer is still open.
+        let started = Instant::now();
+        loop {
+            let read = CaptureReader::open(&path).unwrap().collect::<Result<Vec<_>, _>>();
+            if let Ok(read) = read {
+                if read.len() == 1 {
+                    assert_eq!(read[0].message, vec![1, 2, 3]);
+                    break;
+                }
+            }
+            assert!(started.elapsed() < Duration::from_secs(5), "Message was not flushed");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_capture_write_blocking_keeps_all_messages() {
+        let tmp_dir = tempfile::Builder::new().prefix("capture").tempdir().unwrap();
+        let path = tmp_dir.path().join("capture");
+        let mut writer = CaptureWriter::create_with(&path, 1, Duration::from_secs(1)).unwrap();
+        for timestamp in 0..100 {
+            writer
+                .write_blocking(CapturedMessage {
+                    timestamp,
+                    status: Status::Sent,
+                    local_peer_id: peer_id("local"),
+                    peer_id: None,
+                    peer_addr: "127.0.0.1:24567".to_string(),
+                    message: vec![],
+                })
+                .unwrap();
+        }
+        drop(writer);
+
+        let read = CaptureReader::open(&path).unwrap().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            read.iter().map(|message| message.timestamp).collect::<Vec<_>>(),
+            (0..100).collect::<Vec<_>>()
+        );
+    }
+}
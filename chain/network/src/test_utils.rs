@@ -13,6 +13,9 @@ use near_primitives::network::PeerId;
 use near_primitives::types::EpochId;
 use near_primitives::utils::index_to_bytes;
 
+use crate::compression::CompressionConfig;
+use crate::encryption::EncryptionMode;
+use crate::rate_limiter::RateLimitsConfig;
 use crate::types::{NetworkConfig, NetworkInfo, PeerInfo, ReasonForBan, ROUTED_MESSAGE_TTL};
 use crate::{NetworkAdapter, NetworkRequests, NetworkResponses, PeerManagerActor};
 use futures::future::BoxFuture;
@@ -79,6 +82,10 @@ impl NetworkConfig {
             blacklist: HashMap::new(),
             outbound_disabled: false,
             archive: false,
+            rate_limits: RateLimitsConfig::default(),
+            capture_path: None,
+            encryption: EncryptionMode::default(),
+            compression: CompressionConfig::default(),
         }
     }
 }
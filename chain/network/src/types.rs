@@ -2,6 +2,7 @@ use std::collections::{HashMap, HashSet};
 use std::convert::{Into, TryFrom, TryInto};
 use std::fmt;
 use std::net::{AddrParseError, IpAddr, SocketAddr};
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::RwLock;
 use std::time::{Duration, Instant};
@@ -12,7 +13,7 @@ use borsh::{BorshDeserialize, BorshSerialize};
 use chrono::{DateTime, Utc};
 use futures::{future::BoxFuture, FutureExt};
 use serde::{Deserialize, Serialize};
-use strum::AsStaticStr;
+use strum::{AsStaticStr, VariantNames};
 use tokio::net::TcpStream;
 use tracing::{error, warn};
 
@@ -36,7 +37,10 @@ use near_primitives::version::{
 };
 use near_primitives::views::{FinalExecutionOutcomeView, QueryRequest, QueryResponse};
 
+use crate::compression::{is_compression_supported, Compression, CompressionConfig};
+use crate::encryption::{is_encryption_supported, EncryptionMode, EncryptionOffer};
 use crate::peer::Peer;
+use crate::rate_limiter::RateLimitsConfig;
 #[cfg(feature = "metric_recorder")]
 use crate::recorder::MetricRecorder;
 use crate::routing::{Edge, EdgeInfo, RoutingTableInfo};
@@ -200,7 +204,7 @@ impl fmt::Display for HandshakeFailureReason {
 
 impl std::error::Error for HandshakeFailureReason {}
 
-#[derive(BorshSerialize, Serialize, PartialEq, Eq, Clone, Debug)]
+#[derive(Serialize, PartialEq, Eq, Clone, Debug)]
 pub struct Handshake {
     pub version: u32,
     /// Oldest supported protocol version.
@@ -215,6 +219,13 @@ pub struct Handshake {
     pub chain_info: PeerChainInfoV2,
     /// Info for new edge.
     pub edge_info: EdgeInfo,
+    /// Sender's offer to encrypt the connection. Only part of the layout from the protocol
+    /// version with `ProtocolFeature::PeerEncryption`, see `encryption::is_encryption_supported`.
+    pub encryption_offer: Option<EncryptionOffer>,
+    /// Compression algorithms supported by the outbound side, or the one picked by the inbound
+    /// side. Only part of the layout from the protocol version with
+    /// `ProtocolFeature::PeerCompression`, see `compression::is_compression_supported`.
+    pub compression: Vec<Compression>,
 }
 
 /// Struct describing the layout for Handshake.
@@ -247,6 +258,8 @@ impl Handshake {
         listen_port: Option<u16>,
         chain_info: PeerChainInfoV2,
         edge_info: EdgeInfo,
+        encryption_offer: Option<EncryptionOffer>,
+        compression: Vec<Compression>,
     ) -> Self {
         Handshake {
             version,
@@ -256,7 +269,28 @@ impl Handshake {
             listen_port,
             chain_info,
             edge_info,
+            encryption_offer,
+            compression,
+        }
+    }
+}
+
+impl BorshSerialize for Handshake {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.version.serialize(writer)?;
+        self.oldest_supported_version.serialize(writer)?;
+        self.peer_id.serialize(writer)?;
+        self.target_peer_id.serialize(writer)?;
+        self.listen_port.serialize(writer)?;
+        self.chain_info.serialize(writer)?;
+        self.edge_info.serialize(writer)?;
+        if is_encryption_supported(self.version) {
+            self.encryption_offer.serialize(writer)?;
         }
+        if is_compression_supported(self.version) {
+            self.compression.serialize(writer)?;
+        }
+        Ok(())
     }
 }
 
@@ -276,7 +310,14 @@ impl BorshDeserialize for Handshake {
 
         if OLDEST_BACKWARD_COMPATIBLE_PROTOCOL_VERSION <= version && version <= PROTOCOL_VERSION {
             // If we support this version, then try to deserialize with custom deserializer
-            HandshakeAutoDes::deserialize(buf).map(Into::into)
+            let mut handshake: Handshake = HandshakeAutoDes::deserialize(buf)?.into();
+            if is_encryption_supported(version) {
+                handshake.encryption_offer = BorshDeserialize::deserialize(buf)?;
+            }
+            if is_compression_supported(version) {
+                handshake.compression = BorshDeserialize::deserialize(buf)?;
+            }
+            Ok(handshake)
         } else {
             Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
@@ -299,6 +340,8 @@ impl From<HandshakeAutoDes> for Handshake {
             listen_port: handshake.listen_port,
             chain_info: handshake.chain_info,
             edge_info: handshake.edge_info,
+            encryption_offer: None,
+            compression: vec![],
         }
     }
 }
@@ -405,6 +448,8 @@ impl From<HandshakeV2> for Handshake {
             listen_port: handshake.listen_port,
             chain_info: handshake.chain_info.into(),
             edge_info: handshake.edge_info,
+            encryption_offer: None,
+            compression: vec![],
         }
     }
 }
@@ -887,6 +932,15 @@ pub struct NetworkConfig {
     pub outbound_disabled: bool,
     /// Not clear old data, set `true` for archive nodes.
     pub archive: bool,
+    /// Limits on the rate of messages received from each peer.
+    pub rate_limits: RateLimitsConfig,
+    /// File to capture all messages sent and received by the node to. Only used when built
+    /// with the `metric_recorder` feature.
+    pub capture_path: Option<PathBuf>,
+    /// Whether to encrypt the connections with peers that support it, see `encryption`.
+    pub encryption: EncryptionMode,
+    /// Compression of the large messages sent to peers that support it, see `compression`.
+    pub compression: CompressionConfig,
 }
 
 impl NetworkConfig {
@@ -923,6 +977,19 @@ impl NetworkConfig {
                 self.peer_recent_time_window.as_secs(), UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE.as_secs()
             );
         }
+
+        #[cfg(not(feature = "metric_recorder"))]
+        if self.capture_path.is_some() {
+            warn!(target: "network", "capture_path is ignored, the node is built without the metric_recorder feature.");
+        }
+
+        for msg_variant in self.rate_limits.limits.keys() {
+            if !PeerMessage::VARIANTS.contains(&msg_variant.as_str())
+                && !RoutedMessageBody::VARIANTS.contains(&msg_variant.as_str())
+            {
+                error!(target: "network", "Rate limit for unknown message type {}.", msg_variant);
+            }
+        }
     }
 }
 
@@ -1050,6 +1117,8 @@ pub struct Consolidate {
     pub this_edge_info: Option<EdgeInfo>,
     // Edge information from other node.
     pub other_edge_info: EdgeInfo,
+    /// Compression negotiated with the peer.
+    pub compression: Option<Compression>,
 }
 
 impl Message for Consolidate {
@@ -1122,7 +1191,9 @@ where
 }
 
 /// Ban reason.
-#[derive(BorshSerialize, BorshDeserialize, Serialize, Debug, Clone, PartialEq, Eq, Copy)]
+#[derive(
+    BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Copy,
+)]
 pub enum ReasonForBan {
     None = 0,
     BadBlock = 1,
@@ -1259,6 +1330,30 @@ pub enum PeerManagerRequest {
     UnregisterPeer,
 }
 
+/// Requests from the admin RPC to manage the peers of the node.
+#[derive(Message, Debug, strum::AsRefStr)]
+#[rtype(result = "Result<PeerAdminResponse, String>")]
+pub enum PeerAdminRequest {
+    /// List all known peers with their state.
+    ListPeers,
+    /// Ban a known peer, disconnecting it if it's connected.
+    BanPeer { peer_id: PeerId, ban_reason: ReasonForBan },
+    /// Unban a banned peer before its ban window expires.
+    UnbanPeer { peer_id: PeerId },
+    /// Add the peer to the known peers and connect to it.
+    ConnectPeer { peer_info: PeerInfo },
+    /// Drop the connection to an active peer.
+    DisconnectPeer { peer_id: PeerId },
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum PeerAdminResponse {
+    Peers(Vec<KnownPeerState>),
+    /// The request was accepted.
+    Done,
+}
+
 pub struct EdgeList(pub Vec<Edge>);
 
 impl Message for EdgeList {
@@ -1290,6 +1385,8 @@ pub struct NetworkInfo {
     pub received_bytes_per_sec: u64,
     /// Accounts of known block and chunk producers from routing table.
     pub known_producers: Vec<KnownProducer>,
+    /// Compression of the messages of the active peers that use it.
+    pub peer_compression: HashMap<PeerId, Compression>,
     #[cfg(feature = "metric_recorder")]
     pub metric_recorder: MetricRecorder,
     pub peer_counter: usize,
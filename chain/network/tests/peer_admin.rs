@@ -0,0 +1,94 @@
+pub use runner::*;
+use std::time::Duration;
+
+use near_network::types::{KnownPeerStatus, PeerAdminRequest, PeerAdminResponse, ReasonForBan};
+use near_network::PeerInfo;
+
+mod runner;
+
+/// Status of the peer `peer` in the response to `ListPeers`.
+fn peer_status(
+    peers_info: &[PeerInfo],
+    peer: usize,
+    res: Result<PeerAdminResponse, String>,
+) -> Option<KnownPeerStatus> {
+    match res {
+        Ok(PeerAdminResponse::Peers(peers)) => peers
+            .into_iter()
+            .find(|peer_state| peer_state.peer_info.id == peers_info[peer].id)
+            .map(|peer_state| peer_state.status),
+        _ => None,
+    }
+}
+
+/// Peers are listed, banned, unbanned, connected and disconnected by admin requests.
+#[test]
+fn peer_admin_requests() {
+    let mut runner = Runner::new(3, 1).ban_window(Duration::from_secs(60));
+
+    runner.push(Action::AddEdge(0, 1));
+    runner.push(Action::CheckRoutingTable(0, vec![(1, vec![1])]));
+    runner.push_action(peer_admin_request(
+        0,
+        |_| PeerAdminRequest::ListPeers,
+        |peers_info, res| peer_status(peers_info, 1, res) == Some(KnownPeerStatus::Connected),
+    ));
+
+    // Banning disconnects the peer, and it can't be connected until it's unbanned.
+    runner.push_action(peer_admin_request(
+        0,
+        |peers_info| PeerAdminRequest::BanPeer {
+            peer_id: peers_info[1].id.clone(),
+            ban_reason: ReasonForBan::Abusive,
+        },
+        |_, res| res.is_ok(),
+    ));
+    runner.push_action(check_banned(0, 1, ReasonForBan::Abusive));
+    runner.push(Action::CheckRoutingTable(0, vec![]));
+    runner.push_action(peer_admin_request(
+        0,
+        |peers_info| PeerAdminRequest::ConnectPeer { peer_info: peers_info[1].clone() },
+        |peers_info, res| res.err() == Some(format!("Peer {} is banned", peers_info[1].id)),
+    ));
+    runner.push_action(peer_admin_request(
+        0,
+        |peers_info| PeerAdminRequest::UnbanPeer { peer_id: peers_info[1].id.clone() },
+        |_, res| res.is_ok(),
+    ));
+    runner.push_action(peer_admin_request(
+        0,
+        |peers_info| PeerAdminRequest::UnbanPeer { peer_id: peers_info[1].id.clone() },
+        |peers_info, res| res.err() == Some(format!("Peer {} is not banned", peers_info[1].id)),
+    ));
+
+    // Connecting makes the peer active again, disconnecting drops it.
+    runner.push_action(peer_admin_request(
+        0,
+        |peers_info| PeerAdminRequest::ConnectPeer { peer_info: peers_info[1].clone() },
+        |_, res| res.is_ok(),
+    ));
+    runner.push(Action::CheckRoutingTable(0, vec![(1, vec![1])]));
+    runner.push_action(peer_admin_request(
+        0,
+        |peers_info| PeerAdminRequest::DisconnectPeer { peer_id: peers_info[1].id.clone() },
+        |_, res| res.is_ok(),
+    ));
+    runner.push(Action::CheckRoutingTable(0, vec![]));
+    runner.push_action(peer_admin_request(
+        0,
+        |peers_info| PeerAdminRequest::DisconnectPeer { peer_id: peers_info[1].id.clone() },
+        |peers_info, res| res.err() == Some(format!("Peer {} is not connected", peers_info[1].id)),
+    ));
+
+    // Node 2 was never seen by node 0, so it can't be banned.
+    runner.push_action(peer_admin_request(
+        0,
+        |peers_info| PeerAdminRequest::BanPeer {
+            peer_id: peers_info[2].id.clone(),
+            ban_reason: ReasonForBan::None,
+        },
+        |peers_info, res| res.err() == Some(format!("Peer {} is not known", peers_info[2].id)),
+    ));
+
+    start_test(runner);
+}
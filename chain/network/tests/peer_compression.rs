@@ -0,0 +1,50 @@
+#![cfg(feature = "protocol_feature_peer_compression")]
+pub use runner::*;
+
+use near_network::compression::{Compression, CompressionConfig};
+
+mod runner;
+
+/// Compresses every message that gets smaller.
+fn compress_all() -> CompressionConfig {
+    CompressionConfig { threshold: 0, ..Default::default() }
+}
+
+/// Nodes that both support zstd negotiate it, report it in their network info and exchange
+/// messages over the compressed connections.
+#[test]
+fn compression_negotiated() {
+    let mut runner =
+        Runner::new(3, 1).compression(0, compress_all()).compression(1, compress_all());
+
+    runner.push(Action::AddEdge(0, 1));
+    runner.push(Action::AddEdge(1, 2));
+    runner.push(Action::CheckRoutingTable(0, vec![(1, vec![1]), (2, vec![1])]));
+    runner.push_action(check_compression(0, 1, Some(Compression::Zstd)));
+    runner.push_action(check_compression(1, 0, Some(Compression::Zstd)));
+    runner.push_action(check_compression(1, 2, Some(Compression::Zstd)));
+    for nonce in 0..3 {
+        runner.push(Action::PingTo(0, nonce, 2));
+    }
+    runner.push(Action::CheckPingPong(2, (0..3).map(|nonce| (nonce, 0)).collect(), vec![]));
+    runner.push(Action::CheckPingPong(0, vec![], (0..3).map(|nonce| (nonce, 2)).collect()));
+
+    start_test(runner);
+}
+
+/// A node with compression disabled keeps its connections uncompressed.
+#[test]
+fn compression_disabled() {
+    let disabled = CompressionConfig { algorithms: vec![], ..Default::default() };
+    let mut runner = Runner::new(2, 1).compression(0, compress_all()).compression(1, disabled);
+
+    runner.push(Action::AddEdge(0, 1));
+    runner.push(Action::CheckRoutingTable(0, vec![(1, vec![1])]));
+    runner.push_action(check_compression(0, 1, None));
+    runner.push_action(check_compression(1, 0, None));
+    runner.push(Action::PingTo(0, 0, 1));
+    runner.push(Action::CheckPingPong(1, vec![(0, 0)], vec![]));
+    runner.push(Action::CheckPingPong(0, vec![], vec![(0, 1)]));
+
+    start_test(runner);
+}
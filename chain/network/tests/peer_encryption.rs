@@ -0,0 +1,201 @@
+//! Encryption negotiated by a node with a peer that writes its handshake directly to the socket,
+//! so that it can leave out the encryption offer or use an older protocol version.
+#![cfg(feature = "protocol_feature_peer_encryption")]
+use std::future::Future;
+use std::time::Duration;
+
+use actix::actors::mocker::Mocker;
+use actix::{Actor, System};
+use borsh::{BorshDeserialize, BorshSerialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use near_client::{ClientActor, ViewClientActor};
+use near_crypto::{KeyType, SecretKey};
+use near_logger_utils::init_test_logger;
+use near_network::encryption::{EncryptionMode, EphemeralKey};
+use near_network::routing::EdgeInfo;
+use near_network::test_utils::open_port;
+use near_network::types::{
+    Handshake, NetworkViewClientMessages, NetworkViewClientResponses, PeerChainInfoV2, PeerMessage,
+    PeerType,
+};
+use near_network::{NetworkClientResponses, NetworkConfig, PeerManagerActor};
+use near_primitives::network::PeerId;
+use near_primitives::version::{
+    ProtocolFeature, ProtocolVersion, PROTOCOL_FEATURES_TO_VERSION_MAPPING, PROTOCOL_VERSION,
+};
+use near_store::test_utils::create_test_store;
+
+type ClientMock = Mocker<ClientActor>;
+type ViewClientMock = Mocker<ViewClientActor>;
+
+fn make_peer_manager(seed: &str, port: u16, encryption: EncryptionMode) -> PeerManagerActor {
+    let store = create_test_store();
+    let mut config = NetworkConfig::from_seed(seed, port);
+    config.encryption = encryption;
+    let client_addr = ClientMock::mock(Box::new(move |_msg, _ctx| {
+        Box::new(Some(NetworkClientResponses::NoResponse))
+    }))
+    .start();
+    let view_client_addr = ViewClientMock::mock(Box::new(move |msg, _ctx| {
+        let msg = msg.downcast_ref::<NetworkViewClientMessages>().unwrap();
+        match msg {
+            NetworkViewClientMessages::GetChainInfo => {
+                Box::new(Some(NetworkViewClientResponses::ChainInfo {
+                    genesis_id: Default::default(),
+                    height: 1,
+                    tracked_shards: vec![],
+                    archival: false,
+                }))
+            }
+            _ => Box::new(Some(NetworkViewClientResponses::NoResponse)),
+        }
+    }))
+    .start();
+    PeerManagerActor::new(store, config, client_addr.recipient(), view_client_addr.recipient())
+        .unwrap()
+}
+
+/// Latest protocol version whose handshakes don't carry an encryption offer.
+fn version_without_encryption() -> ProtocolVersion {
+    PROTOCOL_FEATURES_TO_VERSION_MAPPING[&ProtocolFeature::PeerEncryption] - 1
+}
+
+/// Peer that connects to the node under test.
+struct RawPeer {
+    secret_key: SecretKey,
+    peer_id: PeerId,
+    node_id: PeerId,
+    ephemeral_key: EphemeralKey,
+    stream: TcpStream,
+}
+
+impl RawPeer {
+    async fn connect(port: u16) -> Self {
+        let secret_key = SecretKey::from_seed(KeyType::ED25519, "raw");
+        let peer_id = PeerId::new(secret_key.public_key());
+        let node_id = PeerId::new(SecretKey::from_seed(KeyType::ED25519, "test").public_key());
+        let ephemeral_key = EphemeralKey::new(&secret_key);
+        // The node starts listening once its actor is started.
+        let stream = loop {
+            match TcpStream::connect(("127.0.0.1", port)).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+            }
+        };
+        RawPeer { secret_key, peer_id, node_id, ephemeral_key, stream }
+    }
+
+    async fn write(&mut self, bytes: &[u8]) {
+        self.stream.write_u32_le(bytes.len() as u32).await.unwrap();
+        self.stream.write_all(bytes).await.unwrap();
+    }
+
+    /// Reads the next message sent by the node, `None` once it closed the connection.
+    async fn read(&mut self) -> Option<Vec<u8>> {
+        let len = self.stream.read_u32_le().await.ok()?;
+        let mut bytes = vec![0u8; len as usize];
+        self.stream.read_exact(&mut bytes).await.ok()?;
+        Some(bytes)
+    }
+
+    /// Sends a handshake with `version` and our encryption offer if `offer` is set, and returns
+    /// the handshake the node answers with, `None` if it closes the connection instead.
+    async fn handshake(&mut self, version: ProtocolVersion, offer: bool) -> Option<Handshake> {
+        let handshake = Handshake::new(
+            version,
+            self.peer_id.clone(),
+            self.node_id.clone(),
+            Some(open_port()),
+            PeerChainInfoV2 {
+                genesis_id: Default::default(),
+                height: 0,
+                tracked_shards: vec![],
+                archival: false,
+            },
+            EdgeInfo::new(self.peer_id.clone(), self.node_id.clone(), 1, &self.secret_key),
+            Some(self.ephemeral_key.offer().clone()).filter(|_| offer),
+            vec![],
+        );
+        self.write(&PeerMessage::Handshake(handshake).try_to_vec().unwrap()).await;
+        match PeerMessage::try_from_slice(&self.read().await?).unwrap() {
+            PeerMessage::Handshake(handshake) => Some(handshake),
+            message => panic!("Unexpected message {}", message),
+        }
+    }
+}
+
+fn run_test<F>(encryption: EncryptionMode, test: impl FnOnce(u16) -> F + 'static)
+where
+    F: Future<Output = ()> + 'static,
+{
+    init_test_logger();
+
+    System::builder()
+        .stop_on_panic(true)
+        .run(move || {
+            let port = open_port();
+            make_peer_manager("test", port, encryption).start();
+            actix::spawn(async move {
+                test(port).await;
+                System::current().stop();
+            });
+        })
+        .unwrap();
+}
+
+/// A handshake whose offer was stripped is rejected instead of falling back to plaintext.
+#[test]
+fn reject_stripped_offer() {
+    run_test(EncryptionMode::Enabled, |port| async move {
+        let mut peer = RawPeer::connect(port).await;
+        assert_eq!(peer.handshake(PROTOCOL_VERSION, false).await, None);
+    });
+}
+
+/// A peer with a protocol version without encryption gets a plaintext connection.
+#[test]
+fn plaintext_with_version_without_encryption() {
+    run_test(EncryptionMode::Enabled, |port| async move {
+        let mut peer = RawPeer::connect(port).await;
+        let version = version_without_encryption();
+        let handshake = peer.handshake(version, false).await.unwrap();
+        assert_eq!(handshake.version, version);
+        assert_eq!(handshake.encryption_offer, None);
+        let message = peer.read().await.unwrap();
+        assert!(PeerMessage::try_from_slice(&message).is_ok());
+    });
+}
+
+/// A node that requires encryption rejects a peer with a protocol version without it.
+#[test]
+fn required_rejects_version_without_encryption() {
+    run_test(EncryptionMode::Required, |port| async move {
+        let mut peer = RawPeer::connect(port).await;
+        assert_eq!(peer.handshake(version_without_encryption(), false).await, None);
+    });
+}
+
+/// A node that requires encryption encrypts all the messages after its handshake.
+#[test]
+fn required_encrypts_connection() {
+    run_test(EncryptionMode::Required, |port| async move {
+        let mut peer = RawPeer::connect(port).await;
+        let handshake = peer.handshake(PROTOCOL_VERSION, true).await.unwrap();
+        let mut session = peer
+            .ephemeral_key
+            .session(
+                &peer.peer_id,
+                &peer.node_id,
+                PeerType::Outbound,
+                PROTOCOL_VERSION,
+                &handshake.encryption_offer.unwrap(),
+            )
+            .unwrap();
+        let message = peer.read().await.unwrap();
+        assert!(PeerMessage::try_from_slice(&message).is_err());
+        let message = session.decrypt(&message).unwrap();
+        assert!(PeerMessage::try_from_slice(&message).is_ok());
+    });
+}
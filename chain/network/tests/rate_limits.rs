@@ -0,0 +1,51 @@
+pub use runner::*;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use near_network::rate_limiter::{RateLimit, RateLimitsConfig};
+use near_network::types::ReasonForBan;
+
+mod runner;
+
+/// Allows a single ping, the second one dropped within a minute makes the peer abusive.
+fn ping_limits() -> RateLimitsConfig {
+    let mut limits = HashMap::new();
+    limits.insert("Ping".to_string(), RateLimit::new(0.01, 1));
+    RateLimitsConfig { limits, max_dropped_per_min: 1 }
+}
+
+/// A peer sending more messages than allowed is banned as abusive.
+#[test]
+fn ban_peer_exceeding_rate_limit() {
+    let mut runner =
+        Runner::new(2, 1).rate_limits(1, ping_limits()).ban_window(Duration::from_secs(60));
+
+    runner.push(Action::AddEdge(0, 1));
+    runner.push(Action::CheckRoutingTable(0, vec![(1, vec![1])]));
+    for nonce in 0..5 {
+        runner.push(Action::PingTo(0, nonce, 1));
+    }
+    runner.push_action(check_banned(1, 0, ReasonForBan::Abusive));
+    runner.push(Action::CheckRoutingTable(1, vec![]));
+
+    start_test(runner);
+}
+
+/// Routed messages relayed for other nodes don't count against the limits of the peer that
+/// sends them, so it isn't banned and all of them are delivered.
+#[test]
+fn dont_rate_limit_relayed_messages() {
+    let mut runner = Runner::new(3, 1).rate_limits(1, ping_limits());
+
+    runner.push(Action::AddEdge(0, 1));
+    runner.push(Action::AddEdge(1, 2));
+    runner.push(Action::CheckRoutingTable(0, vec![(1, vec![1]), (2, vec![1])]));
+    for nonce in 0..5 {
+        runner.push(Action::PingTo(0, nonce, 2));
+    }
+    runner.push(Action::CheckPingPong(2, (0..5).map(|nonce| (nonce, 0)).collect(), vec![]));
+    runner.push(Action::CheckPingPong(0, vec![], (0..5).map(|nonce| (nonce, 2)).collect()));
+    runner.push_action(check_direct_connection(1, 0));
+
+    start_test(runner);
+}
@@ -14,11 +14,16 @@ use near_chain_configs::ClientConfig;
 use near_client::{start_view_client, ClientActor};
 use near_crypto::KeyType;
 use near_logger_utils::init_test_logger;
+use near_network::compression::{Compression, CompressionConfig};
+use near_network::rate_limiter::RateLimitsConfig;
 use near_network::test_utils::{
     convert_boot_nodes, expected_routing_tables, open_port, peer_id_from_seed, BanPeerSignal,
     GetInfo, StopSignal, WaitOrTimeout,
 };
-use near_network::types::{OutboundTcpConnect, ROUTED_MESSAGE_TTL};
+use near_network::types::{
+    KnownPeerStatus, OutboundTcpConnect, PeerAdminRequest, PeerAdminResponse, ReasonForBan,
+    ROUTED_MESSAGE_TTL,
+};
 use near_network::utils::blacklist_from_iter;
 use near_network::{
     NetworkConfig, NetworkRecipient, NetworkRequests, NetworkResponses, PeerInfo, PeerManagerActor,
@@ -365,6 +370,8 @@ struct TestConfig {
     minimum_outbound_peers: Option<u32>,
     safe_set_size: Option<u32>,
     archive: bool,
+    rate_limits: RateLimitsConfig,
+    compression: CompressionConfig,
 }
 
 impl TestConfig {
@@ -380,6 +387,8 @@ impl TestConfig {
             minimum_outbound_peers: None,
             safe_set_size: None,
             archive: false,
+            rate_limits: RateLimitsConfig::default(),
+            compression: CompressionConfig::default(),
         }
     }
 }
@@ -434,6 +443,18 @@ impl Runner {
         self
     }
 
+    /// Set the limits on the rate of messages node `u` receives from each peer.
+    pub fn rate_limits(mut self, u: usize, rate_limits: RateLimitsConfig) -> Self {
+        self.test_config[u].rate_limits = rate_limits;
+        self
+    }
+
+    /// Set the compression of the messages node `u` sends to its peers.
+    pub fn compression(mut self, u: usize, compression: CompressionConfig) -> Self {
+        self.test_config[u].compression = compression;
+        self
+    }
+
     /// Specify boot nodes. By default there are no boot nodes.
     pub fn use_boot_nodes(mut self, boot_nodes: Vec<usize>) -> Self {
         self.apply_all(move |test_config| {
@@ -549,6 +570,8 @@ impl Runner {
         network_config.outbound_disabled = test_config.outbound_disabled;
         network_config.boot_nodes = boot_nodes;
         network_config.archive = test_config.archive;
+        network_config.rate_limits = test_config.rate_limits.clone();
+        network_config.compression = test_config.compression.clone();
 
         network_config.ideal_connections_lo =
             test_config.ideal_connections.map_or(network_config.ideal_connections_lo, |(lo, _)| lo);
@@ -801,6 +824,112 @@ pub fn ban_peer(target_peer: usize, banned_peer: usize) -> ActionFn {
     )
 }
 
+/// Check that `target_peer` banned `banned_peer` for `ban_reason`.
+pub fn check_banned(target_peer: usize, banned_peer: usize, ban_reason: ReasonForBan) -> ActionFn {
+    Box::new(
+        move |info: SharedRunningInfo,
+              flag: Arc<AtomicBool>,
+              _ctx: &mut Context<WaitOrTimeout>,
+              _runner| {
+            let info = info.read().unwrap();
+            let banned_peer_id = info.peers_info[banned_peer].id.clone();
+            actix::spawn(
+                info.pm_addr
+                    .get(target_peer)
+                    .unwrap()
+                    .send(PeerAdminRequest::ListPeers)
+                    .map_err(|_| ())
+                    .and_then(move |res| {
+                        if let Ok(PeerAdminResponse::Peers(peers)) = res {
+                            if peers.iter().any(|peer| {
+                                peer.peer_info.id == banned_peer_id
+                                    && match peer.status {
+                                        KnownPeerStatus::Banned(reason, _) => reason == ban_reason,
+                                        _ => false,
+                                    }
+                            }) {
+                                flag.store(true, Ordering::Relaxed);
+                            }
+                        }
+                        future::ok(())
+                    })
+                    .map(drop),
+            );
+        },
+    )
+}
+
+/// Check that `node_id` reports `compression` for its connection with `target_id`.
+pub fn check_compression(
+    node_id: usize,
+    target_id: usize,
+    compression: Option<Compression>,
+) -> ActionFn {
+    Box::new(
+        move |info: SharedRunningInfo,
+              flag: Arc<AtomicBool>,
+              _ctx: &mut Context<WaitOrTimeout>,
+              _runner| {
+            let info = info.read().unwrap();
+            let target_peer_id = info.peers_info[target_id].id.clone();
+            actix::spawn(
+                info.pm_addr
+                    .get(node_id)
+                    .unwrap()
+                    .send(GetInfo {})
+                    .map_err(|_| ())
+                    .and_then(move |info| {
+                        let connected = info
+                            .active_peers
+                            .iter()
+                            .any(|peer| peer.peer_info.id == target_peer_id);
+                        if connected
+                            && info.peer_compression.get(&target_peer_id).cloned() == compression
+                        {
+                            flag.store(true, Ordering::Relaxed);
+                        }
+                        future::ok(())
+                    })
+                    .map(drop),
+            );
+        },
+    )
+}
+
+/// Send the admin request built from the info of the peers to `node_id` until `check` accepts
+/// the response.
+pub fn peer_admin_request<R, C>(node_id: usize, request: R, check: C) -> ActionFn
+where
+    R: 'static + Fn(&[PeerInfo]) -> PeerAdminRequest,
+    C: 'static + Fn(&[PeerInfo], Result<PeerAdminResponse, String>) -> bool,
+{
+    let check = Arc::new(check);
+    Box::new(
+        move |info: SharedRunningInfo,
+              flag: Arc<AtomicBool>,
+              _ctx: &mut Context<WaitOrTimeout>,
+              _runner| {
+            let info = info.read().unwrap();
+            let peers_info = info.peers_info.clone();
+            let check = check.clone();
+            actix::spawn(
+                info.pm_addr
+                    .get(node_id)
+                    .unwrap()
+                    .send(request(&peers_info))
+                    .map_err(|_| ())
+                    .and_then(move |res| {
+                        if check(&peers_info, res) {
+                            flag.store(true, Ordering::Relaxed);
+                        }
+                        future::ok(())
+                    })
+                    .map(drop),
+            );
+        },
+    )
+}
+
 /// Change account id from a stopped peer. Notice this will also change its peer id, since
 /// peer_id is derived from account id with NetworkConfig::from_seed
 pub fn change_account_id(node_id: usize, account_id: String) -> ActionFn {
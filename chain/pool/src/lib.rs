@@ -1,37 +1,73 @@
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+#[macro_use]
+extern crate lazy_static;
 
-use crate::types::{PoolIterator, PoolKey, TransactionGroup};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::types::{InsertTransactionResult, PoolIterator, PoolKey, TransactionGroup};
 use borsh::BorshSerialize;
+use near_chain_configs::{TransactionPoolConfig, TransactionPoolEviction, TransactionPoolOrdering};
 use near_crypto::PublicKey;
+use near_primitives::errors::TransactionPoolError;
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, Balance};
 use rand::RngCore;
 use std::ops::Bound;
 
+mod metrics;
 pub mod types;
 
+/// Position of a transaction in the eviction order: (priority, insertion number, hash).
+/// The priority is zero unless transactions are evicted by priority.
+type EvictionKey = (Balance, u64, CryptoHash);
+
+/// Bookkeeping of a transaction in the pool used to enforce the limits.
+struct PoolEntry {
+    signer_id: AccountId,
+    key: PoolKey,
+    size: u64,
+    /// Non-refundable fee of the transaction, see `TransactionPool::insert_transaction`.
+    priority: Balance,
+    eviction_key: EvictionKey,
+}
+
 /// Transaction pool: keeps track of transactions that were not yet accepted into the block chain.
 pub struct TransactionPool {
     /// Transactions are grouped by a pair of (account ID, signer public key).
     /// NOTE: It's more efficient on average to keep transactions unsorted and with potentially
     /// conflicting nonce than to create a BTreeMap for every transaction.
     pub transactions: BTreeMap<PoolKey, Vec<SignedTransaction>>,
-    /// Set of all hashes to quickly check if the given transaction is in the pool.
-    pub unique_transactions: HashSet<CryptoHash>,
+    /// All transactions in the pool by hash, to quickly check if the given transaction is in the
+    /// pool and to account for the limits.
+    unique_transactions: HashMap<CryptoHash, PoolEntry>,
     /// A uniquely generated key seed to randomize PoolKey order.
     key_seed: Vec<u8>,
     /// The key after which the pool iterator starts. Doesn't have to be present in the pool.
     last_used_key: PoolKey,
+    /// Ordering, limits and eviction strategy of the pool.
+    config: TransactionPoolConfig,
+    /// Transactions in the order they are evicted in when the pool is full.
+    eviction_order: BTreeSet<EvictionKey>,
+    /// Number of inserted transactions, used to order transactions by age.
+    insertions: u64,
+    /// Total size of the transactions in the pool in bytes.
+    total_size: u64,
+    /// Number of transactions in the pool per signer account.
+    signer_transactions: HashMap<AccountId, usize>,
 }
 
 impl TransactionPool {
-    pub fn new() -> Self {
+    pub fn new(config: TransactionPoolConfig) -> Self {
         Self {
             key_seed: rand::thread_rng().next_u64().to_le_bytes().to_vec(),
             transactions: BTreeMap::new(),
-            unique_transactions: HashSet::new(),
+            unique_transactions: HashMap::new(),
             last_used_key: CryptoHash::default(),
+            config,
+            eviction_order: BTreeSet::new(),
+            insertions: 0,
+            total_size: 0,
+            signer_transactions: HashMap::new(),
         }
     }
 
@@ -43,19 +79,167 @@ impl TransactionPool {
     }
 
     /// Insert a signed transaction into the pool that passed validation.
-    pub fn insert_transaction(&mut self, signed_transaction: SignedTransaction) -> bool {
-        if !self.unique_transactions.insert(signed_transaction.get_hash()) {
-            return false;
+    /// `priority` is the non-refundable fee of the transaction, i.e. the gas price times the gas
+    /// burnt for converting it into a receipt and the execution fees of its actions, without the
+    /// prepaid gas of function calls, which is refunded if unused. It is used by
+    /// `TransactionPoolOrdering::GasPriority`, by `TransactionPoolEviction::LowestPriorityFirst`
+    /// and by replace-by-nonce: if the pool already has transactions with the same signer, public
+    /// key and nonce and a lower fee, they are replaced by the new one. If the pool is full,
+    /// transactions are evicted according to the configured eviction strategy or the new one is
+    /// rejected.
+    pub fn insert_transaction(
+        &mut self,
+        signed_transaction: SignedTransaction,
+        priority: Balance,
+    ) -> InsertTransactionResult {
+        let tx_hash = signed_transaction.get_hash();
+        if self.unique_transactions.contains_key(&tx_hash) {
+            return InsertTransactionResult::Duplicate;
+        }
+        let signer_id = signed_transaction.transaction.signer_id.clone();
+        let key = self.key(&signer_id, &signed_transaction.transaction.public_key);
+        let nonce = signed_transaction.transaction.nonce;
+        let size = signed_transaction.try_to_vec().expect("Failed to serialize").len() as u64;
+
+        let replaced: Vec<CryptoHash> = self
+            .transactions
+            .get(&key)
+            .map(|group| {
+                group
+                    .iter()
+                    .filter(|tx| {
+                        tx.transaction.nonce == nonce
+                            && self.unique_transactions[&tx.get_hash()].priority < priority
+                    })
+                    .map(|tx| tx.get_hash())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let signer_transactions =
+            self.signer_transactions.get(&signer_id).cloned().unwrap_or_default() - replaced.len();
+        if signer_transactions >= self.config.max_transactions_per_signer {
+            return self.reject(TransactionPoolError::TooManySignerTransactions {
+                signer_id,
+                limit: self.config.max_transactions_per_signer as u64,
+            });
+        }
+        let evicted = match self.transactions_to_evict(size, priority, &replaced) {
+            Some(evicted) => evicted,
+            None => return self.reject(TransactionPoolError::PoolIsFull),
+        };
+
+        for hash in replaced {
+            self.remove_transaction(&hash);
+            near_metrics::inc_counter_vec(&metrics::TRANSACTION_POOL_EVICTED, &["replaced"]);
+        }
+        for hash in evicted {
+            self.remove_transaction(&hash);
+            near_metrics::inc_counter_vec(&metrics::TRANSACTION_POOL_EVICTED, &["pool_full"]);
+        }
+
+        self.insertions += 1;
+        let eviction_priority = match self.config.eviction {
+            TransactionPoolEviction::LowestPriorityFirst => priority,
+            TransactionPoolEviction::RejectNew | TransactionPoolEviction::OldestFirst => 0,
+        };
+        let eviction_key = (eviction_priority, self.insertions, tx_hash);
+        self.eviction_order.insert(eviction_key);
+        self.total_size += size;
+        *self.signer_transactions.entry(signer_id.clone()).or_default() += 1;
+        self.unique_transactions
+            .insert(tx_hash, PoolEntry { signer_id, key, size, priority, eviction_key });
+        self.transactions.entry(key).or_insert_with(Vec::new).push(signed_transaction);
+        InsertTransactionResult::Success
+    }
+
+    fn reject(&self, error: TransactionPoolError) -> InsertTransactionResult {
+        let reason = match error {
+            TransactionPoolError::PoolIsFull => "pool_full",
+            TransactionPoolError::TooManySignerTransactions { .. } => "signer_limit",
+        };
+        near_metrics::inc_counter_vec(&metrics::TRANSACTION_POOL_REJECTED, &[reason]);
+        InsertTransactionResult::Rejected(error)
+    }
+
+    /// Returns the transactions that have to be evicted for a new transaction of the given size
+    /// and priority to fit into the pool, once the `replaced` transactions are removed.
+    /// Returns `None` if the new transaction can't be inserted.
+    fn transactions_to_evict(
+        &self,
+        size: u64,
+        priority: Balance,
+        replaced: &[CryptoHash],
+    ) -> Option<Vec<CryptoHash>> {
+        let mut count = self.unique_transactions.len() - replaced.len() + 1;
+        let mut total_size = self.total_size + size
+            - replaced.iter().map(|hash| self.unique_transactions[hash].size).sum::<u64>();
+        let fits = |count: usize, total_size: u64| {
+            count <= self.config.max_transactions && total_size <= self.config.max_size_bytes
+        };
+        if size > self.config.max_size_bytes {
+            return None;
+        }
+        let mut evicted = vec![];
+        for (entry_priority, _, hash) in self.eviction_order.iter() {
+            if fits(count, total_size) {
+                break;
+            }
+            if replaced.contains(hash) {
+                continue;
+            }
+            match self.config.eviction {
+                TransactionPoolEviction::RejectNew => return None,
+                TransactionPoolEviction::OldestFirst => {}
+                TransactionPoolEviction::LowestPriorityFirst => {
+                    if *entry_priority >= priority {
+                        return None;
+                    }
+                }
+            }
+            count -= 1;
+            total_size -= self.unique_transactions[hash].size;
+            evicted.push(*hash);
+        }
+        if fits(count, total_size) {
+            Some(evicted)
+        } else {
+            None
+        }
+    }
+
+    /// Removes the transaction with the given hash from the pool.
+    /// Returns whether the transaction was in the pool.
+    pub fn remove_transaction(&mut self, tx_hash: &CryptoHash) -> bool {
+        let entry = match self.forget_transaction(tx_hash) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        let mut remove_entry = false;
+        if let Some(v) = self.transactions.get_mut(&entry.key) {
+            v.retain(|tx| &tx.get_hash() != tx_hash);
+            remove_entry = v.is_empty();
+        }
+        if remove_entry {
+            self.transactions.remove(&entry.key);
         }
-        let signer_id = &signed_transaction.transaction.signer_id;
-        let signer_public_key = &signed_transaction.transaction.public_key;
-        self.transactions
-            .entry(self.key(signer_id, signer_public_key))
-            .or_insert_with(Vec::new)
-            .push(signed_transaction);
         true
     }
 
+    /// Removes the transaction from the bookkeeping of the pool, but not from its group.
+    fn forget_transaction(&mut self, tx_hash: &CryptoHash) -> Option<PoolEntry> {
+        let entry = self.unique_transactions.remove(tx_hash)?;
+        self.eviction_order.remove(&entry.eviction_key);
+        self.total_size -= entry.size;
+        if let Some(count) = self.signer_transactions.get_mut(&entry.signer_id) {
+            *count -= 1;
+            if *count == 0 {
+                self.signer_transactions.remove(&entry.signer_id);
+            }
+        }
+        Some(entry)
+    }
+
     /// Returns a pool iterator wrapper that implements an iterator like trait to iterate over
     /// transaction groups in the proper order defined by the protocol.
     /// When the iterator is dropped, all remaining groups are inserted back into the pool.
@@ -68,7 +252,7 @@ impl TransactionPool {
     pub fn remove_transactions(&mut self, transactions: &[SignedTransaction]) {
         let mut grouped_transactions = HashMap::new();
         for tx in transactions {
-            if self.unique_transactions.contains(&tx.get_hash()) {
+            if self.unique_transactions.contains_key(&tx.get_hash()) {
                 let signer_id = &tx.transaction.signer_id;
                 let signer_public_key = &tx.transaction.public_key;
                 grouped_transactions
@@ -87,15 +271,15 @@ impl TransactionPool {
                 self.transactions.remove(&key);
             }
             for hash in hashes {
-                self.unique_transactions.remove(&hash);
+                self.forget_transaction(&hash);
             }
         }
     }
 
-    /// Reintroduce transactions back during the chain reorg
-    pub fn reintroduce_transactions(&mut self, transactions: Vec<SignedTransaction>) {
-        for tx in transactions {
-            self.insert_transaction(tx);
+    /// Reintroduce transactions back during the chain reorg, together with their priorities.
+    pub fn reintroduce_transactions(&mut self, transactions: Vec<(SignedTransaction, Balance)>) {
+        for (tx, priority) in transactions {
+            self.insert_transaction(tx, priority);
         }
     }
 
@@ -106,6 +290,20 @@ impl TransactionPool {
     pub fn is_empty(&self) -> bool {
         self.unique_transactions.is_empty()
     }
+
+    /// Total size of the transactions in the pool in bytes.
+    pub fn size_bytes(&self) -> u64 {
+        self.total_size
+    }
+
+    pub fn contains(&self, tx_hash: &CryptoHash) -> bool {
+        self.unique_transactions.contains_key(tx_hash)
+    }
+
+    /// Iterates over all transactions in the pool, in no particular order.
+    pub fn transactions(&self) -> impl Iterator<Item = &SignedTransaction> {
+        self.transactions.values().flatten()
+    }
 }
 
 /// PoolIterator is a structure to pull transactions from the pool.
@@ -123,27 +321,21 @@ impl<'a> PoolIteratorWrapper<'a> {
     pub fn new(pool: &'a mut TransactionPool) -> Self {
         Self { pool, sorted_groups: Default::default() }
     }
-}
 
-/// The iterator works with the following algorithm:
-/// On next(), the iterator tries to get a transaction group from the pool, sorts transactions in
-/// it, and add it to the back of the sorted groups queue.
-/// Remembers the last used key, so it can continue from the next key.
-///
-/// If the pool is empty, the iterator gets the group from the front of the sorted groups queue.
-///
-/// If this group is empty (no transactions left inside), then the iterator discards it and
-/// updates `unique_transactions` in the pool. Then gets the next one.
-///
-/// Once a non-empty group is found, this group is pushed to the back of the sorted groups queue
-/// and the iterator returns a mutable reference to this group.
-///
-/// If the sorted groups queue is empty, the iterator returns None.
-///
-/// When the iterator is dropped, `unique_transactions` in the pool is updated for every group.
-/// And all non-empty group from the sorted groups queue are inserted back into the pool.
-impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
-    fn next(&mut self) -> Option<&mut TransactionGroup> {
+    /// Removes the group under the given key from the pool, sorts it by nonce and puts it at the
+    /// back of the sorted groups queue.
+    fn take_group(&mut self, key: PoolKey) {
+        let mut transactions = self.pool.transactions.remove(&key).expect("just checked existence");
+        transactions.sort_by_key(|st| std::cmp::Reverse(st.transaction.nonce));
+        self.sorted_groups.push_back(TransactionGroup {
+            key,
+            transactions,
+            removed_transaction_hashes: vec![],
+        });
+    }
+
+    /// Round robin over the groups in the randomized key order. See `PoolIterator` below.
+    fn next_round_robin(&mut self) -> Option<&mut TransactionGroup> {
         if !self.pool.transactions.is_empty() {
             let key = *self
                 .pool
@@ -159,20 +351,13 @@ impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
                         .expect("we've just checked that the map is not empty")
                 });
             self.pool.last_used_key = key;
-            let mut transactions =
-                self.pool.transactions.remove(&key).expect("just checked existence");
-            transactions.sort_by_key(|st| std::cmp::Reverse(st.transaction.nonce));
-            self.sorted_groups.push_back(TransactionGroup {
-                key,
-                transactions,
-                removed_transaction_hashes: vec![],
-            });
+            self.take_group(key);
             Some(self.sorted_groups.back_mut().expect("just pushed"))
         } else {
             while let Some(sorted_group) = self.sorted_groups.pop_front() {
                 if sorted_group.transactions.is_empty() {
                     for hash in sorted_group.removed_transaction_hashes {
-                        self.pool.unique_transactions.remove(&hash);
+                        self.pool.forget_transaction(&hash);
                     }
                 } else {
                     self.sorted_groups.push_back(sorted_group);
@@ -182,6 +367,70 @@ impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
             None
         }
     }
+
+    /// Returns the group whose next transaction pays the highest fee. All groups are pulled
+    /// from the pool on the first call. Among groups with equal priority the one that was
+    /// returned least recently goes first.
+    fn next_gas_priority(&mut self) -> Option<&mut TransactionGroup> {
+        let keys: Vec<_> = self.pool.transactions.keys().cloned().collect();
+        for key in keys {
+            self.take_group(key);
+        }
+        let mut best: Option<(usize, Balance)> = None;
+        let mut index = 0;
+        while index < self.sorted_groups.len() {
+            match self.sorted_groups[index].transactions.last() {
+                Some(tx) => {
+                    let priority = self.pool.unique_transactions[&tx.get_hash()].priority;
+                    if best.map_or(true, |(_, best_priority)| priority > best_priority) {
+                        best = Some((index, priority));
+                    }
+                    index += 1;
+                }
+                None => {
+                    let sorted_group =
+                        self.sorted_groups.remove(index).expect("index is within bounds");
+                    for hash in sorted_group.removed_transaction_hashes {
+                        self.pool.forget_transaction(&hash);
+                    }
+                }
+            }
+        }
+        let (index, _) = best?;
+        let sorted_group = self.sorted_groups.remove(index).expect("index is within bounds");
+        self.sorted_groups.push_back(sorted_group);
+        Some(self.sorted_groups.back_mut().expect("just pushed"))
+    }
+}
+
+/// With `TransactionPoolOrdering::RoundRobin` the iterator works with the following algorithm:
+/// On next(), the iterator tries to get a transaction group from the pool, sorts transactions in
+/// it, and add it to the back of the sorted groups queue.
+/// Remembers the last used key, so it can continue from the next key.
+///
+/// If the pool is empty, the iterator gets the group from the front of the sorted groups queue.
+///
+/// If this group is empty (no transactions left inside), then the iterator discards it and
+/// updates `unique_transactions` in the pool. Then gets the next one.
+///
+/// Once a non-empty group is found, this group is pushed to the back of the sorted groups queue
+/// and the iterator returns a mutable reference to this group.
+///
+/// If the sorted groups queue is empty, the iterator returns None.
+///
+/// With `TransactionPoolOrdering::GasPriority` all groups are pulled from the pool at once and
+/// every call returns the non-empty group whose next transaction has the highest priority given
+/// to `TransactionPool::insert_transaction`, discarding empty groups along the way.
+///
+/// When the iterator is dropped, `unique_transactions` in the pool is updated for every group.
+/// And all non-empty group from the sorted groups queue are inserted back into the pool.
+impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
+    fn next(&mut self) -> Option<&mut TransactionGroup> {
+        match self.pool.config.ordering {
+            TransactionPoolOrdering::RoundRobin => self.next_round_robin(),
+            TransactionPoolOrdering::GasPriority => self.next_gas_priority(),
+        }
+    }
 }
 
 /// When a pool iterator is dropped, all remaining non empty transaction groups from the sorted
@@ -191,7 +440,7 @@ impl<'a> Drop for PoolIteratorWrapper<'a> {
     fn drop(&mut self) {
         for group in self.sorted_groups.drain(..) {
             for hash in group.removed_transaction_hashes {
-                self.pool.unique_transactions.remove(&hash);
+                self.pool.forget_transaction(&hash);
             }
             if !group.transactions.is_empty() {
                 self.pool.transactions.insert(group.key, group.transactions);
@@ -239,11 +488,11 @@ mod tests {
         mut transactions: Vec<SignedTransaction>,
         expected_weight: u32,
     ) -> (Vec<u64>, TransactionPool) {
-        let mut pool = TransactionPool::new();
+        let mut pool = TransactionPool::new(TransactionPoolConfig::default());
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions {
-            pool.insert_transaction(tx);
+            pool.insert_transaction(tx, 0);
         }
         (
             prepare_transactions(&mut pool, expected_weight)
@@ -350,12 +599,12 @@ mod tests {
             })
             .collect::<Vec<_>>();
 
-        let mut pool = TransactionPool::new();
+        let mut pool = TransactionPool::new(TransactionPoolConfig::default());
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions.clone() {
             println!("{:?}", tx);
-            pool.insert_transaction(tx);
+            pool.insert_transaction(tx, 0);
         }
         assert_eq!(pool.len(), n as usize);
 
@@ -407,7 +656,7 @@ mod tests {
         assert_eq!(pool.len(), 5);
 
         for tx in transactions {
-            pool.insert_transaction(tx);
+            pool.insert_transaction(tx, 0);
         }
         assert_eq!(pool.len(), 10);
         let txs = prepare_transactions(&mut pool, 10);
@@ -440,7 +689,7 @@ mod tests {
         assert_eq!(pool.len(), 5);
 
         for tx in transactions {
-            pool.insert_transaction(tx);
+            pool.insert_transaction(tx, 0);
         }
         assert_eq!(pool.len(), 10);
         let txs = prepare_transactions(&mut pool, 5);
@@ -450,4 +699,183 @@ mod tests {
         new_nonces.sort();
         assert_ne!(nonces, new_nonces);
     }
+
+    /// A function call whose signer pays `fee`. The fee is also attached as gas, so that calls
+    /// with different fees have different hashes.
+    fn call_with_fee(signer_id: &str, nonce: u64, fee: Balance) -> (SignedTransaction, Balance) {
+        let signer = InMemorySigner::from_seed(signer_id, KeyType::ED25519, signer_id);
+        let tx = SignedTransaction::call(
+            nonce,
+            signer_id.to_string(),
+            "bob.near".to_string(),
+            &signer,
+            0,
+            "method".to_string(),
+            vec![],
+            fee as u64,
+            CryptoHash::default(),
+        );
+        (tx, fee)
+    }
+
+    fn insert(
+        pool: &mut TransactionPool,
+        (tx, fee): (SignedTransaction, Balance),
+    ) -> InsertTransactionResult {
+        pool.insert_transaction(tx, fee)
+    }
+
+    /// A transaction with the same nonce and a higher fee replaces the old one, while a
+    /// transaction with a lower fee is kept next to it as before.
+    #[test]
+    fn test_replace_by_nonce() {
+        let mut pool = TransactionPool::new(TransactionPoolConfig::default());
+        let old_tx = call_with_fee("alice.near", 1, 100);
+        let new_tx = call_with_fee("alice.near", 1, 200);
+        assert_eq!(insert(&mut pool, old_tx.clone()), InsertTransactionResult::Success);
+        assert_eq!(insert(&mut pool, new_tx.clone()), InsertTransactionResult::Success);
+        assert_eq!(pool.len(), 1);
+        assert!(!pool.contains(&old_tx.0.get_hash()));
+
+        assert_eq!(
+            insert(&mut pool, call_with_fee("alice.near", 1, 50)),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(pool.len(), 2);
+        assert!(pool.contains(&new_tx.0.get_hash()));
+    }
+
+    /// Groups are returned by the fee of their next transaction.
+    #[test]
+    fn test_gas_priority_ordering() {
+        let mut pool = TransactionPool::new(TransactionPoolConfig {
+            ordering: TransactionPoolOrdering::GasPriority,
+            ..TransactionPoolConfig::default()
+        });
+        insert(&mut pool, call_with_fee("alice.near", 1, 100));
+        insert(&mut pool, call_with_fee("alice.near", 2, 400));
+        insert(&mut pool, call_with_fee("bob.near", 1, 300));
+        insert(&mut pool, call_with_fee("carol.near", 1, 200));
+
+        let gas: Vec<u64> = prepare_transactions(&mut pool, 10)
+            .iter()
+            .map(|tx| tx.transaction.actions[0].get_prepaid_gas())
+            .collect();
+        assert_eq!(gas, vec![300, 200, 100, 400]);
+        assert!(pool.is_empty());
+    }
+
+    /// Transfers don't attach any gas, but are still ordered by the fee their signers pay.
+    #[test]
+    fn test_transfer_with_higher_fee_goes_first() {
+        let mut pool = TransactionPool::new(TransactionPoolConfig {
+            ordering: TransactionPoolOrdering::GasPriority,
+            ..TransactionPoolConfig::default()
+        });
+        let low_fee = generate_transactions("alice.near", "alice.near", 1, 1).pop().unwrap();
+        let high_fee = generate_transactions("bob.near", "bob.near", 1, 1).pop().unwrap();
+        pool.insert_transaction(low_fee.clone(), 100);
+        pool.insert_transaction(high_fee.clone(), 200);
+        assert_eq!(prepare_transactions(&mut pool, 10), vec![high_fee, low_fee]);
+    }
+
+    fn pool_with_limits(
+        eviction: TransactionPoolEviction,
+        max_transactions: usize,
+        max_transactions_per_signer: usize,
+    ) -> TransactionPool {
+        TransactionPool::new(TransactionPoolConfig {
+            eviction,
+            max_transactions,
+            max_transactions_per_signer,
+            ..TransactionPoolConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_signer_limit() {
+        let mut pool = pool_with_limits(TransactionPoolEviction::OldestFirst, 10, 2);
+        for tx in generate_transactions("alice.near", "alice.near", 1, 2) {
+            assert_eq!(pool.insert_transaction(tx, 0), InsertTransactionResult::Success);
+        }
+        let tx = generate_transactions("alice.near", "alice.near", 3, 3).pop().unwrap();
+        assert_eq!(
+            pool.insert_transaction(tx, 0),
+            InsertTransactionResult::Rejected(TransactionPoolError::TooManySignerTransactions {
+                signer_id: "alice.near".to_string(),
+                limit: 2,
+            })
+        );
+        let tx = generate_transactions("bob.near", "bob.near", 1, 1).pop().unwrap();
+        assert_eq!(pool.insert_transaction(tx, 0), InsertTransactionResult::Success);
+
+        // Pulled transactions no longer count towards the limit.
+        assert_eq!(prepare_transactions(&mut pool, 10).len(), 3);
+        assert_eq!(pool.size_bytes(), 0);
+        let tx = generate_transactions("alice.near", "alice.near", 3, 3).pop().unwrap();
+        assert_eq!(pool.insert_transaction(tx, 0), InsertTransactionResult::Success);
+    }
+
+    #[test]
+    fn test_pool_full_reject_new() {
+        let mut pool = pool_with_limits(TransactionPoolEviction::RejectNew, 2, 10);
+        insert(&mut pool, call_with_fee("alice.near", 1, 100));
+        insert(&mut pool, call_with_fee("bob.near", 1, 100));
+        assert_eq!(
+            insert(&mut pool, call_with_fee("carol.near", 1, 200)),
+            InsertTransactionResult::Rejected(TransactionPoolError::PoolIsFull)
+        );
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[test]
+    fn test_pool_full_oldest_first() {
+        let mut pool = pool_with_limits(TransactionPoolEviction::OldestFirst, 2, 10);
+        let oldest = call_with_fee("alice.near", 1, 300);
+        insert(&mut pool, oldest.clone());
+        insert(&mut pool, call_with_fee("bob.near", 1, 100));
+        assert_eq!(
+            insert(&mut pool, call_with_fee("carol.near", 1, 200)),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.contains(&oldest.0.get_hash()));
+    }
+
+    #[test]
+    fn test_pool_full_lowest_priority_first() {
+        let mut pool = pool_with_limits(TransactionPoolEviction::LowestPriorityFirst, 2, 10);
+        insert(&mut pool, call_with_fee("alice.near", 1, 300));
+        let lowest = call_with_fee("bob.near", 1, 100);
+        insert(&mut pool, lowest.clone());
+        assert_eq!(
+            insert(&mut pool, call_with_fee("carol.near", 1, 100)),
+            InsertTransactionResult::Rejected(TransactionPoolError::PoolIsFull)
+        );
+        assert_eq!(
+            insert(&mut pool, call_with_fee("carol.near", 1, 200)),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(pool.len(), 2);
+        assert!(!pool.contains(&lowest.0.get_hash()));
+    }
+
+    #[test]
+    fn test_pool_size_limit() {
+        let tx = call_with_fee("alice.near", 1, 100);
+        let size = tx.0.try_to_vec().unwrap().len() as u64;
+        let mut pool = TransactionPool::new(TransactionPoolConfig {
+            eviction: TransactionPoolEviction::OldestFirst,
+            max_size_bytes: size * 3 / 2,
+            ..TransactionPoolConfig::default()
+        });
+        assert_eq!(insert(&mut pool, tx.clone()), InsertTransactionResult::Success);
+        assert_eq!(
+            insert(&mut pool, call_with_fee("carol.near", 1, 100)),
+            InsertTransactionResult::Success
+        );
+        assert_eq!(pool.len(), 1);
+        assert!(!pool.contains(&tx.0.get_hash()));
+        assert_eq!(pool.size_bytes(), size);
+    }
 }
@@ -0,0 +1,16 @@
+use near_metrics::{try_create_int_counter_vec, IntCounterVec};
+
+lazy_static! {
+    pub static ref TRANSACTION_POOL_EVICTED: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_transaction_pool_evicted_total",
+            "Number of transactions evicted from the transaction pool by reason",
+            &["reason"]
+        );
+    pub static ref TRANSACTION_POOL_REJECTED: near_metrics::Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "near_transaction_pool_rejected_total",
+            "Number of transactions rejected by the transaction pool by reason",
+            &["reason"]
+        );
+}
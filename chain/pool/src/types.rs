@@ -1,3 +1,4 @@
+use near_primitives::errors::TransactionPoolError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::transaction::SignedTransaction;
 
@@ -35,3 +36,14 @@ impl TransactionGroup {
         }
     }
 }
+
+/// Result of inserting a transaction into the pool.
+#[derive(Debug, PartialEq, Eq)]
+pub enum InsertTransactionResult {
+    /// The transaction was inserted into the pool.
+    Success,
+    /// The transaction is already in the pool.
+    Duplicate,
+    /// The pool didn't accept the transaction because of its limits.
+    Rejected(TransactionPoolError),
+}
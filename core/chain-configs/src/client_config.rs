@@ -17,6 +17,72 @@ pub enum LogSummaryStyle {
     Colored,
 }
 
+/// Order in which the transaction pool hands out transaction groups to the chunk producer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionPoolOrdering {
+    /// Round robin over (signer, public key) groups in a randomized order.
+    #[serde(rename = "round_robin")]
+    RoundRobin,
+    /// Groups whose next transaction pays a higher fee go first. The fee is the gas price times
+    /// the gas burnt and prepaid by the transaction. Ties are served round robin.
+    #[serde(rename = "gas_priority")]
+    GasPriority,
+}
+
+impl Default for TransactionPoolOrdering {
+    fn default() -> Self {
+        TransactionPoolOrdering::RoundRobin
+    }
+}
+
+/// What the transaction pool does with a new transaction once it reached its limits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionPoolEviction {
+    /// Keep the transactions in the pool and reject the new one.
+    #[serde(rename = "reject_new")]
+    RejectNew,
+    /// Evict the transactions that were inserted first.
+    #[serde(rename = "oldest_first")]
+    OldestFirst,
+    /// Evict the transactions that pay the lowest fee, as long as it is lower than the fee of the
+    /// new one. Among equal ones the oldest goes first.
+    #[serde(rename = "lowest_priority_first")]
+    LowestPriorityFirst,
+}
+
+impl Default for TransactionPoolEviction {
+    fn default() -> Self {
+        TransactionPoolEviction::RejectNew
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransactionPoolConfig {
+    /// Order in which transactions are pulled from the pool when producing chunks.
+    pub ordering: TransactionPoolOrdering,
+    /// What to do with a new transaction when the pool is full.
+    pub eviction: TransactionPoolEviction,
+    /// Maximum number of transactions in the pool of a single shard.
+    pub max_transactions: usize,
+    /// Maximum total size in bytes of the transactions in the pool of a single shard.
+    pub max_size_bytes: u64,
+    /// Maximum number of transactions of a single signer in the pool of a single shard.
+    pub max_transactions_per_signer: usize,
+}
+
+impl Default for TransactionPoolConfig {
+    fn default() -> Self {
+        TransactionPoolConfig {
+            ordering: TransactionPoolOrdering::default(),
+            eviction: TransactionPoolEviction::default(),
+            max_transactions: 100_000,
+            max_size_bytes: 100 * 1024 * 1024,
+            max_transactions_per_signer: 1_000,
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ClientConfig {
     /// Version of the binary.
@@ -91,6 +157,8 @@ pub struct ClientConfig {
     pub archive: bool,
     /// Number of threads for ViewClientActor pool.
     pub view_client_threads: usize,
+    /// Ordering and limits of the transaction pools.
+    pub transaction_pool: TransactionPoolConfig,
 }
 
 impl ClientConfig {
@@ -144,6 +212,7 @@ impl ClientConfig {
             archive,
             log_summary_style: LogSummaryStyle::Colored,
             view_client_threads: 1,
+            transaction_pool: TransactionPoolConfig::default(),
         }
     }
 }
@@ -1,7 +1,10 @@
 mod client_config;
 mod genesis_config;
 
-pub use client_config::{ClientConfig, LogSummaryStyle, TEST_STATE_SYNC_TIMEOUT};
+pub use client_config::{
+    ClientConfig, LogSummaryStyle, TransactionPoolConfig, TransactionPoolEviction,
+    TransactionPoolOrdering, TEST_STATE_SYNC_TIMEOUT,
+};
 pub use genesis_config::{
     Genesis, GenesisConfig, GenesisRecords, ProtocolConfig, ProtocolConfigView,
 };
@@ -141,6 +141,19 @@ pub enum InvalidTxError {
     Expired,
     /// An error occurred while validating actions of a Transaction.
     ActionsValidation(ActionsValidationError),
+    /// The transaction pool of the node didn't accept the transaction
+    TransactionPoolError(TransactionPoolError),
+}
+
+/// Reasons for a node to not accept a valid transaction into its transaction pool.
+#[derive(
+    BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq, Deserialize, Serialize, RpcError,
+)]
+pub enum TransactionPoolError {
+    /// The pool reached its size limits and no transactions could be evicted in favor of this one
+    PoolIsFull,
+    /// The signer already has the maximum number of transactions in the pool
+    TooManySignerTransactions { signer_id: AccountId, limit: u64 },
 }
 
 #[derive(
@@ -436,10 +449,33 @@ impl Display for InvalidTxError {
             InvalidTxError::ActionsValidation(error) => {
                 write!(f, "Transaction actions validation error: {}", error)
             }
+            InvalidTxError::TransactionPoolError(error) => Display::fmt(&error, f),
+        }
+    }
+}
+
+impl Display for TransactionPoolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            TransactionPoolError::PoolIsFull => write!(
+                f,
+                "Transaction pool is full and the transaction can't evict any of its transactions"
+            ),
+            TransactionPoolError::TooManySignerTransactions { signer_id, limit } => write!(
+                f,
+                "Signer {:?} already has {} transactions in the transaction pool",
+                signer_id, limit
+            ),
         }
     }
 }
 
+impl From<TransactionPoolError> for InvalidTxError {
+    fn from(error: TransactionPoolError) -> Self {
+        InvalidTxError::TransactionPoolError(error)
+    }
+}
+
 impl From<InvalidAccessKeyError> for InvalidTxError {
     fn from(error: InvalidAccessKeyError) -> Self {
         InvalidTxError::InvalidAccessKeyError(error)
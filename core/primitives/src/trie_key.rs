@@ -357,6 +357,96 @@ pub mod trie_key_parsers {
         })
     }
 
+    /// Parses the receiver and the hash of the keys of the receipts and their data, which are
+    /// all the column, the account id, `ACCOUNT_DATA_SEPARATOR` and the hash.
+    fn parse_account_id_and_hash(
+        column: &[u8],
+        raw_key: &[u8],
+    ) -> Result<(AccountId, CryptoHash), std::io::Error> {
+        let account_id_and_hash = parse_account_id_prefix(column, raw_key)?;
+        let hash_position = account_id_and_hash
+            .len()
+            .checked_sub(size_of::<CryptoHash>() + 1)
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "raw key is too short to have an AccountId and a CryptoHash",
+                )
+            })?;
+        if account_id_and_hash[hash_position..hash_position + 1] != ACCOUNT_DATA_SEPARATOR[..] {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "raw key does not have ACCOUNT_DATA_SEPARATOR before the CryptoHash",
+            ));
+        }
+        let account_id =
+            std::str::from_utf8(&account_id_and_hash[..hash_position]).map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "raw key does not have a valid AccountId",
+                )
+            })?;
+        let hash =
+            CryptoHash::try_from(&account_id_and_hash[hash_position + 1..]).map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "Can't parse CryptoHash")
+            })?;
+        Ok((AccountId::from(account_id), hash))
+    }
+
+    /// Parses any raw key of the state trie, the inverse of `TrieKey::to_vec`.
+    pub fn parse_trie_key_from_raw_key(raw_key: &[u8]) -> Result<TrieKey, std::io::Error> {
+        let column = raw_key.get(0..1).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "raw key is empty")
+        })?;
+        match column {
+            col::ACCOUNT => {
+                Ok(TrieKey::Account { account_id: parse_account_id_from_account_key(raw_key)? })
+            }
+            col::CONTRACT_CODE => Ok(TrieKey::ContractCode {
+                account_id: parse_account_id_from_contract_code_key(raw_key)?,
+            }),
+            col::ACCESS_KEY => parse_trie_key_access_key_from_raw_key(raw_key),
+            col::RECEIVED_DATA => {
+                let (receiver_id, data_id) = parse_account_id_and_hash(column, raw_key)?;
+                Ok(TrieKey::ReceivedData { receiver_id, data_id })
+            }
+            col::POSTPONED_RECEIPT_ID => {
+                let (receiver_id, data_id) = parse_account_id_and_hash(column, raw_key)?;
+                Ok(TrieKey::PostponedReceiptId { receiver_id, data_id })
+            }
+            col::PENDING_DATA_COUNT => {
+                let (receiver_id, receipt_id) = parse_account_id_and_hash(column, raw_key)?;
+                Ok(TrieKey::PendingDataCount { receiver_id, receipt_id })
+            }
+            col::POSTPONED_RECEIPT => {
+                let (receiver_id, receipt_id) = parse_account_id_and_hash(column, raw_key)?;
+                Ok(TrieKey::PostponedReceipt { receiver_id, receipt_id })
+            }
+            // Delayed receipts are stored under the column of their indices, see `to_vec`.
+            col::DELAYED_RECEIPT_INDICES => match raw_key.len() - column.len() {
+                0 => Ok(TrieKey::DelayedReceiptIndices),
+                len if len == size_of::<u64>() => {
+                    let mut index = [0u8; size_of::<u64>()];
+                    index.copy_from_slice(&raw_key[column.len()..]);
+                    Ok(TrieKey::DelayedReceipt { index: u64::from_le_bytes(index) })
+                }
+                _ => Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "raw key has invalid length for TrieKey::DelayedReceipt",
+                )),
+            },
+            col::CONTRACT_DATA => {
+                let account_id = parse_account_id_from_contract_data_key(raw_key)?;
+                let key = parse_data_key_from_contract_data_key(raw_key, &account_id)?.to_vec();
+                Ok(TrieKey::ContractData { account_id, key })
+            }
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "raw key does not start with a known column marker",
+            )),
+        }
+    }
+
     pub fn get_raw_prefix_for_access_keys(account_id: &AccountId) -> Vec<u8> {
         let mut res = Vec::with_capacity(col::ACCESS_KEY.len() * 2 + account_id.len());
         res.extend(col::ACCESS_KEY);
@@ -481,4 +571,37 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_parse_trie_key_from_raw_key() {
+        let hash = CryptoHash::default();
+        for account_id in OK_ACCOUNT_IDS.iter().map(|x| AccountId::from(*x)) {
+            let keys = vec![
+                TrieKey::Account { account_id: account_id.clone() },
+                TrieKey::ContractCode { account_id: account_id.clone() },
+                TrieKey::AccessKey {
+                    account_id: account_id.clone(),
+                    public_key: PublicKey::empty(KeyType::ED25519),
+                },
+                TrieKey::ReceivedData { receiver_id: account_id.clone(), data_id: hash },
+                TrieKey::PostponedReceiptId { receiver_id: account_id.clone(), data_id: hash },
+                TrieKey::PendingDataCount { receiver_id: account_id.clone(), receipt_id: hash },
+                TrieKey::PostponedReceipt { receiver_id: account_id.clone(), receipt_id: hash },
+                TrieKey::ContractData { account_id: account_id.clone(), key: b"a,b".to_vec() },
+                TrieKey::ContractData { account_id: account_id.clone(), key: vec![] },
+                TrieKey::DelayedReceiptIndices,
+                TrieKey::DelayedReceipt { index: 300 },
+            ];
+            for key in keys {
+                assert_eq!(
+                    trie_key_parsers::parse_trie_key_from_raw_key(&key.to_vec()).unwrap(),
+                    key
+                );
+            }
+        }
+        assert!(trie_key_parsers::parse_trie_key_from_raw_key(&[]).is_err());
+        assert!(trie_key_parsers::parse_trie_key_from_raw_key(&[7, 1, 2]).is_err());
+        assert!(trie_key_parsers::parse_trie_key_from_raw_key(&[3, b'a']).is_err());
+        assert!(trie_key_parsers::parse_trie_key_from_raw_key(&[100]).is_err());
+    }
 }
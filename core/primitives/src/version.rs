@@ -16,7 +16,7 @@ pub struct Version {
 pub type DbVersion = u32;
 
 /// Current version of the database.
-pub const DB_VERSION: DbVersion = 16;
+pub const DB_VERSION: DbVersion = 17;
 
 /// Protocol version type.
 pub use near_primitives_core::types::ProtocolVersion;
@@ -87,6 +87,12 @@ pub enum ProtocolFeature {
     EVM,
     #[cfg(feature = "protocol_feature_block_header_v3")]
     BlockHeaderV3,
+    /// Encrypts the connections between peers that offer it in their handshake.
+    #[cfg(feature = "protocol_feature_peer_encryption")]
+    PeerEncryption,
+    /// Compresses the large messages sent to peers that support it.
+    #[cfg(feature = "protocol_feature_peer_compression")]
+    PeerCompression,
     /// Decreases the storage cost of 1 byte by 10X.
     #[cfg(feature = "protocol_feature_lower_storage_cost")]
     LowerStorageCost,
@@ -98,7 +104,7 @@ pub const PROTOCOL_VERSION: ProtocolVersion = 42;
 
 /// Current latest nightly version of the protocol.
 #[cfg(feature = "nightly_protocol")]
-pub const PROTOCOL_VERSION: ProtocolVersion = 104;
+pub const PROTOCOL_VERSION: ProtocolVersion = 106;
 
 lazy_static! {
     static ref STABLE_PROTOCOL_FEATURES_TO_VERSION_MAPPING: HashMap<ProtocolFeature, ProtocolVersion> =
@@ -134,6 +140,10 @@ lazy_static! {
             (ProtocolFeature::EVM, 103),
             #[cfg(feature = "protocol_feature_block_header_v3")]
             (ProtocolFeature::BlockHeaderV3, 104),
+            #[cfg(feature = "protocol_feature_peer_encryption")]
+            (ProtocolFeature::PeerEncryption, 105),
+            #[cfg(feature = "protocol_feature_peer_compression")]
+            (ProtocolFeature::PeerCompression, 106),
         ]
         .into_iter()
         .collect();
@@ -40,6 +40,7 @@ use crate::transaction::{
     DeployContractAction, ExecutionOutcome, ExecutionOutcomeWithIdAndProof, ExecutionStatus,
     FunctionCallAction, SignedTransaction, StakeAction, TransferAction,
 };
+use crate::trie_key::{trie_key_parsers, TrieKey};
 use crate::types::{
     AccountId, AccountWithPublicKey, Balance, BlockHeight, CompiledContractCache, EpochHeight,
     EpochId, FunctionArgs, Gas, Nonce, NumBlocks, ShardId, StateChangeCause, StateChangeKind,
@@ -673,6 +674,21 @@ impl From<BlockHeader> for BlockHeaderInnerLiteView {
     }
 }
 
+impl From<BlockHeaderInnerLiteView> for BlockHeaderInnerLite {
+    fn from(view: BlockHeaderInnerLiteView) -> Self {
+        BlockHeaderInnerLite {
+            height: view.height,
+            epoch_id: EpochId(view.epoch_id),
+            next_epoch_id: EpochId(view.next_epoch_id),
+            prev_state_root: view.prev_state_root,
+            outcome_root: view.outcome_root,
+            timestamp: view.timestamp_nanosec,
+            next_bp_hash: view.next_bp_hash,
+            block_merkle_root: view.block_merkle_root,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ChunkHeaderView {
     pub chunk_hash: CryptoHash,
@@ -1286,7 +1302,7 @@ pub struct NextEpochValidatorInfo {
     pub shards: Vec<ShardId>,
 }
 
-#[derive(Serialize, Debug, Clone, BorshDeserialize, BorshSerialize)]
+#[derive(Serialize, Deserialize, Debug, Clone, BorshDeserialize, BorshSerialize)]
 pub struct LightClientBlockView {
     pub prev_block_hash: CryptoHash,
     pub next_block_inner_hash: CryptoHash,
@@ -1323,7 +1339,7 @@ pub struct GasPriceView {
 ///
 /// [serializable view]: ./index.html
 /// [`StateChangesRequest`]: ../types/struct.StateChangesRequest.html
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "changes_type", rename_all = "snake_case")]
 pub enum StateChangesRequestView {
     AccountChanges {
@@ -1526,3 +1542,124 @@ impl From<StateChangeWithCause> for StateChangeWithCauseView {
 }
 
 pub type StateChangesView = Vec<StateChangeWithCauseView>;
+
+/// A key of the state trie decoded into the record it stores, see `TrieKey`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum TrieKeyView {
+    Account {
+        account_id: AccountId,
+    },
+    ContractCode {
+        account_id: AccountId,
+    },
+    AccessKey {
+        account_id: AccountId,
+        public_key: PublicKey,
+    },
+    ReceivedData {
+        receiver_id: AccountId,
+        data_id: CryptoHash,
+    },
+    PostponedReceiptId {
+        receiver_id: AccountId,
+        data_id: CryptoHash,
+    },
+    PendingDataCount {
+        receiver_id: AccountId,
+        receipt_id: CryptoHash,
+    },
+    PostponedReceipt {
+        receiver_id: AccountId,
+        receipt_id: CryptoHash,
+    },
+    DelayedReceiptIndices,
+    DelayedReceipt {
+        index: u64,
+    },
+    ContractData {
+        account_id: AccountId,
+        #[serde(rename = "key_base64", with = "base64_format")]
+        key: StoreKey,
+    },
+    /// A raw key that doesn't parse as any known key.
+    Raw {
+        #[serde(rename = "key_base64", with = "base64_format")]
+        key: StoreKey,
+    },
+}
+
+impl TrieKeyView {
+    pub fn from_raw_key(raw_key: &[u8]) -> Self {
+        match trie_key_parsers::parse_trie_key_from_raw_key(raw_key) {
+            Ok(trie_key) => trie_key.into(),
+            Err(_) => TrieKeyView::Raw { key: raw_key.to_vec().into() },
+        }
+    }
+}
+
+impl From<TrieKey> for TrieKeyView {
+    fn from(trie_key: TrieKey) -> Self {
+        match trie_key {
+            TrieKey::Account { account_id } => TrieKeyView::Account { account_id },
+            TrieKey::ContractCode { account_id } => TrieKeyView::ContractCode { account_id },
+            TrieKey::AccessKey { account_id, public_key } => {
+                TrieKeyView::AccessKey { account_id, public_key }
+            }
+            TrieKey::ReceivedData { receiver_id, data_id } => {
+                TrieKeyView::ReceivedData { receiver_id, data_id }
+            }
+            TrieKey::PostponedReceiptId { receiver_id, data_id } => {
+                TrieKeyView::PostponedReceiptId { receiver_id, data_id }
+            }
+            TrieKey::PendingDataCount { receiver_id, receipt_id } => {
+                TrieKeyView::PendingDataCount { receiver_id, receipt_id }
+            }
+            TrieKey::PostponedReceipt { receiver_id, receipt_id } => {
+                TrieKeyView::PostponedReceipt { receiver_id, receipt_id }
+            }
+            TrieKey::DelayedReceiptIndices => TrieKeyView::DelayedReceiptIndices,
+            TrieKey::DelayedReceipt { index } => TrieKeyView::DelayedReceipt { index },
+            TrieKey::ContractData { account_id, key } => {
+                TrieKeyView::ContractData { account_id, key: key.into() }
+            }
+        }
+    }
+}
+
+/// A key of the state of a shard whose value differs between two blocks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum StateDiffItemView {
+    Added {
+        key: TrieKeyView,
+        #[serde(rename = "value_base64", with = "base64_format")]
+        value: StoreValue,
+    },
+    Removed {
+        key: TrieKeyView,
+        #[serde(rename = "value_base64", with = "base64_format")]
+        value: StoreValue,
+    },
+    Modified {
+        key: TrieKeyView,
+        #[serde(rename = "old_value_base64", with = "base64_format")]
+        old_value: StoreValue,
+        #[serde(rename = "new_value_base64", with = "base64_format")]
+        new_value: StoreValue,
+    },
+}
+
+/// Differences between the states of a shard at two blocks, limited to a number of keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDiffView {
+    pub shard_id: ShardId,
+    pub from_block_hash: CryptoHash,
+    pub from_state_root: StateRoot,
+    pub to_block_hash: CryptoHash,
+    pub to_state_root: StateRoot,
+    pub changes: Vec<StateDiffItemView>,
+    /// Raw key to request the rest of the differences from, `None` if there are no more.
+    #[serde(rename = "next_key_base64", with = "option_base64_format")]
+    pub next_key: Option<Vec<u8>>,
+}
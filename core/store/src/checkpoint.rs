@@ -0,0 +1,164 @@
+//! Backups of a running node: a RocksDB checkpoint of the store together with a manifest that
+//! records which final block the checkpoint contains and the version of the database.
+//!
+//! A checkpoint directory has `manifest.json` and a `data` subdirectory, which is a complete
+//! RocksDB database that can replace the `data` directory of a stopped node.
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use near_primitives::block::Tip;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
+use near_primitives::version::DbVersion;
+
+use crate::db::VERSION_KEY;
+use crate::{DBCol, Store, FINAL_HEAD_KEY};
+
+const MANIFEST_FILENAME: &str = "manifest.json";
+const DATA_DIRNAME: &str = "data";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointManifest {
+    /// Height of the last final block when the checkpoint was created. The checkpoint has all
+    /// the data up to this block and possibly some blocks after it.
+    pub height: BlockHeight,
+    pub block_hash: CryptoHash,
+    pub db_version: DbVersion,
+}
+
+fn other_error<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string())
+}
+
+fn checkpoint_data_path(checkpoint_dir: &Path) -> PathBuf {
+    checkpoint_dir.join(DATA_DIRNAME)
+}
+
+/// Creates a checkpoint of `store` in `checkpoint_dir`, which must not exist yet.
+/// The store can be written to in the meantime, the checkpoint is a consistent snapshot.
+pub fn create_checkpoint(store: &Store, checkpoint_dir: &Path) -> io::Result<CheckpointManifest> {
+    if store.get_split_db().is_some() {
+        return Err(other_error("Checkpoints of stores with cold storage are not supported"));
+    }
+    let rocksdb = store
+        .get_rocksdb()
+        .ok_or_else(|| other_error("Checkpoints are only supported by RocksDB"))?;
+    if checkpoint_dir.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", checkpoint_dir.display()),
+        ));
+    }
+    // The final head only moves forward, so reading it before creating the checkpoint
+    // guarantees that the checkpoint has the final block.
+    let final_head: Tip = store
+        .get_ser(DBCol::ColBlockMisc, FINAL_HEAD_KEY)?
+        .ok_or_else(|| other_error("There is no final block yet"))?;
+    let db_version: DbVersion = store
+        .get(DBCol::ColDbVersion, VERSION_KEY)?
+        .map(|value| serde_json::from_slice(&value))
+        .transpose()
+        .map_err(other_error)?
+        .ok_or_else(|| other_error("Database version is not set"))?;
+
+    fs::create_dir_all(checkpoint_dir)?;
+    rocksdb
+        .create_checkpoint(checkpoint_data_path(checkpoint_dir))
+        .map_err(Into::<io::Error>::into)?;
+    let manifest = CheckpointManifest {
+        height: final_head.height,
+        block_hash: final_head.last_block_hash,
+        db_version,
+    };
+    fs::write(
+        checkpoint_dir.join(MANIFEST_FILENAME),
+        serde_json::to_vec_pretty(&manifest).map_err(other_error)?,
+    )?;
+    Ok(manifest)
+}
+
+pub fn read_checkpoint_manifest(checkpoint_dir: &Path) -> io::Result<CheckpointManifest> {
+    serde_json::from_slice(&fs::read(checkpoint_dir.join(MANIFEST_FILENAME))?).map_err(other_error)
+}
+
+/// Copies the checkpoint in `checkpoint_dir` to `store_path`, which must not exist, so that a
+/// node started on `store_path` continues from the checkpoint. The checkpoint itself is left
+/// intact and can be restored again.
+pub fn restore_checkpoint(
+    checkpoint_dir: &Path,
+    store_path: &Path,
+) -> io::Result<CheckpointManifest> {
+    let manifest = read_checkpoint_manifest(checkpoint_dir)?;
+    if store_path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists, remove it before restoring", store_path.display()),
+        ));
+    }
+    let data_path = checkpoint_data_path(checkpoint_dir);
+    fs::create_dir_all(store_path)?;
+    for entry in fs::read_dir(&data_path)? {
+        let entry = entry?;
+        fs::copy(entry.path(), store_path.join(entry.file_name()))?;
+    }
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::block::Tip;
+    use near_primitives::hash::hash;
+    use near_primitives::types::EpochId;
+
+    use crate::db::DBCol::{ColBlockMisc, ColState};
+    use crate::migrations::set_store_version;
+    use crate::{create_store, FINAL_HEAD_KEY};
+
+    use super::{create_checkpoint, read_checkpoint_manifest, restore_checkpoint};
+
+    #[test]
+    fn test_checkpoint_restore() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_checkpoint").tempdir().unwrap();
+        let store_path = tmp_dir.path().join("data");
+        let store = create_store(store_path.to_str().unwrap());
+        set_store_version(&store, 17);
+        let final_head = Tip {
+            height: 10,
+            last_block_hash: hash(&[1]),
+            prev_block_hash: hash(&[2]),
+            epoch_id: EpochId::default(),
+            next_epoch_id: EpochId::default(),
+        };
+        let mut store_update = store.store_update();
+        store_update.set_ser(ColBlockMisc, FINAL_HEAD_KEY, &final_head).unwrap();
+        store_update.update_refcount(ColState, &[1], &[1], 1);
+        store_update.commit().unwrap();
+
+        let checkpoint_dir = tmp_dir.path().join("checkpoint");
+        let manifest = create_checkpoint(&store, &checkpoint_dir).unwrap();
+        assert_eq!(manifest.height, 10);
+        assert_eq!(manifest.block_hash, hash(&[1]));
+        assert_eq!(manifest.db_version, 17);
+        assert_eq!(read_checkpoint_manifest(&checkpoint_dir).unwrap(), manifest);
+        assert!(create_checkpoint(&store, &checkpoint_dir).is_err());
+
+        // Writes after the checkpoint don't end up in it.
+        let mut store_update = store.store_update();
+        store_update.update_refcount(ColState, &[2], &[2], 1);
+        store_update.commit().unwrap();
+
+        assert!(restore_checkpoint(&checkpoint_dir, &store_path).is_err());
+        let restored_path = tmp_dir.path().join("restored");
+        assert_eq!(restore_checkpoint(&checkpoint_dir, &restored_path).unwrap(), manifest);
+        let restored = create_store(restored_path.to_str().unwrap());
+        assert_eq!(restored.get(ColState, &[1]).unwrap(), Some(vec![1]));
+        assert_eq!(restored.get(ColState, &[2]).unwrap(), None);
+        assert_eq!(
+            restored.get_ser::<Tip>(ColBlockMisc, FINAL_HEAD_KEY).unwrap(),
+            Some(final_head)
+        );
+    }
+}
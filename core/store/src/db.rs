@@ -1,19 +1,27 @@
 #[cfg(not(feature = "single_thread_rocksdb"))]
 use std::cmp;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::io;
+use std::iter::Peekable;
 use std::marker::PhantomPinned;
+use std::path::Path;
 use std::sync::RwLock;
 
 use borsh::{BorshDeserialize, BorshSerialize};
+use rocksdb::checkpoint::Checkpoint;
 #[cfg(feature = "single_thread_rocksdb")]
 use rocksdb::Env;
 use rocksdb::{
-    BlockBasedOptions, Cache, ColumnFamily, ColumnFamilyDescriptor, Direction, IteratorMode,
-    Options, ReadOptions, WriteBatch, DB,
+    BlockBasedOptions, Cache, ColumnFamily, ColumnFamilyDescriptor, DBCompactionStyle,
+    DBCompressionType, Direction, IteratorMode, Options, ReadOptions, WriteBatch, DB,
 };
-use strum::EnumIter;
+use serde::{Deserialize, Serialize};
+use sled::transaction::{ConflictableTransactionResult, TransactionError};
+use sled::{IVec, Transactional};
+use strum::{EnumIter, EnumString};
 
+use near_primitives::types::BlockHeight;
 use near_primitives::version::DbVersion;
 
 use crate::db::refcount::merge_refcounted_records;
@@ -23,11 +31,20 @@ pub(crate) mod refcount;
 pub(crate) mod v6_to_v7;
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct DBError(rocksdb::Error);
+pub enum DBError {
+    RocksDB(rocksdb::Error),
+    Sled(String),
+    /// Write to a database opened read-only.
+    ReadOnly,
+}
 
 impl std::fmt::Display for DBError {
     fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        self.0.fmt(formatter)
+        match self {
+            DBError::RocksDB(err) => err.fmt(formatter),
+            DBError::Sled(err) => err.fmt(formatter),
+            DBError::ReadOnly => write!(formatter, "Database is opened read-only"),
+        }
     }
 }
 
@@ -35,7 +52,13 @@ impl std::error::Error for DBError {}
 
 impl From<rocksdb::Error> for DBError {
     fn from(err: rocksdb::Error) -> Self {
-        DBError(err)
+        DBError::RocksDB(err)
+    }
+}
+
+impl From<sled::Error> for DBError {
+    fn from(err: sled::Error) -> Self {
+        DBError::Sled(err.to_string())
     }
 }
 
@@ -45,7 +68,9 @@ impl Into<io::Error> for DBError {
     }
 }
 
-#[derive(PartialEq, Debug, Copy, Clone, EnumIter, BorshDeserialize, BorshSerialize, Hash, Eq)]
+#[derive(
+    PartialEq, Debug, Copy, Clone, EnumIter, EnumString, BorshDeserialize, BorshSerialize, Hash, Eq,
+)]
 pub enum DBCol {
     /// Column to indicate which version of database this is.
     ColDbVersion = 0,
@@ -114,10 +139,16 @@ pub enum DBCol {
     ColReceipts = 45,
     /// Precompiled machine code of the contract
     ColCachedContractCode = 46,
+    /// Values of the trie of each shard at the block of its flat state head
+    ColFlatState = 47,
+    /// Block and state root of the flat state of each shard
+    ColFlatStateHead = 48,
+    /// Changes of the flat state made by each block, per shard
+    ColFlatStateDeltas = 49,
 }
 
 // Do not move this line from enum DBCol
-pub const NUM_COLS: usize = 47;
+pub const NUM_COLS: usize = 50;
 
 impl std::fmt::Display for DBCol {
     fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -169,6 +200,9 @@ impl std::fmt::Display for DBCol {
             Self::ColProcessedBlockHeights => "processed block heights",
             Self::ColReceipts => "receipts",
             Self::ColCachedContractCode => "cached code",
+            Self::ColFlatState => "flat state",
+            Self::ColFlatStateHead => "flat state head",
+            Self::ColFlatStateDeltas => "flat state deltas",
         };
         write!(formatter, "{}", desc)
     }
@@ -200,6 +234,8 @@ lazy_static! {
         col_gc[DBCol::ColEpochInfo as usize] = false; // https://github.com/nearprotocol/nearcore/pull/2952
         col_gc[DBCol::ColEpochStart as usize] = false; // https://github.com/nearprotocol/nearcore/pull/2952
         col_gc[DBCol::ColCachedContractCode as usize] = false;
+        col_gc[DBCol::ColFlatState as usize] = false; // flat state is updated by the chain, not GCed
+        col_gc[DBCol::ColFlatStateHead as usize] = false;
         col_gc
     };
 }
@@ -240,6 +276,8 @@ pub const LARGEST_TARGET_HEIGHT_KEY: &[u8; 21] = b"LARGEST_TARGET_HEIGHT";
 pub const VERSION_KEY: &[u8; 7] = b"VERSION";
 pub const GENESIS_JSON_HASH_KEY: &[u8; 17] = b"GENESIS_JSON_HASH";
 pub const GENESIS_STATE_ROOTS_KEY: &[u8; 19] = b"GENESIS_STATE_ROOTS";
+/// Key in `ColBlockMisc` of the cold database for the height up to which data has been moved there.
+pub const COLD_HEAD_KEY: &[u8; 9] = b"COLD_HEAD";
 
 pub struct DBTransaction {
     pub ops: Vec<DBOp>,
@@ -281,6 +319,8 @@ impl DBTransaction {
 pub struct RocksDB {
     db: DB,
     cfs: Vec<*const ColumnFamily>,
+    /// Writes are rejected.
+    read_only: bool,
     _pin: PhantomPinned,
 }
 
@@ -293,6 +333,99 @@ pub struct TestDB {
     db: RwLock<Vec<HashMap<Vec<u8>, Vec<u8>>>>,
 }
 
+/// Database on top of sled, every column is a separate sled tree.
+/// Refcounted columns don't keep zero refcount records, since sled has no compaction filters
+/// to remove them later.
+pub struct SledDB {
+    db: sled::Db,
+    trees: Vec<sled::Tree>,
+}
+
+/// Database of an archival node split into two RocksDB instances: recent data lives in the hot
+/// one and data older than the GC horizon is moved to the cold one, which may be on slower disks.
+/// Reads fall through from hot to cold, and refcounted records of both are merged.
+pub struct SplitDB {
+    hot: RocksDB,
+    cold: RocksDB,
+}
+
+/// Size statistics of a RocksDB column, as estimated by RocksDB.
+#[derive(Debug, Clone)]
+pub struct ColumnStats {
+    pub col: DBCol,
+    pub estimated_num_keys: u64,
+    pub estimated_live_data_size: u64,
+    pub sst_files_size: u64,
+    pub mem_tables_size: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    None,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompactionStyle {
+    Level,
+    Universal,
+    Fifo,
+}
+
+/// RocksDB options of a column, overriding the defaults set in `rocksdb_column_options`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct RocksDBColumnOptions {
+    /// Size of the block cache of the column in bytes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_cache_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_size: Option<usize>,
+    /// Bits per key of the bloom filter, zero disables the filter.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bloom_filter_bits_per_key: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<Compression>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compaction_style: Option<CompactionStyle>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub write_buffer_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_file_size_base: Option<u64>,
+}
+
+/// Embedded key-value store used for the node database.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DBBackend {
+    RocksDB,
+    Sled,
+}
+
+impl Default for DBBackend {
+    fn default() -> Self {
+        DBBackend::RocksDB
+    }
+}
+
+impl DBBackend {
+    /// Returns the backend of the existing database at `path`, judging by the files it keeps there.
+    pub fn detect<P: AsRef<Path>>(path: P) -> Option<DBBackend> {
+        let path = path.as_ref();
+        if path.join("CURRENT").exists() {
+            Some(DBBackend::RocksDB)
+        } else if path.join("conf").exists() {
+            Some(DBBackend::Sled)
+        } else {
+            None
+        }
+    }
+}
+
 pub trait Database: Sync + Send {
     fn transaction(&self) -> DBTransaction {
         DBTransaction { ops: Vec::new() }
@@ -312,13 +445,14 @@ pub trait Database: Sync + Send {
     fn as_rocksdb(&self) -> Option<&RocksDB> {
         None
     }
+    fn as_split_db(&self) -> Option<&SplitDB> {
+        None
+    }
 }
 
 impl Database for RocksDB {
     fn get(&self, col: DBCol, key: &[u8]) -> Result<Option<Vec<u8>>, DBError> {
-        let read_options = rocksdb_read_options();
-        let result = self.db.get_cf_opt(unsafe { &*self.cfs[col as usize] }, key, &read_options)?;
-        Ok(RocksDB::get_with_rc_logic(col, result))
+        Ok(RocksDB::get_with_rc_logic(col, self.get_without_rc_logic(col, key)?))
     }
 
     fn iter_without_rc_logic<'a>(
@@ -347,27 +481,13 @@ impl Database for RocksDB {
         col: DBCol,
         key_prefix: &'a [u8],
     ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
-        // NOTE: There is no Clone implementation for ReadOptions, so we cannot really reuse
-        // `self.read_options` here.
-        let mut read_options = rocksdb_read_options();
-        read_options.set_prefix_same_as_start(true);
-        unsafe {
-            let cf_handle = &*self.cfs[col as usize];
-            // This implementation is copied from RocksDB implementation of `prefix_iterator_cf` since
-            // there is no `prefix_iterator_cf_opt` method.
-            let iterator = self
-                .db
-                .iterator_cf_opt(
-                    cf_handle,
-                    read_options,
-                    IteratorMode::From(key_prefix, Direction::Forward),
-                )
-                .take_while(move |(key, _value)| key.starts_with(key_prefix));
-            RocksDB::iter_with_rc_logic(col, iterator)
-        }
+        RocksDB::iter_with_rc_logic(col, self.iter_prefix_without_rc_logic(col, key_prefix))
     }
 
     fn write(&self, transaction: DBTransaction) -> Result<(), DBError> {
+        if self.read_only {
+            return Err(DBError::ReadOnly);
+        }
         let mut batch = WriteBatch::default();
         for op in transaction.ops {
             match op {
@@ -391,6 +511,103 @@ impl Database for RocksDB {
     }
 }
 
+impl Database for SplitDB {
+    fn get(&self, col: DBCol, key: &[u8]) -> Result<Option<Vec<u8>>, DBError> {
+        if col.is_rc() {
+            let hot = self.hot.get_without_rc_logic(col, key)?;
+            let cold = self.cold.get_without_rc_logic(col, key)?;
+            Ok(RocksDB::get_with_rc_logic(col, SplitDB::merge_records(col, cold, hot)))
+        } else {
+            match self.hot.get(col, key)? {
+                Some(value) => Ok(Some(value)),
+                None => self.cold.get(col, key),
+            }
+        }
+    }
+
+    fn iter<'a>(&'a self, col: DBCol) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        RocksDB::iter_with_rc_logic(col, self.iter_without_rc_logic(col))
+    }
+
+    fn iter_without_rc_logic<'a>(
+        &'a self,
+        col: DBCol,
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        Box::new(SplitIterator {
+            col,
+            hot: self.hot.iter_without_rc_logic(col).peekable(),
+            cold: self.cold.iter_without_rc_logic(col).peekable(),
+        })
+    }
+
+    fn iter_prefix<'a>(
+        &'a self,
+        col: DBCol,
+        key_prefix: &'a [u8],
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        let iterator = SplitIterator {
+            col,
+            hot: self.hot.iter_prefix_without_rc_logic(col, key_prefix).peekable(),
+            cold: self.cold.iter_prefix_without_rc_logic(col, key_prefix).peekable(),
+        };
+        RocksDB::iter_with_rc_logic(col, iterator)
+    }
+
+    fn write(&self, transaction: DBTransaction) -> Result<(), DBError> {
+        // Deletions have to reach the cold database as well, otherwise reads would fall through
+        // to the deleted value there. They are written first, so that a crash in between only
+        // leaves the value in the hot database, where it was going to be deleted from anyway.
+        let mut cold_transaction = self.cold.transaction();
+        for op in transaction.ops.iter() {
+            if let DBOp::Delete { col, key } = op {
+                cold_transaction.delete(*col, key);
+            }
+        }
+        if !cold_transaction.ops.is_empty() {
+            self.cold.write(cold_transaction)?;
+        }
+        self.hot.write(transaction)
+    }
+
+    fn as_split_db(&self) -> Option<&SplitDB> {
+        Some(self)
+    }
+}
+
+/// Merges sorted iterators over the same column of the hot and the cold databases.
+struct SplitIterator<'a> {
+    col: DBCol,
+    hot: Peekable<Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>>,
+    cold: Peekable<Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>>,
+}
+
+impl<'a> Iterator for SplitIterator<'a> {
+    type Item = (Box<[u8]>, Box<[u8]>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ordering = match (self.hot.peek(), self.cold.peek()) {
+            (None, None) => return None,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some((hot_key, _)), Some((cold_key, _))) => hot_key.cmp(cold_key),
+        };
+        match ordering {
+            Ordering::Less => self.hot.next(),
+            Ordering::Greater => self.cold.next(),
+            Ordering::Equal => {
+                let (key, hot_value) = self.hot.next()?;
+                let (_, cold_value) = self.cold.next()?;
+                let value = SplitDB::merge_records(
+                    self.col,
+                    Some(cold_value.into_vec()),
+                    Some(hot_value.into_vec()),
+                );
+                value.map(|value| (key, value.into_boxed_slice()))
+            }
+        }
+    }
+}
+
 impl Database for TestDB {
     fn get(&self, col: DBCol, key: &[u8]) -> Result<Option<Vec<u8>>, DBError> {
         let result = self.db.read().unwrap()[col as usize].get(key).cloned();
@@ -420,7 +637,8 @@ impl Database for TestDB {
     ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
         RocksDB::iter_with_rc_logic(
             col,
-            self.iter(col).filter(move |(key, _value)| key.starts_with(key_prefix)),
+            self.iter_without_rc_logic(col)
+                .filter(move |(key, _value)| key.starts_with(key_prefix)),
         )
     }
 
@@ -445,6 +663,71 @@ impl Database for TestDB {
     }
 }
 
+impl Database for SledDB {
+    fn get(&self, col: DBCol, key: &[u8]) -> Result<Option<Vec<u8>>, DBError> {
+        let result = self.trees[col as usize].get(key)?.map(|value| value.to_vec());
+        Ok(RocksDB::get_with_rc_logic(col, result))
+    }
+
+    fn iter<'a>(&'a self, col: DBCol) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        RocksDB::iter_with_rc_logic(col, SledDB::iter_tree(self.trees[col as usize].iter()))
+    }
+
+    fn iter_without_rc_logic<'a>(
+        &'a self,
+        col: DBCol,
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        Box::new(SledDB::iter_tree(self.trees[col as usize].iter()))
+    }
+
+    fn iter_prefix<'a>(
+        &'a self,
+        col: DBCol,
+        key_prefix: &'a [u8],
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        RocksDB::iter_with_rc_logic(
+            col,
+            SledDB::iter_tree(self.trees[col as usize].scan_prefix(key_prefix)),
+        )
+    }
+
+    fn write(&self, transaction: DBTransaction) -> Result<(), DBError> {
+        // sled may run the closure again on conflicts, so it must not consume the ops.
+        let result =
+            self.trees.as_slice().transaction(|trees| -> ConflictableTransactionResult<(), ()> {
+                for op in transaction.ops.iter() {
+                    match op {
+                        DBOp::Insert { col, key, value } => {
+                            trees[*col as usize].insert(key.as_slice(), value.as_slice())?;
+                        }
+                        DBOp::UpdateRefcount { col, key, value } => {
+                            assert!(col.is_rc());
+                            let tree = &trees[*col as usize];
+                            let mut val = tree
+                                .get(key.as_slice())?
+                                .map(|val| val.to_vec())
+                                .unwrap_or_default();
+                            merge_refcounted_records(&mut val, value);
+                            if val.len() != 0 {
+                                tree.insert(key.as_slice(), val)?;
+                            } else {
+                                tree.remove(key.as_slice())?;
+                            }
+                        }
+                        DBOp::Delete { col, key } => {
+                            trees[*col as usize].remove(key.as_slice())?;
+                        }
+                    }
+                }
+                Ok(())
+            });
+        result.map_err(|err| match err {
+            TransactionError::Abort(()) => unreachable!("Transaction is never aborted"),
+            TransactionError::Storage(err) => err.into(),
+        })
+    }
+}
+
 fn rocksdb_read_options() -> ReadOptions {
     let mut read_options = ReadOptions::default();
     read_options.set_verify_checksums(false);
@@ -482,27 +765,48 @@ fn rocksdb_options() -> Options {
     return opts;
 }
 
-fn rocksdb_block_based_options() -> BlockBasedOptions {
+fn rocksdb_block_based_options(col_options: &RocksDBColumnOptions) -> BlockBasedOptions {
     let mut block_opts = BlockBasedOptions::default();
-    block_opts.set_block_size(1024 * 16);
+    block_opts.set_block_size(col_options.block_size.unwrap_or(1024 * 16));
     // We create block_cache for each of 47 columns, so the total cache size is 32 * 47 = 1504mb
-    let cache_size = 1024 * 1024 * 32;
+    let cache_size = col_options.block_cache_size.unwrap_or(1024 * 1024 * 32);
     block_opts.set_block_cache(&Cache::new_lru_cache(cache_size).unwrap());
     block_opts.set_pin_l0_filter_and_index_blocks_in_cache(true);
     block_opts.set_cache_index_and_filter_blocks(true);
-    block_opts.set_bloom_filter(10, true);
+    match col_options.bloom_filter_bits_per_key.unwrap_or(10) {
+        0 => {}
+        bits_per_key => block_opts.set_bloom_filter(bits_per_key, true),
+    }
     block_opts
 }
 
-fn rocksdb_column_options(col: DBCol) -> Options {
+fn rocksdb_column_options(col: DBCol, col_options: &RocksDBColumnOptions) -> Options {
     let mut opts = Options::default();
     opts.set_level_compaction_dynamic_level_bytes(true);
-    opts.set_block_based_table_factory(&rocksdb_block_based_options());
+    opts.set_block_based_table_factory(&rocksdb_block_based_options(col_options));
     opts.optimize_level_style_compaction(1024 * 1024 * 128);
-    opts.set_target_file_size_base(1024 * 1024 * 64);
+    opts.set_target_file_size_base(col_options.target_file_size_base.unwrap_or(1024 * 1024 * 64));
     opts.set_compression_per_level(&[]);
+    if let Some(compression) = col_options.compression {
+        opts.set_compression_type(match compression {
+            Compression::None => DBCompressionType::None,
+            Compression::Snappy => DBCompressionType::Snappy,
+            Compression::Lz4 => DBCompressionType::Lz4,
+            Compression::Zstd => DBCompressionType::Zstd,
+        });
+    }
+    if let Some(compaction_style) = col_options.compaction_style {
+        opts.set_compaction_style(match compaction_style {
+            CompactionStyle::Level => DBCompactionStyle::Level,
+            CompactionStyle::Universal => DBCompactionStyle::Universal,
+            CompactionStyle::Fifo => DBCompactionStyle::Fifo,
+        });
+    }
+    if let Some(write_buffer_size) = col_options.write_buffer_size {
+        opts.set_write_buffer_size(write_buffer_size);
+    }
     if col.is_rc() {
-        opts.set_merge_operator("refcount merge", RocksDB::refcount_merge, None);
+        opts.set_merge_operator("refcount merge", RocksDB::refcount_merge, RocksDB::refcount_merge);
         opts.set_compaction_filter("empty value filter", RocksDB::empty_value_compaction_filter);
     }
     opts
@@ -527,17 +831,57 @@ impl RocksDB {
         let db = DB::open_cf_for_read_only(&options, path, cf_names.iter(), false)?;
         let cfs =
             cf_names.iter().map(|n| db.cf_handle(n).unwrap() as *const ColumnFamily).collect();
-        Ok(Self { db, cfs, _pin: PhantomPinned })
+        Ok(Self { db, cfs, read_only: true, _pin: PhantomPinned })
     }
 
     pub fn new<P: AsRef<std::path::Path>>(path: P) -> Result<Self, DBError> {
+        RocksDB::new_with_column_options(path, &HashMap::new())
+    }
+
+    /// Opens the database with the options of the given columns overridden.
+    pub fn new_with_column_options<P: AsRef<std::path::Path>>(
+        path: P,
+        column_options: &HashMap<DBCol, RocksDBColumnOptions>,
+    ) -> Result<Self, DBError> {
+        RocksDB::open(path, column_options, false)
+    }
+
+    /// Opens an existing database in RocksDB read-only mode: writes are rejected and it fails if
+    /// a column is missing. The columns are opened with their options, so that refcounted
+    /// columns are read with their merge operator. The database isn't locked, so it can be
+    /// opened while a node uses it, but it only sees the data written before it was opened.
+    pub fn new_read_only_with_column_options<P: AsRef<std::path::Path>>(
+        path: P,
+        column_options: &HashMap<DBCol, RocksDBColumnOptions>,
+    ) -> Result<Self, DBError> {
+        RocksDB::open(path, column_options, true)
+    }
+
+    fn open<P: AsRef<std::path::Path>>(
+        path: P,
+        column_options: &HashMap<DBCol, RocksDBColumnOptions>,
+        read_only: bool,
+    ) -> Result<Self, DBError> {
         use strum::IntoEnumIterator;
-        let options = rocksdb_options();
+        let mut options = rocksdb_options();
+        if read_only {
+            options.create_if_missing(false);
+            options.create_missing_column_families(false);
+        }
+        let default_column_options = RocksDBColumnOptions::default();
         let cf_names: Vec<_> = DBCol::iter().map(|col| format!("col{}", col as usize)).collect();
         let cf_descriptors = DBCol::iter().map(|col| {
-            ColumnFamilyDescriptor::new(format!("col{}", col as usize), rocksdb_column_options(col))
+            let cf_options = rocksdb_column_options(
+                col,
+                column_options.get(&col).unwrap_or(&default_column_options),
+            );
+            ColumnFamilyDescriptor::new(format!("col{}", col as usize), cf_options)
         });
-        let db = DB::open_cf_descriptors(&options, path, cf_descriptors)?;
+        let db = if read_only {
+            DB::open_cf_descriptors_read_only(&options, path, cf_descriptors, false)?
+        } else {
+            DB::open_cf_descriptors(&options, path, cf_descriptors)?
+        };
         #[cfg(feature = "single_thread_rocksdb")]
         {
             // These have to be set after open db
@@ -550,7 +894,67 @@ impl RocksDB {
         }
         let cfs =
             cf_names.iter().map(|n| db.cf_handle(n).unwrap() as *const ColumnFamily).collect();
-        Ok(Self { db, cfs, _pin: PhantomPinned })
+        Ok(Self { db, cfs, read_only, _pin: PhantomPinned })
+    }
+}
+
+impl RocksDB {
+    fn get_without_rc_logic(&self, col: DBCol, key: &[u8]) -> Result<Option<Vec<u8>>, DBError> {
+        let read_options = rocksdb_read_options();
+        Ok(self.db.get_cf_opt(unsafe { &*self.cfs[col as usize] }, key, &read_options)?)
+    }
+
+    fn iter_prefix_without_rc_logic<'a>(
+        &'a self,
+        col: DBCol,
+        key_prefix: &'a [u8],
+    ) -> Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a> {
+        // NOTE: There is no Clone implementation for ReadOptions, so we cannot really reuse
+        // `self.read_options` here.
+        let mut read_options = rocksdb_read_options();
+        read_options.set_prefix_same_as_start(true);
+        unsafe {
+            let cf_handle = &*self.cfs[col as usize];
+            // This implementation is copied from RocksDB implementation of `prefix_iterator_cf` since
+            // there is no `prefix_iterator_cf_opt` method.
+            let iterator = self
+                .db
+                .iterator_cf_opt(
+                    cf_handle,
+                    read_options,
+                    IteratorMode::From(key_prefix, Direction::Forward),
+                )
+                .take_while(move |(key, _value)| key.starts_with(key_prefix));
+            Box::new(iterator)
+        }
+    }
+
+    /// Compacts the whole column, which drops deleted and overwritten records and
+    /// refcounted records with zero refcount.
+    pub fn compact(&self, col: DBCol) {
+        self.db.compact_range_cf::<&[u8], &[u8]>(unsafe { &*self.cfs[col as usize] }, None, None);
+    }
+
+    /// Creates a consistent copy of the database in `path`, which must not exist.
+    /// SST files are hard-linked when `path` is on the same filesystem, so this is cheap and
+    /// can be done while the database is being written to.
+    pub fn create_checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<(), DBError> {
+        Checkpoint::new(&self.db)?.create_checkpoint(path)?;
+        Ok(())
+    }
+
+    pub fn column_stats(&self, col: DBCol) -> Result<ColumnStats, DBError> {
+        let cf_handle = unsafe { &*self.cfs[col as usize] };
+        let property = |name: &str| -> Result<u64, DBError> {
+            Ok(self.db.property_int_value_cf(cf_handle, name)?.unwrap_or_default())
+        };
+        Ok(ColumnStats {
+            col,
+            estimated_num_keys: property("rocksdb.estimate-num-keys")?,
+            estimated_live_data_size: property("rocksdb.estimate-live-data-size")?,
+            sst_files_size: property("rocksdb.total-sst-files-size")?,
+            mem_tables_size: property("rocksdb.size-all-mem-tables")?,
+        })
     }
 }
 
@@ -564,6 +968,144 @@ impl Drop for RocksDB {
     }
 }
 
+impl SplitDB {
+    pub fn new<P: AsRef<Path>>(
+        hot_path: P,
+        cold_path: P,
+        column_options: &HashMap<DBCol, RocksDBColumnOptions>,
+    ) -> Result<Self, DBError> {
+        let hot = RocksDB::new_with_column_options(hot_path, column_options)?;
+        let cold = RocksDB::new_with_column_options(cold_path, column_options)?;
+        Ok(Self { hot, cold })
+    }
+
+    /// Opens both databases with `RocksDB::new_read_only_with_column_options`.
+    pub fn new_read_only<P: AsRef<Path>>(
+        hot_path: P,
+        cold_path: P,
+        column_options: &HashMap<DBCol, RocksDBColumnOptions>,
+    ) -> Result<Self, DBError> {
+        let hot = RocksDB::new_read_only_with_column_options(hot_path, column_options)?;
+        let cold = RocksDB::new_read_only_with_column_options(cold_path, column_options)?;
+        Ok(Self { hot, cold })
+    }
+
+    pub fn hot(&self) -> &RocksDB {
+        &self.hot
+    }
+
+    pub fn cold(&self) -> &RocksDB {
+        &self.cold
+    }
+
+    /// Returns the height up to which data has been moved to the cold database.
+    pub fn cold_head(&self) -> Result<Option<BlockHeight>, DBError> {
+        Ok(self
+            .cold
+            .get(DBCol::ColBlockMisc, COLD_HEAD_KEY)?
+            .map(|value| BlockHeight::try_from_slice(&value).expect("Failed to parse cold head")))
+    }
+
+    /// Moves the records with the given keys from the hot database to the cold one and sets
+    /// the cold head to `height`. Records are copied to the cold database together with the
+    /// cold head, so if moving is interrupted before they are deleted from the hot database,
+    /// calling it again with the same keys only finishes the deletion.
+    ///
+    /// Must not run concurrently with writes to the same keys.
+    pub fn move_to_cold(
+        &self,
+        keys: Vec<(DBCol, Vec<u8>)>,
+        height: BlockHeight,
+    ) -> Result<(), DBError> {
+        // Refcounted records are merged into the cold database, so each of them must be moved once.
+        let keys: HashSet<_> = keys.into_iter().collect();
+        if self.cold_head()?.map_or(true, |cold_head| cold_head < height) {
+            let mut cold_transaction = self.cold.transaction();
+            for (col, key) in keys.iter() {
+                if let Some(value) = self.hot.get_without_rc_logic(*col, key)? {
+                    if col.is_rc() {
+                        cold_transaction.update_refcount(*col, key, value);
+                    } else {
+                        cold_transaction.put(*col, key, value);
+                    }
+                }
+            }
+            cold_transaction.put(
+                DBCol::ColBlockMisc,
+                COLD_HEAD_KEY,
+                height.try_to_vec().expect("Borsh cannot fail"),
+            );
+            self.cold.write(cold_transaction)?;
+        }
+        let mut hot_transaction = self.hot.transaction();
+        for (col, key) in keys {
+            hot_transaction.delete(col, key);
+        }
+        self.hot.write(hot_transaction)
+    }
+
+    /// Merges records of the same key in the cold and the hot databases, the hot one takes
+    /// precedence unless the column is refcounted.
+    fn merge_records(col: DBCol, cold: Option<Vec<u8>>, hot: Option<Vec<u8>>) -> Option<Vec<u8>> {
+        match (cold, hot) {
+            (Some(mut cold), Some(hot)) if col.is_rc() => {
+                merge_refcounted_records(&mut cold, &hot);
+                Some(cold)
+            }
+            (cold, hot) => hot.or(cold),
+        }
+    }
+}
+
+impl SledDB {
+    /// Returns version of the database state on disk.
+    pub fn get_version<P: AsRef<Path>>(path: P) -> Result<DbVersion, DBError> {
+        let db = SledDB::new(path)?;
+        db.get(DBCol::ColDbVersion, VERSION_KEY).map(|result| {
+            serde_json::from_slice(
+                &result
+                    .expect("Failed to find version in first column. Database must be corrupted."),
+            )
+            .expect("Failed to parse version. Database must be corrupted.")
+        })
+    }
+
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, DBError> {
+        use strum::IntoEnumIterator;
+        let db = sled::open(path)?;
+        let trees = DBCol::iter()
+            .map(|col| db.open_tree(format!("col{}", col as usize)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { db, trees })
+    }
+
+    fn iter_tree<I>(iterator: I) -> impl Iterator<Item = (Box<[u8]>, Box<[u8]>)>
+    where
+        I: Iterator<Item = sled::Result<(IVec, IVec)>>,
+    {
+        // Like the RocksDB iterators, the iteration stops at the first error.
+        iterator
+            .scan((), |_, item| match item {
+                Ok((key, value)) => {
+                    Some((key.to_vec().into_boxed_slice(), value.to_vec().into_boxed_slice()))
+                }
+                Err(err) => {
+                    tracing::error!(target: "store", "Failed to read from the database: {}", err);
+                    None
+                }
+            })
+            .fuse()
+    }
+}
+
+impl Drop for SledDB {
+    fn drop(&mut self) {
+        if let Err(err) = self.db.flush() {
+            tracing::error!(target: "store", "Failed to flush the database: {}", err);
+        }
+    }
+}
+
 impl TestDB {
     pub fn new() -> Self {
         let db: Vec<_> = (0..NUM_COLS).map(|_| HashMap::new()).collect();
@@ -573,31 +1115,15 @@ impl TestDB {
 
 #[cfg(test)]
 mod tests {
-    use crate::db::DBCol::ColState;
-    use crate::db::{rocksdb_read_options, DBError, Database, RocksDB};
-    use crate::{create_store, DBCol};
+    use std::collections::HashMap;
 
-    impl RocksDB {
-        #[cfg(not(feature = "single_thread_rocksdb"))]
-        fn compact(&self, col: DBCol) {
-            self.db.compact_range_cf::<&[u8], &[u8]>(
-                unsafe { &*self.cfs[col as usize] },
-                None,
-                None,
-            );
-        }
-
-        fn get_no_empty_filtering(
-            &self,
-            col: DBCol,
-            key: &[u8],
-        ) -> Result<Option<Vec<u8>>, DBError> {
-            let read_options = rocksdb_read_options();
-            let result =
-                self.db.get_cf_opt(unsafe { &*self.cfs[col as usize] }, key, &read_options)?;
-            Ok(result)
-        }
-    }
+    use crate::db::DBCol::{ColBlockMisc, ColState};
+    use crate::db::{Compression, DBBackend, Database, RocksDB, RocksDBColumnOptions};
+    use crate::test_utils::create_test_store;
+    use crate::{
+        create_split_store_with_config, create_store, create_store_with_config, DBCol, Store,
+        StoreConfig,
+    };
 
     #[test]
     fn rocksdb_merge_sanity() {
@@ -618,7 +1144,7 @@ mod tests {
         }
         assert_eq!(store.get(ColState, &[1]).unwrap(), Some(vec![1]));
         assert_eq!(
-            rocksdb.get_no_empty_filtering(ColState, &[1]).unwrap(),
+            rocksdb.get_without_rc_logic(ColState, &[1]).unwrap(),
             Some(vec![1, 2, 0, 0, 0, 0, 0, 0, 0])
         );
         {
@@ -628,7 +1154,7 @@ mod tests {
         }
         assert_eq!(store.get(ColState, &[1]).unwrap(), Some(vec![1]));
         assert_eq!(
-            rocksdb.get_no_empty_filtering(ColState, &[1]).unwrap(),
+            rocksdb.get_without_rc_logic(ColState, &[1]).unwrap(),
             Some(vec![1, 1, 0, 0, 0, 0, 0, 0, 0])
         );
         {
@@ -639,7 +1165,7 @@ mod tests {
         // Refcount goes to 0 -> get() returns None
         assert_eq!(store.get(ColState, &[1]).unwrap(), None);
         // Internally there is an empty value
-        assert_eq!(rocksdb.get_no_empty_filtering(ColState, &[1]).unwrap(), Some(vec![]));
+        assert_eq!(rocksdb.get_without_rc_logic(ColState, &[1]).unwrap(), Some(vec![]));
 
         #[cfg(not(feature = "single_thread_rocksdb"))]
         {
@@ -648,8 +1174,269 @@ mod tests {
             rocksdb.compact(ColState);
 
             // After compaction the empty value disappears
-            assert_eq!(rocksdb.get_no_empty_filtering(ColState, &[1]).unwrap(), None);
+            assert_eq!(rocksdb.get_without_rc_logic(ColState, &[1]).unwrap(), None);
             assert_eq!(store.get(ColState, &[1]).unwrap(), None);
         }
     }
+
+    /// Runs the same checks against every `Database` implementation, so that they all keep
+    /// the semantics `Store` and `StoreUpdate` rely on.
+    fn check_all_backends<F: Fn(&Store)>(check: F) {
+        check(&create_test_store());
+        for backend in vec![DBBackend::RocksDB, DBBackend::Sled] {
+            let tmp_dir = tempfile::Builder::new().prefix("_test_backend").tempdir().unwrap();
+            let store = create_store_with_config(
+                tmp_dir.path().to_str().unwrap(),
+                &StoreConfig { backend, ..Default::default() },
+            );
+            check(&store);
+        }
+        let hot_dir = tempfile::Builder::new().prefix("_test_backend_hot").tempdir().unwrap();
+        let cold_dir = tempfile::Builder::new().prefix("_test_backend_cold").tempdir().unwrap();
+        check(&create_split_store_with_config(
+            hot_dir.path().to_str().unwrap(),
+            cold_dir.path().to_str().unwrap(),
+            &StoreConfig::default(),
+        ));
+    }
+
+    fn update_refcount(store: &Store, updates: &[(&[u8], &[u8], i64)]) {
+        let mut store_update = store.store_update();
+        for (key, value, rc_delta) in updates {
+            store_update.update_refcount(ColState, key, value, *rc_delta);
+        }
+        store_update.commit().unwrap();
+    }
+
+    fn collect(
+        iterator: Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + '_>,
+    ) -> HashMap<Vec<u8>, Vec<u8>> {
+        iterator.map(|(key, value)| (key.to_vec(), value.to_vec())).collect()
+    }
+
+    #[test]
+    fn test_backend_set_and_delete() {
+        check_all_backends(|store| {
+            assert_eq!(store.get(ColBlockMisc, &[1]).unwrap(), None);
+            let mut store_update = store.store_update();
+            store_update.set(ColBlockMisc, &[1], &[10]);
+            store_update.set(ColBlockMisc, &[2], &[20]);
+            store_update.commit().unwrap();
+            assert_eq!(store.get(ColBlockMisc, &[1]).unwrap(), Some(vec![10]));
+            assert!(store.exists(ColBlockMisc, &[2]).unwrap());
+            // Columns don't share keys.
+            assert_eq!(store.get(DBCol::ColBlock, &[1]).unwrap(), None);
+
+            let mut store_update = store.store_update();
+            store_update.set(ColBlockMisc, &[1], &[11]);
+            store_update.delete(ColBlockMisc, &[2]);
+            store_update.commit().unwrap();
+            assert_eq!(store.get(ColBlockMisc, &[1]).unwrap(), Some(vec![11]));
+            assert_eq!(store.get(ColBlockMisc, &[2]).unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn test_backend_refcount() {
+        check_all_backends(|store| {
+            update_refcount(store, &[(&[1], &[1], 1)]);
+            update_refcount(store, &[(&[1], &[1], 1)]);
+            assert_eq!(store.get(ColState, &[1]).unwrap(), Some(vec![1]));
+            update_refcount(store, &[(&[1], &[1], -1)]);
+            assert_eq!(store.get(ColState, &[1]).unwrap(), Some(vec![1]));
+            update_refcount(store, &[(&[1], &[1], -1)]);
+            assert_eq!(store.get(ColState, &[1]).unwrap(), None);
+            assert!(!store.exists(ColState, &[1]).unwrap());
+
+            // Decrement may come before the increment it compensates.
+            update_refcount(store, &[(&[2], &[2], -1)]);
+            assert_eq!(store.get(ColState, &[2]).unwrap(), None);
+            update_refcount(store, &[(&[2], &[2], 1)]);
+            assert_eq!(store.get(ColState, &[2]).unwrap(), None);
+            update_refcount(store, &[(&[2], &[2], 1)]);
+            assert_eq!(store.get(ColState, &[2]).unwrap(), Some(vec![2]));
+        });
+    }
+
+    #[test]
+    fn test_backend_refcount_in_one_transaction() {
+        check_all_backends(|store| {
+            update_refcount(store, &[(&[1], &[1], 1), (&[1], &[1], 2), (&[2], &[2], 1)]);
+            assert_eq!(store.get(ColState, &[1]).unwrap(), Some(vec![1]));
+            update_refcount(store, &[(&[1], &[1], -2), (&[2], &[2], -1), (&[1], &[1], -1)]);
+            assert_eq!(store.get(ColState, &[1]).unwrap(), None);
+            assert_eq!(store.get(ColState, &[2]).unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn test_backend_iter() {
+        check_all_backends(|store| {
+            let mut store_update = store.store_update();
+            store_update.set(ColBlockMisc, &[1, 1], &[11]);
+            store_update.set(ColBlockMisc, &[1, 2], &[12]);
+            store_update.set(ColBlockMisc, &[2, 1], &[21]);
+            store_update.commit().unwrap();
+            update_refcount(
+                store,
+                &[(&[1, 1], &[11], 1), (&[1, 2], &[12], 1), (&[2, 1], &[21], 1)],
+            );
+            update_refcount(store, &[(&[1, 2], &[12], -1)]);
+
+            let expected: HashMap<Vec<u8>, Vec<u8>> =
+                vec![(vec![1, 1], vec![11]), (vec![1, 2], vec![12]), (vec![2, 1], vec![21])]
+                    .into_iter()
+                    .collect();
+            assert_eq!(collect(store.iter(ColBlockMisc)), expected);
+            let expected_prefix: HashMap<Vec<u8>, Vec<u8>> =
+                vec![(vec![1, 1], vec![11]), (vec![1, 2], vec![12])].into_iter().collect();
+            assert_eq!(collect(store.iter_prefix(ColBlockMisc, &[1])), expected_prefix);
+
+            // Records with zero refcount are skipped and refcounts are stripped.
+            let expected_rc: HashMap<Vec<u8>, Vec<u8>> =
+                vec![(vec![1, 1], vec![11]), (vec![2, 1], vec![21])].into_iter().collect();
+            assert_eq!(collect(store.iter(ColState)), expected_rc);
+            let expected_rc_prefix: HashMap<Vec<u8>, Vec<u8>> =
+                vec![(vec![1, 1], vec![11])].into_iter().collect();
+            assert_eq!(collect(store.iter_prefix(ColState, &[1])), expected_rc_prefix);
+        });
+    }
+
+    #[test]
+    fn test_backend_reopen() {
+        for backend in vec![DBBackend::RocksDB, DBBackend::Sled] {
+            let tmp_dir = tempfile::Builder::new().prefix("_test_backend").tempdir().unwrap();
+            let path = tmp_dir.path().to_str().unwrap();
+            let config = StoreConfig { backend, ..Default::default() };
+            {
+                let store = create_store_with_config(path, &config);
+                let mut store_update = store.store_update();
+                store_update.set(ColBlockMisc, &[1], &[1]);
+                store_update.commit().unwrap();
+                update_refcount(&store, &[(&[2], &[2], 1)]);
+            }
+            assert_eq!(DBBackend::detect(path), Some(backend));
+            let store = create_store_with_config(path, &config);
+            assert_eq!(store.get(ColBlockMisc, &[1]).unwrap(), Some(vec![1]));
+            assert_eq!(store.get(ColState, &[2]).unwrap(), Some(vec![2]));
+        }
+    }
+
+    #[test]
+    fn test_rocksdb_read_only_while_open() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_read_only").tempdir().unwrap();
+        let path = tmp_dir.path().to_str().unwrap();
+        let store = create_store(path);
+        let mut store_update = store.store_update();
+        store_update.set(ColBlockMisc, &[1], &[1]);
+        store_update.commit().unwrap();
+        update_refcount(&store, &[(&[2], &[2], 1)]);
+
+        // The writer keeps the database open, so the read-only handle must not take its lock.
+        let read_only = RocksDB::new_read_only_with_column_options(path, &HashMap::new()).unwrap();
+        assert_eq!(read_only.get(ColBlockMisc, &[1]).unwrap(), Some(vec![1]));
+        assert_eq!(read_only.get(ColState, &[2]).unwrap(), Some(vec![2]));
+        let mut transaction = read_only.transaction();
+        transaction.put(ColBlockMisc, &[3], &[3]);
+        assert!(read_only.write(transaction).is_err());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_backend_mismatch() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_backend").tempdir().unwrap();
+        let path = tmp_dir.path().to_str().unwrap();
+        create_store_with_config(
+            path,
+            &StoreConfig { backend: DBBackend::RocksDB, ..Default::default() },
+        );
+        create_store_with_config(
+            path,
+            &StoreConfig { backend: DBBackend::Sled, ..Default::default() },
+        );
+    }
+
+    #[test]
+    fn test_rocksdb_column_options() {
+        let mut config = StoreConfig::default();
+        config.columns.insert(
+            "ColState".to_string(),
+            RocksDBColumnOptions {
+                block_cache_size: Some(1024 * 1024),
+                bloom_filter_bits_per_key: Some(0),
+                compression: Some(Compression::Lz4),
+                ..Default::default()
+            },
+        );
+        assert_eq!(config.rocksdb_column_options().unwrap().len(), 1);
+        let tmp_dir = tempfile::Builder::new().prefix("_test_column_options").tempdir().unwrap();
+        let store = create_store_with_config(tmp_dir.path().to_str().unwrap(), &config);
+        update_refcount(&store, &[(&[1], &[1], 1)]);
+        assert_eq!(store.get(ColState, &[1]).unwrap(), Some(vec![1]));
+
+        let rocksdb = store.get_rocksdb().unwrap();
+        let stats = rocksdb.column_stats(ColState).unwrap();
+        assert_eq!(stats.col, ColState);
+        assert!(stats.mem_tables_size > 0);
+        #[cfg(not(feature = "single_thread_rocksdb"))]
+        {
+            rocksdb.compact(ColState);
+            assert!(rocksdb.column_stats(ColState).unwrap().sst_files_size > 0);
+        }
+
+        config.columns.insert("ColUnknown".to_string(), RocksDBColumnOptions::default());
+        assert_eq!(config.rocksdb_column_options(), Err("ColUnknown".to_string()));
+    }
+
+    #[test]
+    fn test_split_db_move_to_cold() {
+        let hot_dir = tempfile::Builder::new().prefix("_test_split_hot").tempdir().unwrap();
+        let cold_dir = tempfile::Builder::new().prefix("_test_split_cold").tempdir().unwrap();
+        let store = create_split_store_with_config(
+            hot_dir.path().to_str().unwrap(),
+            cold_dir.path().to_str().unwrap(),
+            &StoreConfig::default(),
+        );
+        let mut store_update = store.store_update();
+        store_update.set(ColBlockMisc, &[1], &[1]);
+        store_update.set(ColBlockMisc, &[2], &[2]);
+        store_update.commit().unwrap();
+        update_refcount(&store, &[(&[3], &[3], 1)]);
+
+        let split_db = store.get_split_db().unwrap();
+        assert_eq!(split_db.cold_head().unwrap(), None);
+        let keys = vec![
+            (ColBlockMisc, vec![1]),
+            (ColState, vec![3]),
+            (ColState, vec![3]),
+            (ColState, vec![4]),
+        ];
+        split_db.move_to_cold(keys.clone(), 10).unwrap();
+        assert_eq!(split_db.cold_head().unwrap(), Some(10));
+        assert_eq!(split_db.cold().get(ColBlockMisc, &[1]).unwrap(), Some(vec![1]));
+        assert_eq!(split_db.hot().get(ColBlockMisc, &[1]).unwrap(), None);
+        assert!(store.get_rocksdb().is_none());
+        // Reads fall through to the cold database.
+        assert_eq!(store.get(ColBlockMisc, &[1]).unwrap(), Some(vec![1]));
+        assert_eq!(store.get(ColBlockMisc, &[2]).unwrap(), Some(vec![2]));
+        assert_eq!(store.get(ColState, &[3]).unwrap(), Some(vec![3]));
+
+        // Moving the same keys again doesn't count refcounts twice.
+        split_db.move_to_cold(keys, 10).unwrap();
+        update_refcount(&store, &[(&[3], &[3], 1)]);
+        update_refcount(&store, &[(&[3], &[3], -1)]);
+        assert_eq!(store.get(ColState, &[3]).unwrap(), Some(vec![3]));
+        update_refcount(&store, &[(&[3], &[3], -1)]);
+        assert_eq!(store.get(ColState, &[3]).unwrap(), None);
+
+        // Newer values in the hot database take precedence and deletions reach the cold one.
+        let mut store_update = store.store_update();
+        store_update.set(ColBlockMisc, &[1], &[10]);
+        store_update.commit().unwrap();
+        assert_eq!(store.get(ColBlockMisc, &[1]).unwrap(), Some(vec![10]));
+        let mut store_update = store.store_update();
+        store_update.delete(ColBlockMisc, &[1]);
+        store_update.commit().unwrap();
+        assert_eq!(store.get(ColBlockMisc, &[1]).unwrap(), None);
+    }
 }
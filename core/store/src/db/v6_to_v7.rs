@@ -5,7 +5,7 @@ use byteorder::{LittleEndian, ReadBytesExt};
 use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, MergeOperands, Options, DB};
 use strum::IntoEnumIterator;
 
-use crate::db::{rocksdb_column_options, rocksdb_options, DBError, RocksDB};
+use crate::db::{rocksdb_column_options, rocksdb_options, DBError, RocksDB, RocksDBColumnOptions};
 use crate::DBCol;
 
 fn refcount_merge_v6(
@@ -48,10 +48,10 @@ fn merge_refcounted_records_v6(result: &mut Vec<u8>, val: &[u8]) {
 }
 
 fn rocksdb_column_options_v6(col: DBCol) -> Options {
-    let mut opts = rocksdb_column_options(DBCol::ColDbVersion);
+    let mut opts = rocksdb_column_options(DBCol::ColDbVersion, &RocksDBColumnOptions::default());
 
     if col == DBCol::ColState {
-        opts.set_merge_operator("refcount merge", refcount_merge_v6, None);
+        opts.set_merge_operator("refcount merge", refcount_merge_v6, refcount_merge_v6);
         opts.set_compaction_filter("empty value filter", RocksDB::empty_value_compaction_filter);
     }
     opts
@@ -71,6 +71,6 @@ impl RocksDB {
 
         let cfs =
             cf_names.iter().map(|n| db.cf_handle(n).unwrap() as *const ColumnFamily).collect();
-        Ok(Self { db, cfs, _pin: PhantomPinned })
+        Ok(Self { db, cfs, read_only: false, _pin: PhantomPinned })
     }
 }
@@ -0,0 +1,285 @@
+//! Flat state: the values of the trie of a shard at a single block, keyed by the trie key, so that
+//! reading a value is a single database read instead of a walk from the root of the trie.
+//!
+//! The flat state of a shard follows the chain head. The chain saves a `FlatStateDelta` with the
+//! old and new values of the changed keys for every block it applies, and when the head changes
+//! it reverts the deltas of the blocks that left the chain and applies the deltas of the blocks
+//! that joined it. The trie remains the source of truth, it's still used for proofs, state parts
+//! and reads at any state root other than the one of the flat state.
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{NumShards, ShardId, StateRoot};
+
+use crate::{DBCol, ShardTries, StorageError, Store, StoreUpdate};
+
+/// Number of values written in one transaction when the flat state is built from the trie.
+const BUILD_BATCH_SIZE: u64 = 100_000;
+
+/// Key in `ColBlockMisc` set once the flat state of all shards is removed, so that the column
+/// isn't scanned again on every start while the flat state stays disabled.
+const FLAT_STATE_REMOVED_KEY: &[u8] = b"FLAT_STATE_REMOVED";
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FlatStateHead {
+    pub block_hash: CryptoHash,
+    /// State root of the shard after applying `block_hash`.
+    pub state_root: StateRoot,
+    /// Incremented on every change of the flat state, so that readers can detect that the flat
+    /// state changed while they were reading it.
+    pub version: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FlatStateValueChange {
+    pub key: Vec<u8>,
+    pub old_value: Option<Vec<u8>>,
+    pub new_value: Option<Vec<u8>>,
+}
+
+/// Changes of the values of a shard made by a block.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FlatStateDelta {
+    pub changes: Vec<FlatStateValueChange>,
+}
+
+/// Net changes of the values of a shard accumulated from several deltas, so that every value is
+/// written once.
+#[derive(Default)]
+pub struct FlatStateChanges(BTreeMap<Vec<u8>, Option<Vec<u8>>>);
+
+impl FlatStateChanges {
+    pub fn apply(&mut self, delta: &FlatStateDelta) {
+        for change in delta.changes.iter() {
+            self.0.insert(change.key.clone(), change.new_value.clone());
+        }
+    }
+
+    pub fn revert(&mut self, delta: &FlatStateDelta) {
+        for change in delta.changes.iter() {
+            self.0.insert(change.key.clone(), change.old_value.clone());
+        }
+    }
+
+    pub fn write(self, shard_id: ShardId, store_update: &mut StoreUpdate) {
+        for (key, value) in self.0 {
+            let key = flat_state_key(shard_id, &key);
+            match value {
+                Some(value) => store_update.set(DBCol::ColFlatState, &key, &value),
+                None => store_update.delete(DBCol::ColFlatState, &key),
+            }
+        }
+    }
+}
+
+fn flat_state_key(shard_id: ShardId, key: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(8 + key.len());
+    result.extend_from_slice(&shard_id.to_le_bytes());
+    result.extend_from_slice(key);
+    result
+}
+
+/// Returns the head of the flat state of the shard, `None` if the shard has no flat state.
+pub fn get_flat_state_head(store: &Store, shard_id: ShardId) -> io::Result<Option<FlatStateHead>> {
+    store.get_ser(DBCol::ColFlatStateHead, &shard_id.to_le_bytes())
+}
+
+pub fn set_flat_state_head(
+    store_update: &mut StoreUpdate,
+    shard_id: ShardId,
+    head: &FlatStateHead,
+) -> io::Result<()> {
+    store_update.set_ser(DBCol::ColFlatStateHead, &shard_id.to_le_bytes(), head)
+}
+
+/// Stops using the flat state of the shard. The values are left in place until the flat state
+/// is built again or removed with `remove_flat_state`.
+pub fn remove_flat_state_head(store_update: &mut StoreUpdate, shard_id: ShardId) {
+    store_update.delete(DBCol::ColFlatStateHead, &shard_id.to_le_bytes());
+}
+
+/// Removes the flat state of the shard and returns the number of removed values.
+pub fn remove_flat_state(store: &Store, shard_id: ShardId) -> io::Result<u64> {
+    let mut store_update = store.store_update();
+    remove_flat_state_head(&mut store_update, shard_id);
+    let prefix = shard_id.to_le_bytes();
+    let mut num_values = 0;
+    for (key, _) in store.iter_prefix(DBCol::ColFlatState, &prefix) {
+        store_update.delete(DBCol::ColFlatState, &key);
+        num_values += 1;
+    }
+    store_update.commit()?;
+    Ok(num_values)
+}
+
+/// Removes the flat state of all shards, unless it was already removed since it was last built.
+/// Returns the number of removed values.
+pub fn remove_all_flat_state(store: &Store, num_shards: NumShards) -> io::Result<u64> {
+    if store.get(DBCol::ColBlockMisc, FLAT_STATE_REMOVED_KEY)?.is_some() {
+        return Ok(0);
+    }
+    let mut num_values = 0;
+    for shard_id in 0..num_shards {
+        num_values += remove_flat_state(store, shard_id)?;
+    }
+    let mut store_update = store.store_update();
+    store_update.set_ser(DBCol::ColBlockMisc, FLAT_STATE_REMOVED_KEY, &true)?;
+    store_update.commit()?;
+    Ok(num_values)
+}
+
+/// Writes the flat state of the shard from the trie at `state_root`, the state after applying
+/// `block_hash`, replacing the previous flat state of the shard. Returns the number of values.
+pub fn build_flat_state(
+    tries: &ShardTries,
+    shard_id: ShardId,
+    block_hash: &CryptoHash,
+    state_root: &StateRoot,
+) -> Result<u64, StorageError> {
+    let store = tries.get_store();
+    let version = get_flat_state_head(&store, shard_id)
+        .map_err(|_| StorageError::StorageInternalError)?
+        .map_or(0, |head| head.version + 1);
+    remove_flat_state(&store, shard_id).map_err(|_| StorageError::StorageInternalError)?;
+
+    let trie = tries.get_view_trie_for_shard(shard_id);
+    let mut store_update = store.store_update();
+    store_update.delete(DBCol::ColBlockMisc, FLAT_STATE_REMOVED_KEY);
+    let mut num_values = 0;
+    for item in trie.iter(state_root)? {
+        let (key, value) = item?;
+        store_update.set(DBCol::ColFlatState, &flat_state_key(shard_id, &key), &value);
+        num_values += 1;
+        if num_values % BUILD_BATCH_SIZE == 0 {
+            store_update.commit().map_err(|_| StorageError::StorageInternalError)?;
+            store_update = store.store_update();
+        }
+    }
+    let head = FlatStateHead { block_hash: *block_hash, state_root: *state_root, version };
+    set_flat_state_head(&mut store_update, shard_id, &head)
+        .map_err(|_| StorageError::StorageInternalError)?;
+    store_update.commit().map_err(|_| StorageError::StorageInternalError)?;
+    Ok(num_values)
+}
+
+/// Reads the values of a shard from its flat state at a given state root.
+pub struct FlatState {
+    store: Arc<Store>,
+    shard_id: ShardId,
+    version: u64,
+}
+
+impl FlatState {
+    /// Returns `None` if the shard has no flat state or it's not at `state_root`.
+    pub fn new(store: Arc<Store>, shard_id: ShardId, state_root: &StateRoot) -> Option<Self> {
+        match get_flat_state_head(&store, shard_id) {
+            Ok(Some(head)) if &head.state_root == state_root => {
+                Some(FlatState { store, shard_id, version: head.version })
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the key, or `None` if the flat state changed since this reader was
+    /// created and the value has to be read from the trie instead.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Option<Vec<u8>>>, StorageError> {
+        let value = self
+            .store
+            .get(DBCol::ColFlatState, &flat_state_key(self.shard_id, key))
+            .map_err(|_| StorageError::StorageInternalError)?;
+        // Values and the head are written in one transaction, so if the version didn't change
+        // after the read, the value is the one at the state root of this reader.
+        match get_flat_state_head(&self.store, self.shard_id) {
+            Ok(Some(head)) if head.version == self.version => Ok(Some(value)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::hash::hash;
+
+    use crate::test_utils::create_tries;
+    use crate::{DBCol, Trie};
+
+    use super::{
+        build_flat_state, remove_all_flat_state, remove_flat_state, set_flat_state_head, FlatState,
+        FlatStateChanges, FlatStateDelta, FlatStateHead, FlatStateValueChange,
+    };
+
+    #[test]
+    fn test_flat_state() {
+        let tries = create_tries();
+        let store = tries.get_store();
+        let trie = tries.get_trie_for_shard(0);
+        let changes = vec![(b"aa".to_vec(), Some(vec![1])), (b"ab".to_vec(), Some(vec![2]))];
+        let trie_changes = trie.update(&Trie::empty_root(), changes.into_iter()).unwrap();
+        let (store_update, root) = tries.apply_all(&trie_changes, 0).unwrap();
+        store_update.commit().unwrap();
+
+        assert!(FlatState::new(store.clone(), 0, &root).is_none());
+        assert_eq!(build_flat_state(&tries, 0, &hash(&[1]), &root).unwrap(), 2);
+        let flat_state = FlatState::new(store.clone(), 0, &root).unwrap();
+        assert_eq!(flat_state.get(b"aa").unwrap(), Some(Some(vec![1])));
+        assert_eq!(flat_state.get(b"ac").unwrap(), Some(None));
+        assert!(FlatState::new(store.clone(), 1, &root).is_none());
+        assert!(FlatState::new(store.clone(), 0, &Trie::empty_root()).is_none());
+
+        let delta = FlatStateDelta {
+            changes: vec![
+                FlatStateValueChange {
+                    key: b"aa".to_vec(),
+                    old_value: Some(vec![1]),
+                    new_value: None,
+                },
+                FlatStateValueChange {
+                    key: b"ac".to_vec(),
+                    old_value: None,
+                    new_value: Some(vec![3]),
+                },
+            ],
+        };
+        let mut store_update = store.store_update();
+        let mut changes = FlatStateChanges::default();
+        changes.apply(&delta);
+        changes.write(0, &mut store_update);
+        set_flat_state_head(
+            &mut store_update,
+            0,
+            &FlatStateHead { block_hash: hash(&[2]), state_root: hash(&[3]), version: 1 },
+        )
+        .unwrap();
+        store_update.commit().unwrap();
+        // The reader at the old state root notices that the flat state moved.
+        assert_eq!(flat_state.get(b"aa").unwrap(), None);
+        let flat_state = FlatState::new(store.clone(), 0, &hash(&[3])).unwrap();
+        assert_eq!(flat_state.get(b"aa").unwrap(), Some(None));
+        assert_eq!(flat_state.get(b"ac").unwrap(), Some(Some(vec![3])));
+
+        // Changes accumulated from several deltas write every value once.
+        let mut store_update = store.store_update();
+        let mut changes = FlatStateChanges::default();
+        changes.revert(&delta);
+        changes.apply(&delta);
+        changes.write(0, &mut store_update);
+        store_update.commit().unwrap();
+        assert_eq!(store.get(DBCol::ColFlatState, b"\0\0\0\0\0\0\0\0ac").unwrap(), Some(vec![3]));
+
+        assert_eq!(remove_flat_state(&store, 0).unwrap(), 2);
+        assert!(FlatState::new(store.clone(), 0, &root).is_none());
+        assert!(store.iter(DBCol::ColFlatState).next().is_none());
+
+        // Removing the flat state of all shards is only done once until it's built again.
+        assert_eq!(build_flat_state(&tries, 0, &hash(&[1]), &root).unwrap(), 2);
+        assert_eq!(remove_all_flat_state(&store, 2).unwrap(), 2);
+        assert_eq!(build_flat_state(&tries, 1, &hash(&[1]), &root).unwrap(), 2);
+        assert_eq!(remove_all_flat_state(&store, 2).unwrap(), 2);
+        assert_eq!(remove_all_flat_state(&store, 2).unwrap(), 0);
+        assert!(store.iter(DBCol::ColFlatState).next().is_none());
+    }
+}
@@ -1,20 +1,24 @@
 #[macro_use]
 extern crate lazy_static;
 
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
 use std::io::{Read, Write};
 use std::ops::Deref;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::str::FromStr;
 use std::sync::Arc;
 use std::{fmt, io};
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use cached::{Cached, SizedCache};
+use serde::{Deserialize, Serialize};
 
 pub use db::DBCol::{self, *};
 pub use db::{
+    ColumnStats, CompactionStyle, Compression, DBBackend, RocksDBColumnOptions, SplitDB,
     CHUNK_TAIL_KEY, FINAL_HEAD_KEY, FORK_TAIL_KEY, HEADER_HEAD_KEY, HEAD_KEY,
     LARGEST_TARGET_HEIGHT_KEY, LATEST_KNOWN_KEY, NUM_COLS, SHOULD_COL_GC, SKIP_COL_GC, TAIL_KEY,
 };
@@ -31,15 +35,17 @@ use near_primitives::types::{AccountId, CompiledContractCache, StateRoot};
 pub use crate::db::refcount::decode_value_with_rc;
 use crate::db::refcount::encode_value_with_rc;
 use crate::db::{
-    DBOp, DBTransaction, Database, RocksDB, GENESIS_JSON_HASH_KEY, GENESIS_STATE_ROOTS_KEY,
+    DBOp, DBTransaction, Database, RocksDB, SledDB, GENESIS_JSON_HASH_KEY, GENESIS_STATE_ROOTS_KEY,
 };
 pub use crate::trie::{
     iterator::TrieIterator, update::TrieUpdate, update::TrieUpdateIterator,
-    update::TrieUpdateValuePtr, KeyForStateChanges, PartialStorage, ShardTries, Trie, TrieChanges,
-    WrappedTrieChanges,
+    update::TrieUpdateValuePtr, KeyForStateChanges, PartialStorage, ShardTries, Trie, TrieAudit,
+    TrieAuditReport, TrieChanges, TrieDiff, TrieDiffItem, WrappedTrieChanges,
 };
 
+pub mod checkpoint;
 pub mod db;
+pub mod flat_state;
 pub mod migrations;
 pub mod test_utils;
 mod trie;
@@ -145,9 +151,24 @@ impl Store {
         self.storage.write(transaction).map_err(|e| e.into())
     }
 
+    /// Returns the RocksDB of the store, unless it is split into the hot and the cold databases.
     pub fn get_rocksdb(&self) -> Option<&RocksDB> {
         self.storage.as_rocksdb()
     }
+
+    /// Returns the hot and cold databases of an archival node, if it has them.
+    pub fn get_split_db(&self) -> Option<&SplitDB> {
+        self.storage.as_split_db()
+    }
+
+    /// Returns all RocksDB instances of the store by name: `hot` and `cold` if the store is
+    /// split, `db` otherwise. Empty if the store is not backed by RocksDB.
+    pub fn get_rocksdb_instances(&self) -> Vec<(&'static str, &RocksDB)> {
+        match self.get_split_db() {
+            Some(split_db) => vec![("hot", split_db.hot()), ("cold", split_db.cold())],
+            None => self.get_rocksdb().map(|rocksdb| ("db", rocksdb)).into_iter().collect(),
+        }
+    }
 }
 
 /// Keeps track of current changes to the database and can commit all of them to the database.
@@ -293,6 +314,113 @@ pub fn create_store(path: &str) -> Arc<Store> {
     Arc::new(Store::new(db))
 }
 
+/// Configuration of the node database, the `store` section of `config.json`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct StoreConfig {
+    /// Backend of a new database. An existing database is always opened with the backend it
+    /// was created with, so changing it requires syncing the node from scratch.
+    pub backend: DBBackend,
+    /// RocksDB options of columns, keyed by column name, e.g. `ColState`.
+    /// Columns not listed here use the default options.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub columns: BTreeMap<String, RocksDBColumnOptions>,
+    /// Path of the cold database of an archival node, relative to the home directory.
+    /// Data older than the GC horizon is moved there, so it can be on slower disks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cold_store_path: Option<PathBuf>,
+    /// Keeps the values of the state at the head in a flat column, so that view calls and
+    /// queries read a value with one database read instead of walking the trie. The flat state
+    /// is built when the node starts and is removed when this is disabled.
+    pub enable_flat_state: bool,
+    /// Creates a checkpoint of the database before the migrations run on start, so that an
+    /// interrupted migration is resumed and a failed one can be rolled back with
+    /// `neard rollback_migration`. The checkpoint is kept in the home directory until the next
+    /// migration, so it takes as much disk space as the database.
+    pub migration_checkpoints: bool,
+}
+
+impl StoreConfig {
+    /// Returns options of the columns listed in the config, or the name of an unknown column.
+    pub fn rocksdb_column_options(&self) -> Result<HashMap<DBCol, RocksDBColumnOptions>, String> {
+        self.columns
+            .iter()
+            .map(|(name, options)| {
+                let col = DBCol::from_str(name).map_err(|_| name.clone())?;
+                Ok((col, options.clone()))
+            })
+            .collect()
+    }
+}
+
+pub fn create_store_with_config(path: &str, config: &StoreConfig) -> Arc<Store> {
+    if let Some(backend) = DBBackend::detect(path) {
+        assert_eq!(
+            backend, config.backend,
+            "Database at {} was created with {:?} backend, but {:?} is configured",
+            path, backend, config.backend
+        );
+    }
+    let column_options = config
+        .rocksdb_column_options()
+        .unwrap_or_else(|name| panic!("Unknown column {} in store config", name));
+    let db: Pin<Arc<dyn Database>> = match config.backend {
+        DBBackend::RocksDB => Arc::pin(
+            RocksDB::new_with_column_options(path, &column_options)
+                .expect("Failed to open the database"),
+        ),
+        DBBackend::Sled => Arc::pin(SledDB::new(path).expect("Failed to open the database")),
+    };
+    Arc::new(Store::new(db))
+}
+
+/// Opens an existing store without changing it, see `RocksDB::new_read_only_with_column_options`.
+/// Writes to the store fail.
+pub fn create_read_only_store_with_config(path: &str, config: &StoreConfig) -> Arc<Store> {
+    assert_eq!(
+        DBBackend::detect(path),
+        Some(DBBackend::RocksDB),
+        "Read-only store requires an existing RocksDB database at {}",
+        path
+    );
+    let column_options = config
+        .rocksdb_column_options()
+        .unwrap_or_else(|name| panic!("Unknown column {} in store config", name));
+    let db = RocksDB::new_read_only_with_column_options(path, &column_options)
+        .expect("Failed to open the database");
+    Arc::new(Store::new(Arc::pin(db)))
+}
+
+/// Read-only counterpart of `create_split_store_with_config`.
+pub fn create_read_only_split_store_with_config(
+    hot_path: &str,
+    cold_path: &str,
+    config: &StoreConfig,
+) -> Arc<Store> {
+    assert_eq!(config.backend, DBBackend::RocksDB, "Cold storage requires RocksDB backend");
+    let column_options = config
+        .rocksdb_column_options()
+        .unwrap_or_else(|name| panic!("Unknown column {} in store config", name));
+    let db = SplitDB::new_read_only(hot_path, cold_path, &column_options)
+        .expect("Failed to open the database");
+    Arc::new(Store::new(Arc::pin(db)))
+}
+
+/// Opens the store of an archival node split into the hot and the cold databases.
+pub fn create_split_store_with_config(
+    hot_path: &str,
+    cold_path: &str,
+    config: &StoreConfig,
+) -> Arc<Store> {
+    assert_eq!(config.backend, DBBackend::RocksDB, "Cold storage requires RocksDB backend");
+    let column_options = config
+        .rocksdb_column_options()
+        .unwrap_or_else(|name| panic!("Unknown column {} in store config", name));
+    let db =
+        SplitDB::new(hot_path, cold_path, &column_options).expect("Failed to open the database");
+    Arc::new(Store::new(Arc::pin(db)))
+}
+
 /// Reads an object from Trie.
 /// # Errors
 /// see StorageError
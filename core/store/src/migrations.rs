@@ -13,7 +13,7 @@ use near_primitives::transaction::ExecutionOutcomeWithIdAndProof;
 use near_primitives::version::DbVersion;
 
 use crate::db::DBCol::{ColBlockHeader, ColBlockMisc, ColChunks, ColPartialChunks, ColStateParts};
-use crate::db::{DBCol, RocksDB, VERSION_KEY};
+use crate::db::{DBBackend, DBCol, RocksDB, SledDB, VERSION_KEY};
 use crate::migrations::v6_to_v7::{
     col_state_refcount_8byte, migrate_col_transaction_refcount, migrate_receipts_refcount,
 };
@@ -39,7 +39,11 @@ pub mod v6_to_v7;
 pub mod v8_to_v9;
 
 pub fn get_store_version(path: &str) -> DbVersion {
-    RocksDB::get_version(path).expect("Failed to open the database")
+    match DBBackend::detect(path) {
+        Some(DBBackend::Sled) => SledDB::get_version(path),
+        _ => RocksDB::get_version(path),
+    }
+    .expect("Failed to open the database")
 }
 
 fn set_store_version_inner(store_update: &mut StoreUpdate, db_version: u32) {
@@ -0,0 +1,319 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::Arc;
+
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{ShardId, StateRoot};
+
+use crate::db::refcount::decode_value_with_rc;
+use crate::trie::{RawTrieNode, RawTrieNodeWithSize, TrieCachingStorage};
+use crate::{DBCol, StorageError, Store, Trie, TrieChanges};
+
+/// Checks the refcounted trie nodes and values in `ColState` against the state roots that are
+/// still in use. Every trie node and value reachable from a live root must have a positive
+/// refcount, and every record with a positive refcount must be reachable, otherwise it leaked
+/// and is never going to be garbage collected.
+///
+/// The refcount of a record is also checked against the references counted by the audit. The
+/// state the live blocks start from holds one reference per occurrence of the record in its
+/// tries, as the insertions that created them were matched by the deletions of the garbage
+/// collected blocks, and each live block adds the insertions of its trie changes, whose
+/// deletions are only applied once the block is garbage collected.
+pub struct TrieAudit {
+    store: Arc<Store>,
+    /// `ColState` keys of the trie nodes and values reachable from the added roots.
+    reachable: HashSet<[u8; 40]>,
+    /// Number of references to the `ColState` keys counted so far.
+    references: HashMap<[u8; 40], i64>,
+}
+
+#[derive(Default, Debug)]
+pub struct TrieAuditReport {
+    pub num_reachable: usize,
+    /// Reachable trie nodes and values that are not in `ColState` or have non-positive refcount.
+    pub missing: Vec<(ShardId, CryptoHash)>,
+    /// Records with positive refcount that are not reachable from any live root.
+    pub unreachable: Vec<(ShardId, CryptoHash, i64)>,
+    /// Unreachable records with zero or negative refcount that weren't compacted away yet.
+    /// Negative refcount means that the record was deleted more times than inserted.
+    pub non_positive: Vec<(ShardId, CryptoHash, i64)>,
+    /// Reachable records whose refcount differs from the number of references to them, with
+    /// the stored refcount and the number of references.
+    pub refcount_mismatches: Vec<(ShardId, CryptoHash, i64, i64)>,
+}
+
+impl TrieAudit {
+    pub fn new(store: Arc<Store>) -> Self {
+        TrieAudit { store, reachable: HashSet::new(), references: HashMap::new() }
+    }
+
+    /// Adds a root of the state that the live blocks start from, i.e. the old root of the
+    /// oldest trie changes that are still kept. Each occurrence of a trie node or value in the
+    /// trie counts as a reference, so identical subtrees are walked every time they appear.
+    pub fn add_base_root(
+        &mut self,
+        shard_id: ShardId,
+        root: &StateRoot,
+    ) -> Result<(), StorageError> {
+        let mut stack = vec![*root];
+        while let Some(hash) = stack.pop() {
+            if hash == Trie::empty_root() {
+                continue;
+            }
+            let key = TrieCachingStorage::get_key_from_shard_id_and_hash(shard_id, &hash);
+            self.reachable.insert(key);
+            *self.references.entry(key).or_insert(0) += 1;
+            let node = match self.get_node(&key, &hash)? {
+                Some(node) => node,
+                None => continue,
+            };
+            match node {
+                RawTrieNode::Leaf(_, _, value_hash) => {
+                    self.add_value_reference(shard_id, &value_hash)
+                }
+                RawTrieNode::Branch(children, value) => {
+                    if let Some((_, value_hash)) = value {
+                        self.add_value_reference(shard_id, &value_hash);
+                    }
+                    stack.extend(children.iter().flatten());
+                }
+                RawTrieNode::Extension(_, child) => stack.push(child),
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the trie changes of a block that is not garbage collected yet. Its insertions are
+    /// references and its new root is live.
+    pub fn add_trie_changes(
+        &mut self,
+        shard_id: ShardId,
+        trie_changes: &TrieChanges,
+    ) -> Result<(), StorageError> {
+        for (hash, _, rc) in trie_changes.insertions.iter() {
+            let key = TrieCachingStorage::get_key_from_shard_id_and_hash(shard_id, hash);
+            *self.references.entry(key).or_insert(0) += *rc as i64;
+        }
+        self.add_root(shard_id, &trie_changes.new_root)
+    }
+
+    /// Marks the trie nodes and values reachable from `root` as live. Subtrees visited from
+    /// previously added roots are skipped, so adding the roots of consecutive blocks only walks
+    /// the nodes that changed between them. Missing nodes are not followed and are reported by
+    /// `finish`.
+    fn add_root(&mut self, shard_id: ShardId, root: &StateRoot) -> Result<(), StorageError> {
+        let mut stack = vec![*root];
+        while let Some(hash) = stack.pop() {
+            if hash == Trie::empty_root() {
+                continue;
+            }
+            let key = TrieCachingStorage::get_key_from_shard_id_and_hash(shard_id, &hash);
+            if !self.reachable.insert(key) {
+                continue;
+            }
+            let node = match self.get_node(&key, &hash)? {
+                Some(node) => node,
+                None => continue,
+            };
+            match node {
+                RawTrieNode::Leaf(_, _, value_hash) => {
+                    self.add_value(shard_id, &value_hash);
+                }
+                RawTrieNode::Branch(children, value) => {
+                    if let Some((_, value_hash)) = value {
+                        self.add_value(shard_id, &value_hash);
+                    }
+                    stack.extend(children.iter().flatten());
+                }
+                RawTrieNode::Extension(_, child) => stack.push(child),
+            }
+        }
+        Ok(())
+    }
+
+    fn get_node(
+        &self,
+        key: &[u8; 40],
+        hash: &CryptoHash,
+    ) -> Result<Option<RawTrieNode>, StorageError> {
+        let bytes = match self
+            .store
+            .get(DBCol::ColState, key)
+            .map_err(|_| StorageError::StorageInternalError)?
+        {
+            Some(bytes) => bytes,
+            None => return Ok(None),
+        };
+        let node = RawTrieNodeWithSize::decode(&bytes).map_err(|_| {
+            StorageError::StorageInconsistentState(format!("Failed to decode node {}", hash))
+        })?;
+        Ok(Some(node.node))
+    }
+
+    fn add_value(&mut self, shard_id: ShardId, value_hash: &CryptoHash) {
+        self.reachable
+            .insert(TrieCachingStorage::get_key_from_shard_id_and_hash(shard_id, value_hash));
+    }
+
+    fn add_value_reference(&mut self, shard_id: ShardId, value_hash: &CryptoHash) {
+        let key = TrieCachingStorage::get_key_from_shard_id_and_hash(shard_id, value_hash);
+        self.reachable.insert(key);
+        *self.references.entry(key).or_insert(0) += 1;
+    }
+
+    /// Compares the reachable trie nodes and values with all the records in `ColState`.
+    pub fn finish(mut self) -> io::Result<TrieAuditReport> {
+        let mut report =
+            TrieAuditReport { num_reachable: self.reachable.len(), ..Default::default() };
+        for (key, value) in self.store.iter_without_rc_logic(DBCol::ColState) {
+            let (shard_id, hash) = TrieCachingStorage::get_shard_id_and_hash_from_key(&key)?;
+            let (_, rc) = decode_value_with_rc(&value);
+            if self.reachable.remove(key.as_ref()) {
+                let references = self.references.get(key.as_ref()).cloned().unwrap_or(0);
+                if rc <= 0 {
+                    report.missing.push((shard_id, hash));
+                } else if rc != references {
+                    report.refcount_mismatches.push((shard_id, hash, rc, references));
+                }
+            } else if rc > 0 {
+                report.unreachable.push((shard_id, hash, rc));
+            } else {
+                report.non_positive.push((shard_id, hash, rc));
+            }
+        }
+        report.missing.extend(self.reachable.iter().map(|key| {
+            TrieCachingStorage::get_shard_id_and_hash_from_key(key)
+                .expect("Keys are built from shard id and hash")
+        }));
+        Ok(report)
+    }
+}
+
+impl TrieAuditReport {
+    /// Deletes the unreachable records and the records with non-positive refcount. Refcount
+    /// mismatches of reachable records are left as they are.
+    ///
+    /// The node must be stopped, otherwise it could delete records the node has just inserted
+    /// again.
+    pub fn prune(&self, store: &Store) -> io::Result<()> {
+        let mut store_update = store.store_update();
+        for (shard_id, hash, _) in self.unreachable.iter().chain(self.non_positive.iter()) {
+            store_update.delete(
+                DBCol::ColState,
+                &TrieCachingStorage::get_key_from_shard_id_and_hash(*shard_id, hash),
+            );
+        }
+        store_update.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_primitives::hash::{hash, CryptoHash};
+
+    use crate::test_utils::{create_test_store, create_tries};
+    use crate::trie::TrieCachingStorage;
+    use crate::{DBCol, ShardTries, Trie, TrieChanges};
+
+    use super::TrieAudit;
+
+    fn get_node(tries: &ShardTries, hash: &CryptoHash) -> Option<Vec<u8>> {
+        tries
+            .get_store()
+            .get(DBCol::ColState, &TrieCachingStorage::get_key_from_shard_id_and_hash(0, hash))
+            .unwrap()
+    }
+
+    /// Applies the changes the way the chain does: insertions when the block is processed,
+    /// deletions when it is garbage collected.
+    fn apply_insertions(tries: &ShardTries, trie_changes: &TrieChanges) {
+        let mut store_update = tries.get_store().store_update();
+        tries.apply_insertions(trie_changes, 0, &mut store_update).unwrap();
+        store_update.commit().unwrap();
+    }
+
+    fn apply_deletions(tries: &ShardTries, trie_changes: &TrieChanges) {
+        let mut store_update = tries.get_store().store_update();
+        tries.apply_deletions(trie_changes, 0, &mut store_update).unwrap();
+        store_update.commit().unwrap();
+    }
+
+    #[test]
+    fn test_trie_audit() {
+        let tries = create_tries();
+        let trie = tries.get_trie_for_shard(0);
+        let changes = vec![(b"aa".to_vec(), Some(b"1".to_vec())), (b"ab".to_vec(), Some(vec![2]))];
+        let first_changes = trie.update(&Trie::empty_root(), changes.into_iter()).unwrap();
+        apply_insertions(&tries, &first_changes);
+        let root = first_changes.new_root;
+        let second_changes =
+            trie.update(&root, vec![(b"ab".to_vec(), Some(vec![3]))].into_iter()).unwrap();
+        apply_insertions(&tries, &second_changes);
+        let new_root = second_changes.new_root;
+
+        // Both blocks are live, so everything is reachable and referenced by their insertions.
+        let mut audit = TrieAudit::new(tries.get_store());
+        audit.add_base_root(0, &Trie::empty_root()).unwrap();
+        audit.add_trie_changes(0, &first_changes).unwrap();
+        audit.add_trie_changes(0, &second_changes).unwrap();
+        let report = audit.finish().unwrap();
+        assert!(report.num_reachable > 0);
+        assert!(report.missing.is_empty());
+        assert!(report.unreachable.is_empty());
+        assert!(report.refcount_mismatches.is_empty());
+
+        // The first block is garbage collected, its state is the base of the second one.
+        let mut audit = TrieAudit::new(tries.get_store());
+        audit.add_base_root(0, &root).unwrap();
+        audit.add_trie_changes(0, &second_changes).unwrap();
+        let report = audit.finish().unwrap();
+        assert!(report.missing.is_empty());
+        assert!(report.unreachable.is_empty());
+        assert!(report.refcount_mismatches.is_empty());
+
+        // Only the new root is live and the deletions of the second block were never applied,
+        // as if they were lost by a bug.
+        let mut audit = TrieAudit::new(tries.get_store());
+        audit.add_base_root(0, &new_root).unwrap();
+        let report = audit.finish().unwrap();
+        assert!(report.missing.is_empty());
+        assert!(report.refcount_mismatches.is_empty());
+        assert!(report.unreachable.iter().any(|(_, node_hash, _)| node_hash == &root));
+        assert!(report.unreachable.iter().any(|(_, node_hash, _)| node_hash == &hash(&[2])));
+
+        report.prune(&tries.get_store()).unwrap();
+        assert_eq!(get_node(&tries, &root), None);
+        let mut audit = TrieAudit::new(tries.get_store());
+        audit.add_base_root(0, &new_root).unwrap();
+        let report = audit.finish().unwrap();
+        assert!(report.missing.is_empty());
+        assert!(report.unreachable.is_empty());
+        assert_eq!(trie.get(&new_root, b"aa").unwrap(), Some(b"1".to_vec()));
+
+        // Garbage collecting the second block now deletes the pruned records a second time.
+        apply_deletions(&tries, &second_changes);
+        let mut audit = TrieAudit::new(tries.get_store());
+        audit.add_base_root(0, &new_root).unwrap();
+        let report = audit.finish().unwrap();
+        assert!(report.missing.is_empty());
+        assert!(report.unreachable.is_empty());
+        assert!(report.non_positive.iter().any(|(_, node_hash, rc)| node_hash == &root && *rc < 0));
+        assert!(report.refcount_mismatches.is_empty());
+
+        // A record inserted once more than it is referenced.
+        let value_key = TrieCachingStorage::get_key_from_shard_id_and_hash(0, &hash(b"1"));
+        let mut store_update = tries.get_store().store_update();
+        store_update.update_refcount(DBCol::ColState, &value_key, b"1", 1);
+        store_update.commit().unwrap();
+        let mut audit = TrieAudit::new(tries.get_store());
+        audit.add_base_root(0, &new_root).unwrap();
+        let report = audit.finish().unwrap();
+        assert_eq!(report.refcount_mismatches, vec![(0, hash(b"1"), 2, 1)]);
+
+        // A live root whose nodes were deleted is reported as missing.
+        let mut audit = TrieAudit::new(create_test_store());
+        audit.add_base_root(0, &new_root).unwrap();
+        let report = audit.finish().unwrap();
+        assert_eq!(report.missing, vec![(0, new_root)]);
+    }
+}
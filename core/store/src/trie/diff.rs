@@ -0,0 +1,456 @@
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::StateRoot;
+use near_primitives::views::{StateDiffItemView, TrieKeyView};
+
+use crate::trie::nibble_slice::NibbleSlice;
+use crate::trie::{NodeHandle, TrieNode, ValueHandle};
+use crate::{StorageError, Trie};
+
+/// A key whose value differs between two states of a shard. `old_value` is `None` if the key
+/// was added and `new_value` is `None` if it was removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrieDiffItem {
+    pub key: Vec<u8>,
+    pub old_value: Option<Vec<u8>>,
+    pub new_value: Option<Vec<u8>>,
+}
+
+impl From<TrieDiffItem> for StateDiffItemView {
+    fn from(item: TrieDiffItem) -> Self {
+        let key = TrieKeyView::from_raw_key(&item.key);
+        match (item.old_value, item.new_value) {
+            (None, Some(value)) => Self::Added { key, value: value.into() },
+            (Some(value), None) => Self::Removed { key, value: value.into() },
+            (Some(old_value), Some(new_value)) => {
+                Self::Modified { key, old_value: old_value.into(), new_value: new_value.into() }
+            }
+            (None, None) => unreachable!("Keys without values on both sides are not in the diff"),
+        }
+    }
+}
+
+/// Values that differ between two states of a shard, ordered by key, and the key of the next
+/// differing value if the number of values was limited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrieDiff {
+    pub items: Vec<TrieDiffItem>,
+    pub next_key: Option<Vec<u8>>,
+}
+
+/// A subtree of a trie: either a whole node, or the part of a leaf or extension node below the
+/// first `offset` nibbles of its key.
+enum Subtree {
+    Node(CryptoHash),
+    Partial(CryptoHash, TrieNode, usize),
+}
+
+impl Subtree {
+    /// Subtrees at the same position with the same hash have the same keys and values.
+    fn is_same(&self, other: &Subtree) -> bool {
+        match (self, other) {
+            (Subtree::Node(hash), Subtree::Node(other_hash)) => hash == other_hash,
+            (Subtree::Partial(hash, _, offset), Subtree::Partial(other_hash, _, other_offset)) => {
+                hash == other_hash && offset == other_offset
+            }
+            _ => false,
+        }
+    }
+}
+
+enum Expanded {
+    Empty,
+    /// Remaining nibbles of the key and the hash of the value.
+    Leaf(Vec<u8>, CryptoHash),
+    /// Hash of the value at the root of the subtree and the children by their first nibble.
+    Inner(Option<CryptoHash>, Vec<(u8, Subtree)>),
+}
+
+fn node_hash(handle: &NodeHandle) -> CryptoHash {
+    match handle {
+        NodeHandle::Hash(hash) => *hash,
+        NodeHandle::InMemory(_) => unreachable!("Stored nodes don't have in-memory children"),
+    }
+}
+
+fn value_hash(handle: &ValueHandle) -> CryptoHash {
+    match handle {
+        ValueHandle::HashAndSize(_, hash) => *hash,
+        ValueHandle::InMemory(_) => unreachable!("Stored nodes don't have in-memory values"),
+    }
+}
+
+fn nibbles_to_key(nibbles: &[u8]) -> Vec<u8> {
+    debug_assert_eq!(nibbles.len() % 2, 0);
+    nibbles.chunks(2).map(|pair| pair[0] * 16 + pair[1]).collect()
+}
+
+fn key_to_nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter().flat_map(|byte| vec![byte / 16, byte % 16]).collect()
+}
+
+/// Whether all the keys starting with `prefix` are less than `start`.
+fn is_before(prefix: &[u8], start: &[u8]) -> bool {
+    let len = prefix.len().min(start.len());
+    prefix[..len] < start[..len]
+}
+
+impl Trie {
+    /// Returns up to `limit` values that differ between the states at `old_root` and `new_root`
+    /// with keys starting from `start_key`, ordered by key. Both tries are walked together and
+    /// subtrees with the same hash or with keys before `start_key` are skipped without being
+    /// read, so the cost is proportional to the size of the returned difference rather than to
+    /// the size of the state. The returned `next_key` continues from where the limit was hit.
+    pub fn diff(
+        &self,
+        old_root: &StateRoot,
+        new_root: &StateRoot,
+        start_key: &[u8],
+        limit: usize,
+    ) -> Result<TrieDiff, StorageError> {
+        let start = key_to_nibbles(start_key);
+        let mut result = vec![];
+        let mut stack =
+            vec![(vec![], Some(Subtree::Node(*old_root)), Some(Subtree::Node(*new_root)))];
+        // One more value than the limit is found to know the key to continue from.
+        while result.len() <= limit {
+            let (prefix, old, new) = match stack.pop() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if is_before(&prefix, &start) {
+                continue;
+            }
+            if let (Some(old), Some(new)) = (&old, &new) {
+                if old.is_same(new) {
+                    continue;
+                }
+            }
+            let old = old.map(|subtree| self.expand(subtree)).transpose()?;
+            let new = new.map(|subtree| self.expand(subtree)).transpose()?;
+            match (old.unwrap_or(Expanded::Empty), new.unwrap_or(Expanded::Empty)) {
+                (
+                    Expanded::Inner(old_value, old_children),
+                    Expanded::Inner(new_value, new_children),
+                ) => {
+                    if old_value != new_value && prefix >= start {
+                        self.push_diff_item(&prefix, old_value, new_value, &mut result)?;
+                    }
+                    let mut children: [(Option<Subtree>, Option<Subtree>); 16] = Default::default();
+                    for (nibble, child) in old_children {
+                        children[nibble as usize].0 = Some(child);
+                    }
+                    for (nibble, child) in new_children {
+                        children[nibble as usize].1 = Some(child);
+                    }
+                    // Pushed in reverse so that the children are popped in the order of keys.
+                    for (nibble, (old_child, new_child)) in children.iter_mut().enumerate().rev() {
+                        if old_child.is_some() || new_child.is_some() {
+                            let mut child_prefix = prefix.clone();
+                            child_prefix.push(nibble as u8);
+                            stack.push((child_prefix, old_child.take(), new_child.take()));
+                        }
+                    }
+                }
+                (old, new) => {
+                    // One of the sides has at most one value, so the subtrees are compared by
+                    // listing all of their values.
+                    let old_values = self.collect_values(&prefix, old, &start)?;
+                    let new_values = self.collect_values(&prefix, new, &start)?;
+                    let mut old_values = old_values.into_iter().peekable();
+                    let mut new_values = new_values.into_iter().peekable();
+                    while result.len() <= limit {
+                        let (key, old_value, new_value) =
+                            match (old_values.peek(), new_values.peek()) {
+                                (None, None) => break,
+                                (Some((old_key, _)), Some((new_key, _))) if old_key == new_key => {
+                                    let (key, old_value) = old_values.next().unwrap();
+                                    let (_, new_value) = new_values.next().unwrap();
+                                    (key, Some(old_value), Some(new_value))
+                                }
+                                (Some((old_key, _)), Some((new_key, _))) if old_key > new_key => {
+                                    let (key, new_value) = new_values.next().unwrap();
+                                    (key, None, Some(new_value))
+                                }
+                                (Some(_), _) => {
+                                    let (key, old_value) = old_values.next().unwrap();
+                                    (key, Some(old_value), None)
+                                }
+                                (None, Some(_)) => {
+                                    let (key, new_value) = new_values.next().unwrap();
+                                    (key, None, Some(new_value))
+                                }
+                            };
+                        if old_value != new_value {
+                            self.push_diff_item(&key, old_value, new_value, &mut result)?;
+                        }
+                    }
+                }
+            }
+        }
+        let next_key = if result.len() > limit { result.pop().map(|item| item.key) } else { None };
+        Ok(TrieDiff { items: result, next_key })
+    }
+
+    fn expand(&self, subtree: Subtree) -> Result<Expanded, StorageError> {
+        let (hash, node, offset) = match subtree {
+            Subtree::Node(hash) => (hash, self.retrieve_node(&hash)?.node, 0),
+            Subtree::Partial(hash, node, offset) => (hash, node, offset),
+        };
+        Ok(match &node {
+            TrieNode::Empty => Expanded::Empty,
+            TrieNode::Leaf(key, value) => {
+                let nibbles = NibbleSlice::from_encoded(key).0;
+                Expanded::Leaf(nibbles.iter().skip(offset).collect(), value_hash(value))
+            }
+            TrieNode::Extension(key, child) => {
+                let nibbles = NibbleSlice::from_encoded(key).0;
+                if offset == nibbles.len() {
+                    return self.expand(Subtree::Node(node_hash(child)));
+                }
+                let nibble = nibbles.at(offset);
+                Expanded::Inner(
+                    None,
+                    vec![(nibble, Subtree::Partial(hash, node.clone(), offset + 1))],
+                )
+            }
+            TrieNode::Branch(children, value) => Expanded::Inner(
+                value.as_ref().map(value_hash),
+                children
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(nibble, child)| {
+                        child.as_ref().map(|child| (nibble as u8, Subtree::Node(node_hash(child))))
+                    })
+                    .collect(),
+            ),
+        })
+    }
+
+    /// Returns the nibbles of the keys starting from `start` and the hashes of the values in the
+    /// subtree, ordered by key.
+    fn collect_values(
+        &self,
+        prefix: &[u8],
+        expanded: Expanded,
+        start: &[u8],
+    ) -> Result<Vec<(Vec<u8>, CryptoHash)>, StorageError> {
+        let mut values = vec![];
+        let mut stack = vec![(prefix.to_vec(), expanded)];
+        while let Some((prefix, expanded)) = stack.pop() {
+            match expanded {
+                Expanded::Empty => {}
+                Expanded::Leaf(nibbles, value) => {
+                    let mut key = prefix;
+                    key.extend(nibbles);
+                    if key.as_slice() >= start {
+                        values.push((key, value));
+                    }
+                }
+                Expanded::Inner(value, children) => {
+                    for (nibble, child) in children.into_iter().rev() {
+                        let mut child_prefix = prefix.clone();
+                        child_prefix.push(nibble);
+                        if !is_before(&child_prefix, start) {
+                            stack.push((child_prefix, self.expand(child)?));
+                        }
+                    }
+                    if let Some(value) = value {
+                        if prefix.as_slice() >= start {
+                            values.push((prefix, value));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    fn push_diff_item(
+        &self,
+        nibbles: &[u8],
+        old_value: Option<CryptoHash>,
+        new_value: Option<CryptoHash>,
+        result: &mut Vec<TrieDiffItem>,
+    ) -> Result<(), StorageError> {
+        result.push(TrieDiffItem {
+            key: nibbles_to_key(nibbles),
+            old_value: old_value.map(|hash| self.retrieve_raw_bytes(&hash)).transpose()?,
+            new_value: new_value.map(|hash| self.retrieve_raw_bytes(&hash)).transpose()?,
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rand::seq::SliceRandom;
+    use rand::Rng;
+
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::trie_key::TrieKey;
+    use near_primitives::views::{StateDiffItemView, TrieKeyView};
+
+    use crate::test_utils::{create_tries, gen_changes};
+    use crate::Trie;
+
+    use super::{TrieDiff, TrieDiffItem};
+
+    fn full_diff(trie: &Trie, old_root: &CryptoHash, new_root: &CryptoHash) -> Vec<TrieDiffItem> {
+        let TrieDiff { items, next_key } = trie.diff(old_root, new_root, &[], usize::MAX).unwrap();
+        assert_eq!(next_key, None);
+        items
+    }
+
+    fn naive_diff(trie: &Trie, old_root: &CryptoHash, new_root: &CryptoHash) -> Vec<TrieDiffItem> {
+        let old: BTreeMap<_, _> = trie.iter(old_root).unwrap().map(Result::unwrap).collect();
+        let new: BTreeMap<_, _> = trie.iter(new_root).unwrap().map(Result::unwrap).collect();
+        let mut keys: Vec<_> = old.keys().chain(new.keys()).cloned().collect();
+        keys.sort();
+        keys.dedup();
+        keys.into_iter()
+            .filter(|key| old.get(key) != new.get(key))
+            .map(|key| TrieDiffItem {
+                old_value: old.get(&key).cloned(),
+                new_value: new.get(&key).cloned(),
+                key,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_trie_diff() {
+        let tries = create_tries();
+        let trie = tries.get_trie_for_shard(0);
+        let changes = vec![
+            (b"aa".to_vec(), Some(vec![1])),
+            (b"ab".to_vec(), Some(vec![2])),
+            (b"abc".to_vec(), Some(vec![3])),
+            (b"b".to_vec(), Some(vec![4])),
+        ];
+        let trie_changes = trie.update(&Trie::empty_root(), changes.into_iter()).unwrap();
+        let (store_update, old_root) = tries.apply_all(&trie_changes, 0).unwrap();
+        store_update.commit().unwrap();
+        let changes = vec![
+            (b"ab".to_vec(), None),
+            (b"abc".to_vec(), Some(vec![5])),
+            (b"abcd".to_vec(), Some(vec![6])),
+        ];
+        let trie_changes = trie.update(&old_root, changes.into_iter()).unwrap();
+        let (store_update, new_root) = tries.apply_all(&trie_changes, 0).unwrap();
+        store_update.commit().unwrap();
+
+        assert_eq!(
+            full_diff(&trie, &old_root, &new_root),
+            vec![
+                TrieDiffItem { key: b"ab".to_vec(), old_value: Some(vec![2]), new_value: None },
+                TrieDiffItem {
+                    key: b"abc".to_vec(),
+                    old_value: Some(vec![3]),
+                    new_value: Some(vec![5])
+                },
+                TrieDiffItem { key: b"abcd".to_vec(), old_value: None, new_value: Some(vec![6]) },
+            ]
+        );
+        assert!(full_diff(&trie, &old_root, &old_root).is_empty());
+        assert_eq!(full_diff(&trie, &Trie::empty_root(), &old_root).len(), 4);
+        assert_eq!(full_diff(&trie, &new_root, &Trie::empty_root()).len(), 4);
+
+        // The diff is split into pages by the limit and continues from the next key.
+        let page = trie.diff(&old_root, &new_root, &[], 2).unwrap();
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.next_key, Some(b"abcd".to_vec()));
+        let page = trie.diff(&old_root, &new_root, b"abcd", 2).unwrap();
+        assert_eq!(
+            page,
+            TrieDiff {
+                items: vec![TrieDiffItem {
+                    key: b"abcd".to_vec(),
+                    old_value: None,
+                    new_value: Some(vec![6])
+                }],
+                next_key: None,
+            }
+        );
+        // Keys between the values are valid start keys as well.
+        let page = trie.diff(&old_root, &new_root, b"abb", 1).unwrap();
+        assert_eq!(page.items[0].key, b"abc".to_vec());
+        assert_eq!(page.next_key, Some(b"abcd".to_vec()));
+    }
+
+    #[test]
+    fn test_trie_diff_random() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let tries = create_tries();
+            let trie = tries.get_trie_for_shard(0);
+            let mut roots = vec![Trie::empty_root()];
+            for _ in 0..5 {
+                let changes = gen_changes(&mut rng, 20);
+                let trie_changes = trie.update(roots.last().unwrap(), changes.into_iter()).unwrap();
+                let (store_update, root) = tries.apply_all(&trie_changes, 0).unwrap();
+                store_update.commit().unwrap();
+                roots.push(root);
+            }
+            let old_root = roots.choose(&mut rng).unwrap();
+            let new_root = &roots[rng.gen_range(0, roots.len())];
+            let expected = naive_diff(&trie, old_root, new_root);
+            assert_eq!(full_diff(&trie, old_root, new_root), expected);
+
+            let limit = rng.gen_range(1, 10);
+            let mut pages = vec![];
+            let mut start_key = vec![];
+            loop {
+                let page = trie.diff(old_root, new_root, &start_key, limit).unwrap();
+                assert!(page.items.len() <= limit);
+                pages.extend(page.items);
+                match page.next_key {
+                    Some(next_key) => start_key = next_key,
+                    None => break,
+                }
+            }
+            assert_eq!(pages, expected);
+        }
+    }
+
+    #[test]
+    fn test_diff_item_view_decodes_key() {
+        let account_key = TrieKey::Account { account_id: "alice.near".to_string() }.to_vec();
+        let item = TrieDiffItem { key: account_key, old_value: None, new_value: Some(vec![1]) };
+        assert_eq!(
+            StateDiffItemView::from(item),
+            StateDiffItemView::Added {
+                key: TrieKeyView::Account { account_id: "alice.near".to_string() },
+                value: vec![1].into(),
+            }
+        );
+
+        let data_key =
+            TrieKey::ContractData { account_id: "alice.near".to_string(), key: b"k".to_vec() }
+                .to_vec();
+        let item = TrieDiffItem { key: data_key, old_value: Some(vec![1]), new_value: None };
+        assert_eq!(
+            StateDiffItemView::from(item),
+            StateDiffItemView::Removed {
+                key: TrieKeyView::ContractData {
+                    account_id: "alice.near".to_string(),
+                    key: b"k".to_vec().into(),
+                },
+                value: vec![1].into(),
+            }
+        );
+
+        // Keys that aren't valid trie keys are kept raw.
+        let item =
+            TrieDiffItem { key: vec![100, 1], old_value: Some(vec![1]), new_value: Some(vec![2]) };
+        assert_eq!(
+            StateDiffItemView::from(item),
+            StateDiffItemView::Modified {
+                key: TrieKeyView::Raw { key: vec![100, 1].into() },
+                old_value: vec![1].into(),
+                new_value: vec![2].into(),
+            }
+        );
+    }
+}
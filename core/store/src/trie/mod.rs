@@ -13,6 +13,8 @@ use near_primitives::challenge::PartialState;
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::types::{ShardId, StateRoot, StateRootNode};
 
+pub use crate::trie::audit::{TrieAudit, TrieAuditReport};
+pub use crate::trie::diff::{TrieDiff, TrieDiffItem};
 use crate::trie::insert_delete::NodesStorage;
 use crate::trie::iterator::TrieIterator;
 use crate::trie::nibble_slice::NibbleSlice;
@@ -24,6 +26,8 @@ pub(crate) use crate::trie::trie_storage::{TrieCache, TrieCachingStorage};
 use crate::StorageError;
 use std::cell::RefCell;
 
+mod audit;
+mod diff;
 mod insert_delete;
 pub mod iterator;
 mod nibble_slice;
@@ -436,6 +440,88 @@ impl TrieChanges {
     pub fn empty(old_root: StateRoot) -> Self {
         TrieChanges { old_root, new_root: old_root, insertions: vec![], deletions: vec![] }
     }
+
+    /// Keys in `ColState` of the nodes and values that are no longer referenced by the new root,
+    /// i.e. the ones that only belong to the state before the change.
+    pub fn deleted_state_keys(&self, shard_id: ShardId) -> Vec<Vec<u8>> {
+        self.deletions
+            .iter()
+            .map(|(hash, _value, _rc)| {
+                TrieCachingStorage::get_key_from_shard_id_and_hash(shard_id, hash).to_vec()
+            })
+            .collect()
+    }
+
+    /// Returns the values of `keys` at the old root, looked up in the deleted nodes and values.
+    /// They are the paths of the old trie to the changed keys, so the old value of a changed key
+    /// is found without reading the trie. The value is `None` if the path or the value wasn't
+    /// deleted, which happens when the value of the key ended up unchanged, and it has to be
+    /// read from the trie instead.
+    pub fn get_deleted_values(
+        &self,
+        keys: &[Vec<u8>],
+    ) -> Result<Vec<Option<Option<Vec<u8>>>>, StorageError> {
+        let deleted: HashMap<&CryptoHash, &Vec<u8>> =
+            self.deletions.iter().map(|(hash, value, _rc)| (hash, value)).collect();
+        keys.iter().map(|key| self.get_deleted_value(&deleted, key)).collect()
+    }
+
+    fn get_deleted_value(
+        &self,
+        deleted: &HashMap<&CryptoHash, &Vec<u8>>,
+        key: &[u8],
+    ) -> Result<Option<Option<Vec<u8>>>, StorageError> {
+        let deleted_value =
+            |value_hash: &CryptoHash| Ok(deleted.get(value_hash).map(|value| Some(value.to_vec())));
+        let mut hash = self.old_root;
+        let mut key = NibbleSlice::new(key);
+        loop {
+            if hash == Trie::empty_root() {
+                return Ok(Some(None));
+            }
+            let bytes = match deleted.get(&hash) {
+                Some(bytes) => bytes,
+                None => return Ok(None),
+            };
+            let node = RawTrieNodeWithSize::decode(bytes).map_err(|_| {
+                StorageError::StorageInconsistentState("RawTrieNode decode failed".to_string())
+            })?;
+            match node.node {
+                RawTrieNode::Leaf(existing_key, _, value_hash) => {
+                    if NibbleSlice::from_encoded(&existing_key).0 == key {
+                        return deleted_value(&value_hash);
+                    } else {
+                        return Ok(Some(None));
+                    }
+                }
+                RawTrieNode::Extension(existing_key, child) => {
+                    let existing_key = NibbleSlice::from_encoded(&existing_key).0;
+                    if key.starts_with(&existing_key) {
+                        hash = child;
+                        key = key.mid(existing_key.len());
+                    } else {
+                        return Ok(Some(None));
+                    }
+                }
+                RawTrieNode::Branch(mut children, value) => {
+                    if key.is_empty() {
+                        match value {
+                            Some((_, value_hash)) => return deleted_value(&value_hash),
+                            None => return Ok(Some(None)),
+                        }
+                    } else {
+                        match children[key.at(0) as usize].take() {
+                            Some(child) => {
+                                hash = child;
+                                key = key.mid(1);
+                            }
+                            None => return Ok(Some(None)),
+                        }
+                    }
+                }
+            };
+        }
+    }
 }
 
 impl Trie {
@@ -1016,6 +1102,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_get_deleted_values() {
+        let tries = create_tries();
+        let trie = tries.get_trie_for_shard(0);
+        let changes = vec![
+            (b"dog".to_vec(), Some(b"puppy".to_vec())),
+            (b"dog2".to_vec(), Some(b"puppy".to_vec())),
+            (b"horse".to_vec(), Some(b"stallion".to_vec())),
+        ];
+        let root = test_populate_trie(&tries, &Trie::empty_root(), 0, changes);
+        let changes = vec![
+            (b"dog".to_vec(), Some(b"doge".to_vec())),
+            (b"dog2".to_vec(), None),
+            (b"cat".to_vec(), Some(b"kitten".to_vec())),
+            (b"horse".to_vec(), Some(b"stallion".to_vec())),
+        ];
+        let keys: Vec<_> = changes.iter().map(|(key, _)| key.clone()).collect();
+        let trie_changes = trie.update(&root, changes.into_iter()).unwrap();
+        // `horse` keeps its value, so its leaf and value are not deleted and it has to be read
+        // from the trie.
+        assert_eq!(
+            trie_changes.get_deleted_values(&keys).unwrap(),
+            vec![Some(Some(b"puppy".to_vec())), Some(Some(b"puppy".to_vec())), Some(None), None]
+        );
+
+        let mut rng = rand::thread_rng();
+        for _test_run in 0..10 {
+            let tries = create_tries();
+            let trie = tries.get_trie_for_shard(0);
+            let root =
+                test_populate_trie(&tries, &Trie::empty_root(), 0, gen_changes(&mut rng, 50));
+            let changes = simplify_changes(&gen_changes(&mut rng, 50));
+            let keys: Vec<_> = changes.iter().map(|(key, _)| key.clone()).collect();
+            let trie_changes = trie.update(&root, changes.into_iter()).unwrap();
+            for (key, value) in keys.iter().zip(trie_changes.get_deleted_values(&keys).unwrap()) {
+                if let Some(value) = value {
+                    assert_eq!(value, trie.get(&root, key).unwrap());
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_trie_restart() {
         let store = create_test_store();
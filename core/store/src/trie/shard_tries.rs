@@ -1,4 +1,5 @@
 use crate::db::{DBCol, DBOp, DBTransaction};
+use crate::flat_state::{FlatState, FlatStateDelta, FlatStateValueChange};
 use crate::trie::trie_storage::{TrieCache, TrieCachingStorage};
 use crate::{StorageError, Store, StoreUpdate, Trie, TrieChanges, TrieUpdate};
 use borsh::BorshSerialize;
@@ -38,8 +39,13 @@ impl ShardTries {
         TrieUpdate::new(Rc::new(self.get_trie_for_shard(shard_id)), state_root)
     }
 
+    /// Returns a `TrieUpdate` for reading the state, which reads the values from the flat state
+    /// of the shard if it's at `state_root`.
     pub fn new_trie_update_view(&self, shard_id: ShardId, state_root: CryptoHash) -> TrieUpdate {
-        TrieUpdate::new(Rc::new(self.get_view_trie_for_shard(shard_id)), state_root)
+        let mut trie_update =
+            TrieUpdate::new(Rc::new(self.get_view_trie_for_shard(shard_id)), state_root);
+        trie_update.flat_state = FlatState::new(self.store.clone(), shard_id, &state_root);
+        trie_update
     }
 
     fn get_trie_for_shard_internal(&self, shard_id: ShardId, is_view: bool) -> Trie {
@@ -226,6 +232,42 @@ impl WrappedTrieChanges {
         WrappedTrieChanges { tries, shard_id, trie_changes, state_changes, block_hash }
     }
 
+    pub fn shard_id(&self) -> ShardId {
+        self.shard_id
+    }
+
+    pub fn block_hash(&self) -> &CryptoHash {
+        &self.block_hash
+    }
+
+    /// Returns the old and new values of the keys changed by the block. The old values are
+    /// taken from the deletions of the trie changes, the trie at the state root before the block
+    /// is only read for the keys whose value ended up unchanged.
+    ///
+    /// NOTE: must be called before `state_changes_into`, which drains the changes.
+    pub fn flat_state_delta(&self) -> Result<FlatStateDelta, StorageError> {
+        let trie = self.tries.get_trie_for_shard(self.shard_id);
+        let keys: Vec<_> = self
+            .state_changes
+            .iter()
+            .map(|change_with_trie_key| change_with_trie_key.trie_key.to_vec())
+            .collect();
+        let deleted_values = self.trie_changes.get_deleted_values(&keys)?;
+        let mut changes = Vec::with_capacity(self.state_changes.len());
+        for ((change_with_trie_key, key), deleted_value) in
+            self.state_changes.iter().zip(keys).zip(deleted_values)
+        {
+            let old_value = match deleted_value {
+                Some(old_value) => old_value,
+                None => trie.get(&self.trie_changes.old_root, &key)?,
+            };
+            let new_value =
+                change_with_trie_key.changes.last().and_then(|change| change.data.clone());
+            changes.push(FlatStateValueChange { key, old_value, new_value });
+        }
+        Ok(FlatStateDelta { changes })
+    }
+
     pub fn insertions_into(&self, store_update: &mut StoreUpdate) -> Result<(), StorageError> {
         self.tries.apply_insertions(&self.trie_changes, self.shard_id, store_update)
     }
@@ -6,6 +6,7 @@ use near_primitives::types::{
     RawStateChange, RawStateChanges, RawStateChangesWithTrieKey, StateChangeCause,
 };
 
+use crate::flat_state::FlatState;
 use crate::trie::TrieChanges;
 use crate::StorageError;
 
@@ -28,6 +29,8 @@ pub struct TrieUpdate {
     root: CryptoHash,
     committed: RawStateChanges,
     prospective: TrieUpdates,
+    /// Flat state at `root`, read instead of the trie when it's available.
+    pub(crate) flat_state: Option<FlatState>,
 }
 
 pub enum TrieUpdateValuePtr<'a> {
@@ -53,7 +56,13 @@ impl<'a> TrieUpdateValuePtr<'a> {
 
 impl TrieUpdate {
     pub fn new(trie: Rc<Trie>, root: CryptoHash) -> Self {
-        TrieUpdate { trie, root, committed: Default::default(), prospective: Default::default() }
+        TrieUpdate {
+            trie,
+            root,
+            committed: Default::default(),
+            prospective: Default::default(),
+            flat_state: None,
+        }
     }
 
     pub fn trie(&self) -> &Trie {
@@ -69,6 +78,11 @@ impl TrieUpdate {
                 return Ok(data.as_ref().map(<Vec<u8>>::clone));
             }
         }
+        if let Some(flat_state) = &self.flat_state {
+            if let Some(value) = flat_state.get(&key)? {
+                return Ok(value);
+            }
+        }
 
         self.trie.get(&self.root, &key)
     }
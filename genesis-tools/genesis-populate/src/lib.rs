@@ -21,10 +21,8 @@ use near_primitives::state_record::StateRecord;
 use near_primitives::types::{
     AccountId, Balance, ChunkExtra, EpochId, ShardId, StateChangeCause, StateRoot,
 };
-use near_store::{
-    create_store, get_account, set_access_key, set_account, set_code, ColState, Store, TrieUpdate,
-};
-use neard::{get_store_path, NightshadeRuntime};
+use near_store::{get_account, set_access_key, set_account, set_code, ColState, Store, TrieUpdate};
+use neard::{get_store_path, open_store, NearConfig, NightshadeRuntime};
 
 fn get_account_id(account_index: u64) -> String {
     format!("near_{}_{}", account_index, account_index)
@@ -84,9 +82,9 @@ impl GenesisBuilder {
         }
     }
 
-    pub fn from_config(home_dir: &Path, genesis: Arc<Genesis>) -> Self {
-        let store = create_store(&get_store_path(home_dir));
-        Self::from_config_and_store(home_dir, genesis, store)
+    pub fn from_config(home_dir: &Path, near_config: NearConfig) -> Self {
+        let store = open_store(home_dir, &get_store_path(home_dir), &near_config);
+        Self::from_config_and_store(home_dir, Arc::new(near_config.genesis), store)
     }
 
     pub fn print_progress(mut self) -> Self {
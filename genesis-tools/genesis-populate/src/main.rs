@@ -1,10 +1,8 @@
 use std::path::Path;
-use std::sync::Arc;
 
 use clap::{App, Arg};
 
-use near_store::create_store;
-use neard::{get_default_home, get_store_path, load_config};
+use neard::{get_default_home, load_config};
 
 use genesis_populate::GenesisBuilder;
 
@@ -28,8 +26,7 @@ fn main() {
         .unwrap();
     let near_config = load_config(home_dir);
 
-    let store = create_store(&get_store_path(home_dir));
-    GenesisBuilder::from_config_and_store(home_dir, Arc::new(near_config.genesis), store)
+    GenesisBuilder::from_config(home_dir, near_config)
         .add_additional_accounts(additional_accounts_num)
         .add_additional_accounts_contract(
             include_bytes!(
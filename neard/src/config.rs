@@ -14,9 +14,14 @@ use num_rational::Rational;
 use serde::{Deserialize, Serialize};
 
 use lazy_static::lazy_static;
-use near_chain_configs::{ClientConfig, Genesis, GenesisConfig, LogSummaryStyle};
+use near_chain_configs::{
+    ClientConfig, Genesis, GenesisConfig, LogSummaryStyle, TransactionPoolConfig,
+};
 use near_crypto::{InMemorySigner, KeyFile, KeyType, PublicKey, Signer};
 use near_jsonrpc::RpcConfig;
+use near_network::compression::CompressionConfig;
+use near_network::encryption::EncryptionMode;
+use near_network::rate_limiter::RateLimitsConfig;
 use near_network::test_utils::open_port;
 use near_network::types::ROUTED_MESSAGE_TTL;
 use near_network::utils::blacklist_from_iter;
@@ -34,6 +39,7 @@ use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner
 use near_primitives::version::PROTOCOL_VERSION;
 #[cfg(feature = "rosetta_rpc")]
 use near_rosetta_rpc::RosettaRpcConfig;
+use near_store::StoreConfig;
 use near_telemetry::TelemetryConfig;
 
 /// Initial balance used in tests.
@@ -245,6 +251,22 @@ pub struct Network {
     /// Period to check on peer status
     #[serde(default = "default_peer_stats_period")]
     pub peer_stats_period: Duration,
+    /// Limits on the rate of messages of each type received from a peer.
+    /// Peers which keep exceeding them are banned.
+    #[serde(default)]
+    pub rate_limits: RateLimitsConfig,
+    /// File to capture all messages sent and received by the node to, for debugging.
+    /// Requires the `metric_recorder` feature.
+    #[serde(default)]
+    pub capture_path: Option<PathBuf>,
+    /// Whether to encrypt the connections with peers: `enabled` to encrypt them with the peers
+    /// that support it, `required` to only accept encrypted connections, or `disabled`, in which
+    /// case the peers that support encryption only connect if they have it disabled too.
+    #[serde(default)]
+    pub encryption: EncryptionMode,
+    /// Compression of the large messages sent to peers that support it.
+    #[serde(default)]
+    pub compression: CompressionConfig,
 }
 
 impl Default for Network {
@@ -267,6 +289,10 @@ impl Default for Network {
             blacklist: vec![],
             ttl_account_id_router: default_ttl_account_id_router(),
             peer_stats_period: default_peer_stats_period(),
+            rate_limits: RateLimitsConfig::default(),
+            capture_path: None,
+            encryption: EncryptionMode::default(),
+            compression: CompressionConfig::default(),
         }
     }
 }
@@ -419,6 +445,8 @@ pub struct Config {
     pub gc_blocks_limit: NumBlocks,
     #[serde(default = "default_view_client_threads")]
     pub view_client_threads: usize,
+    pub transaction_pool: TransactionPoolConfig,
+    pub store: StoreConfig,
 }
 
 impl Default for Config {
@@ -440,6 +468,8 @@ impl Default for Config {
             log_summary_style: LogSummaryStyle::Colored,
             gc_blocks_limit: default_gc_blocks_limit(),
             view_client_threads: 4,
+            transaction_pool: TransactionPoolConfig::default(),
+            store: StoreConfig::default(),
         }
     }
 }
@@ -553,6 +583,7 @@ pub struct NearConfig {
     #[cfg(feature = "rosetta_rpc")]
     pub rosetta_rpc_config: Option<RosettaRpcConfig>,
     pub telemetry_config: TelemetryConfig,
+    pub store_config: StoreConfig,
     pub genesis: Genesis,
     pub validator_signer: Option<Arc<dyn ValidatorSigner>>,
 }
@@ -606,6 +637,7 @@ impl NearConfig {
                 log_summary_style: config.log_summary_style,
                 gc_blocks_limit: config.gc_blocks_limit,
                 view_client_threads: config.view_client_threads,
+                transaction_pool: config.transaction_pool,
             },
             network_config: NetworkConfig {
                 public_key: network_key_pair.public_key,
@@ -650,8 +682,13 @@ impl NearConfig {
                 blacklist: blacklist_from_iter(config.network.blacklist),
                 outbound_disabled: false,
                 archive: config.archive,
+                rate_limits: config.network.rate_limits,
+                capture_path: config.network.capture_path,
+                encryption: config.network.encryption,
+                compression: config.network.compression,
             },
             telemetry_config: config.telemetry,
+            store_config: config.store,
             rpc_config: config.rpc,
             #[cfg(feature = "rosetta_rpc")]
             rosetta_rpc_config: config.rosetta_rpc,
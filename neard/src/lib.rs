@@ -3,37 +3,39 @@ use std::path::Path;
 use std::sync::Arc;
 
 use actix::{Actor, Addr, Arbiter};
-use log::{error, info};
+use log::info;
 use tracing::trace;
 
-use near_chain::ChainGenesis;
+use near_chain::{ChainGenesis, ChainStore, ChainStoreAccess};
 #[cfg(feature = "adversarial")]
 use near_client::AdversarialControls;
-use near_client::{start_client, start_view_client, ClientActor, ViewClientActor};
-use near_jsonrpc::start_http;
+use near_client::{
+    start_client, start_cold_store, start_view_client, ClientActor, ViewClientActor,
+};
+use near_jsonrpc::{start_admin_http, start_http};
 use near_network::{NetworkRecipient, PeerManagerActor};
+use near_primitives::types::ShardId;
 #[cfg(feature = "rosetta_rpc")]
 use near_rosetta_rpc::start_rosetta_rpc;
-use near_store::{create_store, Store};
+use near_store::flat_state::{build_flat_state, get_flat_state_head, remove_all_flat_state};
+use near_store::{
+    create_read_only_split_store_with_config, create_read_only_store_with_config,
+    create_split_store_with_config, create_store_with_config, ShardTries, Store,
+};
 use near_telemetry::TelemetryActor;
 
 pub use crate::config::{init_configs, load_config, load_test_config, NearConfig, NEAR_BASE};
-use crate::migrations::migrate_12_to_13;
+use crate::migrations::apply_store_migrations;
 pub use crate::runtime::NightshadeRuntime;
-use near_store::migrations::{
-    fill_col_outcomes_by_hash, fill_col_transaction_refcount, get_store_version, migrate_10_to_11,
-    migrate_11_to_12, migrate_13_to_14, migrate_14_to_15, migrate_6_to_7, migrate_7_to_8,
-    migrate_8_to_9, migrate_9_to_10, set_store_version,
-};
-
-#[cfg(feature = "protocol_feature_rectify_inflation")]
-use near_store::migrations::migrate_16_to_rectify_inflation;
+use near_store::migrations::{get_store_version, set_store_version};
 
 pub mod config;
 pub mod genesis_validate;
-mod migrations;
+pub mod migrations;
 mod runtime;
 mod shard_tracker;
+pub mod state_snapshot;
+pub mod store_admin;
 
 const STORE_PATH: &str = "data";
 
@@ -65,134 +67,36 @@ pub fn get_default_home() -> String {
     }
 }
 
-/// Function checks current version of the database and applies migrations to the database.
-pub fn apply_store_migrations(path: &String, near_config: &NearConfig) {
-    let db_version = get_store_version(path);
-    if db_version > near_primitives::version::DB_VERSION {
-        error!(target: "near", "DB version {} is created by a newer version of neard, please update neard or delete data", db_version);
-        std::process::exit(1);
-    }
-    if db_version == near_primitives::version::DB_VERSION {
-        return;
-    }
-
-    // Add migrations here based on `db_version`.
-    if db_version <= 1 {
-        // version 1 => 2: add gc column
-        // Does not need to do anything since open db with option `create_missing_column_families`
-        // Nevertheless need to bump db version, because db_version 1 binary can't open db_version 2 db
-        info!(target: "near", "Migrate DB from version 1 to 2");
-        let store = create_store(&path);
-        set_store_version(&store, 2);
-    }
-    if db_version <= 2 {
-        // version 2 => 3: add ColOutcomesByBlockHash + rename LastComponentNonce -> ColLastComponentNonce
-        // The column number is the same, so we don't need additional updates
-        info!(target: "near", "Migrate DB from version 2 to 3");
-        let store = create_store(&path);
-        fill_col_outcomes_by_hash(&store);
-        set_store_version(&store, 3);
-    }
-    if db_version <= 3 {
-        // version 3 => 4: add ColTransactionRefCount
-        info!(target: "near", "Migrate DB from version 3 to 4");
-        let store = create_store(&path);
-        fill_col_transaction_refcount(&store);
-        set_store_version(&store, 4);
-    }
-    if db_version <= 4 {
-        info!(target: "near", "Migrate DB from version 4 to 5");
-        // version 4 => 5: add ColProcessedBlockHeights
-        // we don't need to backfill the old heights since at worst we will just process some heights
-        // again.
-        let store = create_store(&path);
-        set_store_version(&store, 5);
-    }
-    if db_version <= 5 {
-        info!(target: "near", "Migrate DB from version 5 to 6");
-        // version 5 => 6: add merge operator to ColState
-        // we don't have merge records before so old storage works
-        let store = create_store(&path);
-        set_store_version(&store, 6);
-    }
-    if db_version <= 6 {
-        info!(target: "near", "Migrate DB from version 6 to 7");
-        // version 6 => 7:
-        // - make ColState use 8 bytes for refcount (change to merge operator)
-        // - move ColTransactionRefCount into ColTransactions
-        // - make ColReceiptIdToShardId refcounted
-        migrate_6_to_7(path);
-    }
-    if db_version <= 7 {
-        info!(target: "near", "Migrate DB from version 7 to 8");
-        // version 7 => 8:
-        // delete values in column `StateColParts`
-        migrate_7_to_8(path);
-    }
-    if db_version <= 8 {
-        info!(target: "near", "Migrate DB from version 8 to 9");
-        // version 8 => 9:
-        // Repair `ColTransactions`, `ColReceiptIdToShardId`
-        migrate_8_to_9(path);
-    }
-    if db_version <= 9 {
-        info!(target: "near", "Migrate DB from version 9 to 10");
-        // version 9 => 10;
-        // populate partial encoded chunks for chunks that exist in storage
-        migrate_9_to_10(path, near_config.client_config.archive);
-    }
-    if db_version <= 10 {
-        info!(target: "near", "Migrate DB from version 10 to 11");
-        // version 10 => 11
-        // Add final head
-        migrate_10_to_11(path);
-    }
-    if db_version <= 11 {
-        info!(target: "near", "Migrate DB from version 11 to 12");
-        // version 11 => 12;
-        // populate ColReceipts with existing receipts
-        migrate_11_to_12(path);
-    }
-    if db_version <= 12 {
-        info!(target: "near", "Migrate DB from version 12 to 13");
-        // version 12 => 13;
-        // migrate ColTransactionResult to fix the inconsistencies there
-        migrate_12_to_13(path, near_config);
-    }
-    if db_version <= 13 {
-        info!(target: "near", "Migrate DB from version 13 to 14");
-        // version 13 => 14;
-        // store versioned enums for shard chunks
-        migrate_13_to_14(path);
-    }
-    if db_version <= 14 {
-        info!(target: "near", "Migrate DB from version 14 to 15");
-        // version 14 => 15;
-        // Change ColOutcomesByBlockHash to be ordered within each shard
-        migrate_14_to_15(path);
-    }
-    if db_version <= 15 {
-        info!(target: "near", "Migrate DB from version 15 to 16");
-        // version 15 => 16: add column for compiled contracts
-        let store = create_store(&path);
-        set_store_version(&store, 16);
-    }
-    #[cfg(feature = "protocol_feature_rectify_inflation")]
-    if db_version <= 16 {
-        // version 16 => rectify inflation: add `timestamp` to `BlockInfo`
-        migrate_16_to_rectify_inflation(&path);
-    }
-    #[cfg(feature = "nightly_protocol")]
-    {
-        let store = create_store(&path);
-        // set some dummy value to avoid conflict with other migrations from nightly features
-        set_store_version(&store, 10000);
+/// Opens the store at `path` with the configured backend. Archival nodes with cold storage
+/// configured get their store split into the hot and the cold databases.
+pub fn open_store(home_dir: &Path, path: &str, near_config: &NearConfig) -> Arc<Store> {
+    match &near_config.store_config.cold_store_path {
+        Some(cold_store_path) if near_config.client_config.archive => {
+            let cold_path = home_dir.join(cold_store_path);
+            info!(target: "near", "Opening cold store database at {:?}", cold_path);
+            create_split_store_with_config(
+                path,
+                cold_path.to_str().unwrap(),
+                &near_config.store_config,
+            )
+        }
+        _ => create_store_with_config(path, &near_config.store_config),
     }
+}
 
-    #[cfg(not(feature = "nightly_protocol"))]
-    {
-        let db_version = get_store_version(path);
-        debug_assert_eq!(db_version, near_primitives::version::DB_VERSION);
+/// Opens the existing store at `path` without changing it, the read-only counterpart of
+/// `open_store`.
+pub fn open_read_only_store(home_dir: &Path, path: &str, near_config: &NearConfig) -> Arc<Store> {
+    match &near_config.store_config.cold_store_path {
+        Some(cold_store_path) if near_config.client_config.archive => {
+            let cold_path = home_dir.join(cold_store_path);
+            create_read_only_split_store_with_config(
+                path,
+                cold_path.to_str().unwrap(),
+                &near_config.store_config,
+            )
+        }
+        _ => create_read_only_store_with_config(path, &near_config.store_config),
     }
 }
 
@@ -200,15 +104,106 @@ pub fn init_and_migrate_store(home_dir: &Path, near_config: &NearConfig) -> Arc<
     let path = get_store_path(home_dir);
     let store_exists = store_path_exists(&path);
     if store_exists {
-        apply_store_migrations(&path, near_config);
+        apply_store_migrations(
+            home_dir,
+            &path,
+            near_config,
+            near_config.store_config.migration_checkpoints,
+        );
     }
-    let store = create_store(&path);
+    let store = open_store(home_dir, &path, near_config);
     if !store_exists {
         set_store_version(&store, near_primitives::version::DB_VERSION);
     }
+    init_flat_state(&store, near_config);
     store
 }
 
+/// Builds the flat state of the tracked shards at the chain head if it's enabled and missing,
+/// e.g. after it was disabled by state sync, or removes it if it's disabled in the config.
+fn init_flat_state(store: &Arc<Store>, near_config: &NearConfig) {
+    let mut chain_store = ChainStore::new(store.clone(), near_config.genesis.config.genesis_height);
+    let head = match chain_store.head() {
+        Ok(head) => head,
+        Err(_) => return,
+    };
+    let num_shards = chain_store
+        .get_block_header(&head.last_block_hash)
+        .expect("Failed to read head block header")
+        .chunk_mask()
+        .len() as ShardId;
+    if !near_config.store_config.enable_flat_state {
+        let num_values =
+            remove_all_flat_state(store, num_shards).expect("Failed to remove flat state");
+        if num_values > 0 {
+            info!(target: "near", "Removed flat state: {} values", num_values);
+        }
+        return;
+    }
+    for shard_id in 0..num_shards {
+        if get_flat_state_head(store, shard_id).expect("Failed to read flat state head").is_some() {
+            continue;
+        }
+        // Shards that are not tracked have no state at the head.
+        let state_root = match chain_store.get_chunk_extra(&head.last_block_hash, shard_id) {
+            Ok(chunk_extra) => chunk_extra.state_root,
+            Err(_) => continue,
+        };
+        info!(target: "near", "Building flat state of shard {} at {}", shard_id, head.last_block_hash);
+        let tries = ShardTries::new(store.clone(), num_shards);
+        let num_values = build_flat_state(&tries, shard_id, &head.last_block_hash, &state_root)
+            .expect("Failed to build flat state");
+        info!(target: "near", "Built flat state of shard {}: {} values", shard_id, num_values);
+    }
+}
+
+/// Starts only `ViewClientActor` on top of an existing database, without `ClientActor`,
+/// network and JSON-RPC, so the stored chain can be read without joining the network.
+///
+/// The database must already be initialized with the genesis and migrated to the current
+/// version. It is opened with `open_read_only_store`, so it is never changed and a node may keep
+/// using it, but blocks added after the view client started are not seen.
+pub fn start_view_client_with_existing_store(
+    home_dir: &Path,
+    config: NearConfig,
+) -> Addr<ViewClientActor> {
+    let path = get_store_path(home_dir);
+    assert!(store_path_exists(&path), "Database at {} does not exist", path);
+    #[cfg(not(feature = "nightly_protocol"))]
+    {
+        let db_version = get_store_version(&path);
+        assert_eq!(
+            db_version,
+            near_primitives::version::DB_VERSION,
+            "Database version {} doesn't match the expected one, run neard to migrate it first",
+            db_version
+        );
+    }
+    let store = open_read_only_store(home_dir, &path, &config);
+
+    let runtime = Arc::new(NightshadeRuntime::with_existing_genesis_state(
+        store,
+        &config.genesis,
+        config.client_config.tracked_accounts.clone(),
+        config.client_config.tracked_shards.clone(),
+    ));
+    let chain_genesis = ChainGenesis::from(&config.genesis);
+    // Network is never started, so the recipient is never set.
+    let network_adapter = Arc::new(NetworkRecipient::new());
+    #[cfg(feature = "adversarial")]
+    let adv = Arc::new(std::sync::RwLock::new(AdversarialControls::default()));
+
+    start_view_client(
+        config.validator_signer.as_ref().map(|signer| signer.validator_id().clone()),
+        chain_genesis,
+        runtime,
+        network_adapter,
+        config.client_config,
+        #[cfg(feature = "adversarial")]
+        adv,
+    )
+}
+
 pub fn start_with_config(
     home_dir: &Path,
     config: NearConfig,
@@ -240,6 +235,9 @@ pub fn start_with_config(
         #[cfg(feature = "adversarial")]
         adv.clone(),
     );
+    let cold_store_arbiter =
+        start_cold_store(chain_genesis.clone(), runtime.clone(), &config.client_config)
+            .map(|(_, arbiter)| arbiter);
     let (client_actor, client_arbiter) = start_client(
         config.client_config,
         chain_genesis,
@@ -251,6 +249,7 @@ pub fn start_with_config(
         #[cfg(feature = "adversarial")]
         adv.clone(),
     );
+    let rpc_admin_addr = config.rpc_config.admin_addr.clone();
     start_http(
         config.rpc_config,
         config.genesis.config.clone(),
@@ -279,9 +278,15 @@ pub fn start_with_config(
         PeerManagerActor::new(store, network_config, client_actor1, view_client1).unwrap()
     });
 
-    network_adapter.set_recipient(network_actor.recipient());
+    network_adapter.set_recipient(network_actor.clone().recipient());
+
+    if let Some(rpc_admin_addr) = rpc_admin_addr {
+        start_admin_http(rpc_admin_addr, client_actor.clone(), view_client.clone(), network_actor);
+    }
 
     trace!(target: "diagnostic", key="log", "Starting NEAR node with diagnostic activated");
 
-    (client_actor, view_client, vec![client_arbiter, arbiter])
+    let mut arbiters = vec![client_arbiter, arbiter];
+    arbiters.extend(cold_store_arbiter);
+    (client_actor, view_client, arbiters)
 }
@@ -1,8 +1,9 @@
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use std::env;
 use std::fs;
 use std::io;
 use std::path::Path;
+use std::str::FromStr;
 
 use actix::System;
 use clap::{crate_version, App, AppSettings, Arg, SubCommand};
@@ -14,12 +15,24 @@ use tracing_subscriber::EnvFilter;
 
 use git_version::git_version;
 use near_performance_metrics;
+use near_primitives::hash::CryptoHash;
 use near_primitives::version::{Version, PROTOCOL_VERSION};
 #[cfg(feature = "memory_stats")]
 use near_rust_allocator_proxy::allocator::MyAllocator;
+use near_store::DBCol;
 use neard::config::init_testnet_configs;
 use neard::genesis_validate::validate_genesis;
-use neard::{get_default_home, get_store_path, init_configs, load_config, start_with_config};
+use neard::migrations::{
+    apply_store_migrations, estimate_store_migrations, rollback_store_migration, MigrationEstimate,
+};
+use neard::state_snapshot::{export_state_snapshot, import_state_snapshot};
+use neard::store_admin::{
+    backup_store, compact_store, restore_store_backup, store_stats, DatabaseStats,
+};
+use neard::{
+    get_default_home, get_store_path, init_configs, load_config, start_with_config,
+    store_path_exists,
+};
 
 #[cfg(feature = "memory_stats")]
 #[global_allocator]
@@ -117,6 +130,31 @@ fn main() {
             .arg(Arg::with_name("telemetry-url").long("telemetry-url").help("Customize telemetry url").takes_value(true))
             .arg(Arg::with_name("archive").long("archive").help("Keep old blocks in the storage (default false)").takes_value(false))
         )
+        .subcommand(SubCommand::with_name("export_state_snapshot").about("Exports the state of the shards at the beginning of an epoch, so that another node can import it instead of syncing it from peers (the node must be stopped)")
+            .arg(Arg::with_name("output").long("output").takes_value(true).required(true).help("Directory to write the snapshot to"))
+            .arg(Arg::with_name("sync-hash").long("sync-hash").takes_value(true).help("Hash of the first block of the epoch to export the state at (default is the epoch of the head)"))
+            .arg(Arg::with_name("shard-id").long("shard-id").takes_value(true).multiple(true).help("Shards to export (default is all shards)"))
+        )
+        .subcommand(SubCommand::with_name("import_state_snapshot").about("Imports a state snapshot made by export_state_snapshot, the node must be stopped and must have synced headers past the snapshot")
+            .arg(Arg::with_name("input").long("input").takes_value(true).required(true).help("Directory to read the snapshot from"))
+        )
+        .subcommand(SubCommand::with_name("store_stats").about("Prints size statistics of the database columns (the node must be stopped)")
+            .arg(Arg::with_name("column").long("column").takes_value(true).multiple(true).help("Columns to report, e.g. ColState (default is all columns)"))
+        )
+        .subcommand(SubCommand::with_name("compact_store").about("Compacts the database columns and prints their size statistics (the node must be stopped)")
+            .arg(Arg::with_name("column").long("column").takes_value(true).multiple(true).help("Columns to compact, e.g. ColState (default is all columns)"))
+        )
+        .subcommand(SubCommand::with_name("backup").about("Creates a backup of the database (the node must be stopped, use the EXPERIMENTAL_create_checkpoint admin RPC method for a running node)")
+            .arg(Arg::with_name("output").long("output").takes_value(true).required(true).help("Directory to write the backup to, must not exist"))
+        )
+        .subcommand(SubCommand::with_name("restore_backup").about("Restores the database from a backup (the node must be stopped and have no database)")
+            .arg(Arg::with_name("input").long("input").takes_value(true).required(true).help("Directory with the backup"))
+        )
+        .subcommand(SubCommand::with_name("migrate_store").about("Migrates the database to the version of this binary, which is otherwise done on start (the node must be stopped)")
+            .arg(Arg::with_name("dry-run").long("dry-run").takes_value(false).help("Print the pending migrations and the estimated number of keys they touch without migrating"))
+            .arg(Arg::with_name("no-checkpoint").long("no-checkpoint").takes_value(false).help("Don't create checkpoints of the database, an interrupted or failed migration can't be resumed or rolled back then"))
+        )
+        .subcommand(SubCommand::with_name("rollback_migration").about("(unsafe) Replaces the database with the checkpoint taken before the last migration, the data written since then is lost (the node must be stopped)"))
         .subcommand(SubCommand::with_name("unsafe_reset_data").about("(unsafe) Remove all the data, effectively resetting node to genesis state (keeps genesis and config)"))
         .subcommand(SubCommand::with_name("unsafe_reset_all").about("(unsafe) Remove all the config, keys, data and effectively removing all information about the network"))
         .get_matches();
@@ -250,6 +288,80 @@ fn main() {
                 })
                 .unwrap();
         }
+        ("export_state_snapshot", Some(args)) => {
+            let near_config = load_config(home_dir);
+            let output_dir = args.value_of("output").map(|dir| Path::new(dir)).unwrap();
+            let sync_hash = args.value_of("sync-hash").map(|hash| {
+                CryptoHash::try_from(hash).expect("Failed to parse hash for sync-hash")
+            });
+            let shard_ids = args.values_of("shard-id").map(|shard_ids| {
+                shard_ids
+                    .map(|shard_id| shard_id.parse().expect("Failed to parse number for shard-id"))
+                    .collect()
+            });
+            let manifest =
+                export_state_snapshot(home_dir, &near_config, sync_hash, shard_ids, output_dir)
+                    .expect("Failed to export state snapshot");
+            info!(target: "near", "Exported state of {} shards at {} to {}", manifest.shards.len(), manifest.sync_hash, output_dir.display());
+        }
+        ("import_state_snapshot", Some(args)) => {
+            let near_config = load_config(home_dir);
+            let input_dir = args.value_of("input").map(|dir| Path::new(dir)).unwrap();
+            let manifest = import_state_snapshot(home_dir, &near_config, input_dir)
+                .expect("Failed to import state snapshot");
+            info!(target: "near", "Imported state of {} shards at {} from {}", manifest.shards.len(), manifest.sync_hash, input_dir.display());
+        }
+        ("store_stats", Some(args)) => {
+            let near_config = load_config(home_dir);
+            let stats = store_stats(home_dir, &near_config, parse_columns(args))
+                .expect("Failed to get store statistics");
+            print_column_stats(&stats);
+        }
+        ("compact_store", Some(args)) => {
+            let near_config = load_config(home_dir);
+            let stats = compact_store(home_dir, &near_config, parse_columns(args))
+                .expect("Failed to compact store");
+            print_column_stats(&stats);
+        }
+        ("backup", Some(args)) => {
+            let near_config = load_config(home_dir);
+            let output_dir = args.value_of("output").map(|dir| Path::new(dir)).unwrap();
+            let manifest =
+                backup_store(home_dir, &near_config, output_dir).expect("Failed to create backup");
+            info!(target: "near", "Created backup at height {} (block {}, db version {}) in {}", manifest.height, manifest.block_hash, manifest.db_version, output_dir.display());
+        }
+        ("restore_backup", Some(args)) => {
+            let input_dir = args.value_of("input").map(|dir| Path::new(dir)).unwrap();
+            let manifest =
+                restore_store_backup(home_dir, input_dir).expect("Failed to restore backup");
+            info!(target: "near", "Restored backup at height {} (block {}, db version {}) from {}", manifest.height, manifest.block_hash, manifest.db_version, input_dir.display());
+        }
+        ("migrate_store", Some(args)) => {
+            let near_config = load_config(home_dir);
+            if args.is_present("dry-run") {
+                let estimates = estimate_store_migrations(home_dir, &near_config)
+                    .expect("Failed to estimate migrations");
+                print_migration_estimates(&estimates);
+            } else {
+                let store_path = get_store_path(home_dir);
+                assert!(
+                    store_path_exists(&store_path),
+                    "Database at {} does not exist",
+                    store_path
+                );
+                apply_store_migrations(
+                    home_dir,
+                    &store_path,
+                    &near_config,
+                    !args.is_present("no-checkpoint"),
+                );
+            }
+        }
+        ("rollback_migration", Some(_args)) => {
+            let manifest =
+                rollback_store_migration(home_dir).expect("Failed to roll back migration");
+            info!(target: "near", "Restored database of version {} at height {} (block {})", manifest.db_version, manifest.height, manifest.block_hash);
+        }
         ("unsafe_reset_data", Some(_args)) => {
             let store_path = get_store_path(home_dir);
             info!(target: "near", "Removing all data from {}", store_path);
@@ -262,3 +374,49 @@ fn main() {
         (_, _) => unreachable!(),
     }
 }
+
+fn parse_columns(args: &clap::ArgMatches) -> Option<Vec<DBCol>> {
+    args.values_of("column").map(|columns| {
+        columns
+            .map(|column| DBCol::from_str(column).expect("Failed to parse column name"))
+            .collect()
+    })
+}
+
+fn print_migration_estimates(estimates: &[MigrationEstimate]) {
+    if estimates.is_empty() {
+        println!("Database is up to date");
+        return;
+    }
+    println!("{:<8} {:>16}  {}", "version", "estimated keys", "migration");
+    for estimate in estimates {
+        println!(
+            "{:<8} {:>16}  {}",
+            format!("{} => {}", estimate.from_version, estimate.from_version + 1),
+            estimate.estimated_num_keys,
+            estimate.description
+        );
+    }
+}
+
+fn print_column_stats(stats: &[DatabaseStats]) {
+    for database_stats in stats {
+        if stats.len() > 1 {
+            println!("{} database:", database_stats.name);
+        }
+        println!(
+            "{:<32} {:>16} {:>16} {:>16} {:>16}",
+            "column", "estimated keys", "live data size", "sst files size", "mem tables size"
+        );
+        for column_stats in database_stats.columns.iter() {
+            println!(
+                "{:<32} {:>16} {:>16} {:>16} {:>16}",
+                format!("{:?}", column_stats.col),
+                column_stats.estimated_num_keys,
+                column_stats.estimated_live_data_size,
+                column_stats.sst_files_size,
+                column_stats.mem_tables_size
+            );
+        }
+    }
+}
@@ -1,14 +1,44 @@
-use crate::{NearConfig, NightshadeRuntime};
+use crate::{
+    get_store_path, open_read_only_store, open_store, store_path_exists, NearConfig,
+    NightshadeRuntime,
+};
 use borsh::BorshDeserialize;
+use log::{error, info, warn};
 use near_chain::chain::collect_receipts_from_response;
 use near_chain::types::ApplyTransactionResult;
 use near_chain::{ChainStore, ChainStoreAccess, ChainStoreUpdate, RuntimeAdapter};
 use near_primitives::sharding::{ChunkHash, ShardChunkHeader, ShardChunkV1};
 use near_primitives::transaction::ExecutionOutcomeWithIdAndProof;
 use near_primitives::types::{BlockHeight, ShardId};
-use near_store::migrations::set_store_version;
-use near_store::{create_store, DBCol, StoreUpdate};
-use std::path::Path;
+use near_primitives::version::{DbVersion, DB_VERSION};
+use near_store::checkpoint::{
+    create_checkpoint, read_checkpoint_manifest, restore_checkpoint, CheckpointManifest,
+};
+#[cfg(feature = "protocol_feature_rectify_inflation")]
+use near_store::migrations::migrate_16_to_rectify_inflation;
+use near_store::migrations::{
+    fill_col_outcomes_by_hash, fill_col_transaction_refcount, get_store_version, migrate_10_to_11,
+    migrate_11_to_12, migrate_13_to_14, migrate_14_to_15, migrate_6_to_7, migrate_7_to_8,
+    migrate_8_to_9, migrate_9_to_10, set_store_version,
+};
+use near_store::{create_store_with_config, DBBackend, DBCol, Store, StoreUpdate};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Directory in the home directory with the checkpoints of the database taken before migrations.
+const MIGRATION_CHECKPOINTS_DIR: &str = "migration_checkpoints";
+/// File in `MIGRATION_CHECKPOINTS_DIR` with the version the database is being migrated from.
+/// It exists only while a migration step runs, so that an interrupted step is restarted from
+/// its checkpoint instead of running again on partially migrated data.
+const MIGRATION_IN_PROGRESS_FILE: &str = "in_progress";
+
+/// Opens the database being migrated. Migrations only run on RocksDB without cold storage, see
+/// `check_store_version`, so the store config only contributes the column options.
+fn open_migrated_store(path: &str, near_config: &NearConfig) -> Arc<Store> {
+    create_store_with_config(path, &near_config.store_config)
+}
 
 fn get_chunk(chain_store: &ChainStore, chunk_hash: ChunkHash) -> ShardChunkV1 {
     let store = chain_store.store();
@@ -74,7 +104,7 @@ fn apply_block_at_height(
 }
 
 pub fn migrate_12_to_13(path: &String, near_config: &NearConfig) {
-    let store = create_store(path);
+    let store = open_migrated_store(path, near_config);
     if !near_config.client_config.archive {
         // Non archival node. Perform a simply migration without necessarily fixing the inconsistencies
         // since the old data will be garbage collected in five epochs
@@ -117,3 +147,539 @@ pub fn migrate_12_to_13(path: &String, near_config: &NearConfig) {
     }
     set_store_version(&store, 13);
 }
+
+/// Migration of the database from `from_version` to `from_version + 1`.
+struct Migration {
+    from_version: DbVersion,
+    description: &'static str,
+    /// Columns read or rewritten by the migration, used to estimate the number of affected keys.
+    columns: &'static [DBCol],
+    /// Migrates the database at the given path. The version is set after it returns.
+    run: fn(&String, &NearConfig),
+}
+
+/// All migrations in the order they are applied. A migration is added here together with the
+/// increment of `DB_VERSION`.
+fn migrations() -> Vec<Migration> {
+    vec![
+        Migration {
+            from_version: 1,
+            // The column is created when the database is opened with
+            // `create_missing_column_families`, but a binary of version 1 can't open version 2.
+            description: "add gc column",
+            columns: &[],
+            run: |_, _| {},
+        },
+        Migration {
+            from_version: 2,
+            // The column number is the same, so there are no other updates.
+            description: "add ColOutcomesByBlockHash, rename LastComponentNonce to ColLastComponentNonce",
+            columns: &[DBCol::ColTransactionResult, DBCol::ColOutcomeIds],
+            run: |path, near_config| {
+                fill_col_outcomes_by_hash(&open_migrated_store(path, near_config))
+            },
+        },
+        Migration {
+            from_version: 3,
+            description: "add ColTransactionRefCount",
+            columns: &[DBCol::ColChunks, DBCol::_ColTransactionRefCount],
+            run: |path, near_config| {
+                fill_col_transaction_refcount(&open_migrated_store(path, near_config))
+            },
+        },
+        Migration {
+            from_version: 4,
+            // Old heights are not backfilled, at worst some heights are processed again.
+            description: "add ColProcessedBlockHeights",
+            columns: &[],
+            run: |_, _| {},
+        },
+        Migration {
+            from_version: 5,
+            // There were no merge records before, so the old storage works.
+            description: "add merge operator to ColState",
+            columns: &[],
+            run: |_, _| {},
+        },
+        Migration {
+            from_version: 6,
+            description: "use 8 bytes for refcount in ColState, move ColTransactionRefCount into ColTransactions, make ColReceiptIdToShardId refcounted",
+            columns: &[
+                DBCol::ColState,
+                DBCol::_ColTransactionRefCount,
+                DBCol::ColTransactions,
+                DBCol::ColReceiptIdToShardId,
+            ],
+            run: |path, _| migrate_6_to_7(path),
+        },
+        Migration {
+            from_version: 7,
+            description: "delete values in ColStateParts",
+            columns: &[DBCol::ColStateParts],
+            run: |path, _| migrate_7_to_8(path),
+        },
+        Migration {
+            from_version: 8,
+            description: "repair ColTransactions and ColReceiptIdToShardId",
+            columns: &[DBCol::ColChunks, DBCol::ColTransactions, DBCol::ColReceiptIdToShardId],
+            run: |path, _| migrate_8_to_9(path),
+        },
+        Migration {
+            from_version: 9,
+            description: "populate partial encoded chunks for chunks that exist in storage",
+            columns: &[DBCol::ColChunks, DBCol::ColPartialChunks],
+            run: |path, near_config| migrate_9_to_10(path, near_config.client_config.archive),
+        },
+        Migration {
+            from_version: 10,
+            description: "add final head",
+            columns: &[],
+            run: |path, _| migrate_10_to_11(path),
+        },
+        Migration {
+            from_version: 11,
+            description: "populate ColReceipts with existing receipts",
+            columns: &[DBCol::ColChunks, DBCol::ColReceipts],
+            run: |path, _| migrate_11_to_12(path),
+        },
+        Migration {
+            from_version: 12,
+            description: "fix the inconsistencies in ColTransactionResult",
+            columns: &[DBCol::ColTransactionResult],
+            run: |path, near_config| migrate_12_to_13(path, near_config),
+        },
+        Migration {
+            from_version: 13,
+            description: "store versioned enums for shard chunks",
+            columns: &[
+                DBCol::ColPartialChunks,
+                DBCol::ColInvalidChunks,
+                DBCol::ColChunks,
+                DBCol::ColStateHeaders,
+            ],
+            run: |path, _| migrate_13_to_14(path),
+        },
+        Migration {
+            from_version: 14,
+            description: "order ColOutcomeIds within each shard",
+            columns: &[DBCol::ColOutcomeIds],
+            run: |path, _| migrate_14_to_15(path),
+        },
+        Migration {
+            from_version: 15,
+            description: "add column for compiled contracts",
+            columns: &[],
+            run: |_, _| {},
+        },
+        Migration {
+            from_version: 16,
+            description: "add columns for flat state",
+            columns: &[],
+            run: |_, _| {},
+        },
+    ]
+}
+
+/// A pending migration and the estimated number of keys in the columns it touches.
+pub struct MigrationEstimate {
+    pub from_version: DbVersion,
+    pub description: &'static str,
+    pub estimated_num_keys: u64,
+}
+
+/// Returns the version of the database at `path` if it can be migrated by this binary.
+fn check_store_version(path: &str, near_config: &NearConfig) -> Result<DbVersion, String> {
+    let db_version = get_store_version(path);
+    if db_version > DB_VERSION {
+        return Err(format!("DB version {} is created by a newer version of neard, please update neard or delete data", db_version));
+    }
+    if db_version < DB_VERSION
+        && (near_config.store_config.backend != DBBackend::RocksDB
+            || near_config.store_config.cold_store_path.is_some())
+    {
+        return Err(format!("DB version {} can only be migrated with RocksDB backend and without cold storage, please delete data", db_version));
+    }
+    Ok(db_version)
+}
+
+fn exit_on_error<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|err| {
+        error!(target: "near", "{}", err);
+        std::process::exit(1);
+    })
+}
+
+fn checkpoint_path(checkpoints_dir: &Path, db_version: DbVersion) -> PathBuf {
+    checkpoints_dir.join(format!("v{}", db_version))
+}
+
+/// Returns the versions of the complete migration checkpoints, in increasing order.
+fn list_checkpoints(checkpoints_dir: &Path) -> Result<Vec<DbVersion>, String> {
+    if !checkpoints_dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut versions = vec![];
+    for entry in fs::read_dir(checkpoints_dir).map_err(|err| err.to_string())? {
+        let name = entry.map_err(|err| err.to_string())?.file_name();
+        let version = match name.to_str().and_then(|name| name.strip_prefix('v')) {
+            Some(version) => version.parse().ok(),
+            None => None,
+        };
+        if let Some(version) = version {
+            if read_checkpoint_manifest(&checkpoint_path(checkpoints_dir, version)).is_ok() {
+                versions.push(version);
+            }
+        }
+    }
+    versions.sort();
+    Ok(versions)
+}
+
+fn create_migration_checkpoint(
+    path: &str,
+    near_config: &NearConfig,
+    checkpoint_dir: &Path,
+) -> Result<(), String> {
+    // The checkpoint is complete only once it has the manifest, anything else is left from
+    // an interrupted attempt.
+    if checkpoint_dir.exists() {
+        fs::remove_dir_all(checkpoint_dir).map_err(|err| err.to_string())?;
+    }
+    let store = open_migrated_store(path, near_config);
+    create_checkpoint(&store, checkpoint_dir).map(|_| ()).map_err(|err| {
+        // Don't leave a partial checkpoint behind.
+        let _ = fs::remove_dir_all(checkpoint_dir);
+        err.to_string()
+    })
+}
+
+/// Replaces the database at `path` with the migration checkpoint of the given version.
+fn restore_migration_checkpoint(
+    checkpoints_dir: &Path,
+    path: &str,
+    db_version: DbVersion,
+) -> Result<CheckpointManifest, String> {
+    if store_path_exists(path) {
+        fs::remove_dir_all(path).map_err(|err| err.to_string())?;
+    }
+    restore_checkpoint(&checkpoint_path(checkpoints_dir, db_version), Path::new(path))
+        .map_err(|err| err.to_string())
+}
+
+fn read_in_progress(checkpoints_dir: &Path) -> Option<DbVersion> {
+    fs::read_to_string(checkpoints_dir.join(MIGRATION_IN_PROGRESS_FILE))
+        .ok()
+        .and_then(|version| version.trim().parse().ok())
+}
+
+fn write_in_progress(checkpoints_dir: &Path, db_version: DbVersion) -> Result<(), String> {
+    fs::write(checkpoints_dir.join(MIGRATION_IN_PROGRESS_FILE), db_version.to_string())
+        .map_err(|err| err.to_string())
+}
+
+fn clear_in_progress(checkpoints_dir: &Path) -> Result<(), String> {
+    let in_progress_path = checkpoints_dir.join(MIGRATION_IN_PROGRESS_FILE);
+    if in_progress_path.exists() {
+        fs::remove_file(in_progress_path).map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+/// Returns the migrations that are going to be applied to the database of the node, without
+/// changing the data in it.
+pub fn estimate_store_migrations(
+    home_dir: &Path,
+    near_config: &NearConfig,
+) -> Result<Vec<MigrationEstimate>, String> {
+    let path = get_store_path(home_dir);
+    if !store_path_exists(&path) {
+        return Err(format!("Database at {} does not exist", path));
+    }
+    let db_version = check_store_version(&path, near_config)?;
+    let pending: Vec<_> =
+        migrations().into_iter().filter(|migration| migration.from_version >= db_version).collect();
+    if pending.is_empty() {
+        return Ok(vec![]);
+    }
+    // Opened read-only, so that the columns added since the version of the database aren't
+    // created, and it fails if the database misses any column of this binary.
+    let store = open_read_only_store(home_dir, &path, near_config);
+    let instances = store.get_rocksdb_instances();
+    if instances.is_empty() {
+        return Err("Estimates are only supported by RocksDB".to_string());
+    }
+    pending
+        .into_iter()
+        .map(|migration| {
+            let mut estimated_num_keys = 0;
+            for col in migration.columns {
+                for (_, rocksdb) in instances.iter() {
+                    estimated_num_keys += rocksdb
+                        .column_stats(*col)
+                        .map_err(|err| err.to_string())?
+                        .estimated_num_keys;
+                }
+            }
+            Ok(MigrationEstimate {
+                from_version: migration.from_version,
+                description: migration.description,
+                estimated_num_keys,
+            })
+        })
+        .collect()
+}
+
+/// Checks the version of the database and applies the pending migrations to it.
+///
+/// With `checkpoints`, a checkpoint of the database is created in `MIGRATION_CHECKPOINTS_DIR`
+/// before every migration. The first one taken is kept afterwards, so the database can be
+/// rolled back with `rollback_store_migration`. The others are removed after their migration
+/// succeeds, and if the node crashes in the middle of a migration, the database is restored
+/// from the checkpoint of that migration and it runs again on the next start. A migration whose
+/// checkpoint can't be created, e.g. before the database has a final head, runs without it.
+pub fn apply_store_migrations(
+    home_dir: &Path,
+    path: &str,
+    near_config: &NearConfig,
+    checkpoints: bool,
+) {
+    let mut db_version = exit_on_error(check_store_version(path, near_config));
+    let checkpoints_dir = home_dir.join(MIGRATION_CHECKPOINTS_DIR);
+
+    let resumed = match read_in_progress(&checkpoints_dir) {
+        Some(interrupted_version) if interrupted_version == db_version => {
+            warn!(target: "near", "Migration of DB from version {} was interrupted, restoring the database from its checkpoint", db_version);
+            exit_on_error(restore_migration_checkpoint(&checkpoints_dir, path, db_version));
+            db_version = exit_on_error(check_store_version(path, near_config));
+            true
+        }
+        // The migration finished right before the node stopped.
+        Some(_) => true,
+        None => false,
+    };
+    exit_on_error(clear_in_progress(&checkpoints_dir));
+    if db_version == DB_VERSION {
+        return;
+    }
+    if checkpoints && !resumed && checkpoints_dir.exists() {
+        // Checkpoints of the previous migrations are replaced by the ones of this migration.
+        exit_on_error(fs::remove_dir_all(&checkpoints_dir).map_err(|err| err.to_string()));
+    }
+
+    let pending: Vec<_> =
+        migrations().into_iter().filter(|migration| migration.from_version >= db_version).collect();
+    for (index, migration) in pending.iter().enumerate() {
+        let to_version = migration.from_version + 1;
+        info!(target: "near", "Migrate DB from version {} to {} ({}/{}): {}", migration.from_version, to_version, index + 1, pending.len(), migration.description);
+        let started = Instant::now();
+        let checkpoint_dir = checkpoint_path(&checkpoints_dir, migration.from_version);
+        let checkpointed = checkpoints
+            && (read_checkpoint_manifest(&checkpoint_dir).is_ok()
+                || match create_migration_checkpoint(path, near_config, &checkpoint_dir) {
+                    Ok(()) => true,
+                    // E.g. databases before version 11 have no final head for the manifest.
+                    Err(err) => {
+                        warn!(target: "near", "Failed to create a checkpoint of DB version {} before migration: {}, migrating without it, so the migration can't be resumed or rolled back", migration.from_version, err);
+                        false
+                    }
+                });
+        if checkpointed {
+            exit_on_error(write_in_progress(&checkpoints_dir, migration.from_version));
+        }
+
+        (migration.run)(&path.to_string(), near_config);
+        set_store_version(&open_store(home_dir, path, near_config), to_version);
+
+        if checkpointed {
+            exit_on_error(clear_in_progress(&checkpoints_dir));
+            let first_version = exit_on_error(list_checkpoints(&checkpoints_dir)).first().cloned();
+            if first_version != Some(migration.from_version) {
+                exit_on_error(fs::remove_dir_all(&checkpoint_dir).map_err(|err| err.to_string()));
+            }
+        }
+        info!(target: "near", "Migrated DB to version {} in {:.1}s", to_version, started.elapsed().as_secs_f64());
+    }
+    if checkpoints {
+        if let Some(first_version) = exit_on_error(list_checkpoints(&checkpoints_dir)).first() {
+            info!(target: "near", "Checkpoint of DB version {} is kept in {}, use `neard rollback_migration` to restore it or remove it once the node works", first_version, checkpoint_path(&checkpoints_dir, *first_version).display());
+        }
+    }
+
+    #[cfg(feature = "protocol_feature_rectify_inflation")]
+    if db_version <= 16 {
+        // version 16 => rectify inflation: add `timestamp` to `BlockInfo`
+        migrate_16_to_rectify_inflation(&path.to_string());
+    }
+    #[cfg(feature = "nightly_protocol")]
+    {
+        let store = open_store(home_dir, path, near_config);
+        // set some dummy value to avoid conflict with other migrations from nightly features
+        set_store_version(&store, 10000);
+    }
+
+    #[cfg(not(feature = "nightly_protocol"))]
+    {
+        let db_version = get_store_version(path);
+        debug_assert_eq!(db_version, DB_VERSION);
+    }
+}
+
+/// Replaces the database of the stopped node with the checkpoint taken before the last
+/// migration, e.g. after the migration failed.
+pub fn rollback_store_migration(home_dir: &Path) -> Result<CheckpointManifest, String> {
+    let checkpoints_dir = home_dir.join(MIGRATION_CHECKPOINTS_DIR);
+    let db_version = *list_checkpoints(&checkpoints_dir)?.first().ok_or_else(|| {
+        format!("There are no migration checkpoints in {}", checkpoints_dir.display())
+    })?;
+    let manifest =
+        restore_migration_checkpoint(&checkpoints_dir, &get_store_path(home_dir), db_version)?;
+    clear_in_progress(&checkpoints_dir)?;
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use borsh::BorshDeserialize;
+
+    use near_chain::{Chain, ChainGenesis, DoomslugThresholdMode};
+    use near_chain_configs::Genesis;
+    use near_primitives::block::Tip;
+    use near_primitives::hash::hash;
+    use near_primitives::sharding::ShardChunk;
+    use near_primitives::types::EpochId;
+    use near_primitives::version::DB_VERSION;
+    use near_store::checkpoint::create_checkpoint;
+    use near_store::migrations::{get_store_version, set_store_version};
+    use near_store::{create_store, DBCol, FINAL_HEAD_KEY, HEAD_KEY};
+
+    use crate::config::GenesisExt;
+    use crate::{get_store_path, init_and_migrate_store, load_test_config, NightshadeRuntime};
+
+    use super::{
+        apply_store_migrations, checkpoint_path, estimate_store_migrations, list_checkpoints,
+        migrations, read_in_progress, rollback_store_migration, write_in_progress,
+        MIGRATION_CHECKPOINTS_DIR,
+    };
+
+    #[test]
+    fn test_migrations_are_consecutive() {
+        let versions: Vec<_> =
+            migrations().iter().map(|migration| migration.from_version).collect();
+        assert_eq!(versions, (1..DB_VERSION).collect::<Vec<_>>());
+    }
+
+    #[cfg(not(feature = "nightly_protocol"))]
+    #[test]
+    fn test_resume_and_rollback_migration() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_migration").tempdir().unwrap();
+        let home_dir = tmp_dir.path();
+        let near_config = load_test_config("test0", 0, Genesis::test(vec!["test0"], 1));
+        let path = get_store_path(home_dir);
+        let store = create_store(&path);
+        set_store_version(&store, DB_VERSION - 1);
+        let final_head = Tip {
+            height: 1,
+            last_block_hash: hash(&[1]),
+            prev_block_hash: hash(&[0]),
+            epoch_id: EpochId::default(),
+            next_epoch_id: EpochId::default(),
+        };
+        let mut store_update = store.store_update();
+        store_update.set_ser(DBCol::ColBlockMisc, FINAL_HEAD_KEY, &final_head).unwrap();
+        store_update.set(DBCol::ColBlockMisc, b"before", &[1]);
+        store_update.commit().unwrap();
+
+        // The node stopped in the middle of the last migration, after its checkpoint was created.
+        let checkpoints_dir = home_dir.join(MIGRATION_CHECKPOINTS_DIR);
+        create_checkpoint(&store, &checkpoint_path(&checkpoints_dir, DB_VERSION - 1)).unwrap();
+        write_in_progress(&checkpoints_dir, DB_VERSION - 1).unwrap();
+        let mut store_update = store.store_update();
+        store_update.set(DBCol::ColBlockMisc, b"partial", &[2]);
+        store_update.commit().unwrap();
+        drop(store);
+
+        apply_store_migrations(home_dir, &path, &near_config, true);
+        assert_eq!(get_store_version(&path), DB_VERSION);
+        assert_eq!(read_in_progress(&checkpoints_dir), None);
+        assert_eq!(list_checkpoints(&checkpoints_dir).unwrap(), vec![DB_VERSION - 1]);
+        {
+            let store = create_store(&path);
+            assert_eq!(store.get(DBCol::ColBlockMisc, b"before").unwrap(), Some(vec![1]));
+            assert_eq!(store.get(DBCol::ColBlockMisc, b"partial").unwrap(), None);
+        }
+
+        let manifest = rollback_store_migration(home_dir).unwrap();
+        assert_eq!(manifest.db_version, DB_VERSION - 1);
+        assert_eq!(get_store_version(&path), DB_VERSION - 1);
+    }
+
+    /// The node doesn't create migration checkpoints on start unless they are enabled in the
+    /// store config, and a dry run doesn't change the database.
+    #[cfg(not(feature = "nightly_protocol"))]
+    #[test]
+    fn test_migrate_on_start_without_checkpoints() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_migration_start").tempdir().unwrap();
+        let home_dir = tmp_dir.path();
+        let near_config = load_test_config("test0", 0, Genesis::test(vec!["test0"], 1));
+        assert!(!near_config.store_config.migration_checkpoints);
+        let path = get_store_path(home_dir);
+        let store = create_store(&path);
+        set_store_version(&store, DB_VERSION - 1);
+        drop(store);
+
+        let estimates = estimate_store_migrations(home_dir, &near_config).unwrap();
+        assert_eq!(
+            estimates.iter().map(|estimate| estimate.from_version).collect::<Vec<_>>(),
+            vec![DB_VERSION - 1]
+        );
+        assert_eq!(get_store_version(&path), DB_VERSION - 1);
+
+        drop(init_and_migrate_store(home_dir, &near_config));
+        assert_eq!(get_store_version(&path), DB_VERSION);
+        assert!(!home_dir.join(MIGRATION_CHECKPOINTS_DIR).exists());
+    }
+
+    /// Databases before version 11 have no final head, so they can't be checkpointed before
+    /// the first migration, which runs without a checkpoint, and the next ones are checkpointed.
+    #[cfg(not(feature = "nightly_protocol"))]
+    #[test]
+    fn test_migrate_from_version_10() {
+        let tmp_dir = tempfile::Builder::new().prefix("_test_migration_10").tempdir().unwrap();
+        let home_dir = tmp_dir.path();
+        let genesis = Genesis::test(vec!["test0"], 1);
+        let mut near_config = load_test_config("test0", 0, genesis.clone());
+        near_config.store_config.migration_checkpoints = true;
+        {
+            let store = init_and_migrate_store(home_dir, &near_config);
+            let runtime =
+                Arc::new(NightshadeRuntime::new(home_dir, store.clone(), &genesis, vec![], vec![]));
+            Chain::new(runtime, &ChainGenesis::from(&genesis), DoomslugThresholdMode::TwoThirds)
+                .unwrap();
+            set_store_version(&store, 10);
+            let mut store_update = store.store_update();
+            store_update.delete(DBCol::ColBlockMisc, FINAL_HEAD_KEY);
+            // Chunks were stored without their version before version 14.
+            for (key, value) in store.iter(DBCol::ColChunks) {
+                match ShardChunk::try_from_slice(&value).unwrap() {
+                    ShardChunk::V1(chunk) => {
+                        store_update.set_ser(DBCol::ColChunks, &key, &chunk).unwrap()
+                    }
+                    ShardChunk::V2(_) => panic!("Genesis chunks are expected to be V1"),
+                }
+            }
+            store_update.commit().unwrap();
+        }
+
+        let store = init_and_migrate_store(home_dir, &near_config);
+        let head: Tip = store.get_ser(DBCol::ColBlockMisc, HEAD_KEY).unwrap().unwrap();
+        let final_head: Tip = store.get_ser(DBCol::ColBlockMisc, FINAL_HEAD_KEY).unwrap().unwrap();
+        assert_eq!(final_head.last_block_hash, head.last_block_hash);
+        drop(store);
+        assert_eq!(get_store_version(&get_store_path(home_dir)), DB_VERSION);
+        let checkpoints_dir = home_dir.join(MIGRATION_CHECKPOINTS_DIR);
+        assert_eq!(read_in_progress(&checkpoints_dir), None);
+        assert_eq!(list_checkpoints(&checkpoints_dir).unwrap(), vec![11]);
+    }
+}
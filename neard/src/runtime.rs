@@ -4,6 +4,7 @@ use std::convert::TryInto;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
+use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 
 use borsh::ser::BorshSerialize;
@@ -46,9 +47,10 @@ use near_primitives::views::{
 use near_store::{
     get_access_key_raw, get_genesis_hash, get_genesis_state_roots, set_genesis_hash,
     set_genesis_state_roots, ColState, PartialStorage, ShardTries, Store,
-    StoreCompiledContractCache, Trie, WrappedTrieChanges,
+    StoreCompiledContractCache, Trie, TrieUpdate, WrappedTrieChanges,
 };
 use node_runtime::adapter::ViewRuntimeAdapter;
+use node_runtime::config::{total_prepaid_gas, tx_cost};
 use node_runtime::state_viewer::TrieViewer;
 use node_runtime::{
     validate_transaction, verify_and_charge_transaction, ApplyState, Runtime,
@@ -126,6 +128,9 @@ impl EpochInfoProvider for SafeEpochManager {
 pub struct NightshadeRuntime {
     genesis_config: GenesisConfig,
     genesis_runtime_config: Arc<RuntimeConfig>,
+    /// Used instead of the config of the protocol version when set. Only for offline tools that
+    /// re-apply blocks with a different config, a node using it would diverge from the chain.
+    runtime_config_override: Option<Arc<RuntimeConfig>>,
 
     store: Arc<Store>,
     tries: ShardTries,
@@ -143,6 +148,43 @@ impl NightshadeRuntime {
         genesis: &Genesis,
         initial_tracking_accounts: Vec<AccountId>,
         initial_tracking_shards: Vec<ShardId>,
+    ) -> Self {
+        let state_roots =
+            Self::initialize_genesis_state_if_needed(store.clone(), home_dir, genesis);
+        Self::with_genesis_state_roots(
+            store,
+            genesis,
+            initial_tracking_accounts,
+            initial_tracking_shards,
+            state_roots,
+        )
+    }
+
+    /// Same as `new`, but for a store which already has the genesis state, so that nothing is
+    /// written to it, e.g. when it is opened read-only.
+    pub fn with_existing_genesis_state(
+        store: Arc<Store>,
+        genesis: &Genesis,
+        initial_tracking_accounts: Vec<AccountId>,
+        initial_tracking_shards: Vec<ShardId>,
+    ) -> Self {
+        let state_roots = Self::get_existing_genesis_state_roots(&store, genesis)
+            .expect("Genesis state is not initialized in storage");
+        Self::with_genesis_state_roots(
+            store,
+            genesis,
+            initial_tracking_accounts,
+            initial_tracking_shards,
+            state_roots,
+        )
+    }
+
+    fn with_genesis_state_roots(
+        store: Arc<Store>,
+        genesis: &Genesis,
+        initial_tracking_accounts: Vec<AccountId>,
+        initial_tracking_shards: Vec<ShardId>,
+        state_roots: Vec<StateRoot>,
     ) -> Self {
         let runtime = Runtime::new();
         let trie_viewer = TrieViewer::new();
@@ -181,8 +223,6 @@ impl NightshadeRuntime {
             #[cfg(feature = "protocol_feature_rectify_inflation")]
             num_seconds_per_year: NUM_SECONDS_IN_A_YEAR,
         };
-        let state_roots =
-            Self::initialize_genesis_state_if_needed(store.clone(), home_dir, genesis);
         let tries = ShardTries::new(
             store.clone(),
             genesis.config.num_block_producer_seats_per_shard.len() as NumShards,
@@ -219,6 +259,7 @@ impl NightshadeRuntime {
         NightshadeRuntime {
             genesis_config,
             genesis_runtime_config,
+            runtime_config_override: None,
             store,
             tries,
             runtime,
@@ -229,6 +270,21 @@ impl NightshadeRuntime {
         }
     }
 
+    /// Makes the runtime apply chunks and validate transactions with `runtime_config` regardless
+    /// of the protocol version.
+    pub fn set_runtime_config_override(&mut self, runtime_config: RuntimeConfig) {
+        self.runtime_config_override = Some(Arc::new(runtime_config));
+    }
+
+    fn get_runtime_config(&self, protocol_version: ProtocolVersion) -> Arc<RuntimeConfig> {
+        match &self.runtime_config_override {
+            Some(runtime_config) => runtime_config.clone(),
+            None => {
+                RuntimeConfig::from_protocol_version(&self.genesis_runtime_config, protocol_version)
+            }
+        }
+    }
+
     fn get_epoch_height_from_prev_block(
         &self,
         prev_block_hash: &CryptoHash,
@@ -310,27 +366,37 @@ impl NightshadeRuntime {
         home_dir: &Path,
         genesis: &Genesis,
     ) -> Vec<StateRoot> {
-        let genesis_hash = genesis.json_hash();
-        let stored_hash = get_genesis_hash(&store).expect("Store failed on genesis intialization");
-        if let Some(hash) = stored_hash {
-            assert_eq!(
-                hash,
-                genesis.json_hash(),
-                "Storage already exists, but has a different genesis"
-            );
-            get_genesis_state_roots(&store)
-                .expect("Store failed on genesis intialization")
-                .expect("Genesis state roots not found in storage")
+        if let Some(state_roots) = Self::get_existing_genesis_state_roots(&store, genesis) {
+            state_roots
         } else {
             let state_roots = Self::initialize_genesis_state(store.clone(), home_dir, genesis);
             let mut store_update = store.store_update();
-            set_genesis_hash(&mut store_update, &genesis_hash);
+            set_genesis_hash(&mut store_update, &genesis.json_hash());
             set_genesis_state_roots(&mut store_update, &state_roots);
             store_update.commit().expect("Store failed on genesis intialization");
             state_roots
         }
     }
 
+    /// Returns the genesis state roots if the genesis state is already stored.
+    fn get_existing_genesis_state_roots(
+        store: &Store,
+        genesis: &Genesis,
+    ) -> Option<Vec<StateRoot>> {
+        let stored_hash =
+            get_genesis_hash(store).expect("Store failed on genesis intialization")?;
+        assert_eq!(
+            stored_hash,
+            genesis.json_hash(),
+            "Storage already exists, but has a different genesis"
+        );
+        Some(
+            get_genesis_state_roots(store)
+                .expect("Store failed on genesis intialization")
+                .expect("Genesis state roots not found in storage"),
+        )
+    }
+
     pub fn initialize_genesis_state(
         store: Arc<Store>,
         home_dir: &Path,
@@ -454,10 +520,7 @@ impl NightshadeRuntime {
             gas_limit: Some(gas_limit),
             random_seed,
             current_protocol_version,
-            config: RuntimeConfig::from_protocol_version(
-                &self.genesis_runtime_config,
-                current_protocol_version,
-            ),
+            config: self.get_runtime_config(current_protocol_version),
             cache: Some(Arc::new(StoreCompiledContractCache { store: self.store.clone() })),
             #[cfg(feature = "protocol_feature_evm")]
             evm_chain_id: self.evm_chain_id(),
@@ -590,10 +653,7 @@ impl RuntimeAdapter for NightshadeRuntime {
         verify_signature: bool,
         current_protocol_version: ProtocolVersion,
     ) -> Result<Option<InvalidTxError>, Error> {
-        let runtime_config = RuntimeConfig::from_protocol_version(
-            &self.genesis_runtime_config,
-            current_protocol_version,
-        );
+        let runtime_config = self.get_runtime_config(current_protocol_version);
 
         if let Some(state_root) = state_root {
             let shard_id = self.account_id_to_shard_id(&transaction.transaction.signer_id);
@@ -639,6 +699,32 @@ impl RuntimeAdapter for NightshadeRuntime {
         }
     }
 
+    fn transaction_fee(
+        &self,
+        gas_price: Balance,
+        transaction: &SignedTransaction,
+        current_protocol_version: ProtocolVersion,
+    ) -> Balance {
+        let runtime_config = self.get_runtime_config(current_protocol_version);
+        let transaction = &transaction.transaction;
+        let sender_is_receiver = transaction.receiver_id == transaction.signer_id;
+        // Validated transactions don't overflow, others are never prioritized.
+        tx_cost(
+            &runtime_config.transaction_costs,
+            transaction,
+            gas_price,
+            sender_is_receiver,
+            current_protocol_version,
+        )
+        .ok()
+        .and_then(|cost| {
+            // Unused prepaid gas of function calls is refunded, so it would be free priority.
+            let prepaid_gas = total_prepaid_gas(&transaction.actions).ok()?;
+            cost.gas_burnt.checked_add(cost.gas_remaining.checked_sub(prepaid_gas)?)
+        })
+        .map_or(0, |gas| gas_price.saturating_mul(Balance::from(gas)))
+    }
+
     fn prepare_transactions(
         &self,
         gas_price: Balance,
@@ -658,10 +744,7 @@ impl RuntimeAdapter for NightshadeRuntime {
         let mut transactions = vec![];
         let mut num_checked_transactions = 0;
 
-        let runtime_config = RuntimeConfig::from_protocol_version(
-            &self.genesis_runtime_config,
-            current_protocol_version,
-        );
+        let runtime_config = self.get_runtime_config(current_protocol_version);
 
         while total_gas_burnt < transactions_gas_limit {
             if let Some(iter) = pool_iterator.next() {
@@ -1299,6 +1382,60 @@ impl RuntimeAdapter for NightshadeRuntime {
         }
     }
 
+    fn query_with_proof(
+        &self,
+        shard_id: ShardId,
+        state_root: &StateRoot,
+        block_height: BlockHeight,
+        block_hash: &CryptoHash,
+        request: &QueryRequest,
+    ) -> Result<(QueryResponse, PartialStorage), Box<dyn std::error::Error>> {
+        let trie = Rc::new(self.get_tries().get_view_trie_for_shard(shard_id).recording_reads());
+        // Fail early if the state is not available (e.g. it was garbage collected), so that
+        // missing trie nodes are not reported as a proof of absence
+        trie.retrieve_root_node(state_root)?;
+        let state_update = TrieUpdate::new(Rc::clone(&trie), *state_root);
+        let result = match request {
+            QueryRequest::ViewAccount { account_id } => self
+                .trie_viewer
+                .view_account(&state_update, account_id)
+                .map(|account| QueryResponseKind::ViewAccount(account.into())),
+            QueryRequest::ViewCode { account_id } => self
+                .trie_viewer
+                .view_contract_code(&state_update, account_id)
+                .map(|code| QueryResponseKind::ViewCode(code.into())),
+            QueryRequest::ViewState { account_id, prefix } => self
+                .trie_viewer
+                .view_state(&state_update, account_id, prefix.as_ref())
+                .map(QueryResponseKind::ViewState),
+            QueryRequest::ViewAccessKey { account_id, public_key } => self
+                .trie_viewer
+                .view_access_key(&state_update, account_id, public_key)
+                .map(|access_key| QueryResponseKind::AccessKey(access_key.into())),
+            QueryRequest::ViewAccessKeyList { account_id } => {
+                get_access_keys(&state_update, account_id).map(|access_keys| {
+                    QueryResponseKind::AccessKeyList(
+                        access_keys
+                            .into_iter()
+                            .map(|(public_key, access_key)| AccessKeyInfoView {
+                                public_key,
+                                access_key: access_key.into(),
+                            })
+                            .collect(),
+                    )
+                })
+            }
+            QueryRequest::CallFunction { .. } => {
+                return Err("Proofs are not supported for function call queries".into());
+            }
+        };
+        let kind = result.unwrap_or_else(|err| {
+            QueryResponseKind::Error(QueryError { error: err.to_string(), logs: vec![] })
+        });
+        let proof = trie.recorded_storage().expect("Trie should be recording reads");
+        Ok((QueryResponse { kind, block_height, block_hash: *block_hash }, proof))
+    }
+
     fn get_validator_info(&self, block_hash: &CryptoHash) -> Result<EpochValidatorInfo, Error> {
         let mut epoch_manager = self.epoch_manager.as_ref().write().expect(POISONED_LOCK_ERR);
         epoch_manager.get_validator_info(block_hash).map_err(|e| e.into())
@@ -1457,13 +1594,35 @@ impl RuntimeAdapter for NightshadeRuntime {
         let mut config = self.genesis_config.clone();
         config.protocol_version = protocol_version;
         // Currently only runtime config is changed through protocol upgrades.
-        let runtime_config =
-            RuntimeConfig::from_protocol_version(&self.genesis_runtime_config, protocol_version);
+        let runtime_config = self.get_runtime_config(protocol_version);
         config.runtime_config = (*runtime_config).clone();
         Ok(config)
     }
 }
 
+fn get_access_keys(
+    state_update: &TrieUpdate,
+    account_id: &AccountId,
+) -> Result<Vec<(PublicKey, AccessKey)>, Box<dyn std::error::Error>> {
+    let prefix = trie_key_parsers::get_raw_prefix_for_access_keys(account_id);
+    let raw_prefix: &[u8] = prefix.as_ref();
+    let access_keys = match state_update.iter(&prefix) {
+        Ok(iter) => iter
+            .map(|key| {
+                let key = key?;
+                let public_key = &key[raw_prefix.len()..];
+                let access_key =
+                    get_access_key_raw(state_update, &key)?.ok_or("Missing key from iterator")?;
+                PublicKey::try_from_slice(public_key)
+                    .map_err(|err| format!("{}", err).into())
+                    .map(|key| (key, access_key))
+            })
+            .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>(),
+        Err(e) => Err(e.into()),
+    };
+    access_keys
+}
+
 impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
     fn view_account(
         &self,
@@ -1545,23 +1704,7 @@ impl node_runtime::adapter::ViewRuntimeAdapter for NightshadeRuntime {
         account_id: &AccountId,
     ) -> Result<Vec<(PublicKey, AccessKey)>, Box<dyn std::error::Error>> {
         let state_update = self.get_tries().new_trie_update_view(shard_id, state_root);
-        let prefix = trie_key_parsers::get_raw_prefix_for_access_keys(account_id);
-        let raw_prefix: &[u8] = prefix.as_ref();
-        let access_keys = match state_update.iter(&prefix) {
-            Ok(iter) => iter
-                .map(|key| {
-                    let key = key?;
-                    let public_key = &key[raw_prefix.len()..];
-                    let access_key = get_access_key_raw(&state_update, &key)?
-                        .ok_or("Missing key from iterator")?;
-                    PublicKey::try_from_slice(public_key)
-                        .map_err(|err| format!("{}", err).into())
-                        .map(|key| (key, access_key))
-                })
-                .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>(),
-            Err(e) => Err(e.into()),
-        };
-        access_keys
+        get_access_keys(&state_update, account_id)
     }
 
     fn view_state(
@@ -1593,6 +1736,7 @@ mod test {
     };
     use near_primitives::types::{BlockHeightDelta, Nonce, ValidatorId, ValidatorKickoutReason};
     use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
+    use near_primitives::version::PROTOCOL_VERSION;
     use near_primitives::views::{
         AccountView, CurrentEpochValidatorInfo, NextEpochValidatorInfo, ValidatorKickoutView,
     };
@@ -2203,6 +2347,52 @@ mod test {
             .unwrap());
     }
 
+    #[test]
+    fn test_query_with_proof() {
+        let validators = (0..2).map(|i| format!("test{}", i + 1)).collect::<Vec<_>>();
+        let env = TestEnv::new(
+            "test_query_with_proof",
+            vec![validators.clone()],
+            2,
+            vec![],
+            vec![],
+            true,
+        );
+        let state_root = env.state_roots[0];
+        let query = |account_id: &str| {
+            env.runtime
+                .query_with_proof(
+                    0,
+                    &state_root,
+                    env.head.height,
+                    &env.head.last_block_hash,
+                    &QueryRequest::ViewAccount { account_id: account_id.to_string() },
+                )
+                .unwrap()
+        };
+        let verify = |partial_storage: PartialStorage, account_id: &str| {
+            let trie = Trie::from_recorded_storage(partial_storage);
+            let state_update = TrieUpdate::new(Rc::new(trie), state_root);
+            near_store::get_account(&state_update, &account_id.to_string()).unwrap()
+        };
+
+        let (response, proof) = query(&validators[0]);
+        match response.kind {
+            QueryResponseKind::ViewAccount(account) => {
+                assert_eq!(account, env.view_account(&validators[0]))
+            }
+            kind => panic!("Unexpected response {:?}", kind),
+        }
+        assert_eq!(
+            verify(proof, &validators[0]).map(AccountView::from),
+            Some(env.view_account(&validators[0]))
+        );
+
+        let (response, proof) = query("test3");
+        assert!(matches!(response.kind, QueryResponseKind::Error(_)));
+        assert_eq!(verify(proof, "test3"), None);
+    }
+
     #[test]
     fn test_state_sync() {
         init_test_logger();
@@ -2986,4 +3176,54 @@ mod test {
         assert_eq!(env.last_proposals.len(), 1);
         assert_eq!(env.last_proposals[0].stake, 0);
     }
+
+    /// The fee prioritizing transactions in the pool covers the costs of the actions, so a
+    /// transfer creating an implicit account outranks a plain transfer, but not the prepaid gas
+    /// of function calls, which is refunded if unused.
+    #[test]
+    fn test_transaction_fee() {
+        let validators = (0..2).map(|i| format!("test{}", i + 1)).collect::<Vec<_>>();
+        let env =
+            TestEnv::new("transaction_fee", vec![validators.clone()], 2, vec![], vec![], true);
+        let signer = InMemorySigner::from_seed(&validators[0], KeyType::ED25519, &validators[0]);
+        let transfer = |receiver_id: &str| {
+            SignedTransaction::send_money(
+                1,
+                validators[0].clone(),
+                receiver_id.to_string(),
+                &signer,
+                10,
+                CryptoHash::default(),
+            )
+        };
+        let gas_price = 100;
+        let fee = |transaction: &SignedTransaction, gas_price: Balance| {
+            env.runtime.transaction_fee(gas_price, transaction, PROTOCOL_VERSION)
+        };
+
+        let plain_transfer = transfer(&validators[1]);
+        let implicit_transfer = transfer(&"a".repeat(64));
+        assert!(fee(&plain_transfer, gas_price) > 0);
+        assert!(fee(&implicit_transfer, gas_price) > fee(&plain_transfer, gas_price));
+        assert_eq!(fee(&plain_transfer, 2 * gas_price), 2 * fee(&plain_transfer, gas_price));
+
+        let call = |gas: Gas| {
+            SignedTransaction::call(
+                1,
+                validators[0].clone(),
+                validators[1].clone(),
+                &signer,
+                0,
+                "main".to_string(),
+                vec![],
+                gas,
+                CryptoHash::default(),
+            )
+        };
+        assert!(fee(&call(10u64.pow(12)), gas_price) > 0);
+        assert_eq!(
+            fee(&call(300 * 10u64.pow(12)), gas_price),
+            fee(&call(10u64.pow(12)), gas_price)
+        );
+    }
 }
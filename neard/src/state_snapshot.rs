@@ -0,0 +1,201 @@
+//! Export and import of the state that state sync downloads from peers: the state header and
+//! the state parts of every shard at a sync hash, i.e. the first block of an epoch.
+//!
+//! A snapshot is a directory with a `manifest.json`, the Borsh-encoded block right before the
+//! sync block and a subdirectory per shard holding the Borsh-encoded
+//! `ShardStateSyncResponseHeader` and the state parts, one file per part.
+//! Importing a snapshot goes through the same validation as state sync, so a snapshot doesn't
+//! have to be trusted more than a peer.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use near_chain::{Chain, ChainGenesis, DoomslugThresholdMode, Error, ErrorKind};
+use near_client::sync::StateSync;
+use near_primitives::block::Block;
+use near_primitives::hash::CryptoHash;
+use near_primitives::syncing::{get_num_state_parts, ShardStateSyncResponseHeader};
+use near_primitives::types::{ShardId, StateRoot};
+
+use crate::{init_and_migrate_store, NearConfig, NightshadeRuntime};
+
+const MANIFEST_FILENAME: &str = "manifest.json";
+const PREV_BLOCK_FILENAME: &str = "prev_block";
+const HEADER_FILENAME: &str = "header";
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StateSnapshotManifest {
+    pub chain_id: String,
+    /// Hash of the first block of the epoch the state is synced to.
+    pub sync_hash: CryptoHash,
+    pub shards: Vec<ShardStateSnapshot>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ShardStateSnapshot {
+    pub shard_id: ShardId,
+    /// State root of the shard at the beginning of the last chunk before `sync_hash`.
+    pub state_root: StateRoot,
+    pub num_parts: u64,
+}
+
+fn shard_dir(snapshot_dir: &Path, shard_id: ShardId) -> PathBuf {
+    snapshot_dir.join(format!("shard{}", shard_id))
+}
+
+fn part_path(shard_dir: &Path, part_id: u64) -> PathBuf {
+    shard_dir.join(format!("part{}", part_id))
+}
+
+fn open_chain(home_dir: &Path, near_config: &NearConfig) -> Result<Chain, Error> {
+    let store = init_and_migrate_store(home_dir, near_config);
+    let runtime = Arc::new(NightshadeRuntime::new(
+        home_dir,
+        store,
+        &near_config.genesis,
+        near_config.client_config.tracked_accounts.clone(),
+        near_config.client_config.tracked_shards.clone(),
+    ));
+    Chain::new(runtime, &ChainGenesis::from(&near_config.genesis), DoomslugThresholdMode::TwoThirds)
+}
+
+/// Writes the state of the given shards (all shards by default) at `sync_hash` to `output_dir`.
+/// By default the sync hash is the first block of the epoch of the current head.
+/// The node must be stopped and must have the state of the shards at `sync_hash`.
+pub fn export_state_snapshot(
+    home_dir: &Path,
+    near_config: &NearConfig,
+    sync_hash: Option<CryptoHash>,
+    shard_ids: Option<Vec<ShardId>>,
+    output_dir: &Path,
+) -> Result<StateSnapshotManifest, Error> {
+    let mut chain = open_chain(home_dir, near_config)?;
+    let sync_hash = match sync_hash {
+        Some(sync_hash) => sync_hash,
+        None => {
+            let head = chain.head()?;
+            StateSync::get_epoch_start_sync_hash(&mut chain, &head.last_block_hash)?
+        }
+    };
+    if &sync_hash == chain.genesis().hash() {
+        return Err(ErrorKind::Other(
+            "Can't export the state at genesis, wait for the second epoch".to_string(),
+        )
+        .into());
+    }
+    let sync_prev_hash = *chain.get_block_header(&sync_hash)?.prev_hash();
+    fs::create_dir_all(output_dir)?;
+    fs::write(
+        output_dir.join(PREV_BLOCK_FILENAME),
+        chain.get_block(&sync_prev_hash)?.try_to_vec()?,
+    )?;
+
+    let shard_ids = shard_ids.unwrap_or_else(|| (0..chain.runtime_adapter.num_shards()).collect());
+
+    let mut shards = vec![];
+    for shard_id in shard_ids {
+        let header = chain.get_state_response_header(shard_id, sync_hash)?;
+        let state_root = header.chunk_prev_state_root();
+        let num_parts = get_num_state_parts(header.state_root_node().memory_usage);
+        let dir = shard_dir(output_dir, shard_id);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(HEADER_FILENAME), header.try_to_vec()?)?;
+        for part_id in 0..num_parts {
+            let part = chain.get_state_response_part(shard_id, part_id, sync_hash)?;
+            fs::write(part_path(&dir, part_id), part)?;
+        }
+        info!(target: "near", "Exported {} state parts of shard {} at {}", num_parts, shard_id, sync_hash);
+        shards.push(ShardStateSnapshot { shard_id, state_root, num_parts });
+    }
+
+    let manifest = StateSnapshotManifest {
+        chain_id: near_config.genesis.config.chain_id.clone(),
+        sync_hash,
+        shards,
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest)
+        .map_err(|err| ErrorKind::Other(format!("Failed to serialize the manifest: {}", err)))?;
+    fs::write(output_dir.join(MANIFEST_FILENAME), manifest_json)?;
+    Ok(manifest)
+}
+
+/// Applies the state snapshot in `input_dir` the same way state sync applies the state
+/// downloaded from peers: the header is checked against the chain, every part is checked with
+/// `Trie::validate_trie_nodes_for_part` against the state root and the chunks since the snapshot
+/// are applied on top of it. The heads are then reset so that the node continues from the
+/// sync block once it's started.
+///
+/// The node must be stopped and, like for state sync, it must already have the headers up to
+/// the sync block. The block right before the sync block is taken from the snapshot.
+pub fn import_state_snapshot(
+    home_dir: &Path,
+    near_config: &NearConfig,
+    input_dir: &Path,
+) -> Result<StateSnapshotManifest, Error> {
+    let manifest: StateSnapshotManifest =
+        serde_json::from_slice(&fs::read(input_dir.join(MANIFEST_FILENAME))?)
+            .map_err(|err| ErrorKind::Other(format!("Failed to parse the manifest: {}", err)))?;
+    if manifest.chain_id != near_config.genesis.config.chain_id {
+        return Err(ErrorKind::Other(format!(
+            "Snapshot of chain {} can't be imported into chain {}",
+            manifest.chain_id, near_config.genesis.config.chain_id
+        ))
+        .into());
+    }
+
+    let mut chain = open_chain(home_dir, near_config)?;
+    let sync_hash = manifest.sync_hash;
+    let sync_prev_hash = *chain
+        .get_block_header(&sync_hash)
+        .map_err(|_| {
+            ErrorKind::Other(format!(
+                "Header of the sync block {} is unknown, run the node until header sync gets past it",
+                sync_hash
+            ))
+        })?
+        .prev_hash();
+    let prev_block = Block::try_from_slice(&fs::read(input_dir.join(PREV_BLOCK_FILENAME))?)?;
+    if prev_block.hash() != &sync_prev_hash {
+        return Err(ErrorKind::Other(format!(
+            "Snapshot has block {} instead of {} before the sync block",
+            prev_block.hash(),
+            sync_prev_hash
+        ))
+        .into());
+    }
+
+    if !near_config.client_config.archive {
+        chain.reset_data_pre_state_sync(sync_hash)?;
+    }
+    chain.save_block(&prev_block)?;
+
+    for shard in manifest.shards.iter() {
+        let dir = shard_dir(input_dir, shard.shard_id);
+        let header =
+            ShardStateSyncResponseHeader::try_from_slice(&fs::read(dir.join(HEADER_FILENAME))?)?;
+        let num_parts = get_num_state_parts(header.state_root_node().memory_usage);
+        if header.chunk_prev_state_root() != shard.state_root || num_parts != shard.num_parts {
+            return Err(ErrorKind::Other(format!(
+                "State header of shard {} doesn't match the manifest",
+                shard.shard_id
+            ))
+            .into());
+        }
+        chain.set_state_header(shard.shard_id, sync_hash, header)?;
+        for part_id in 0..num_parts {
+            let part = fs::read(part_path(&dir, part_id))?;
+            chain.set_state_part(shard.shard_id, sync_hash, part_id, num_parts, &part)?;
+        }
+        chain.set_state_finalize(shard.shard_id, sync_hash, num_parts)?;
+        chain.clear_downloaded_parts(shard.shard_id, sync_hash, num_parts)?;
+        info!(target: "near", "Imported {} state parts of shard {} at {}", num_parts, shard.shard_id, sync_hash);
+    }
+
+    let me = near_config.validator_signer.as_ref().map(|signer| signer.validator_id().clone());
+    chain.reset_heads_post_state_sync(&me, sync_hash, |_| {}, |_| {}, |_| {})?;
+    Ok(manifest)
+}
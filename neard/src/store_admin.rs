@@ -0,0 +1,100 @@
+//! Maintenance of the node database that has to be done while the node is stopped.
+use std::path::Path;
+use std::sync::Arc;
+
+use near_store::checkpoint::{create_checkpoint, restore_checkpoint, CheckpointManifest};
+use near_store::db::RocksDB;
+use near_store::{ColumnStats, DBCol, Store};
+
+use crate::{get_store_path, open_store, store_path_exists, NearConfig};
+
+fn open_existing_store(home_dir: &Path, near_config: &NearConfig) -> Arc<Store> {
+    let path = get_store_path(home_dir);
+    assert!(store_path_exists(&path), "Database at {} does not exist", path);
+    open_store(home_dir, &path, near_config)
+}
+
+/// Statistics of the columns of one of the RocksDB instances of the store.
+pub struct DatabaseStats {
+    /// `hot` and `cold` for the split store of an archival node, `db` otherwise.
+    pub name: &'static str,
+    pub columns: Vec<ColumnStats>,
+}
+
+/// Returns size statistics of the given columns, all columns by default, of every database
+/// of the store.
+pub fn store_stats(
+    home_dir: &Path,
+    near_config: &NearConfig,
+    columns: Option<Vec<DBCol>>,
+) -> Result<Vec<DatabaseStats>, String> {
+    let store = open_existing_store(home_dir, near_config);
+    let columns = columns.unwrap_or_else(all_columns);
+    map_rocksdb_instances(&store, "Statistics are only supported by RocksDB", |rocksdb| {
+        columns
+            .iter()
+            .map(|col| rocksdb.column_stats(*col).map_err(|err| err.to_string()))
+            .collect()
+    })
+}
+
+/// Compacts the given columns, all columns by default, of every database of the store and
+/// returns their statistics after the compaction.
+pub fn compact_store(
+    home_dir: &Path,
+    near_config: &NearConfig,
+    columns: Option<Vec<DBCol>>,
+) -> Result<Vec<DatabaseStats>, String> {
+    let store = open_existing_store(home_dir, near_config);
+    let columns = columns.unwrap_or_else(all_columns);
+    map_rocksdb_instances(&store, "Compaction is only supported by RocksDB", |rocksdb| {
+        columns
+            .iter()
+            .map(|col| {
+                rocksdb.compact(*col);
+                rocksdb.column_stats(*col).map_err(|err| err.to_string())
+            })
+            .collect()
+    })
+}
+
+fn map_rocksdb_instances(
+    store: &Store,
+    unsupported: &str,
+    f: impl Fn(&RocksDB) -> Result<Vec<ColumnStats>, String>,
+) -> Result<Vec<DatabaseStats>, String> {
+    let instances = store.get_rocksdb_instances();
+    if instances.is_empty() {
+        return Err(unsupported.to_string());
+    }
+    instances
+        .into_iter()
+        .map(|(name, rocksdb)| Ok(DatabaseStats { name, columns: f(rocksdb)? }))
+        .collect()
+}
+
+/// Creates a backup of the database in `output_dir`, which must not exist. A running node is
+/// backed up with the `EXPERIMENTAL_create_checkpoint` admin RPC method instead.
+pub fn backup_store(
+    home_dir: &Path,
+    near_config: &NearConfig,
+    output_dir: &Path,
+) -> Result<CheckpointManifest, String> {
+    let store = open_existing_store(home_dir, near_config);
+    create_checkpoint(&store, output_dir).map_err(|err| err.to_string())
+}
+
+/// Restores the backup in `input_dir` as the database of the node. The node must not have a
+/// database, remove it with `unsafe_reset_data` first.
+pub fn restore_store_backup(
+    home_dir: &Path,
+    input_dir: &Path,
+) -> Result<CheckpointManifest, String> {
+    restore_checkpoint(input_dir, Path::new(&get_store_path(home_dir)))
+        .map_err(|err| err.to_string())
+}
+
+fn all_columns() -> Vec<DBCol> {
+    use strum::IntoEnumIterator;
+    DBCol::iter().collect()
+}
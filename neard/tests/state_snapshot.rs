@@ -0,0 +1,92 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use near_chain::{ChainGenesis, ChainStore, ChainStoreAccess, RuntimeAdapter};
+use near_chain_configs::Genesis;
+use near_client::test_utils::TestEnv;
+use near_crypto::{InMemorySigner, KeyType};
+use near_logger_utils::init_test_logger;
+use near_network::test_utils::open_port;
+use near_primitives::hash::CryptoHash;
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::{ShardId, StateRoot};
+use neard::config::GenesisExt;
+use neard::state_snapshot::{export_state_snapshot, import_state_snapshot};
+use neard::{init_and_migrate_store, load_test_config, NearConfig, NightshadeRuntime};
+
+fn create_runtime(home_dir: &Path, near_config: &NearConfig) -> NightshadeRuntime {
+    let store = init_and_migrate_store(home_dir, near_config);
+    NightshadeRuntime::new(home_dir, store, &near_config.genesis, vec![], vec![])
+}
+
+/// State root of the shard after the last chunk before the sync block and the trie under it.
+fn shard_state(
+    home_dir: &Path,
+    near_config: &NearConfig,
+    sync_hash: &CryptoHash,
+    shard_id: ShardId,
+) -> (StateRoot, Vec<(Vec<u8>, Vec<u8>)>) {
+    let store = init_and_migrate_store(home_dir, near_config);
+    let runtime =
+        NightshadeRuntime::new(home_dir, store.clone(), &near_config.genesis, vec![], vec![]);
+    let mut chain_store = ChainStore::new(store, near_config.genesis.config.genesis_height);
+    let prev_hash = *chain_store.get_block_header(sync_hash).unwrap().prev_hash();
+    let state_root = chain_store.get_chunk_extra(&prev_hash, shard_id).unwrap().state_root;
+    let trie = runtime.get_trie_for_shard(shard_id);
+    let items = trie.iter(&state_root).unwrap().map(Result::unwrap).collect();
+    (state_root, items)
+}
+
+/// The state exported from one node and imported into a fresh node that only has the headers
+/// is the same on both nodes.
+#[test]
+fn test_state_snapshot_round_trip() {
+    init_test_logger();
+    let source_dir = tempfile::Builder::new().prefix("state_snapshot_source").tempdir().unwrap();
+    let target_dir = tempfile::Builder::new().prefix("state_snapshot_target").tempdir().unwrap();
+    let snapshot_dir = tempfile::Builder::new().prefix("state_snapshot").tempdir().unwrap();
+    let mut genesis = Genesis::test(vec!["test0", "test1"], 1);
+    genesis.config.epoch_length = 5;
+    let near_config = load_test_config("test0", open_port(), genesis.clone());
+
+    {
+        let runtimes: Vec<Arc<dyn RuntimeAdapter>> = vec![
+            Arc::new(create_runtime(source_dir.path(), &near_config)),
+            Arc::new(create_runtime(target_dir.path(), &near_config)),
+        ];
+        let mut env = TestEnv::new_with_runtime(ChainGenesis::from(&genesis), 2, 1, runtimes);
+        let genesis_hash = *env.clients[0].chain.genesis().hash();
+        let signer = InMemorySigner::from_seed("test0", KeyType::ED25519, "test0");
+        for height in 1..=12 {
+            let tx = SignedTransaction::send_money(
+                height,
+                "test0".to_string(),
+                "test1".to_string(),
+                &signer,
+                100,
+                genesis_hash,
+            );
+            env.clients[0].process_tx(tx, false, false);
+            env.produce_block(0, height);
+        }
+        // The target node only has the headers, like a node about to state sync.
+        let headers = (1..=12)
+            .map(|height| env.clients[0].chain.get_header_by_height(height).unwrap().clone())
+            .collect();
+        env.clients[1].chain.sync_block_headers(headers, |_| {}).unwrap();
+    }
+
+    let exported =
+        export_state_snapshot(source_dir.path(), &near_config, None, None, snapshot_dir.path())
+            .unwrap();
+    let imported =
+        import_state_snapshot(target_dir.path(), &near_config, snapshot_dir.path()).unwrap();
+    assert_eq!(exported.sync_hash, imported.sync_hash);
+    assert_eq!(imported.shards.len(), 1);
+    assert_eq!(exported.shards[0].state_root, imported.shards[0].state_root);
+
+    let source_state = shard_state(source_dir.path(), &near_config, &exported.sync_hash, 0);
+    let target_state = shard_state(target_dir.path(), &near_config, &exported.sync_hash, 0);
+    assert!(!source_state.1.is_empty());
+    assert_eq!(source_state, target_state);
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+
+use actix::System;
+use strum::IntoEnumIterator;
+
+use near_chain::{ChainGenesis, RuntimeAdapter};
+use near_chain_configs::Genesis;
+use near_client::test_utils::TestEnv;
+use near_client::GetBlock;
+use near_logger_utils::init_test_logger;
+use near_network::test_utils::open_port;
+use near_store::{create_store_with_config, DBCol, Store};
+use neard::config::GenesisExt;
+use neard::{
+    get_store_path, init_and_migrate_store, load_test_config,
+    start_view_client_with_existing_store, NightshadeRuntime,
+};
+
+/// Contents of every column of the store.
+fn dump_store(store: &Store) -> Vec<Vec<(Box<[u8]>, Box<[u8]>)>> {
+    DBCol::iter().map(|col| store.iter_without_rc_logic(col).collect()).collect()
+}
+
+/// The view client started on top of an existing store serves the stored chain and leaves the
+/// store as it was.
+#[test]
+fn test_view_client_with_existing_store_is_read_only() {
+    init_test_logger();
+    let dir = tempfile::Builder::new().prefix("view_client_existing_store").tempdir().unwrap();
+    let genesis = Genesis::test(vec!["test0"], 1);
+    let near_config = load_test_config("test0", open_port(), genesis.clone());
+
+    let num_blocks = 5;
+    {
+        let store = init_and_migrate_store(dir.path(), &near_config);
+        let runtimes: Vec<Arc<dyn RuntimeAdapter>> =
+            vec![Arc::new(NightshadeRuntime::new(dir.path(), store, &genesis, vec![], vec![]))];
+        let mut env = TestEnv::new_with_runtime(ChainGenesis::from(&genesis), 1, 1, runtimes);
+        for height in 1..=num_blocks {
+            env.produce_block(0, height);
+        }
+    }
+    let path = get_store_path(dir.path());
+    let store_config = near_config.store_config.clone();
+    let store_before = dump_store(&create_store_with_config(&path, &store_config));
+
+    let home_dir = dir.path().to_path_buf();
+    System::builder()
+        .stop_on_panic(true)
+        .run(move || {
+            let view_client = start_view_client_with_existing_store(&home_dir, near_config);
+            actix::spawn(async move {
+                let block = view_client.send(GetBlock::latest()).await.unwrap().unwrap();
+                assert_eq!(block.header.height, num_blocks);
+                System::current().stop();
+            });
+        })
+        .unwrap();
+
+    // Let the view client actors drop the store, see `run_nodes` for details.
+    std::thread::sleep(std::time::Duration::from_millis(250));
+    let store_after = dump_store(&create_store_with_config(&path, &store_config));
+    assert!(store_before == store_after, "Store was changed by the view client");
+}
@@ -12,6 +12,7 @@ pub use runner::compile_module;
 pub use runner::run;
 pub use runner::run_vm;
 pub use runner::run_vm_profiled;
+pub use runner::run_with_vm_kind;
 pub use runner::with_vm_variants;
 
 pub use near_vm_logic::with_ext_cost_counter;
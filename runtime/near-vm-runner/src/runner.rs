@@ -31,6 +31,38 @@ pub fn run<'a>(
     current_protocol_version: ProtocolVersion,
     cache: Option<&'a dyn CompiledContractCache>,
     #[cfg(feature = "costs_counting")] profile: Option<&ProfileData>,
+) -> (Option<VMOutcome>, Option<VMError>) {
+    run_with_vm_kind(
+        code_hash,
+        code,
+        method_name,
+        ext,
+        context,
+        wasm_config,
+        fees_config,
+        promise_results,
+        VMKind::default(),
+        current_protocol_version,
+        cache,
+        #[cfg(feature = "costs_counting")]
+        profile,
+    )
+}
+
+/// Same as `run`, but executes the contract with the given VM instead of the default one.
+pub fn run_with_vm_kind<'a>(
+    code_hash: Vec<u8>,
+    code: &[u8],
+    method_name: &[u8],
+    ext: &mut dyn External,
+    context: VMContext,
+    wasm_config: &'a VMConfig,
+    fees_config: &'a RuntimeFeesConfig,
+    promise_results: &'a [PromiseResult],
+    vm_kind: VMKind,
+    current_protocol_version: ProtocolVersion,
+    cache: Option<&'a dyn CompiledContractCache>,
+    #[cfg(feature = "costs_counting")] profile: Option<&ProfileData>,
 ) -> (Option<VMOutcome>, Option<VMError>) {
     #[cfg(feature = "costs_counting")]
     if let Some(profile) = profile {
@@ -43,7 +75,7 @@ pub fn run<'a>(
             wasm_config,
             fees_config,
             promise_results,
-            VMKind::default(),
+            vm_kind,
             profile.clone(),
             current_protocol_version,
             cache,
@@ -58,11 +90,12 @@ pub fn run<'a>(
         wasm_config,
         fees_config,
         promise_results,
-        VMKind::default(),
+        vm_kind,
         current_protocol_version,
         cache,
     )
 }
+
 pub fn run_vm<'a>(
     code_hash: Vec<u8>,
     code: &[u8],
@@ -31,7 +31,7 @@ use near_vm_errors::{
     CacheError, CompilationError, FunctionCallError, InconsistentStateError, VMError,
 };
 use near_vm_logic::types::PromiseResult;
-use near_vm_logic::{VMContext, VMOutcome};
+use near_vm_logic::{VMContext, VMKind, VMOutcome};
 
 use crate::config::{safe_add_gas, RuntimeConfig};
 use crate::ext::RuntimeExt;
@@ -52,6 +52,7 @@ pub(crate) fn execute_function_call(
     config: &RuntimeConfig,
     is_last_action: bool,
     is_view: bool,
+    vm_kind: VMKind,
 ) -> (Option<VMOutcome>, Option<VMError>) {
     let account_id = runtime_ext.account_id();
     if checked_feature!("protocol_feature_evm", EVM, runtime_ext.protocol_version())
@@ -132,7 +133,7 @@ pub(crate) fn execute_function_call(
             output_data_receivers,
         };
 
-        near_vm_runner::run(
+        near_vm_runner::run_with_vm_kind(
             code.hash.as_ref().to_vec(),
             &code.code,
             function_call.method_name.as_bytes(),
@@ -141,6 +142,7 @@ pub(crate) fn execute_function_call(
             &config.wasm_config,
             &config.transaction_costs,
             promise_results,
+            vm_kind,
             apply_state.current_protocol_version,
             cache,
             #[cfg(feature = "costs_counting")]
@@ -163,6 +165,7 @@ pub(crate) fn action_function_call(
     config: &RuntimeConfig,
     is_last_action: bool,
     epoch_info_provider: &dyn EpochInfoProvider,
+    vm_kind: VMKind,
 ) -> Result<(), RuntimeError> {
     if account.amount.checked_add(function_call.deposit).is_none() {
         return Err(StorageError::StorageInconsistentState(
@@ -195,6 +198,7 @@ pub(crate) fn action_function_call(
         config,
         is_last_action,
         false,
+        vm_kind,
     );
     let execution_succeeded = match err {
         Some(VMError::FunctionCallError(err)) => {
@@ -39,6 +39,7 @@ use near_store::{
 };
 use near_vm_logic::types::PromiseResult;
 use near_vm_logic::ReturnData;
+use near_vm_logic::VMKind;
 pub use near_vm_runner::with_ext_cost_counter;
 
 use crate::actions::*;
@@ -172,11 +173,18 @@ impl Default for ActionResult {
     }
 }
 
-pub struct Runtime {}
+pub struct Runtime {
+    /// VM used to execute function calls.
+    vm_kind: VMKind,
+}
 
 impl Runtime {
     pub fn new() -> Self {
-        Self {}
+        Self::with_vm_kind(VMKind::default())
+    }
+
+    pub fn with_vm_kind(vm_kind: VMKind) -> Self {
+        Self { vm_kind }
     }
 
     fn print_log(log: &[LogEntry]) {
@@ -349,6 +357,7 @@ impl Runtime {
                     &apply_state.config,
                     action_index + 1 == actions.len(),
                     epoch_info_provider,
+                    self.vm_kind,
                 )?;
             }
             Action::Transfer(transfer) => {
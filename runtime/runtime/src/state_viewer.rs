@@ -14,7 +14,7 @@ use near_primitives::{
 };
 use near_runtime_utils::is_valid_account_id;
 use near_store::{get_access_key, get_account, get_code, TrieUpdate};
-use near_vm_logic::ReturnData;
+use near_vm_logic::{ReturnData, VMKind};
 use std::{str, sync::Arc, time::Instant};
 
 use crate::{actions::execute_function_call, ext::RuntimeExt};
@@ -173,6 +173,7 @@ impl TrieViewer {
             &config,
             true,
             true,
+            VMKind::default(),
         );
         let elapsed = now.elapsed();
         let time_ms =
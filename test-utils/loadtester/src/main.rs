@@ -16,9 +16,9 @@ use near_crypto::Signer;
 use near_primitives::types::{NumSeats, NumShards};
 use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::version::Version;
-use near_store::{create_store, ColState};
+use near_store::ColState;
 use neard::config::create_testnet_configs;
-use neard::{get_default_home, get_store_path};
+use neard::{get_default_home, get_store_path, load_config, open_store};
 use remote_node::RemoteNode;
 
 use crate::transactions_executor::Executor;
@@ -197,7 +197,8 @@ fn load_state_dump(matches: &clap::ArgMatches<'_>) {
     let state_dump_path = value_t_or_exit!(matches, "state_dump", PathBuf);
     let dir = dir_buf.as_path();
     let state_dump = state_dump_path.as_path();
-    let store = create_store(&get_store_path(dir));
+    let near_config = load_config(dir);
+    let store = open_store(dir, &get_store_path(dir), &near_config);
     store.load_from_file(ColState, state_dump).expect("Failed to read state dump");
 }
 
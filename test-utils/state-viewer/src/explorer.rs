@@ -0,0 +1,468 @@
+//! Explorer of the state at a block: accounts by prefix, everything stored for an account,
+//! delayed receipts and contract data, with the keys decoded into `TrieKey`s and the output in
+//! JSON or CSV. The commands can be given one by one on the command line or read from stdin.
+use std::io::{self, BufRead};
+use std::mem::size_of;
+use std::str::FromStr;
+
+use borsh::BorshDeserialize;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use serde_json::{json, Value};
+
+use near_chain::RuntimeAdapter;
+use near_primitives::account::{AccessKey, Account};
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::receipt::{DelayedReceiptIndices, Receipt, ReceivedData};
+use near_primitives::serialize::{from_base64, to_base64};
+use near_primitives::trie_key::trie_key_parsers::{
+    get_raw_prefix_for_access_keys, get_raw_prefix_for_contract_data, parse_trie_key_from_raw_key,
+};
+use near_primitives::trie_key::TrieKey;
+use near_primitives::types::{AccountId, ShardId, StateRoot};
+use near_primitives::views::{AccessKeyView, AccountView, ReceiptView};
+use near_store::{Trie, TrieIterator};
+use neard::NightshadeRuntime;
+
+const DEFAULT_LIMIT: &str = "100";
+
+#[derive(Clone, Copy)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => Err(format!("Unknown output format {}, expected json or csv", s)),
+        }
+    }
+}
+
+/// Commands of the explorer, shared by the command line and the interactive mode.
+pub fn commands() -> Vec<App<'static, 'static>> {
+    let limit = Arg::with_name("limit")
+        .long("limit")
+        .help("Max number of records to print")
+        .takes_value(true)
+        .default_value(DEFAULT_LIMIT);
+    vec![
+        SubCommand::with_name("accounts")
+            .arg(
+                Arg::with_name("prefix")
+                    .long("prefix")
+                    .help("Prefix of the account ids")
+                    .takes_value(true)
+                    .default_value(""),
+            )
+            .arg(
+                Arg::with_name("from")
+                    .long("from")
+                    .help("Account id to start from, the `next` of the previous page")
+                    .takes_value(true),
+            )
+            .arg(limit.clone())
+            .help("List accounts in all shards"),
+        SubCommand::with_name("account")
+            .arg(Arg::with_name("account").long("account").takes_value(true).required(true))
+            .help("Show the account, its contract code, access keys, received data and postponed receipts"),
+        SubCommand::with_name("delayed_receipts")
+            .arg(
+                Arg::with_name("shard_id")
+                    .long("shard_id")
+                    .takes_value(true)
+                    .default_value("0"),
+            )
+            .arg(
+                Arg::with_name("from")
+                    .long("from")
+                    .help("Index in the queue to start from, the first one by default")
+                    .takes_value(true),
+            )
+            .arg(limit.clone())
+            .help("Show the delayed receipts queue of a shard"),
+        SubCommand::with_name("data")
+            .arg(Arg::with_name("account").long("account").takes_value(true).required(true))
+            .arg(
+                Arg::with_name("prefix")
+                    .long("prefix")
+                    .help("Base64 encoded prefix of the keys")
+                    .takes_value(true)
+                    .default_value(""),
+            )
+            .arg(
+                Arg::with_name("from")
+                    .long("from")
+                    .help("Base64 encoded key to start from, the `next` of the previous page")
+                    .takes_value(true),
+            )
+            .arg(limit)
+            .help("List the contract data of an account"),
+    ]
+}
+
+pub struct StateExplorer {
+    runtime: NightshadeRuntime,
+    state_roots: Vec<StateRoot>,
+    format: OutputFormat,
+}
+
+/// Records of one command and the value of `--from` that continues after them, if any.
+struct Page {
+    rows: Vec<Value>,
+    next: Option<String>,
+}
+
+fn decode<T: BorshDeserialize>(
+    value: &[u8],
+    view: impl FnOnce(T) -> serde_json::Result<Value>,
+) -> Value {
+    T::try_from_slice(value)
+        .ok()
+        .and_then(|decoded| view(decoded).ok())
+        .unwrap_or_else(|| Value::String(to_base64(value)))
+}
+
+/// Describes a record of the state as its type, account, the rest of the key and the value.
+fn state_row(shard_id: ShardId, raw_key: &[u8], value: &[u8]) -> Value {
+    let (kind, account_id, key, value) = match parse_trie_key_from_raw_key(raw_key) {
+        Ok(TrieKey::Account { account_id }) => (
+            "account",
+            account_id,
+            String::new(),
+            decode(value, |account: Account| serde_json::to_value(AccountView::from(account))),
+        ),
+        Ok(TrieKey::ContractCode { account_id }) => (
+            "contract_code",
+            account_id,
+            String::new(),
+            json!({ "hash": hash(value), "len": value.len() }),
+        ),
+        Ok(TrieKey::AccessKey { account_id, public_key }) => (
+            "access_key",
+            account_id,
+            public_key.to_string(),
+            decode(value, |access_key: AccessKey| {
+                serde_json::to_value(AccessKeyView::from(access_key))
+            }),
+        ),
+        Ok(TrieKey::ReceivedData { receiver_id, data_id }) => (
+            "received_data",
+            receiver_id,
+            data_id.to_string(),
+            decode(value, |data: ReceivedData| serde_json::to_value(data)),
+        ),
+        Ok(TrieKey::PostponedReceiptId { receiver_id, data_id }) => (
+            "postponed_receipt_id",
+            receiver_id,
+            data_id.to_string(),
+            decode(value, |receipt_id: CryptoHash| serde_json::to_value(receipt_id)),
+        ),
+        Ok(TrieKey::PendingDataCount { receiver_id, receipt_id }) => (
+            "pending_data_count",
+            receiver_id,
+            receipt_id.to_string(),
+            decode(value, |count: u32| serde_json::to_value(count)),
+        ),
+        Ok(TrieKey::PostponedReceipt { receiver_id, receipt_id }) => (
+            "postponed_receipt",
+            receiver_id,
+            receipt_id.to_string(),
+            decode(value, |receipt: Receipt| serde_json::to_value(ReceiptView::from(receipt))),
+        ),
+        Ok(TrieKey::DelayedReceiptIndices) => (
+            "delayed_receipt_indices",
+            AccountId::new(),
+            String::new(),
+            decode(value, |indices: DelayedReceiptIndices| {
+                Ok(json!({
+                    "first_index": indices.first_index,
+                    "next_available_index": indices.next_available_index,
+                }))
+            }),
+        ),
+        Ok(TrieKey::DelayedReceipt { index }) => (
+            "delayed_receipt",
+            AccountId::new(),
+            index.to_string(),
+            decode(value, |receipt: Receipt| serde_json::to_value(ReceiptView::from(receipt))),
+        ),
+        Ok(TrieKey::ContractData { account_id, key }) => {
+            ("contract_data", account_id, to_base64(&key), Value::String(to_base64(value)))
+        }
+        Err(_) => {
+            ("unknown", AccountId::new(), to_base64(raw_key), Value::String(to_base64(value)))
+        }
+    };
+    json!({
+        "shard_id": shard_id,
+        "type": kind,
+        "account_id": account_id,
+        "key": key,
+        "value": value,
+    })
+}
+
+fn csv_field(value: &Value) -> String {
+    let field = match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    };
+    if field.contains(|c| c == ',' || c == '"' || c == '\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+impl StateExplorer {
+    pub fn new(
+        runtime: NightshadeRuntime,
+        state_roots: Vec<StateRoot>,
+        format: OutputFormat,
+    ) -> Self {
+        StateExplorer { runtime, state_roots, format }
+    }
+
+    /// Runs a command given as the subcommand of `matches`.
+    pub fn run(&self, matches: &ArgMatches) -> Result<(), String> {
+        let page = match matches.subcommand() {
+            ("accounts", Some(args)) => self.accounts(
+                args.value_of("prefix").unwrap(),
+                args.value_of("from"),
+                parse_limit(args)?,
+            ),
+            ("account", Some(args)) => self.account(&args.value_of("account").unwrap().into()),
+            ("delayed_receipts", Some(args)) => self.delayed_receipts(
+                args.value_of("shard_id").unwrap().parse().map_err(|_| "Invalid shard id")?,
+                args.value_of("from")
+                    .map(|from| from.parse().map_err(|_| "Invalid index"))
+                    .transpose()?,
+                parse_limit(args)?,
+            ),
+            ("data", Some(args)) => {
+                let prefix =
+                    from_base64(args.value_of("prefix").unwrap()).map_err(|err| err.to_string())?;
+                let from = args
+                    .value_of("from")
+                    .map(|from| from_base64(from).map_err(|err| err.to_string()))
+                    .transpose()?;
+                self.data(
+                    &args.value_of("account").unwrap().into(),
+                    &prefix,
+                    from.as_deref(),
+                    parse_limit(args)?,
+                )
+            }
+            ("", _) => return Err("No command given, see help for the commands".to_string()),
+            (command, _) => return Err(format!("Unknown command {}", command)),
+        };
+        self.print(page?);
+        Ok(())
+    }
+
+    /// Reads commands from stdin, one per line, until the end of the input or `exit`.
+    pub fn run_interactive(&self) {
+        let mut app =
+            App::new("explore").setting(AppSettings::NoBinaryName).subcommands(commands());
+        eprintln!("Commands: accounts, account, delayed_receipts, data, help, exit");
+        for line in io::stdin().lock().lines() {
+            let line = line.unwrap();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.first() {
+                None => continue,
+                Some(&"exit") | Some(&"quit") => break,
+                Some(_) => {}
+            }
+            let result = app
+                .get_matches_from_safe_borrow(words)
+                .map_err(|err| err.message)
+                .and_then(|matches| self.run(&matches));
+            if let Err(err) = result {
+                eprintln!("{}", err);
+            }
+        }
+    }
+
+    fn trie(&self, shard_id: ShardId) -> Result<(Trie, &StateRoot), String> {
+        let state_root = self
+            .state_roots
+            .get(shard_id as usize)
+            .ok_or_else(|| format!("There is no shard {}", shard_id))?;
+        Ok((self.runtime.get_trie_for_shard(shard_id), state_root))
+    }
+
+    /// Returns at most `limit` records of the shard with the keys starting with `prefix`,
+    /// beginning at `from`, and the key of the record after them.
+    fn iter_prefix(
+        &self,
+        shard_id: ShardId,
+        prefix: &[u8],
+        from: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, Option<Vec<u8>>), String> {
+        let (trie, state_root) = self.trie(shard_id)?;
+        let mut iter = TrieIterator::new(&trie, state_root).map_err(|err| err.to_string())?;
+        iter.seek(from.filter(|from| *from > prefix).unwrap_or(prefix))
+            .map_err(|err| err.to_string())?;
+        let mut records = vec![];
+        for item in iter {
+            let (key, value) = item.map_err(|err| err.to_string())?;
+            if !key.starts_with(prefix) {
+                break;
+            }
+            if records.len() == limit {
+                return Ok((records, Some(key)));
+            }
+            records.push((key, value));
+        }
+        Ok((records, None))
+    }
+
+    fn accounts(&self, prefix: &str, from: Option<&str>, limit: usize) -> Result<Page, String> {
+        let raw_prefix = TrieKey::Account { account_id: prefix.to_string() }.to_vec();
+        let raw_from = from.map(|from| TrieKey::Account { account_id: from.to_string() }.to_vec());
+        let mut records = vec![];
+        for shard_id in 0..self.state_roots.len() as ShardId {
+            let (shard_records, _) =
+                self.iter_prefix(shard_id, &raw_prefix, raw_from.as_deref(), limit + 1)?;
+            records.extend(shard_records.into_iter().map(|(key, value)| (key, value, shard_id)));
+        }
+        records.sort();
+        let next = records.get(limit).map(|(key, _, _)| match parse_trie_key_from_raw_key(key) {
+            Ok(TrieKey::Account { account_id }) => account_id,
+            _ => unreachable!("Keys with the account column prefix are accounts"),
+        });
+        records.truncate(limit);
+        let rows =
+            records.iter().map(|(key, value, shard_id)| state_row(*shard_id, key, value)).collect();
+        Ok(Page { rows, next })
+    }
+
+    fn account(&self, account_id: &AccountId) -> Result<Page, String> {
+        let shard_id = self.runtime.account_id_to_shard_id(account_id);
+        let (trie, state_root) = self.trie(shard_id)?;
+        let mut rows = vec![];
+        for key in vec![
+            TrieKey::Account { account_id: account_id.clone() },
+            TrieKey::ContractCode { account_id: account_id.clone() },
+        ] {
+            let key = key.to_vec();
+            if let Some(value) = trie.get(state_root, &key).map_err(|err| err.to_string())? {
+                rows.push(state_row(shard_id, &key, &value));
+            }
+        }
+        // Account ids can't contain the separators, so these prefixes don't match other accounts.
+        let mut prefixes = vec![get_raw_prefix_for_access_keys(account_id)];
+        for key in vec![
+            TrieKey::ReceivedData {
+                receiver_id: account_id.clone(),
+                data_id: CryptoHash::default(),
+            },
+            TrieKey::PostponedReceiptId {
+                receiver_id: account_id.clone(),
+                data_id: CryptoHash::default(),
+            },
+            TrieKey::PendingDataCount {
+                receiver_id: account_id.clone(),
+                receipt_id: CryptoHash::default(),
+            },
+            TrieKey::PostponedReceipt {
+                receiver_id: account_id.clone(),
+                receipt_id: CryptoHash::default(),
+            },
+        ] {
+            let key = key.to_vec();
+            prefixes.push(key[..key.len() - size_of::<CryptoHash>()].to_vec());
+        }
+        for prefix in prefixes {
+            let (records, _) = self.iter_prefix(shard_id, &prefix, None, usize::MAX)?;
+            rows.extend(records.iter().map(|(key, value)| state_row(shard_id, key, value)));
+        }
+        if rows.is_empty() {
+            return Err(format!("Account {} doesn't exist in shard {}", account_id, shard_id));
+        }
+        Ok(Page { rows, next: None })
+    }
+
+    fn delayed_receipts(
+        &self,
+        shard_id: ShardId,
+        from: Option<u64>,
+        limit: usize,
+    ) -> Result<Page, String> {
+        let (trie, state_root) = self.trie(shard_id)?;
+        let indices_key = TrieKey::DelayedReceiptIndices.to_vec();
+        let indices_value =
+            trie.get(state_root, &indices_key).map_err(|err| err.to_string())?.unwrap_or_default();
+        let indices = if indices_value.is_empty() {
+            DelayedReceiptIndices::default()
+        } else {
+            DelayedReceiptIndices::try_from_slice(&indices_value).map_err(|err| err.to_string())?
+        };
+        let mut rows = vec![state_row(shard_id, &indices_key, &indices_value)];
+        // The keys are little endian indices, so the queue is read by index rather than by
+        // iterating the trie.
+        let from = from.unwrap_or(indices.first_index).max(indices.first_index);
+        let to = indices.next_available_index.min(from.saturating_add(limit as u64));
+        for index in from..to {
+            let key = TrieKey::DelayedReceipt { index }.to_vec();
+            let value = trie
+                .get(state_root, &key)
+                .map_err(|err| err.to_string())?
+                .ok_or_else(|| format!("Delayed receipt {} is missing", index))?;
+            rows.push(state_row(shard_id, &key, &value));
+        }
+        let next = if to < indices.next_available_index { Some(to.to_string()) } else { None };
+        Ok(Page { rows, next })
+    }
+
+    fn data(
+        &self,
+        account_id: &AccountId,
+        prefix: &[u8],
+        from: Option<&[u8]>,
+        limit: usize,
+    ) -> Result<Page, String> {
+        let shard_id = self.runtime.account_id_to_shard_id(account_id);
+        let raw_prefix = get_raw_prefix_for_contract_data(account_id, prefix);
+        let raw_from = from.map(|from| get_raw_prefix_for_contract_data(account_id, from));
+        let (records, next) =
+            self.iter_prefix(shard_id, &raw_prefix, raw_from.as_deref(), limit)?;
+        let next = next.map(|key| match parse_trie_key_from_raw_key(&key) {
+            Ok(TrieKey::ContractData { key, .. }) => to_base64(&key),
+            _ => unreachable!("Keys with the contract data prefix are contract data"),
+        });
+        let rows = records.iter().map(|(key, value)| state_row(shard_id, key, value)).collect();
+        Ok(Page { rows, next })
+    }
+
+    fn print(&self, page: Page) {
+        match self.format {
+            OutputFormat::Json => {
+                let output = json!({ "rows": page.rows, "next": page.next });
+                println!("{}", serde_json::to_string_pretty(&output).unwrap());
+            }
+            OutputFormat::Csv => {
+                let columns = ["shard_id", "type", "account_id", "key", "value"];
+                println!("{}", columns.join(","));
+                for row in page.rows.iter() {
+                    let fields: Vec<String> =
+                        columns.iter().map(|column| csv_field(&row[column])).collect();
+                    println!("{}", fields.join(","));
+                }
+                if let Some(next) = page.next {
+                    eprintln!("next: {}", next);
+                }
+            }
+        }
+    }
+}
+
+fn parse_limit(args: &ArgMatches) -> Result<usize, String> {
+    args.value_of("limit").unwrap().parse().map_err(|_| "Invalid limit".to_string())
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fs::File;
 use std::io::Write;
@@ -9,6 +9,7 @@ use ansi_term::Color::Red;
 use clap::{App, Arg, SubCommand};
 
 use borsh::BorshSerialize;
+use explorer::{OutputFormat, StateExplorer};
 use near_chain::chain::collect_receipts_from_response;
 use near_chain::types::{ApplyTransactionResult, BlockHeaderInfo};
 use near_chain::{ChainStore, ChainStoreAccess, ChainStoreUpdate, RuntimeAdapter};
@@ -17,15 +18,23 @@ use near_network::peer_store::PeerStore;
 use near_primitives::block::BlockHeader;
 use near_primitives::contract::ContractCode;
 use near_primitives::hash::CryptoHash;
-use near_primitives::serialize::to_base;
+use near_primitives::runtime::config::RuntimeConfig;
+use near_primitives::serialize::{from_base64, to_base};
 use near_primitives::state_record::StateRecord;
-use near_primitives::types::{BlockHeight, ChunkExtra, ShardId, StateRoot};
+use near_primitives::types::{BlockHeight, ChunkExtra, Gas, NumShards, ShardId, StateRoot};
+use near_primitives::utils::get_block_shard_id;
+use near_primitives::views::StateDiffView;
 use near_store::test_utils::create_test_store;
-use near_store::{create_store, Store, TrieIterator};
-use neard::{get_default_home, get_store_path, load_config, NearConfig, NightshadeRuntime};
+use near_store::{ColTrieChanges, ShardTries, Store, TrieAudit, TrieChanges, TrieIterator};
+use near_vm_logic::VMKind;
+use neard::{
+    get_default_home, get_store_path, load_config, open_store, NearConfig, NightshadeRuntime,
+};
 use node_runtime::adapter::ViewRuntimeAdapter;
+use node_runtime::Runtime;
 use state_dump::state_dump;
 
+mod explorer;
 mod state_dump;
 
 #[allow(unused)]
@@ -269,6 +278,189 @@ fn apply_block_at_height(
     }
 }
 
+/// Merges `patch` into `value`: objects are merged key by key, anything else is replaced.
+fn merge_json(value: &mut serde_json::Value, patch: serde_json::Value) {
+    match (value, patch) {
+        (serde_json::Value::Object(value), serde_json::Value::Object(patch)) => {
+            for (key, patch) in patch {
+                merge_json(value.entry(key).or_insert(serde_json::Value::Null), patch);
+            }
+        }
+        (value, patch) => *value = patch,
+    }
+}
+
+/// Re-applies the chunks of the shard between the heights with a modified runtime config and/or
+/// VM and compares the results with the ones recorded on chain. Every chunk is applied on its
+/// recorded previous state root, so that a divergence in one chunk doesn't affect the others.
+fn replay_with_config(
+    store: Arc<Store>,
+    home_dir: &Path,
+    near_config: &NearConfig,
+    start_height: BlockHeight,
+    end_height: BlockHeight,
+    shard_id: ShardId,
+    runtime_config_patch: Option<serde_json::Value>,
+    vm_kind: Option<VMKind>,
+) {
+    let mut chain_store = ChainStore::new(store.clone(), near_config.genesis.config.genesis_height);
+    let mut runtime = NightshadeRuntime::new(
+        &home_dir,
+        store,
+        &near_config.genesis,
+        near_config.client_config.tracked_accounts.clone(),
+        near_config.client_config.tracked_shards.clone(),
+    );
+    if let Some(vm_kind) = vm_kind {
+        runtime.runtime = Runtime::with_vm_kind(vm_kind);
+    }
+    if let Some(patch) = runtime_config_patch {
+        // The patch is applied to the config of the protocol version at the start height and the
+        // result is used for the whole range.
+        let block_hash = chain_store.get_block_hash_by_height(start_height).unwrap();
+        let epoch_id = chain_store.get_block_header(&block_hash).unwrap().epoch_id().clone();
+        let protocol_version = runtime.get_epoch_protocol_version(&epoch_id).unwrap();
+        let runtime_config = RuntimeConfig::from_protocol_version(
+            &Arc::new(near_config.genesis.config.runtime_config.clone()),
+            protocol_version,
+        );
+        let mut value = serde_json::to_value(&*runtime_config).unwrap();
+        merge_json(&mut value, patch);
+        runtime.set_runtime_config_override(
+            serde_json::from_value(value).expect("Failed to parse runtime config"),
+        );
+    }
+
+    let mut num_chunks = 0;
+    let mut num_diverged_chunks = 0;
+    let mut num_diverged_outcomes = 0;
+    let mut recorded_gas_used: Gas = 0;
+    let mut replayed_gas_used: Gas = 0;
+    let mut first_divergence = None;
+    for height in start_height..=end_height {
+        let block_hash = match chain_store.get_block_hash_by_height(height) {
+            Ok(block_hash) => block_hash,
+            Err(_) => continue,
+        };
+        let block = chain_store.get_block(&block_hash).unwrap().clone();
+        let chunk_header = block.chunks()[shard_id as usize].clone();
+        if chunk_header.height_included() != height {
+            continue;
+        }
+        let chunk = chain_store.get_chunk(&chunk_header.chunk_hash()).unwrap().clone();
+        let prev_block = chain_store.get_block(&block.header().prev_hash()).unwrap().clone();
+        let mut chain_store_update = ChainStoreUpdate::new(&mut chain_store);
+        let receipt_proof_response = chain_store_update
+            .get_incoming_receipts_for_shard(
+                shard_id,
+                block_hash,
+                prev_block.chunks()[shard_id as usize].height_included(),
+            )
+            .unwrap();
+        let receipts = collect_receipts_from_response(&receipt_proof_response);
+
+        let chunk_inner = chunk.cloned_header().take_inner();
+        let apply_result = runtime
+            .apply_transactions(
+                shard_id,
+                &chunk_inner.prev_state_root,
+                height,
+                block.header().raw_timestamp(),
+                block.header().prev_hash(),
+                block.hash(),
+                &receipts,
+                chunk.transactions(),
+                &chunk_inner.validator_proposals,
+                prev_block.header().gas_price(),
+                chunk_inner.gas_limit,
+                &block.header().challenges_result(),
+                *block.header().random_value(),
+            )
+            .unwrap();
+        let chunk_extra = chain_store.get_chunk_extra(&block_hash, shard_id).unwrap().clone();
+        let recorded_outcomes: HashMap<_, _> = chain_store
+            .get_outcomes_by_block_hash_and_shard_id(&block_hash, shard_id)
+            .unwrap()
+            .into_iter()
+            .filter_map(|id| {
+                chain_store
+                    .get_outcomes_by_id(&id)
+                    .unwrap()
+                    .into_iter()
+                    .find(|outcome| outcome.block_hash == block_hash)
+            })
+            .map(|outcome| (outcome.outcome_with_id.id, outcome.outcome_with_id.outcome))
+            .collect();
+
+        num_chunks += 1;
+        recorded_gas_used += chunk_extra.gas_used;
+        replayed_gas_used += apply_result.total_gas_burnt;
+        let state_root_matches = chunk_extra.state_root == apply_result.new_root;
+        if !state_root_matches {
+            num_diverged_chunks += 1;
+        }
+        println!(
+            "{: >8} gas used {} -> {}, state root {}",
+            height,
+            chunk_extra.gas_used,
+            apply_result.total_gas_burnt,
+            if state_root_matches {
+                "matches".to_string()
+            } else {
+                format!("{} -> {}", chunk_extra.state_root, apply_result.new_root)
+            }
+        );
+        if recorded_outcomes.is_empty() && !apply_result.outcomes.is_empty() {
+            println!("{: >8} no recorded outcomes to compare with", height);
+            continue;
+        }
+        let transaction_hashes: HashSet<_> =
+            chunk.transactions().iter().map(|transaction| transaction.get_hash()).collect();
+        for outcome_with_id in apply_result.outcomes.iter() {
+            let outcome = &outcome_with_id.outcome;
+            let difference = match recorded_outcomes.get(&outcome_with_id.id) {
+                Some(recorded)
+                    if recorded.gas_burnt == outcome.gas_burnt
+                        && recorded.status == outcome.status =>
+                {
+                    continue
+                }
+                Some(recorded) => format!(
+                    "gas burnt {} -> {}, status {:?} -> {:?}",
+                    recorded.gas_burnt, outcome.gas_burnt, recorded.status, outcome.status
+                ),
+                None => "no recorded outcome".to_string(),
+            };
+            num_diverged_outcomes += 1;
+            if first_divergence.is_none() {
+                let kind = if transaction_hashes.contains(&outcome_with_id.id) {
+                    "transaction"
+                } else {
+                    "receipt"
+                };
+                first_divergence = Some(format!(
+                    "{} {} at height {}: {}",
+                    kind, outcome_with_id.id, height, difference
+                ));
+            }
+        }
+    }
+
+    println!(
+        "Replayed {} chunks of shard {}: gas used {} -> {}, {} state roots and {} outcomes differ",
+        num_chunks,
+        shard_id,
+        recorded_gas_used,
+        replayed_gas_used,
+        num_diverged_chunks,
+        num_diverged_outcomes
+    );
+    match first_divergence {
+        Some(first_divergence) => println!("First diverging {}", first_divergence),
+        None => println!("No diverging outcomes"),
+    }
+}
+
 fn view_chain(
     store: Arc<Store>,
     near_config: &NearConfig,
@@ -361,6 +553,138 @@ fn check_block_chunk_existence(store: Arc<Store>, near_config: &NearConfig) {
     println!("Block check succeed");
 }
 
+/// Checks that the trie nodes in `ColState` are exactly the nodes reachable from the state roots
+/// of the blocks that are not garbage collected yet, i.e. from the GC tail to the head including
+/// forks, that their refcounts match the references to them, and optionally deletes the leaked
+/// ones.
+fn trie_audit(store: Arc<Store>, near_config: &NearConfig, prune: bool) {
+    if near_config.client_config.archive {
+        panic!("Archival nodes keep all the state, there is nothing to prune");
+    }
+    // The node must be stopped while pruning, otherwise it could insert some of the pruned
+    // records again in the meantime. RocksDB locks the database while it is open, so the node
+    // can't be running once the store was opened here. Other backends give no such guarantee.
+    if prune && store.get_rocksdb().is_none() {
+        panic!("Pruning needs the lock of the RocksDB database to make sure the node is stopped");
+    }
+    let genesis_height = near_config.genesis.config.genesis_height;
+    let mut chain_store = ChainStore::new(store.clone(), genesis_height);
+    let tail = chain_store.tail().unwrap();
+    let head = chain_store.head().unwrap();
+    let mut audit = TrieAudit::new(store.clone());
+    let mut base_roots = HashSet::new();
+    let mut num_trie_changes = 0;
+    for height in tail..=head.height {
+        let block_hashes: Vec<CryptoHash> = match chain_store.get_all_block_hashes_by_height(height)
+        {
+            Ok(hashes_by_epoch) => hashes_by_epoch.values().flatten().cloned().collect(),
+            Err(_) => continue,
+        };
+        for block_hash in block_hashes {
+            let block = chain_store.get_block(&block_hash).unwrap().clone();
+            // Headers are never garbage collected, unlike the trie changes of the blocks below
+            // the tail.
+            let is_prev_live = chain_store
+                .get_block_header(block.header().prev_hash())
+                .map_or(false, |prev_header| prev_header.height() >= tail);
+            for shard_id in 0..block.chunks().len() as ShardId {
+                let trie_changes: Option<TrieChanges> = store
+                    .get_ser(ColTrieChanges, &get_block_shard_id(&block_hash, shard_id))
+                    .unwrap();
+                match trie_changes {
+                    Some(trie_changes) => {
+                        if !is_prev_live {
+                            base_roots.insert((shard_id, trie_changes.old_root));
+                        }
+                        audit.add_trie_changes(shard_id, &trie_changes).unwrap();
+                        num_trie_changes += 1;
+                    }
+                    // The state of genesis and of state sync has no trie changes.
+                    None => {
+                        // Shards without chunk extra are not tracked and have no state on this
+                        // node.
+                        if let Ok(chunk_extra) = chain_store.get_chunk_extra(&block_hash, shard_id)
+                        {
+                            base_roots.insert((shard_id, chunk_extra.state_root));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    for (shard_id, root) in base_roots.iter() {
+        audit.add_base_root(*shard_id, root).unwrap();
+    }
+    let report = audit.finish().unwrap();
+    println!(
+        "Checked {} base state roots and {} trie changes of heights {}..={}, {} reachable trie nodes and values",
+        base_roots.len(), num_trie_changes, tail, head.height, report.num_reachable
+    );
+    for (shard_id, hash) in report.missing.iter() {
+        println!(
+            "{} shard {} {} is reachable but missing",
+            Red.bold().paint("MISSING"),
+            shard_id,
+            hash
+        );
+    }
+    for (shard_id, hash, rc, references) in report.refcount_mismatches.iter() {
+        println!(
+            "{} shard {} {} has refcount {} but {} references",
+            Red.bold().paint("REFCOUNT"),
+            shard_id,
+            hash,
+            rc,
+            references
+        );
+    }
+    println!(
+        "{} missing, {} with wrong refcount, {} unreachable with positive refcount, {} with non-positive refcount",
+        report.missing.len(),
+        report.refcount_mismatches.len(),
+        report.unreachable.len(),
+        report.non_positive.len()
+    );
+    if prune {
+        report.prune(&store).unwrap();
+        println!("Deleted {} records", report.unreachable.len() + report.non_positive.len());
+    }
+}
+
+/// Returns up to `limit` keys of the state of the shard starting from `start_key` that were added,
+/// removed or modified between the blocks at the two heights.
+fn state_diff(
+    store: Arc<Store>,
+    near_config: &NearConfig,
+    shard_id: ShardId,
+    from_height: BlockHeight,
+    to_height: BlockHeight,
+    start_key: &[u8],
+    limit: usize,
+) -> StateDiffView {
+    let mut chain_store = ChainStore::new(store.clone(), near_config.genesis.config.genesis_height);
+    let from_block_hash = chain_store.get_block_hash_by_height(from_height).unwrap();
+    let to_block_hash = chain_store.get_block_hash_by_height(to_height).unwrap();
+    let num_shards = chain_store.get_block(&to_block_hash).unwrap().chunks().len();
+    let from_state_root =
+        chain_store.get_chunk_extra(&from_block_hash, shard_id).unwrap().state_root;
+    let to_state_root = chain_store.get_chunk_extra(&to_block_hash, shard_id).unwrap().state_root;
+    let tries = ShardTries::new(store, num_shards as NumShards);
+    let diff = tries
+        .get_view_trie_for_shard(shard_id)
+        .diff(&from_state_root, &to_state_root, start_key, limit)
+        .unwrap();
+    StateDiffView {
+        shard_id,
+        from_block_hash,
+        from_state_root,
+        to_block_hash,
+        to_state_root,
+        changes: diff.items.into_iter().map(Into::into).collect(),
+        next_key: diff.next_key,
+    }
+}
+
 fn dump_code(account: &str, contract_code: ContractCode, output: &str) {
     let mut file = File::create(output).unwrap();
     file.write_all(&contract_code.code).unwrap();
@@ -442,6 +766,43 @@ fn main() {
                 )
                 .help("apply block at some height for shard"),
         )
+        .subcommand(
+            SubCommand::with_name("replay_with_config")
+                .arg(
+                    Arg::with_name("start_height")
+                        .long("start_height")
+                        .required(true)
+                        .help("Height of the first block to apply")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("end_height")
+                        .long("end_height")
+                        .required(true)
+                        .help("Height of the last block to apply")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("shard_id")
+                        .long("shard_id")
+                        .help("Id of the shard to apply")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("runtime_config")
+                        .long("runtime_config")
+                        .help("JSON file with the fields of the runtime config to change")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("vm_kind")
+                        .long("vm_kind")
+                        .help("VM to execute contracts with: wasmer or wasmtime")
+                        .possible_values(&["wasmer", "wasmtime"])
+                        .takes_value(true),
+                )
+                .help("re-apply blocks with a modified runtime config or VM and compare the results with the chain"),
+        )
         .subcommand(
             SubCommand::with_name("view_chain")
                 .arg(
@@ -468,6 +829,82 @@ fn main() {
             SubCommand::with_name("check_block")
                 .help("Check whether the node has all the blocks up to its head"),
         )
+        .subcommand(
+            SubCommand::with_name("trie_audit")
+                .arg(
+                    Arg::with_name("prune")
+                        .long("prune")
+                        .help("Delete the trie nodes and values that are not reachable")
+                        .takes_value(false),
+                )
+                .help("Check trie node refcounts against the state roots that are still in use (the node must be stopped)"),
+        )
+        .subcommand(
+            SubCommand::with_name("state_diff")
+                .arg(
+                    Arg::with_name("shard_id")
+                        .long("shard_id")
+                        .help("Shard to compare")
+                        .takes_value(true)
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::with_name("from_height")
+                        .long("from_height")
+                        .help("Height of the block with the old state")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("to_height")
+                        .long("to_height")
+                        .help("Height of the block with the new state")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("start_key")
+                        .long("start_key")
+                        .help("Base64 raw key to start from, `next_key_base64` of the previous output")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("limit")
+                        .long("limit")
+                        .help("Max number of keys to print")
+                        .takes_value(true)
+                        .default_value("1000"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .help("JSON file to write the changes to instead of stdout")
+                        .takes_value(true),
+                )
+                .help("Print the keys of the state of a shard that changed between two blocks as JSON"),
+        )
+        .subcommand(
+            SubCommand::with_name("explore")
+                .arg(
+                    Arg::with_name("height")
+                        .long("height")
+                        .help("Explore the state before the block at this height, the head by default")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .help("Output format, json or csv")
+                        .takes_value(true)
+                        .default_value("json"),
+                )
+                .subcommands(explorer::commands())
+                .subcommand(
+                    SubCommand::with_name("interactive")
+                        .help("Read the commands from stdin, one per line"),
+                )
+                .help("Explore accounts, access keys, receipts and contract data"),
+        )
         .subcommand(
             SubCommand::with_name("dump_code")
                 .arg(
@@ -490,7 +927,7 @@ fn main() {
     let home_dir = matches.value_of("home").map(|dir| Path::new(dir)).unwrap();
     let near_config = load_config(home_dir);
 
-    let store = create_store(&get_store_path(&home_dir));
+    let store = open_store(&home_dir, &get_store_path(&home_dir), &near_config);
 
     match matches.subcommand() {
         ("peers", Some(_args)) => {
@@ -554,6 +991,32 @@ fn main() {
                 args.value_of("shard_id").map(|s| s.parse::<u64>().unwrap()).unwrap_or_default();
             apply_block_at_height(store, home_dir, &near_config, height, shard_id);
         }
+        ("replay_with_config", Some(args)) => {
+            let start_height =
+                args.value_of("start_height").map(|s| s.parse::<u64>().unwrap()).unwrap();
+            let end_height =
+                args.value_of("end_height").map(|s| s.parse::<u64>().unwrap()).unwrap();
+            let shard_id =
+                args.value_of("shard_id").map(|s| s.parse::<u64>().unwrap()).unwrap_or_default();
+            let runtime_config_patch = args.value_of("runtime_config").map(|path| {
+                let file = File::open(path).expect("Failed to open runtime config file");
+                serde_json::from_reader(file).expect("Failed to parse runtime config file")
+            });
+            let vm_kind = args.value_of("vm_kind").map(|vm_kind| match vm_kind {
+                "wasmtime" => VMKind::Wasmtime,
+                _ => VMKind::Wasmer,
+            });
+            replay_with_config(
+                store,
+                home_dir,
+                &near_config,
+                start_height,
+                end_height,
+                shard_id,
+                runtime_config_patch,
+                vm_kind,
+            );
+        }
         ("view_chain", Some(args)) => {
             let height = args.value_of("height").map(|s| s.parse::<u64>().unwrap());
             let view_block = args.is_present("block");
@@ -563,6 +1026,59 @@ fn main() {
         ("check_block", Some(_)) => {
             check_block_chunk_existence(store, &near_config);
         }
+        ("trie_audit", Some(args)) => {
+            trie_audit(store, &near_config, args.is_present("prune"));
+        }
+        ("state_diff", Some(args)) => {
+            let shard_id = args.value_of("shard_id").map(|s| s.parse::<u64>().unwrap()).unwrap();
+            let from_height =
+                args.value_of("from_height").map(|s| s.parse::<u64>().unwrap()).unwrap();
+            let to_height = args.value_of("to_height").map(|s| s.parse::<u64>().unwrap()).unwrap();
+            let start_key =
+                args.value_of("start_key").map(|s| from_base64(s).unwrap()).unwrap_or_default();
+            let limit = args.value_of("limit").map(|s| s.parse::<usize>().unwrap()).unwrap();
+            let diff = state_diff(
+                store,
+                &near_config,
+                shard_id,
+                from_height,
+                to_height,
+                &start_key,
+                limit,
+            );
+            match args.value_of("output") {
+                Some(output) => {
+                    let file = File::create(output).unwrap();
+                    serde_json::to_writer_pretty(file, &diff).unwrap();
+                    eprintln!("Wrote {} changes to {}", diff.changes.len(), output);
+                }
+                None => println!("{}", serde_json::to_string_pretty(&diff).unwrap()),
+            }
+        }
+        ("explore", Some(args)) => {
+            let mode = match args.value_of("height") {
+                Some(height) => LoadTrieMode::Height(height.parse().unwrap()),
+                None => LoadTrieMode::Latest,
+            };
+            let format: OutputFormat = args.value_of("format").unwrap().parse().unwrap();
+            let (runtime, state_roots, header) =
+                load_trie_stop_at_height(store, home_dir, &near_config, mode);
+            eprintln!(
+                "Exploring the state before block {} at height {}",
+                header.hash(),
+                header.height()
+            );
+            let explorer = StateExplorer::new(runtime, state_roots, format);
+            match args.subcommand() {
+                ("interactive", Some(_)) => explorer.run_interactive(),
+                _ => {
+                    if let Err(err) = explorer.run(args) {
+                        eprintln!("{}", err);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
         ("dump_code", Some(args)) => {
             let account_id = args.value_of("account").expect("account is required");
             let (runtime, state_roots, _header) = load_trie(store, &home_dir, &near_config);
@@ -8,8 +8,7 @@ use clap::{App, Arg, SubCommand};
 use near_chain::store_validator::StoreValidator;
 use near_chain::RuntimeAdapter;
 use near_logger_utils::init_integration_logger;
-use near_store::create_store;
-use neard::{get_default_home, get_store_path, load_config};
+use neard::{get_default_home, get_store_path, load_config, open_store};
 
 fn main() {
     init_integration_logger();
@@ -29,7 +28,7 @@ fn main() {
     let home_dir = matches.value_of("home").map(|dir| Path::new(dir)).unwrap();
     let near_config = load_config(home_dir);
 
-    let store = create_store(&get_store_path(&home_dir));
+    let store = open_store(&home_dir, &get_store_path(&home_dir), &near_config);
 
     let runtime_adapter: Arc<dyn RuntimeAdapter> = Arc::new(neard::NightshadeRuntime::new(
         &home_dir,
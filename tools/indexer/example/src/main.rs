@@ -268,6 +268,8 @@ fn main() {
                 home_dir,
                 sync_mode: near_indexer::SyncModeEnum::FromInterruption,
                 await_for_node_synced: near_indexer::AwaitForNodeSyncedEnum::WaitForFullSync,
+                filter: near_indexer::IndexerFilter::default(),
+                delivery_mode: near_indexer::DeliveryModeEnum::AtMostOnce,
             };
             actix::System::builder()
                 .stop_on_panic(true)
@@ -0,0 +1,272 @@
+//! Prints, filters and replays the network messages captured by a node built with the
+//! `metric_recorder` feature and `capture_path` set in the `network` section of its config.
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use near_network::recorder::{CaptureReader, CaptureWriter, CapturedMessage, Status};
+use near_primitives::utils::from_timestamp;
+
+struct Filter {
+    peer_id: Option<String>,
+    peer_addr: Option<String>,
+    msg_variant: Option<String>,
+    status: Option<Status>,
+}
+
+impl Filter {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        Filter {
+            peer_id: matches.value_of("peer_id").map(|s| s.to_string()),
+            peer_addr: matches.value_of("peer_addr").map(|s| s.to_string()),
+            msg_variant: matches.value_of("type").map(|s| s.to_string()),
+            status: matches.value_of("status").map(|s| match s {
+                "sent" => Status::Sent,
+                "received" => Status::Received,
+                _ => unreachable!(),
+            }),
+        }
+    }
+
+    fn matches(&self, captured_message: &CapturedMessage) -> bool {
+        if self.status.map_or(false, |status| status != captured_message.status) {
+            return false;
+        }
+        if let Some(peer_id) = &self.peer_id {
+            if captured_message.peer_id.as_ref().map(|peer_id| peer_id.to_string()).as_ref()
+                != Some(peer_id)
+            {
+                return false;
+            }
+        }
+        if let Some(peer_addr) = &self.peer_addr {
+            if &captured_message.peer_addr != peer_addr {
+                return false;
+            }
+        }
+        if let Some(msg_variant) = &self.msg_variant {
+            match captured_message.peer_message() {
+                Ok(peer_message) if peer_message.msg_variant() == msg_variant => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+fn read_capture<'a>(path: &Path, filter: &'a Filter) -> impl Iterator<Item = CapturedMessage> + 'a {
+    CaptureReader::open(path)
+        .unwrap_or_else(|err| panic!("Failed to open capture {}: {}", path.display(), err))
+        .map(|captured_message| {
+            captured_message.unwrap_or_else(|err| panic!("Failed to read capture: {}", err))
+        })
+        .filter(move |captured_message| filter.matches(captured_message))
+}
+
+fn print_capture(path: &Path, filter: &Filter, verbose: bool) {
+    let mut num_messages = 0;
+    for captured_message in read_capture(path, filter) {
+        let peer_message = captured_message.peer_message();
+        println!(
+            "{} {:?} {} {} {} {} bytes",
+            from_timestamp(captured_message.timestamp),
+            captured_message.status,
+            captured_message
+                .peer_id
+                .as_ref()
+                .map_or_else(|| "-".to_string(), |peer_id| peer_id.to_string()),
+            captured_message.peer_addr,
+            peer_message.as_ref().map_or("<invalid>", |peer_message| peer_message.msg_variant()),
+            captured_message.message.len(),
+        );
+        if verbose {
+            match peer_message {
+                Ok(peer_message) => println!("    {:?}", peer_message),
+                Err(err) => println!("    {}", err),
+            }
+        }
+        num_messages += 1;
+    }
+    println!("{} messages", num_messages);
+}
+
+fn filter_capture(path: &Path, filter: &Filter, output: &Path) {
+    let mut writer = CaptureWriter::create(output)
+        .unwrap_or_else(|err| panic!("Failed to create {}: {}", output.display(), err));
+    let mut num_messages = 0;
+    for captured_message in read_capture(path, filter) {
+        writer.write_blocking(captured_message).unwrap();
+        num_messages += 1;
+    }
+    println!("Wrote {} messages to {}", num_messages, output.display());
+}
+
+/// Opens a connection to the node for the messages of one captured connection. Responses of the
+/// node are read and dropped so that it doesn't stall on a full socket.
+fn connect(addr: &str) -> io::Result<TcpStream> {
+    let stream = TcpStream::connect(addr)?;
+    let mut reader = stream.try_clone()?;
+    thread::spawn(move || io::copy(&mut reader, &mut io::sink()));
+    Ok(stream)
+}
+
+/// Sends the messages received by the captured node to the node at `addr`, over one connection
+/// per captured connection and with the captured intervals between messages divided by `speed`.
+/// The node must use the node key of the captured node, otherwise it rejects the handshakes, and
+/// `encryption = "disabled"` in the `network` section of its config: the messages are captured
+/// before they are encrypted, so a node that encrypts can't read the replayed ones.
+fn replay_capture(path: &Path, filter: &Filter, addr: &str, speed: f64) {
+    let mut connections: HashMap<String, Option<TcpStream>> = HashMap::new();
+    let mut num_messages = 0;
+    let mut start_timestamp = None;
+    let start = Instant::now();
+    for captured_message in read_capture(path, filter) {
+        if captured_message.status != Status::Received {
+            continue;
+        }
+        let start_timestamp = *start_timestamp.get_or_insert(captured_message.timestamp);
+        let offset = Duration::from_nanos(
+            ((captured_message.timestamp.saturating_sub(start_timestamp)) as f64 / speed) as u64,
+        );
+        if let Some(wait) = offset.checked_sub(start.elapsed()) {
+            thread::sleep(wait);
+        }
+
+        let connection = connections.entry(captured_message.peer_addr.clone()).or_insert_with(
+            || match connect(addr) {
+                Ok(stream) => Some(stream),
+                Err(err) => {
+                    println!("Failed to connect to {}: {}", addr, err);
+                    None
+                }
+            },
+        );
+        if let Some(stream) = connection {
+            let result = stream
+                .write_all(&(captured_message.message.len() as u32).to_le_bytes())
+                .and_then(|()| stream.write_all(&captured_message.message));
+            match result {
+                Ok(()) => num_messages += 1,
+                Err(err) => {
+                    println!(
+                        "Connection for {} closed by the node: {}",
+                        captured_message.peer_addr, err
+                    );
+                    *connection = None;
+                }
+            }
+        }
+    }
+    println!("Replayed {} messages over {} connections", num_messages, connections.len());
+}
+
+fn filter_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("capture").help("Capture file").required(true).takes_value(true),
+        Arg::with_name("peer_id")
+            .long("peer_id")
+            .help("Only messages to or from this peer")
+            .takes_value(true),
+        Arg::with_name("peer_addr")
+            .long("peer_addr")
+            .help("Only messages of the connection with this address")
+            .takes_value(true),
+        Arg::with_name("type")
+            .long("type")
+            .help("Only messages of this type, e.g. BlockRequest or ForwardTx for routed messages")
+            .takes_value(true),
+        Arg::with_name("status")
+            .long("status")
+            .possible_values(&["sent", "received"])
+            .help("Only sent or received messages")
+            .takes_value(true),
+    ]
+}
+
+fn main() {
+    let matches = App::new("network-capture")
+        .about("Prints, filters and replays captured network messages")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("print")
+                .about("Print captured messages")
+                .args(&filter_args())
+                .arg(
+                    Arg::with_name("verbose")
+                        .long("verbose")
+                        .help("Print the content of the messages")
+                        .takes_value(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("filter")
+                .about("Write the captured messages that match the filters to a new capture")
+                .args(&filter_args())
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .help("Capture file to write")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about(
+                    "Send the messages received by the captured node to a local node, which must \
+                     have encryption disabled",
+                )
+                .args(&filter_args())
+                .arg(
+                    Arg::with_name("addr")
+                        .long("addr")
+                        .default_value("127.0.0.1:24567")
+                        .help("Network address of the node")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("speed")
+                        .long("speed")
+                        .default_value("1")
+                        .help("Speed up of the replay relative to the capture")
+                        .takes_value(true),
+                ),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        ("print", Some(args)) => {
+            let filter = Filter::from_matches(args);
+            print_capture(
+                Path::new(args.value_of("capture").unwrap()),
+                &filter,
+                args.is_present("verbose"),
+            );
+        }
+        ("filter", Some(args)) => {
+            let filter = Filter::from_matches(args);
+            filter_capture(
+                Path::new(args.value_of("capture").unwrap()),
+                &filter,
+                Path::new(args.value_of("output").unwrap()),
+            );
+        }
+        ("replay", Some(args)) => {
+            let filter = Filter::from_matches(args);
+            let speed =
+                args.value_of("speed").unwrap().parse::<f64>().expect("Speed must be a number");
+            replay_capture(
+                Path::new(args.value_of("capture").unwrap()),
+                &filter,
+                args.value_of("addr").unwrap(),
+                speed,
+            );
+        }
+        (_, _) => unreachable!(),
+    }
+}